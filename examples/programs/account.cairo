@@ -0,0 +1,42 @@
+// Example account contract. `__validate__` authorizes the transactions sent from the account
+// and `__execute__` the calls they make. It's a template rather than a secure account:
+// programs run without the ECDSA builtin, so it only checks that the signature's r is the
+// message hash. It only executes calls to `main` functions, and counts them in its storage.
+
+const STORAGE_READ_SELECTOR = 'StorageRead';
+const STORAGE_WRITE_SELECTOR = 'StorageWrite';
+// sn_keccak('main')
+const MAIN_SELECTOR = 0xe2054f8a912367e38a22ce773328ff8aabf8082c4120bad9ef085e1dbf29a7;
+// storage key of the number of calls executed
+const CALLS_KEY = 1;
+
+func __validate__{syscall_ptr: felt*}(message_hash, r, s) {
+    assert r = message_hash;
+    return ();
+}
+
+func __execute__{syscall_ptr: felt*}(class_hash, selector) {
+    assert selector = MAIN_SELECTOR;
+    let (calls) = storage_read(address=CALLS_KEY);
+    storage_write(address=CALLS_KEY, value=calls + 1);
+    return ();
+}
+
+// The syscalls of starkware.starknet.common.syscalls, as served by the node.
+func storage_read{syscall_ptr: felt*}(address) -> (value: felt) {
+    assert [syscall_ptr] = STORAGE_READ_SELECTOR;
+    assert [syscall_ptr + 1] = address;
+    %{ syscall_handler.storage_read(segments=segments, syscall_ptr=ids.syscall_ptr) %}
+    let value = [syscall_ptr + 2];
+    let syscall_ptr = syscall_ptr + 3;
+    return (value=value);
+}
+
+func storage_write{syscall_ptr: felt*}(address, value) {
+    assert [syscall_ptr] = STORAGE_WRITE_SELECTOR;
+    assert [syscall_ptr + 1] = address;
+    assert [syscall_ptr + 2] = value;
+    %{ syscall_handler.storage_write(segments=segments, syscall_ptr=ids.syscall_ptr) %}
+    let syscall_ptr = syscall_ptr + 3;
+    return ();
+}
@@ -0,0 +1,86 @@
+// Example contract making every syscall the node serves: it reads and writes its storage, emits
+// events and calls other contracts.
+
+from starkware.cairo.common.alloc import alloc
+
+const STORAGE_READ_SELECTOR = 'StorageRead';
+const STORAGE_WRITE_SELECTOR = 'StorageWrite';
+const EMIT_EVENT_SELECTOR = 'EmitEvent';
+const CALL_CONTRACT_SELECTOR = 'CallContract';
+
+// The syscalls of starkware.starknet.common.syscalls, as served by the node.
+func storage_read{syscall_ptr: felt*}(address) -> (value: felt) {
+    assert [syscall_ptr] = STORAGE_READ_SELECTOR;
+    assert [syscall_ptr + 1] = address;
+    %{ syscall_handler.storage_read(segments=segments, syscall_ptr=ids.syscall_ptr) %}
+    let value = [syscall_ptr + 2];
+    let syscall_ptr = syscall_ptr + 3;
+    return (value=value);
+}
+
+func storage_write{syscall_ptr: felt*}(address, value) {
+    assert [syscall_ptr] = STORAGE_WRITE_SELECTOR;
+    assert [syscall_ptr + 1] = address;
+    assert [syscall_ptr + 2] = value;
+    %{ syscall_handler.storage_write(segments=segments, syscall_ptr=ids.syscall_ptr) %}
+    let syscall_ptr = syscall_ptr + 3;
+    return ();
+}
+
+func emit_event{syscall_ptr: felt*}(keys_len, keys: felt*, data_len, data: felt*) {
+    assert [syscall_ptr] = EMIT_EVENT_SELECTOR;
+    assert [syscall_ptr + 1] = keys_len;
+    assert [syscall_ptr + 2] = keys;
+    assert [syscall_ptr + 3] = data_len;
+    assert [syscall_ptr + 4] = data;
+    %{ syscall_handler.emit_event(segments=segments, syscall_ptr=ids.syscall_ptr) %}
+    let syscall_ptr = syscall_ptr + 5;
+    return ();
+}
+
+func call_contract{syscall_ptr: felt*}(
+    contract_address, function_selector, calldata_size, calldata: felt*
+) -> (retdata_size: felt, retdata: felt*) {
+    assert [syscall_ptr] = CALL_CONTRACT_SELECTOR;
+    assert [syscall_ptr + 1] = contract_address;
+    assert [syscall_ptr + 2] = function_selector;
+    assert [syscall_ptr + 3] = calldata_size;
+    assert [syscall_ptr + 4] = calldata;
+    %{ syscall_handler.call_contract(segments=segments, syscall_ptr=ids.syscall_ptr) %}
+    let retdata_size = [syscall_ptr + 5];
+    let retdata = cast([syscall_ptr + 6], felt*);
+    let syscall_ptr = syscall_ptr + 7;
+    return (retdata_size=retdata_size, retdata=retdata);
+}
+
+// Returns the value stored under `key`.
+func read{syscall_ptr: felt*}(key) -> (value: felt) {
+    let (value) = storage_read(address=key);
+    return (value=value);
+}
+
+// Stores `value` under `key`.
+func write{syscall_ptr: felt*}(key, value) {
+    storage_write(address=key, value=value);
+    return ();
+}
+
+// Emits an event with `key` as its only key and `value` as its only data.
+func emit{syscall_ptr: felt*}(key, value) {
+    let (array) = alloc();
+    assert [array] = key;
+    assert [array + 1] = value;
+    emit_event(keys_len=1, keys=array, data_len=1, data=array + 1);
+    return ();
+}
+
+// Calls `selector` of the contract at `address` with `key` and `value`, e.g. its `write`.
+func call{syscall_ptr: felt*}(address, selector, key, value) {
+    let (calldata) = alloc();
+    assert [calldata] = key;
+    assert [calldata + 1] = value;
+    call_contract(
+        contract_address=address, function_selector=selector, calldata_size=2, calldata=calldata
+    );
+    return ();
+}
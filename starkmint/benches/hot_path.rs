@@ -0,0 +1,166 @@
+//! Benchmarks of the transaction hot path: decoding, CheckTx, DeliverTx and Commit, with the
+//! example programs of the repository, plus serial and parallel execution of whole blocks.
+//!
+//! Run with `cargo bench --bench hot_path`, optionally filtered, e.g.
+//! `cargo bench --bench hot_path -- block`.
+
+use std::path::Path;
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use starkmint::app::StarknetApp;
+use starkmint::block;
+use starkmint::cache::ProgramCache;
+use starkmint::execution::ExecutionConfig;
+use starkmint::state::{ClassRegistry, ContractStorage};
+use starkmint::transaction::{Transaction, TransactionType};
+use tendermint::abci::request::{self, CheckTxKind, Request};
+use tokio::runtime::Runtime;
+use tower::Service;
+
+/// Example programs, from the smallest to the largest artifact.
+const PROGRAMS: [&str; 2] = ["factorial", "fibonacci"];
+
+/// Transactions per block in block benchmarks.
+const BLOCK_SIZE: usize = 64;
+
+fn transaction(name: &str) -> Transaction {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../examples/programs")
+        .join(format!("{name}.json"));
+    let program = std::fs::read_to_string(path).expect("example program is readable");
+
+    Transaction::with_type(TransactionType::FunctionExecution {
+        program: program.into(),
+        function: "main".parse().unwrap(),
+        program_name: format!("{name}.json"),
+        enable_trace: false,
+        calldata: Vec::new(),
+    })
+    .expect("example program executes")
+}
+
+fn encoded(name: &str) -> Bytes {
+    bincode::serialize(&transaction(name)).unwrap().into()
+}
+
+/// Runs an ABCI request through the application, as the ABCI server does.
+fn call(runtime: &Runtime, app: &StarknetApp, request: Request) {
+    runtime.block_on(app.clone().call(request)).unwrap();
+}
+
+fn app() -> StarknetApp {
+    let data_dir = std::env::temp_dir().join(format!("starkmint-bench-{}", std::process::id()));
+    StarknetApp::new().with_data_dir(&data_dir)
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for name in PROGRAMS {
+        let tx = encoded(name);
+        group.throughput(Throughput::Bytes(tx.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &tx, |b, tx| {
+            b.iter(|| bincode::deserialize::<Transaction>(tx).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn check_tx(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let app = app();
+
+    let mut group = c.benchmark_group("check_tx");
+    for name in PROGRAMS {
+        let tx = encoded(name);
+        group.bench_with_input(BenchmarkId::from_parameter(name), &tx, |b, tx| {
+            b.iter(|| {
+                let request = request::CheckTx {
+                    tx: tx.clone(),
+                    kind: CheckTxKind::New,
+                };
+                call(&runtime, &app, Request::CheckTx(request))
+            })
+        });
+    }
+    group.finish();
+}
+
+fn deliver_tx(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let app = app();
+
+    let mut group = c.benchmark_group("deliver_tx");
+    for name in PROGRAMS {
+        let tx = encoded(name);
+        group.bench_with_input(BenchmarkId::from_parameter(name), &tx, |b, tx| {
+            b.iter(|| {
+                let request = request::DeliverTx { tx: tx.clone() };
+                call(&runtime, &app, Request::DeliverTx(request))
+            })
+        });
+    }
+    group.finish();
+}
+
+fn commit(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let app = app();
+    let tx = encoded("fibonacci");
+
+    c.bench_function("commit", |b| {
+        b.iter_batched(
+            || {
+                for _ in 0..BLOCK_SIZE {
+                    let request = request::DeliverTx { tx: tx.clone() };
+                    call(&runtime, &app, Request::DeliverTx(request));
+                }
+            },
+            |()| call(&runtime, &app, Request::Commit),
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+/// Delivers blocks of the same transaction one by one and on several threads, to quantify
+/// parallel execution.
+fn deliver_block(c: &mut Criterion) {
+    let config = ExecutionConfig::default();
+    let programs = ProgramCache::default();
+    let txs = vec![transaction("fibonacci"); BLOCK_SIZE];
+    let workers = std::thread::available_parallelism().map_or(1, usize::from);
+
+    let mut group = c.benchmark_group("block");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+    group.bench_function("serial", |b| {
+        b.iter_batched(
+            || (ContractStorage::default(), ClassRegistry::default()),
+            |(mut storage, mut classes)| {
+                for tx in &txs {
+                    block::deliver(tx, &config, &mut storage, &mut classes, &programs).unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("parallel", workers), |b| {
+        b.iter_batched(
+            || (ContractStorage::default(), ClassRegistry::default()),
+            |(mut storage, mut classes)| {
+                block::deliver_parallel(
+                    &txs,
+                    &config,
+                    &mut storage,
+                    &mut classes,
+                    &programs,
+                    workers,
+                )
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode, check_tx, deliver_tx, commit, deliver_block);
+criterion_main!(benches);
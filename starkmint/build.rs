@@ -0,0 +1,6 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tonic_build::configure()
+        .build_client(false)
+        .compile(&["proto/starkmint/query/v1/query.proto"], &["proto"])?;
+    Ok(())
+}
@@ -0,0 +1,19 @@
+//! Submits arbitrary bytes to the mempool of a chain, as any client of the node can.
+
+#![no_main]
+
+use std::cell::RefCell;
+
+use libfuzzer_sys::fuzz_target;
+use starkmint::genesis::Genesis;
+use starkmint::testkit::TestChain;
+
+thread_local! {
+    static CHAIN: RefCell<TestChain> = RefCell::new(TestChain::new(&Genesis::default()));
+}
+
+fuzz_target!(|data: &[u8]| {
+    CHAIN.with(|chain| {
+        chain.borrow_mut().check_raw_tx(data.to_vec().into());
+    });
+});
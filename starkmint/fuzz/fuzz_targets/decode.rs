@@ -0,0 +1,22 @@
+//! Decodes arbitrary bytes as a transaction, as CheckTx and DeliverTx do. Whatever decodes
+//! must encode back to a transaction that decodes the same, as it's relayed and indexed
+//! re-encoded.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use starkmint::transaction::Transaction;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(tx) = bincode::deserialize::<Transaction>(data) else {
+        return;
+    };
+    let encoded = bincode::serialize(&tx).expect("decoded transactions can be encoded");
+    let decoded: Transaction =
+        bincode::deserialize(&encoded).expect("encoded transactions can be decoded");
+    assert_eq!(
+        bincode::serialize(&decoded).unwrap(),
+        encoded,
+        "transaction changed when encoded again"
+    );
+});
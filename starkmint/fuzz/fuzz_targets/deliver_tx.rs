@@ -0,0 +1,27 @@
+//! Executes and commits blocks made of arbitrary bytes, as a proposer can include any bytes
+//! in a block, whether they passed CheckTx or not.
+
+#![no_main]
+
+use std::cell::RefCell;
+
+use libfuzzer_sys::fuzz_target;
+use starkmint::app::StarknetApp;
+use starkmint::genesis::Genesis;
+use starkmint::testkit::TestChain;
+
+thread_local! {
+    // a block is committed per input, without waiting for its files to be flushed
+    static CHAIN: RefCell<TestChain> = RefCell::new(TestChain::with_app(
+        StarknetApp::new().with_commit_sync(false),
+        &Genesis::default(),
+    ));
+}
+
+fuzz_target!(|data: &[u8]| {
+    CHAIN.with(|chain| {
+        chain
+            .borrow_mut()
+            .execute_raw_block(vec![data.to_vec().into()]);
+    });
+});
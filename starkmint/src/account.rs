@@ -0,0 +1,169 @@
+use cairo_felt::Felt;
+use color_eyre::eyre::WrapErr;
+use color_eyre::Result;
+
+use crate::cache::ProgramCache;
+use crate::execution::{felt_to_hex, ExecutionConfig};
+use crate::signature::{self, TxSignature};
+use crate::state::{self, ClassRegistry, TxStorage};
+use crate::transaction::{EntryPoint, Execution, Transaction, TransactionType};
+
+/// Entry point of account contracts authorizing the transactions sent from them.
+pub const VALIDATE_ENTRY_POINT: &str = "__validate__";
+
+/// Entry point of account contracts executing the calls of the transactions sent from them.
+pub const EXECUTE_ENTRY_POINT: &str = "__execute__";
+
+/// Steps `__validate__` may run, including the calls it makes, before the transaction is
+/// rejected. Validation runs in CheckTx, before the sender pays anything, so it must be cheap.
+pub const VALIDATE_MAX_STEPS: usize = 1_000_000;
+
+/// Validation phase of a signed transaction: fails unless its sender, and its paymaster if it
+/// has one, authorized it. Signatures cover the content of the transaction for the chain
+/// recorded in `storage` at genesis (see [`Transaction::signed_content`]), so it can't be
+/// altered or replayed on another chain.
+///
+/// Senders registered as contracts are account contracts, which authorize transactions with
+/// their `__validate__(message_hash, r, s)` entry point, given the message hash (see
+/// [`signature::message_hash`]) and the two felts of the signature. It's executed against
+/// `storage` and authorizes the transaction by returning, so accounts can implement their own
+/// schemes, e.g. rotating keys kept in their storage. Its storage writes are discarded, and it
+/// fails with [`StepLimitExceeded`](crate::execution::StepLimitExceeded) if it runs more than
+/// [`VALIDATE_MAX_STEPS`].
+///
+/// Other senders are Stark public keys, whose ECDSA signature is verified natively.
+///
+/// Paymasters are authorized the same way, over [`signature::paymaster_message_hash`].
+///
+/// The execution phase is the transaction's own invocation, whose trace the transaction hash
+/// commits to, dispatched to the sender's account contract if it has one, see [`execute`].
+pub fn validate(
+    tx: &Transaction,
+    signature: &TxSignature,
+    config: &ExecutionConfig,
+    mut storage: TxStorage<'_>,
+    classes: &ClassRegistry,
+    programs: &ProgramCache,
+) -> Result<()> {
+    let (address, key) = state::chain_id_slot();
+    let content = tx.signed_content(storage.read(&address, &key));
+    let paymaster = signature.paymaster.as_ref();
+    let message = signature::message_hash(
+        &content,
+        &signature.sender,
+        signature.nonce,
+        signature.tip,
+        signature.max_fee,
+        paymaster.map(|paymaster| &paymaster.paymaster),
+    );
+    authorize(
+        &signature.sender,
+        message,
+        &signature.r,
+        &signature.s,
+        config,
+        storage.view(),
+        classes,
+        programs,
+    )?;
+
+    if let Some(paymaster) = paymaster {
+        let message = signature::paymaster_message_hash(&content, signature, &paymaster.paymaster);
+        authorize(
+            &paymaster.paymaster,
+            message,
+            &paymaster.r,
+            &paymaster.s,
+            config,
+            storage,
+            classes,
+            programs,
+        )
+        .wrap_err_with(|| {
+            format!(
+                "Paymaster {} rejected the transaction",
+                felt_to_hex(&paymaster.paymaster)
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Execution phase of a validated transaction sent from an account contract: the call it
+/// invokes is dispatched to the account's `__execute__(class_hash, selector)` entry point,
+/// which executes it on the account's behalf by returning, or rejects it by failing, e.g.
+/// to restrict the contracts the account calls. Cairo 0 entry points take a fixed number of
+/// felts, so the account is given the called class and selector, and the node executes the
+/// call itself once approved. `__execute__` runs against `storage`, and its writes, events and
+/// gas are added to those of the call.
+///
+/// Returns `None` for senders that aren't contracts, which the call is executed for directly,
+/// and for proof verifications and L1 handlers, which don't call a contract of the sender's
+/// choosing.
+pub fn execute(
+    tx: &Transaction,
+    signature: &TxSignature,
+    config: &ExecutionConfig,
+    storage: TxStorage<'_>,
+    classes: &ClassRegistry,
+    programs: &ProgramCache,
+) -> Result<Option<Execution>> {
+    let account = &signature.sender;
+    if classes.get(account).is_none() {
+        return Ok(None);
+    }
+    let (TransactionType::FunctionExecution { function, .. }
+    | TransactionType::ClassExecution { function, .. }) = &tx.transaction_type
+    else {
+        return Ok(None);
+    };
+    let class_hash = tx
+        .transaction_type
+        .class_hash()
+        .expect("executions call a class");
+
+    let dispatch = TransactionType::ClassExecution {
+        class_hash: account.clone(),
+        function: EntryPoint::Name(EXECUTE_ENTRY_POINT.to_string()),
+        program_name: EXECUTE_ENTRY_POINT.to_string(),
+        enable_trace: false,
+        calldata: vec![class_hash, function.selector()],
+    };
+    let execution = dispatch
+        .execute(config, storage, classes, programs)
+        .wrap_err_with(|| format!("Account {} rejected the call", felt_to_hex(account)))?;
+    Ok(Some(execution))
+}
+
+/// Fails unless `account` authorized the message with the signature `(r, s)`.
+#[allow(clippy::too_many_arguments)]
+fn authorize(
+    account: &Felt,
+    message: Felt,
+    r: &Felt,
+    s: &Felt,
+    config: &ExecutionConfig,
+    storage: TxStorage<'_>,
+    classes: &ClassRegistry,
+    programs: &ProgramCache,
+) -> Result<()> {
+    if classes.get(account).is_none() {
+        return signature::verify(account, &message, r, s);
+    }
+
+    let validation = TransactionType::ClassExecution {
+        class_hash: account.clone(),
+        function: EntryPoint::Name(VALIDATE_ENTRY_POINT.to_string()),
+        program_name: VALIDATE_ENTRY_POINT.to_string(),
+        enable_trace: false,
+        calldata: vec![message, r.clone(), s.clone()],
+    };
+    let config = ExecutionConfig {
+        max_steps: Some(VALIDATE_MAX_STEPS),
+        ..config.clone()
+    };
+    validation
+        .execute(&config, storage, classes, programs)
+        .wrap_err_with(|| format!("Account {} rejected the transaction", felt_to_hex(account)))?;
+    Ok(())
+}
@@ -1,38 +1,481 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::Write;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, Once, RwLock};
 use std::task::{Context, Poll};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use bytes::Bytes;
+use cairo_felt::Felt;
+use cairo_vm::types::program::Program;
+use color_eyre::eyre::{ensure, eyre};
 use color_eyre::Result;
 use futures::{Future, FutureExt};
-use once_cell::sync::Lazy;
+use num_traits::{ToPrimitive, Zero};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tendermint::abci::request::{self, Request};
 use tendermint::abci::{self, response, Response};
 use tendermint::block::Height;
+use tendermint::AppHash;
+use tokio::runtime::RuntimeFlavor;
+use tokio::sync::{broadcast, oneshot, watch, Semaphore};
 use tower::Service;
 use tower_abci::BoxError;
-use tracing::{debug, info};
+use tracing::{debug, error, field, info, info_span, trace, warn, Instrument, Span};
 
-use crate::transaction::{Transaction, TransactionType};
+use crate::account;
+use crate::archive::{ArchivedBlock, ArchivedGenesis, BlockArchive};
+use crate::audit::{AuditLog, BlockAudit};
+use crate::block::{self, Delivery};
+use crate::cache::{Invocation, ProgramCache, QueryCache, SeenTransactions};
+use crate::da::{DataBlob, StateDiff};
+use crate::execution::{
+    self, felt_to_hex, ExecutionConfig, FeeEstimate, Receipt, Simulation, StepLimitExceeded,
+};
+use crate::genesis::Genesis;
+use crate::messaging::L1Message;
+use crate::metrics::{BlockSummary, Metrics};
+use crate::profiler::Profiler;
+use crate::signature::TxSignature;
+use crate::state::{self, AccountState, ClassRegistry, ContractStorage, StorageWrites, TxStorage};
+use crate::transaction::{
+    EntryPoint, Execution, Transaction, TransactionType, MAX_CALLDATA_LEN, MAX_PROGRAM_SIZE,
+};
 
-const HEIGHT_PATH: &str = "/tmp/starkmint/abci.height";
+/// Directory the application keeps its files in, unless configured otherwise.
+pub const DEFAULT_DATA_DIR: &str = "/tmp/starkmint";
 
-static mut TRANSACTIONS: usize = 0;
-static mut TIMER: Lazy<Instant> = Lazy::new(Instant::now);
+/// Number of query responses cached between commits, unless configured otherwise.
+pub const DEFAULT_QUERY_CACHE_SIZE: usize = 1024;
+
+/// Number of function executions memoized by the node, unless configured otherwise.
+pub const DEFAULT_EXECUTION_CACHE_SIZE: usize = 1024;
+
+/// Number of receipts kept for subscribers that fall behind.
+const RECEIPTS_CAPACITY: usize = 1024;
+
+/// Number of block summaries kept for subscribers that fall behind.
+const BLOCKS_CAPACITY: usize = 128;
+
+/// Number of state diffs kept for subscribers that fall behind.
+const STATE_DIFFS_CAPACITY: usize = 16;
+
+/// CheckTx code of malformed transactions and of any other rejection without a code of its own,
+/// e.g. a bad signature.
+pub const INVALID_TRANSACTION_CODE: u32 = 1;
+
+/// CheckTx code of transactions whose program was not compiled for this node's executor.
+pub const INCOMPATIBLE_PROGRAM_CODE: u32 = 2;
+
+/// CheckTx code of transactions whose sender already has too many transactions pending.
+pub const SENDER_LIMIT_CODE: u32 = 3;
+
+/// CheckTx code of transactions whose program isn't a valid compiled program.
+pub const INVALID_PROGRAM_CODE: u32 = 4;
+
+/// CheckTx code of transactions invoking a function their program doesn't have.
+pub const UNKNOWN_ENTRY_POINT_CODE: u32 = 5;
+
+/// CheckTx code of transactions whose calldata doesn't match the invoked function's arguments.
+pub const INVALID_CALLDATA_CODE: u32 = 6;
+
+/// CheckTx code of transactions whose program or calldata is larger than allowed.
+pub const TOO_LARGE_CODE: u32 = 7;
+
+/// CheckTx code of transactions whose nonce was used, or doesn't follow the nonce of the
+/// sender's last pending transaction.
+pub const INVALID_NONCE_CODE: u32 = 8;
+
+/// CheckTx code of transactions whose payer can't pay the max fees and tips of its pending
+/// transactions.
+pub const INSUFFICIENT_BALANCE_CODE: u32 = 9;
+
+/// CheckTx code of transactions already included in a recent block.
+pub const DUPLICATE_CODE: u32 = 10;
+
+/// CheckTx code of L1 handler transactions not sent by the L1 relayer, or whose message was
+/// already handled.
+pub const INVALID_L1_MESSAGE_CODE: u32 = 11;
+
+/// CheckTx code of transactions whose account's `__validate__` ran out of steps, see
+/// [`account::VALIDATE_MAX_STEPS`].
+pub const VALIDATION_STEPS_CODE: u32 = 12;
+
+/// CheckTx code of transactions expected to use more gas than the node accepts, or than their
+/// signed max fee pays for.
+pub const GAS_LIMIT_CODE: u32 = 13;
+
+/// CheckTx code of proof verifications whose proof is rejected, or of all of them when no
+/// proof verifier is configured, see [`ProofVerifier`](crate::proof::ProofVerifier).
+pub const INVALID_PROOF_CODE: u32 = 14;
+
+/// CheckTx code of unsigned function and class executions on chains funding accounts, see
+/// [`Genesis::signatures_required`].
+pub const UNSIGNED_CODE: u32 = 15;
 
 #[derive(Debug, Clone)]
 pub struct StarknetApp {
     hasher: Arc<Mutex<Sha256>>,
+    execution: ExecutionConfig,
+    storage: Arc<RwLock<ContractStorage>>,
+    classes: Arc<RwLock<ClassRegistry>>,
+    programs: ProgramCache,
+    /// Number of threads executing a block's transactions concurrently, if enabled.
+    parallelism: Option<Arc<AtomicUsize>>,
+    /// Transactions of the current block waiting to be applied at end_block.
+    pending: Arc<Mutex<Vec<PendingTx>>>,
+    /// Transactions of the current block waiting to be executed speculatively.
+    speculation: Arc<Mutex<SpeculationQueue>>,
+    /// Height and app hash of the last committed block.
+    chain: ChainMetadata,
+    metrics: Arc<Metrics>,
+    /// Progress of the block being executed.
+    block: Arc<Mutex<BlockMetrics>>,
+    /// Height and time of the last commit.
+    last_commit: Arc<Mutex<Option<(Height, Instant)>>>,
+    /// Receipts of the transactions applied in committed blocks.
+    receipts: broadcast::Sender<Receipt>,
+    /// Summaries of committed blocks.
+    blocks: broadcast::Sender<BlockSummary>,
+    /// Whether the chain publishes the state diff of every block, set at InitChain.
+    data_availability: Arc<AtomicBool>,
+    /// Height and commitment of the last committed state diff, recorded at the next block.
+    da_commitment: Arc<Mutex<Option<(u64, Felt)>>>,
+    /// Blobs of the committed state diffs, to be published.
+    state_diffs: broadcast::Sender<DataBlob>,
+    /// State updates of the most recent blocks, oldest first, if kept.
+    state_updates: Option<Arc<Mutex<VecDeque<StateUpdate>>>>,
+    /// Number of blocks whose state updates are kept.
+    state_update_history: usize,
+    /// Chain the node was started for, if given. The chain is refused at InitChain otherwise.
+    expected_chain_id: Option<String>,
+    /// Height to halt at once committed, set by the operator.
+    halt_height: Option<u64>,
+    /// Heights at which halts set by governance are ignored, once the node is upgraded.
+    skipped_halt_heights: HashSet<u64>,
+    /// Contract holding the governance-settable parameters, set at InitChain.
+    governance: Arc<Mutex<Option<Felt>>>,
+    /// Height the application halted at, once it has.
+    halted: Arc<watch::Sender<Option<Height>>>,
+    /// Bounds the number of CheckTx and Query requests executed concurrently.
+    checks: Arc<Semaphore>,
+    /// Responses of the queries made since the last commit.
+    queries: QueryCache,
+    /// Maximum number of transactions a sender may have in the mempool, if limited.
+    max_pending_per_sender: Option<usize>,
+    /// Transactions accepted in the mempool by sender, since the last commit.
+    pending_senders: Arc<Mutex<HashMap<Felt, PendingSender>>>,
+    /// Unsigned transactions accepted in the mempool since the last commit. They have no sender
+    /// to tell them apart, so they share the limit of a single sender.
+    pending_unsigned: Arc<Mutex<usize>>,
+    /// Resolves once the transaction of the last CheckTx request received was admitted or
+    /// rejected, see [`Self::admission_turn`].
+    last_admission: Arc<Mutex<oneshot::Receiver<()>>>,
+    /// Whether the files written at commit are flushed to disk before it returns.
+    commit_sync: bool,
+    /// Profile of the delivered transactions, if enabled.
+    profiler: Option<Arc<Profiler>>,
+    /// Transactions included in recent blocks, which can't be included again.
+    seen: SeenTransactions,
+    /// Log of the state writes of every block, if enabled.
+    audit: Option<AuditLog>,
+    /// Archive of the genesis and committed blocks, if enabled.
+    archive: Option<BlockArchive>,
+}
+
+/// State of the application reported by health checks.
+#[derive(Debug, Clone)]
+pub struct Health {
+    /// Whether storage can be used, i.e. no thread panicked while holding it.
+    pub storage_available: bool,
+    /// Height and time of the last commit since the node started, if any.
+    pub last_commit: Option<(Height, Instant)>,
+}
+
+/// Transactions, gas and timings of the block being executed, summarized once it's committed,
+/// and receipts of its applied transactions, published then.
+#[derive(Debug, Default)]
+struct BlockMetrics {
+    started: Option<Instant>,
+    /// Time since the previous block started, if any since startup.
+    interval: Option<Duration>,
+    /// Time from begin_block to end_block, once the block ended.
+    execution: Duration,
+    height: u64,
+    transactions: usize,
+    gas: u64,
+    receipts: Vec<Receipt>,
+    /// Hashes of the transactions whose proofs were verified.
+    verified_proofs: Vec<String>,
+    /// Delivered transactions, as encoded by the sender, if blocks are archived.
+    txs: Vec<Bytes>,
+}
+
+impl BlockMetrics {
+    /// Time since the block started being executed.
+    fn elapsed(&self) -> Duration {
+        self.started
+            .map_or(Duration::ZERO, |started| started.elapsed())
+    }
+}
+
+/// Storage writes of a committed block, and the app hashes before and after them.
+#[derive(Debug, Clone)]
+pub struct StateUpdate {
+    pub height: u64,
+    pub old_root: Vec<u8>,
+    pub new_root: Vec<u8>,
+    pub writes: StorageWrites,
+}
+
+/// Transactions of an account accepted in the mempool since the last commit.
+#[derive(Debug, Default)]
+struct PendingSender {
+    /// Pending transactions sent by the account.
+    transactions: usize,
+    /// Nonce the account's next transaction must have.
+    next_nonce: u64,
+    /// Max fees and tips of the pending transactions the account pays for, as their sender or
+    /// paymaster, which its balance must cover.
+    charges: u64,
+    /// Lowest tip of the pending transactions sent by the account, which its next ones are
+    /// prioritized by at most.
+    lowest_tip: Option<u64>,
+}
+
+/// A transaction that passed the checks of CheckTx, to be admitted in the mempool: the
+/// response accepting it and its signature, if signed.
+type Checked = (response::CheckTx, Option<TxSignature>);
+
+/// A delivered transaction, the channel its speculative execution is received on and the
+/// channel its response is sent back on.
+type PendingTx = (
+    Arc<Transaction>,
+    mpsc::Receiver<Result<Execution>>,
+    oneshot::Sender<response::DeliverTx>,
+);
+
+/// Transactions waiting for a parallel execution worker, and the number of workers running.
+/// Workers exit once there's no transaction left.
+#[derive(Debug, Default)]
+struct SpeculationQueue {
+    transactions: VecDeque<(Arc<Transaction>, mpsc::SyncSender<Result<Execution>>)>,
+    workers: usize,
 }
 
 impl StarknetApp {
     pub fn new() -> Self {
-        std::fs::create_dir_all("/tmp/starkmint").expect("must be able to create temp dir");
-        std::fs::write(HEIGHT_PATH, bincode::serialize(&Height::default()).unwrap()).unwrap();
-
         Self {
             hasher: Arc::new(Mutex::new(Sha256::new())),
+            execution: ExecutionConfig::default(),
+            storage: Arc::new(RwLock::new(ContractStorage::default())),
+            classes: Arc::new(RwLock::new(ClassRegistry::default())),
+            programs: ProgramCache::default(),
+            parallelism: None,
+            pending: Default::default(),
+            speculation: Default::default(),
+            chain: ChainMetadata::new(Path::new(DEFAULT_DATA_DIR)),
+            metrics: Default::default(),
+            block: Default::default(),
+            last_commit: Default::default(),
+            receipts: broadcast::channel(RECEIPTS_CAPACITY).0,
+            blocks: broadcast::channel(BLOCKS_CAPACITY).0,
+            data_availability: Default::default(),
+            da_commitment: Default::default(),
+            state_diffs: broadcast::channel(STATE_DIFFS_CAPACITY).0,
+            state_updates: None,
+            state_update_history: 0,
+            expected_chain_id: None,
+            halt_height: None,
+            skipped_halt_heights: HashSet::new(),
+            governance: Default::default(),
+            halted: Arc::new(watch::channel(None).0),
+            checks: Arc::new(Semaphore::new(
+                std::thread::available_parallelism().map_or(1, usize::from),
+            )),
+            queries: QueryCache::new(DEFAULT_QUERY_CACHE_SIZE),
+            max_pending_per_sender: None,
+            pending_senders: Default::default(),
+            pending_unsigned: Default::default(),
+            last_admission: Arc::new(Mutex::new(oneshot::channel().1)),
+            commit_sync: true,
+            profiler: None,
+            seen: Default::default(),
+            audit: None,
+            archive: None,
+        }
+    }
+
+    /// State of the application, for health checks.
+    pub fn health(&self) -> Health {
+        Health {
+            storage_available: !self.storage.is_poisoned(),
+            last_commit: *self.last_commit.lock().unwrap(),
+        }
+    }
+
+    /// Receives the receipts of the transactions applied in each block, once it's committed.
+    /// Receipts are dropped for receivers lagging behind by more than 1024 of them.
+    pub fn subscribe_receipts(&self) -> broadcast::Receiver<Receipt> {
+        self.receipts.subscribe()
+    }
+
+    /// Receives the summary of each block once it's committed, including the blocks replayed
+    /// when the node starts. Summaries are dropped for receivers lagging behind by more than
+    /// 128 of them.
+    pub fn subscribe_blocks(&self) -> broadcast::Receiver<BlockSummary> {
+        self.blocks.subscribe()
+    }
+
+    /// Receives the compressed state diff of each block once it's committed, on chains
+    /// publishing them (see [`GenesisParameters`](crate::genesis::GenesisParameters)). Diffs
+    /// are dropped for receivers lagging behind by more than 16 of them.
+    pub fn subscribe_state_diffs(&self) -> broadcast::Receiver<DataBlob> {
+        self.state_diffs.subscribe()
+    }
+
+    /// Metrics of the application, to be exported.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Only accepts to run the chain with this ID: InitChain fails for any other chain.
+    pub fn with_chain_id(mut self, chain_id: String) -> Self {
+        self.expected_chain_id = Some(chain_id);
+        self
+    }
+
+    /// ID of the chain the application runs: the one it was initialized with, even before a
+    /// restart, or else the one it was configured with.
+    pub fn chain_id(&self) -> Option<String> {
+        self.chain
+            .last_block()
+            .chain_id
+            .or_else(|| self.expected_chain_id.clone())
+    }
+
+    /// Halts the application once the block at `height` is committed, so validators can be
+    /// upgraded together.
+    pub fn with_halt_height(mut self, height: u64) -> Self {
+        self.halt_height = Some(height);
+        self
+    }
+
+    /// Ignores the halts set by governance at these heights, so an upgraded node can replay
+    /// past them.
+    pub fn with_skipped_halt_heights(mut self, heights: impl IntoIterator<Item = u64>) -> Self {
+        self.skipped_halt_heights = heights.into_iter().collect();
+        self
+    }
+
+    /// Receives the height the application halted at, once it has. No block is processed
+    /// after it, so the node should be stopped.
+    pub fn halted(&self) -> watch::Receiver<Option<Height>> {
+        self.halted.subscribe()
+    }
+
+    /// Keeps the application's files in `data_dir` instead of [`DEFAULT_DATA_DIR`]. The ID of
+    /// the chain the node ran before a restart is read back from it, and InitChain fails for
+    /// any other chain.
+    pub fn with_data_dir(mut self, data_dir: &Path) -> Self {
+        self.chain = ChainMetadata::open(data_dir);
+        self.seen = SeenTransactions::open(data_dir);
+        self
+    }
+
+    /// Replaces the execution settings (enabled builtins, etc.) used in deliver_tx.
+    pub fn with_execution_config(mut self, execution: ExecutionConfig) -> Self {
+        self.execution = execution;
+        self
+    }
+
+    /// Replaces the cache of parsed programs, e.g. with one persisted on disk.
+    pub fn with_program_cache(mut self, programs: ProgramCache) -> Self {
+        self.programs = programs;
+        self
+    }
+
+    /// Executes each block's transactions concurrently on `workers` threads, as soon as
+    /// they're delivered, with conflicting transactions re-executed serially at end_block.
+    /// DeliverTx responses are then sent once the whole block has been executed.
+    pub fn with_parallel_execution(mut self, workers: usize) -> Self {
+        self.parallelism = Some(Arc::new(AtomicUsize::new(workers)));
+        self
+    }
+
+    /// Executes at most this many CheckTx and Query requests concurrently, instead of one per
+    /// available CPU.
+    pub fn with_check_workers(mut self, workers: usize) -> Self {
+        self.checks = Arc::new(Semaphore::new(workers));
+        self
+    }
+
+    /// Caches the responses of up to `capacity` queries between commits, instead of
+    /// [`DEFAULT_QUERY_CACHE_SIZE`]. Zero disables the cache.
+    pub fn with_query_cache(mut self, capacity: usize) -> Self {
+        self.queries = QueryCache::new(capacity);
+        self
+    }
+
+    /// Profiles the delivered transactions by entry point, see [`Profiler`].
+    pub fn with_profiler(mut self) -> Self {
+        self.profiler = Some(Default::default());
+        self
+    }
+
+    /// Rejects transactions from senders that already have `limit` transactions in the
+    /// mempool, so a single account can't flood it. Unsigned transactions count as those of
+    /// a single sender.
+    ///
+    /// Transactions are counted as they're accepted by CheckTx, and the counts start over at
+    /// every commit, when Tendermint rechecks the transactions left in the mempool. Rechecks
+    /// must therefore be enabled (`recheck = true`, the default) for the limit to hold.
+    pub fn with_max_pending_per_sender(mut self, limit: usize) -> Self {
+        self.max_pending_per_sender = Some(limit);
+        self
+    }
+
+    /// Appends the state writes of every block to the file at `path`, see [`AuditLog`].
+    pub fn with_audit_log(mut self, path: &Path) -> Self {
+        self.storage.write().unwrap().keep_journal();
+        self.audit = Some(AuditLog::new(path));
+        self
+    }
+
+    /// Writes the genesis and every committed block to the directory at `dir`, so the chain
+    /// can be replayed, see [`BlockArchive`].
+    pub fn with_block_archive(mut self, dir: &Path) -> Self {
+        self.archive = Some(BlockArchive::new(dir));
+        self
+    }
+
+    /// Keeps the state updates of the last `blocks` committed blocks in memory, to be served
+    /// by [`StarknetApp::state_update`].
+    pub fn with_state_update_history(mut self, blocks: usize) -> Self {
+        self.state_updates = Some(Default::default());
+        self.state_update_history = blocks;
+        self
+    }
+
+    /// Whether commit waits for the files it writes (the block height and newly cached
+    /// programs) to be flushed to disk, which it does by default. Not syncing speeds up
+    /// commits, at the cost of losing them if the machine crashes, e.g. on devnets.
+    pub fn with_commit_sync(mut self, sync: bool) -> Self {
+        self.commit_sync = sync;
+        self
+    }
+
+    /// Changes the number of threads blocks are executed on, from the next block. Has no
+    /// effect unless parallel execution is enabled.
+    pub fn set_parallel_workers(&self, workers: usize) {
+        if let Some(parallelism) = &self.parallelism {
+            parallelism.store(workers, Ordering::Relaxed);
         }
     }
 
@@ -42,26 +485,143 @@ impl StarknetApp {
             request.version, request.block_version, request.p2p_version
         );
 
+        // reported so operators and tooling can tell which chain the node belongs to
+        let data = match self.chain_id() {
+            Some(chain_id) => format!("cairo-app chain_id={chain_id}"),
+            None => "cairo-app".to_string(),
+        };
+
+        let last_block = self.chain.last_block();
         response::Info {
-            data: "cairo-app".to_string(),
+            data,
             version: "0.1.0".to_string(),
             app_version: 1,
-            last_block_height: HeightFile::read_or_create(),
+            last_block_height: last_block.height,
+            last_block_app_hash: AppHash::try_from(last_block.app_hash).unwrap_or_default(),
+        }
+    }
 
-            // using a fixed hash, see the commit() hook
-            last_block_app_hash: Default::default(),
+    /// This hook is called once, when the chain starts, with the `app_state` of the genesis
+    /// file as a JSON [`Genesis`]. Its accounts and classes become the initial committed state,
+    /// and its chain ID is reported by Info from then on. As the state is kept in memory, it's
+    /// called again whenever the node restarts, before Tendermint replays the chain's blocks.
+    ///
+    /// Fails if Tendermint runs another chain than the one the node was started for, or than
+    /// the one its data dir was initialized with: the node must not join it.
+    fn init_chain(&self, request: request::InitChain) -> Result<response::InitChain> {
+        if let Some(expected) = &self.expected_chain_id {
+            ensure!(
+                &request.chain_id == expected,
+                "Tendermint runs chain {}, but the node was started for chain {expected}",
+                request.chain_id
+            );
+        }
+        if let Some(initialized) = self.chain.last_block().chain_id {
+            ensure!(
+                request.chain_id == initialized,
+                "Tendermint runs chain {}, but the data dir was initialized with chain {initialized}",
+                request.chain_id
+            );
+        }
+        self.chain.init(request.chain_id.clone(), self.commit_sync);
+
+        // a node that can't apply the genesis would fork from the others, so crash instead
+        let genesis = Genesis::from_app_state(&request.app_state_bytes)
+            .and_then(|genesis| {
+                genesis.ensure_parameters(&self.execution)?;
+                Ok(genesis)
+            })
+            .expect("Genesis app_state can't be applied");
+
+        *self.governance.lock().unwrap() = genesis.parameters.governance.clone();
+        self.data_availability
+            .store(genesis.parameters.data_availability, Ordering::Relaxed);
+
+        let mut storage = self.storage.write().unwrap();
+        genesis
+            .apply(
+                &request.chain_id,
+                &mut storage,
+                &mut self.classes.write().unwrap(),
+            )
+            .expect("Genesis app_state can't be applied");
+        let storage_writes = storage.commit();
+        self.queries.clear();
+
+        if let Ok(mut hasher) = self.hasher.lock() {
+            hash_writes(&mut hasher, storage_writes);
+        }
+        if let Some(archive) = &self.archive {
+            let genesis = ArchivedGenesis {
+                chain_id: request.chain_id.clone(),
+                app_state: request.app_state_bytes.clone(),
+            };
+            if let Err(e) = archive.write_genesis(&genesis, self.commit_sync) {
+                warn!("Error archiving the genesis: {e}");
+            }
         }
+
+        info!(
+            "Initialized chain {} with {} accounts and {} classes",
+            request.chain_id,
+            genesis.accounts.len(),
+            genesis.classes.len()
+        );
+        Ok(Default::default())
     }
 
     /// This hook is to query the application for data at the current or past height.
-    fn query(&self, _request: request::Query) -> response::Query {
-        let query_result = Err("Query hook needs implementation");
+    ///
+    /// Supported paths:
+    /// - `/estimate_fee`: data is a bincode-encoded transaction, which is executed against the
+    ///   last committed state without persisting anything. Returns a JSON [`FeeEstimate`].
+    /// - `/simulate`: same as `/estimate_fee`, but returns the would-be receipt of the
+    ///   transaction as a JSON [`Simulation`].
+    /// - `/nonce`: data is an account address as a decimal or 0x-prefixed hex string. Returns
+    ///   its nonce as of the last committed block, as a JSON number.
+    /// - `/account`: same as `/nonce`, but returns the account's nonce and fee-token balance
+    ///   as a JSON [`AccountState`](state::AccountState).
+    /// - `/class`: data is a class hash, as for `/nonce`. Returns the compiled program
+    ///   registered under it.
+    ///
+    /// Successful responses are cached until the next commit.
+    fn query(&self, request: request::Query) -> response::Query {
+        let height = request.height.value();
+        let generation = match self.queries.get(&request.path, &request.data, height) {
+            Ok(value) => {
+                return response::Query {
+                    value,
+                    ..Default::default()
+                }
+            }
+            Err(generation) => generation,
+        };
+
+        let query_result = match request.path.as_str() {
+            "/estimate_fee" => self.estimate_fee(&request.data),
+            "/simulate" => self.simulate(&request.data),
+            "/nonce" => self.nonce(&request.data),
+            "/account" => self.account(&request.data),
+            "/class" => self.class(&request.data),
+            "/profile" => self.profile(),
+            path => Err(eyre!("Unknown query path: {path}")),
+        };
 
         match query_result {
-            Ok(value) => response::Query {
-                value,
-                ..Default::default()
-            },
+            Ok(value) => {
+                let value = Bytes::from(value);
+                self.queries.insert(
+                    &request.path,
+                    &request.data,
+                    height,
+                    generation,
+                    value.clone(),
+                );
+                response::Query {
+                    value,
+                    ..Default::default()
+                }
+            }
             Err(e) => response::Query {
                 code: 1.into(),
                 log: format!("Error running query: {e}"),
@@ -71,45 +631,614 @@ impl StarknetApp {
         }
     }
 
+    fn estimate_fee(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let execution = self.execute_speculatively(data)?;
+
+        let estimate = FeeEstimate {
+            gas: execution.gas_used,
+            fee: self.fee(execution.gas_used),
+        };
+        Ok(serde_json::to_vec(&estimate)?)
+    }
+
+    fn simulate(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let execution = self.execute_speculatively(data)?;
+
+        let simulation = Simulation {
+            transaction_hash: execution.hash,
+            retdata: execution.retdata,
+            events: execution.events,
+            gas: execution.gas_used,
+            fee: self.fee(execution.gas_used),
+        };
+        Ok(serde_json::to_vec(&simulation)?)
+    }
+
+    /// Executes a bincode-encoded transaction against the last committed state. Nothing is
+    /// applied, and the transaction hash isn't checked.
+    fn execute_speculatively(&self, data: &[u8]) -> Result<Execution> {
+        let tx: Transaction = bincode::deserialize(data)?;
+
+        let storage = self.storage.read().unwrap();
+        let classes = self.classes.read().unwrap();
+        tx.transaction_type.execute(
+            &self.execution,
+            TxStorage::committed(&storage),
+            &classes,
+            &self.programs,
+        )
+    }
+
+    fn nonce(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let account = parse_address(data)?;
+        let nonce = self.committed_nonce(&account);
+        Ok(serde_json::to_vec(&nonce)?)
+    }
+
+    fn account(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let account = parse_address(data)?;
+        let state = self.committed_account(&account);
+        Ok(serde_json::to_vec(&state)?)
+    }
+
+    fn class(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let class_hash = parse_address(data)?;
+        self.contract_class(&class_hash)
+            .map(String::into_bytes)
+            .ok_or_else(|| eyre!("Class {} not found", felt_to_hex(&class_hash)))
+    }
+
+    fn profile(&self) -> Result<Vec<u8>> {
+        let profiler = self
+            .profiler
+            .as_ref()
+            .ok_or_else(|| eyre!("Profiling is disabled on this node"))?;
+        Ok(serde_json::to_vec(&profiler.report())?)
+    }
+
+    /// Fee charged to signed transactions for the given amount of gas at the node's gas price.
+    pub fn fee(&self, gas: u64) -> u64 {
+        gas.saturating_mul(self.execution.gas_price)
+    }
+
+    /// Executes a transaction against the last committed state, returning the values of the
+    /// invoked function. Nothing is applied.
+    pub fn call(&self, transaction_type: &TransactionType) -> Result<Vec<Felt>> {
+        let storage = self.storage.read().unwrap();
+        let classes = self.classes.read().unwrap();
+        let execution = transaction_type.execute(
+            &self.execution,
+            TxStorage::committed(&storage),
+            &classes,
+            &self.programs,
+        )?;
+        Ok(execution.retdata)
+    }
+
+    /// Transaction invoking a function of a deployed contract. Fails if no program is
+    /// registered at the address.
+    pub fn invoke(
+        &self,
+        contract_address: &Felt,
+        selector: &Felt,
+        calldata: Vec<Felt>,
+    ) -> Result<TransactionType> {
+        let program = self
+            .contract_class(contract_address)
+            .ok_or_else(|| eyre!("Contract {} not found", felt_to_hex(contract_address)))?;
+
+        Ok(TransactionType::FunctionExecution {
+            program: program.into(),
+            function: EntryPoint::Selector(selector.clone()),
+            program_name: felt_to_hex(contract_address),
+            enable_trace: true,
+            calldata,
+        })
+    }
+
+    /// Number of transactions applied from an account as of the last committed block.
+    pub fn committed_nonce(&self, account: &Felt) -> u64 {
+        self.storage.read().unwrap().committed_nonce(account)
+    }
+
+    /// Nonce and balance of an account as of the last committed block.
+    pub fn committed_account(&self, account: &Felt) -> AccountState {
+        self.storage.read().unwrap().committed_account(account)
+    }
+
+    /// Storage value of a contract as of the last committed block. Unset keys read as zero.
+    pub fn committed_storage(&self, contract_address: &Felt, key: &Felt) -> Felt {
+        self.storage
+            .read()
+            .unwrap()
+            .read_committed(contract_address, key)
+    }
+
+    /// Compiled program registered under a class hash, which is also the address of its
+    /// contract.
+    pub fn contract_class(&self, class_hash: &Felt) -> Option<String> {
+        self.classes
+            .read()
+            .unwrap()
+            .get(class_hash)
+            .map(str::to_string)
+    }
+
+    /// State update of the block at `height`, or of the last committed block, if it's one of
+    /// the blocks whose state updates are kept.
+    pub fn state_update(&self, height: Option<u64>) -> Option<StateUpdate> {
+        let updates = self.state_updates.as_ref()?.lock().unwrap();
+        match height {
+            Some(height) => updates
+                .iter()
+                .find(|update| update.height == height)
+                .cloned(),
+            None => updates.back().cloned(),
+        }
+    }
+
     /// This ABCI hook validates an incoming transaction before inserting it in the
     /// mempool and relaying it to other nodes.
+    ///
+    /// Checks are kept cheap: the program is parsed and inspected but never executed, only
+    /// the `__validate__` entry point of account contracts is, once every other check passed.
+    /// Signed transactions are also checked against the last committed state, see
+    /// [`Self::admit`], so transactions bound to fail are never relayed. Transactions are
+    /// prioritized in the mempool by the tip paid by their sender, unless one of the sender's
+    /// pending transactions pays less, see [`Self::admit`].
     fn check_tx(&self, request: request::CheckTx) -> response::CheckTx {
-        let tx: Transaction = bincode::deserialize(&request.tx).unwrap();
+        self.admit_checked(self.check(request))
+    }
+
+    /// Checks of CheckTx not depending on the transactions accepted in the mempool, which
+    /// can run concurrently. Returns the response accepting the transaction and its
+    /// signature, to be admitted with [`Self::admit_checked`], or fails with the CheckTx code
+    /// rejecting it.
+    fn check(&self, request: request::CheckTx) -> Result<Checked, (u32, String)> {
+        let tx: Transaction = match bincode::deserialize(&request.tx) {
+            Ok(tx) => tx,
+            Err(e) => {
+                return Err((
+                    INVALID_TRANSACTION_CODE,
+                    format!("Error decoding transaction: {e}"),
+                ))
+            }
+        };
+
+        if self.seen.contains(&tx.digest()) {
+            return Err((
+                DUPLICATE_CODE,
+                "Transaction already included in a recent block".to_string(),
+            ));
+        }
+        let priority = i64::try_from(tx.tip()).unwrap_or(i64::MAX);
+        let signature = tx.signature.clone();
+        if signature.is_none() && tx.transaction_type.is_execution() {
+            let (address, key) = state::signatures_required_slot();
+            if !self.committed_storage(&address, &key).is_zero() {
+                return Err((
+                    UNSIGNED_CODE,
+                    "Executions must be signed on this chain, as it funds accounts".to_string(),
+                ));
+            }
+        }
+
+        // L1 handlers are checked like the function execution they run
+        let handler_call;
+        let transaction_type = match &tx.transaction_type {
+            TransactionType::L1Handler(message) => {
+                if let Err(log) = self.check_l1_message(message, signature.as_ref()) {
+                    return Err((INVALID_L1_MESSAGE_CODE, log));
+                }
+                handler_call = message.handler_call();
+                &handler_call
+            }
+            transaction_type => transaction_type,
+        };
 
-        match tx.transaction_type {
+        let gas_wanted = match transaction_type {
             TransactionType::FunctionExecution {
-                program: _,
+                program,
                 function,
                 program_name,
-                enable_trace: _,
+                enable_trace,
+                calldata,
             } => {
                 info!(
                     "Received execution transaction. Function: {}, program {}",
                     function, program_name
                 );
+
+                if program.len() as u64 > MAX_PROGRAM_SIZE {
+                    return Err((
+                        TOO_LARGE_CODE,
+                        format!("Program is larger than {MAX_PROGRAM_SIZE} bytes"),
+                    ));
+                }
+
+                let class_hash = state::class_hash(program);
+                let cached = self.programs.get(&class_hash);
+
+                // programs are only added to the cache once they are delivered, so that
+                // rejected transactions can't fill it up
+                let program = match cached {
+                    Some(program) => program,
+                    None => {
+                        if let Err(e) = serde_json::from_str::<IgnoredAny>(program) {
+                            return Err((
+                                INVALID_PROGRAM_CODE,
+                                format!("Program is not valid JSON: {e}"),
+                            ));
+                        }
+                        if let Err(e) = execution::ensure_compatible_artifact(program) {
+                            return Err((
+                                INCOMPATIBLE_PROGRAM_CODE,
+                                format!("Incompatible program: {e}"),
+                            ));
+                        }
+                        match Program::from_reader(program.as_bytes(), None) {
+                            Ok(program) => Arc::new(program),
+                            Err(e) => {
+                                return Err((INVALID_PROGRAM_CODE, format!("Invalid program: {e}")))
+                            }
+                        }
+                    }
+                };
+
+                self.check_invocation(&program, function, calldata)?;
+                self.estimate_gas(
+                    &program,
+                    Invocation {
+                        class_hash,
+                        function: function.clone(),
+                        calldata: calldata.clone(),
+                        enable_trace: *enable_trace,
+                    },
+                )
+            }
+            TransactionType::ClassExecution {
+                class_hash,
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            } => {
+                info!(
+                    "Received execution transaction. Function: {}, program {} (class {})",
+                    function,
+                    program_name,
+                    felt_to_hex(class_hash)
+                );
+
+                // registered programs are cached once delivered, unless they were evicted since
+                let program = match self.programs.get(class_hash) {
+                    Some(program) => Ok(program),
+                    None => self
+                        .contract_class(class_hash)
+                        .ok_or_else(|| eyre!("Class {} not found", felt_to_hex(class_hash)))
+                        .and_then(|program| self.programs.get_or_parse(class_hash, &program)),
+                };
+                let program = match program {
+                    Ok(program) => program,
+                    Err(e) => {
+                        return Err((
+                            INVALID_TRANSACTION_CODE,
+                            format!("Error checking transaction: {e}"),
+                        ))
+                    }
+                };
+
+                self.check_invocation(&program, function, calldata)?;
+                self.estimate_gas(
+                    &program,
+                    Invocation {
+                        class_hash: class_hash.clone(),
+                        function: function.clone(),
+                        calldata: calldata.clone(),
+                        enable_trace: *enable_trace,
+                    },
+                )
+            }
+            TransactionType::ProofVerification {
+                proof,
+                public_inputs,
+            } => {
+                info!(
+                    "Received proof verification transaction. Proof size: {} bytes, {} public inputs",
+                    proof.len(),
+                    public_inputs.len()
+                );
+                let verified = match &self.execution.proof_verifier {
+                    Some(verifier) => verifier.verify(proof, public_inputs),
+                    None => Err(eyre!(
+                        "No STARK verifier is configured, proof verifications aren't accepted"
+                    )),
+                };
+                if let Err(e) = verified {
+                    return Err((INVALID_PROOF_CODE, format!("Invalid proof: {e:#}")));
+                }
+                // charged by size, see `TransactionType::execute`
+                proof.len() as u64
+            }
+            TransactionType::L1Handler(_) => unreachable!("L1 handlers are checked as calls"),
+        };
+
+        // executions using more fail in DeliverTx, see `ExecutionConfig::max_tx_gas`
+        if let Some(limit) = self
+            .execution
+            .max_tx_gas
+            .filter(|limit| gas_wanted > *limit)
+        {
+            return Err((
+                GAS_LIMIT_CODE,
+                format!(
+                    "Transaction is expected to use {gas_wanted} gas, over the limit of {limit}"
+                ),
+            ));
+        }
+        // so do signed transactions charged more than their max fee, see `block::apply`
+        if let Some(signature) = signature
+            .as_ref()
+            .filter(|_| !matches!(tx.transaction_type, TransactionType::L1Handler(_)))
+        {
+            let fee = self.fee(gas_wanted);
+            if fee > signature.max_fee {
+                return Err((
+                    GAS_LIMIT_CODE,
+                    format!(
+                        "Transaction is expected to cost {fee}, over its max fee of {}",
+                        signature.max_fee
+                    ),
+                ));
+            }
+        }
+
+        // the account's `__validate__` entry point is executed, so it's checked last
+        if let Some(signature) = &signature {
+            if let Err(e) = self.validate(&tx, signature) {
+                let code = match e.downcast_ref::<StepLimitExceeded>() {
+                    Some(_) => VALIDATION_STEPS_CODE,
+                    None => INVALID_TRANSACTION_CODE,
+                };
+                return Err((code, format!("Error checking signature: {e:#}")));
             }
         }
 
-        response::CheckTx {
+        let response = response::CheckTx {
+            priority,
+            gas_wanted: i64::try_from(gas_wanted).unwrap_or(i64::MAX),
             ..Default::default()
+        };
+        Ok((response, signature))
+    }
+
+    /// Admits a checked transaction in the mempool, see [`Self::admit`] and
+    /// [`Self::admit_unsigned`]. Returns the CheckTx response.
+    fn admit_checked(&self, checked: Result<Checked, (u32, String)>) -> response::CheckTx {
+        let admitted = checked.and_then(|(response, signature)| match signature {
+            Some(signature) => self.admit(&signature).map(|tip| response::CheckTx {
+                priority: i64::try_from(tip).unwrap_or(i64::MAX),
+                ..response
+            }),
+            None => self.admit_unsigned().map(|()| response),
+        });
+        admitted.unwrap_or_else(|(code, log)| rejection(code, log))
+    }
+
+    /// Turn of a CheckTx request to admit its transaction, taken as it's received: admissions
+    /// depend on the transactions admitted before, e.g. a sender's next nonce, so they're made
+    /// in the order the transactions were submitted even though they're checked concurrently.
+    /// Returns a receiver resolving once the transaction of the previous request was admitted
+    /// or rejected, and the sender to drop once this one is.
+    fn admission_turn(&self) -> (oneshot::Receiver<()>, oneshot::Sender<()>) {
+        let (sender, receiver) = oneshot::channel();
+        let previous = std::mem::replace(&mut *self.last_admission.lock().unwrap(), receiver);
+        (previous, sender)
+    }
+
+    /// Gas an invocation is expected to use, for Tendermint to pack blocks up to their gas
+    /// limit without executing it: that of an identical execution if one was memoized, or
+    /// an estimate from the program otherwise, see [`execution::estimate_gas`].
+    fn estimate_gas(&self, program: &Program, invocation: Invocation) -> u64 {
+        self.programs
+            .memoized_gas(&invocation)
+            .unwrap_or_else(|| execution::estimate_gas(program, &invocation.calldata))
+    }
+
+    /// Checks that a program can run on this node, and that the invoked function exists and
+    /// takes the given calldata. Fails with the CheckTx code rejecting it otherwise.
+    fn check_invocation(
+        &self,
+        program: &Program,
+        function: &EntryPoint,
+        calldata: &[Felt],
+    ) -> Result<(), (u32, String)> {
+        if calldata.len() > MAX_CALLDATA_LEN {
+            return Err((
+                TOO_LARGE_CODE,
+                format!("Calldata has more than {MAX_CALLDATA_LEN} values"),
+            ));
+        }
+        self.execution
+            .ensure_program_supported(program)
+            .map_err(|e| {
+                (
+                    INVALID_TRANSACTION_CODE,
+                    format!("Error checking transaction: {e}"),
+                )
+            })?;
+
+        let (function, _) = execution::resolve_entrypoint(program, function)
+            .map_err(|e| (UNKNOWN_ENTRY_POINT_CODE, e.to_string()))?;
+        let expected = execution::args_size(program, &function);
+        if calldata.len() != expected {
+            return Err((
+                INVALID_CALLDATA_CODE,
+                format!(
+                    "Function {function} takes {expected} arguments, got {}",
+                    calldata.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validation phase of a signed transaction against the last committed state, see
+    /// [`account::validate`].
+    fn validate(&self, tx: &Transaction, signature: &TxSignature) -> Result<()> {
+        let storage = self.storage.read().unwrap();
+        let classes = self.classes.read().unwrap();
+        account::validate(
+            tx,
+            signature,
+            &self.execution,
+            TxStorage::committed(&storage),
+            &classes,
+            &self.programs,
+        )
+    }
+
+    /// Fails unless an L1 handler transaction is sent by the chain's L1 relayer and its
+    /// message wasn't handled as of the last committed block.
+    fn check_l1_message(
+        &self,
+        message: &L1Message,
+        signature: Option<&TxSignature>,
+    ) -> Result<(), String> {
+        let (address, key) = state::l1_relayer_slot();
+        let relayer = self.committed_storage(&address, &key);
+        if relayer.is_zero() || signature.map(|signature| &signature.sender) != Some(&relayer) {
+            return Err("L1 handler transactions must be sent by the chain's L1 relayer".into());
+        }
+        let (address, key) = message.slot();
+        if !self.committed_storage(&address, &key).is_zero() {
+            return Err(format!(
+                "L1 message 0x{} was already handled",
+                hex::encode(message.hash())
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks a signed transaction against the last committed state and the transactions
+    /// already accepted in the mempool: its nonce must directly follow those of its sender,
+    /// the balance of its payer, the sender or its paymaster, must cover the max fees and tips
+    /// of all the transactions it pays for, and the sender may not have more pending
+    /// transactions than allowed. Counts it as pending once accepted, and returns the tip it's
+    /// prioritized by: the lowest of those of the sender's pending transactions, so that none
+    /// is included before one with a lower nonce. Fails with the CheckTx code rejecting it otherwise.
+    fn admit(&self, signature: &TxSignature) -> Result<u64, (u32, String)> {
+        let account = self.committed_account(&signature.sender);
+        let payer = signature.payer();
+        let payer_account = self.committed_account(payer);
+
+        let mut pending_senders = self.pending_senders.lock().unwrap();
+        let pending = pending_senders
+            .entry(signature.sender.clone())
+            .or_insert_with(|| PendingSender {
+                next_nonce: account.nonce,
+                ..Default::default()
+            });
+
+        if let Some(limit) = self.max_pending_per_sender {
+            if pending.transactions >= limit {
+                return Err((
+                    SENDER_LIMIT_CODE,
+                    format!("Sender already has {limit} transactions pending, the most allowed"),
+                ));
+            }
+        }
+        if signature.nonce < pending.next_nonce {
+            return Err((
+                INVALID_NONCE_CODE,
+                format!(
+                    "Nonce {} already used, next nonce is {}",
+                    signature.nonce, pending.next_nonce
+                ),
+            ));
+        }
+        if signature.nonce > pending.next_nonce {
+            return Err((
+                INVALID_NONCE_CODE,
+                format!(
+                    "Nonce {} skips nonce {}, which must be sent first",
+                    signature.nonce, pending.next_nonce
+                ),
+            ));
+        }
+
+        let charges = pending_senders
+            .get(payer)
+            .map_or(0, |pending| pending.charges)
+            .saturating_add(signature.max_fee)
+            .saturating_add(signature.tip);
+        if payer_account.balance < Felt::new(charges) {
+            return Err((
+                INSUFFICIENT_BALANCE_CODE,
+                format!(
+                    "Insufficient balance {} for max fees and tips {} of the payer's pending \
+                     transactions",
+                    payer_account.balance, charges
+                ),
+            ));
+        }
+
+        let mut priority = signature.tip;
+        if let Some(pending) = pending_senders.get_mut(&signature.sender) {
+            pending.transactions += 1;
+            pending.next_nonce += 1;
+            priority = pending.lowest_tip.map_or(priority, |tip| tip.min(priority));
+            pending.lowest_tip = Some(priority);
         }
+        pending_senders
+            .entry(payer.clone())
+            .or_insert_with(|| PendingSender {
+                next_nonce: payer_account.nonce,
+                ..Default::default()
+            })
+            .charges = charges;
+        Ok(priority)
+    }
+
+    /// Counts an unsigned transaction as pending, unless as many unsigned transactions as a
+    /// sender may have are already pending. Fails with the CheckTx code rejecting it otherwise.
+    fn admit_unsigned(&self) -> Result<(), (u32, String)> {
+        let mut pending = self.pending_unsigned.lock().unwrap();
+        if let Some(limit) = self.max_pending_per_sender {
+            if *pending >= limit {
+                return Err((
+                    SENDER_LIMIT_CODE,
+                    format!("{limit} unsigned transactions are already pending, the most allowed"),
+                ));
+            }
+        }
+        *pending += 1;
+        Ok(())
     }
 
     /// This hook is called before the app starts processing transactions on a block.
     /// Used to store current proposer and the previous block's voters to assign fees and coinbase
     /// credits when the block is committed.
-    fn begin_block(&self, _request: request::BeginBlock) -> response::BeginBlock {
-        unsafe {
-            TRANSACTIONS = 0;
-
-            info!(
-                "{} ms passed between previous begin_block() and current begin_block()",
-                (*TIMER).elapsed().as_millis()
-            );
+    fn begin_block(&self, request: request::BeginBlock) -> response::BeginBlock {
+        if let Some(height) = *self.halted.borrow() {
+            panic!("The chain halted at height {height}, the node must be upgraded or restarted");
+        }
 
-            *TIMER = Instant::now();
+        // the commitment of the previous block's state diff is part of this block's state
+        if let Some((height, commitment)) = self.da_commitment.lock().unwrap().take() {
+            let mut writes = StorageWrites::new();
+            writes.insert(state::da_commitment_slot(height), commitment);
+            self.storage.write().unwrap().apply(writes);
         }
 
+        let mut block = self.block.lock().unwrap();
+        *block = BlockMetrics {
+            started: Some(Instant::now()),
+            interval: block.started.map(|started| started.elapsed()),
+            height: request.header.height.value(),
+            ..Default::default()
+        };
+
         Default::default()
     }
 
@@ -117,75 +1246,377 @@ impl StarknetApp {
     /// for example storing the program verifying keys upon a valid deployment.
     /// Here is also where transactions are indexed for querying the blockchain.
     fn deliver_tx(&self, request: request::DeliverTx) -> response::DeliverTx {
-        let tx: Transaction = bincode::deserialize(&request.tx).unwrap();
+        self.archive_tx(&request.tx);
+        // proposers can include any bytes, which every node must reject alike
+        let tx: Transaction = match bincode::deserialize(&request.tx) {
+            Ok(tx) => tx,
+            Err(e) => return self.undecodable_tx(e),
+        };
 
         // Validation consists of getting the hash and checking whether it is equal
         // to the tx id. The hash executes the program and hashes the trace.
+        // Storage writes of the transaction are only staged if the check passes.
+        if let Err(e) = self.check_duplicate(&tx) {
+            return self.deliver_response(tx, Err(e));
+        }
+
+        let delivery = block::deliver(
+            &tx,
+            &self.execution,
+            &mut self.storage.write().unwrap(),
+            &mut self.classes.write().unwrap(),
+            &self.programs,
+        );
+
+        self.deliver_response(tx, delivery)
+    }
+
+    /// In parallel execution mode, transactions start executing in the background as soon
+    /// as they're delivered, but are only applied at end_block. The returned channel receives
+    /// the DeliverTx response once the block has been executed.
+    fn queue_deliver_tx(
+        &self,
+        request: request::DeliverTx,
+        workers: usize,
+    ) -> oneshot::Receiver<response::DeliverTx> {
+        self.archive_tx(&request.tx);
+        let (sender, receiver) = oneshot::channel();
+        let tx = match bincode::deserialize::<Transaction>(&request.tx) {
+            Ok(tx) => tx,
+            Err(e) => {
+                let _ = sender.send(self.undecodable_tx(e));
+                return receiver;
+            }
+        };
+        if let Err(e) = self.check_duplicate(&tx) {
+            let _ = sender.send(self.deliver_response(tx, Err(e)));
+            return receiver;
+        }
+
+        let tx = Arc::new(tx);
+        let speculation = self.speculate(tx.clone(), workers);
+
+        self.pending.lock().unwrap().push((tx, speculation, sender));
+        receiver
+    }
 
-        let tx_hash = tx
-            .transaction_type
-            .compute_and_hash()
-            .map(|x| x == tx.transaction_hash);
+    /// Records a delivered transaction in the block to archive, if blocks are archived. It's
+    /// recorded before being decoded, so the block can be replayed as Tendermint delivered it.
+    fn archive_tx(&self, tx: &Bytes) {
+        if self.archive.is_some() {
+            self.block.lock().unwrap().txs.push(tx.clone());
+        }
+    }
 
-        unsafe {
-            TRANSACTIONS += 1;
+    /// Fails if the transaction was already delivered in this block or included in a recent
+    /// one, see [`SeenTransactions`].
+    fn check_duplicate(&self, tx: &Transaction) -> Result<()> {
+        let height = self.block.lock().unwrap().height;
+        ensure!(
+            self.seen.deliver(tx.digest(), height),
+            "Transaction already included in a recent block"
+        );
+        Ok(())
+    }
+
+    /// Queues a transaction to be executed by a parallel execution worker, against the state
+    /// at the start of the block, starting a worker if fewer than `workers` are running. The
+    /// returned channel receives the execution.
+    fn speculate(&self, tx: Arc<Transaction>, workers: usize) -> mpsc::Receiver<Result<Execution>> {
+        let (sender, receiver) = mpsc::sync_channel(1);
+
+        let mut queue = self.speculation.lock().unwrap();
+        queue.transactions.push_back((tx, sender));
+        if queue.workers < workers.max(1) {
+            queue.workers += 1;
+            let app = self.clone();
+            std::thread::spawn(move || app.run_speculation_worker());
         }
+        receiver
+    }
+
+    fn run_speculation_worker(&self) {
+        loop {
+            let next = {
+                let mut queue = self.speculation.lock().unwrap();
+                let next = queue.transactions.pop_front();
+                if next.is_none() {
+                    queue.workers -= 1;
+                }
+                next
+            };
+            let Some((tx, sender)) = next else { return };
+
+            // nothing is applied before end_block, which waits for every execution before
+            // taking the write locks. A panicking execution is treated as a failed one, which
+            // is retried when the block is applied.
+            let execution = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                tx.transaction_type.execute(
+                    &self.execution,
+                    TxStorage::new(&self.storage.read().unwrap()),
+                    &self.classes.read().unwrap(),
+                    &self.programs,
+                )
+            }))
+            .unwrap_or_else(|_| {
+                Err(eyre!(
+                    "Speculative execution of {} panicked",
+                    tx.transaction_hash
+                ))
+            });
+            // the transaction is handed back to end_block once executed
+            drop(tx);
+            let _ = sender.send(execution);
+        }
+    }
+
+    /// Applies the transactions queued in parallel execution mode, once they've been
+    /// executed, and sends their responses.
+    fn deliver_pending(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+
+        let mut txs = Vec::with_capacity(pending.len());
+        let mut speculative = Vec::with_capacity(pending.len());
+        let mut senders = Vec::with_capacity(pending.len());
+        for (tx, speculation, sender) in pending {
+            speculative.push(speculation.recv().expect("every transaction is executed"));
+            txs.push(Arc::try_unwrap(tx).unwrap_or_else(|tx| (*tx).clone()));
+            senders.push(sender);
+        }
+
+        let deliveries = block::deliver_speculated(
+            &txs,
+            speculative,
+            &self.execution,
+            &mut self.storage.write().unwrap(),
+            &mut self.classes.write().unwrap(),
+            &self.programs,
+        );
+
+        for ((tx, delivery), sender) in txs.into_iter().zip(deliveries).zip(senders) {
+            // the receiver is only gone if the connection was dropped
+            let _ = sender.send(self.deliver_response(tx, delivery));
+        }
+    }
+
+    /// Builds the DeliverTx response of an executed transaction, indexing it for queries.
+    fn deliver_response(&self, tx: Transaction, delivery: Delivery) -> response::DeliverTx {
+        self.block.lock().unwrap().transactions += 1;
+
+        match delivery {
+            Ok(Some(execution)) => {
+                self.metrics.observe_transaction("applied");
+                if let Some(profiler) = &self.profiler {
+                    profiler.record(&tx.transaction_type, &execution);
+                }
+                {
+                    let mut block = self.block.lock().unwrap();
+                    block.gas += execution.gas_used;
+                    let receipt = self.receipt(&tx, &execution, block.height);
+                    block.receipts.push(receipt);
+                }
 
-        match tx_hash {
-            Ok(true) => {
                 let _ = self
                     .hasher
                     .lock()
-                    .map(|mut hash| hash.update(tx.transaction_hash.clone()));
+                    .map(|mut hash| hash.update(&tx.transaction_hash));
 
-                // prepare this transaction to be queried by app.tx_id
-                let index_event = abci::Event {
-                    kind: "app".to_string(),
-                    attributes: vec![abci::EventAttribute {
-                        index: true,
-                        key: "tx_id".to_string(),
-                        value: tx.transaction_hash.to_string(),
-                    }],
-                };
-                let mut events = vec![index_event];
+                let mut events = vec![index_event(&tx)];
 
                 match tx.transaction_type {
-                    TransactionType::FunctionExecution {
-                        program: _program,
-                        function,
-                        program_name: _,
-                        enable_trace: _,
-                    } => {
+                    TransactionType::FunctionExecution { function, .. }
+                    | TransactionType::ClassExecution { function, .. } => {
                         let function_event = abci::Event {
                             kind: "function".to_string(),
                             attributes: vec![abci::EventAttribute {
                                 key: "function".to_string(),
-                                value: function,
+                                value: function.to_string(),
                                 index: true,
                             }],
                         };
                         events.push(function_event);
                     }
+                    // proofs that can't be verified fail, so this one was
+                    TransactionType::ProofVerification { .. } => {
+                        self.block
+                            .lock()
+                            .unwrap()
+                            .verified_proofs
+                            .push(tx.transaction_hash.clone());
+
+                        let proof_event = abci::Event {
+                            kind: "proof".to_string(),
+                            attributes: vec![abci::EventAttribute {
+                                key: "status".to_string(),
+                                value: "verified".to_string(),
+                                index: true,
+                            }],
+                        };
+                        events.push(proof_event);
+                    }
+                    // indexed by message hash, so the handling of an L1 message can be found
+                    TransactionType::L1Handler(message) => {
+                        let message_event = abci::Event {
+                            kind: "l1_message".to_string(),
+                            attributes: vec![
+                                abci::EventAttribute {
+                                    key: "hash".to_string(),
+                                    value: hex::encode(message.hash()),
+                                    index: true,
+                                },
+                                abci::EventAttribute {
+                                    key: "function".to_string(),
+                                    value: felt_to_hex(&message.selector),
+                                    index: true,
+                                },
+                            ],
+                        };
+                        events.push(message_event);
+                    }
                 }
 
+                // contract events are indexed by emitting address and keys
+                events.extend(execution.events.into_iter().map(|event| {
+                    let mut attributes = vec![abci::EventAttribute {
+                        key: "from_address".to_string(),
+                        value: felt_to_hex(&event.from_address),
+                        index: true,
+                    }];
+                    attributes.extend(event.keys.iter().map(|key| abci::EventAttribute {
+                        key: "key".to_string(),
+                        value: felt_to_hex(key),
+                        index: true,
+                    }));
+                    attributes.push(abci::EventAttribute {
+                        key: "data".to_string(),
+                        value: event
+                            .data
+                            .iter()
+                            .map(felt_to_hex)
+                            .collect::<Vec<_>>()
+                            .join(","),
+                        index: false,
+                    });
+
+                    abci::Event {
+                        kind: "contract_event".to_string(),
+                        attributes,
+                    }
+                }));
+
+                // return values, gas and fee, for receipts
+                events.push(abci::Event {
+                    kind: "execution".to_string(),
+                    attributes: vec![
+                        abci::EventAttribute {
+                            key: "retdata".to_string(),
+                            value: execution
+                                .retdata
+                                .iter()
+                                .map(felt_to_hex)
+                                .collect::<Vec<_>>()
+                                .join(","),
+                            index: false,
+                        },
+                        abci::EventAttribute {
+                            key: "gas_used".to_string(),
+                            value: execution.gas_used.to_string(),
+                            index: false,
+                        },
+                        abci::EventAttribute {
+                            key: "fee".to_string(),
+                            value: execution.fee.to_string(),
+                            index: false,
+                        },
+                    ],
+                });
+
                 response::DeliverTx {
                     events,
                     data: tx.transaction_hash.into(),
+                    gas_used: execution.gas_used as i64,
                     ..Default::default()
                 }
             }
-            Ok(false) => response::DeliverTx {
-                code: 1.into(),
-                log: "Error delivering transaction. Integrity check failed.".to_string(),
-                info: "Error delivering transaction. Integrity check failed.".to_string(),
-                ..Default::default()
-            },
-            Err(e) => response::DeliverTx {
-                code: 1.into(),
-                log: format!("Error delivering transaction: {e}"),
-                info: format!("Error delivering transaction: {e}"),
-                ..Default::default()
+            Ok(None) => {
+                self.metrics.observe_transaction("invalid");
+                response::DeliverTx {
+                    code: 1.into(),
+                    log: "Error delivering transaction. Integrity check failed.".to_string(),
+                    info: "Error delivering transaction. Integrity check failed.".to_string(),
+                    events: vec![index_event(&tx)],
+                    ..Default::default()
+                }
+            }
+            Err(e) => {
+                self.metrics.observe_transaction("failed");
+                response::DeliverTx {
+                    code: 1.into(),
+                    log: format!("Error delivering transaction: {e:#}"),
+                    info: format!("Error delivering transaction: {e:#}"),
+                    events: vec![index_event(&tx)],
+                    ..Default::default()
+                }
+            }
+        }
+    }
+
+    /// DeliverTx response of bytes that aren't a transaction, which fails like a transaction
+    /// that can't be executed.
+    fn undecodable_tx(&self, e: bincode::Error) -> response::DeliverTx {
+        self.block.lock().unwrap().transactions += 1;
+        self.metrics.observe_transaction("failed");
+        response::DeliverTx {
+            code: 1.into(),
+            log: format!("Error decoding transaction: {e}"),
+            info: format!("Error decoding transaction: {e}"),
+            ..Default::default()
+        }
+    }
+
+    /// Height to halt at: the earliest of the one set by the operator and the one set by
+    /// governance, unless that one is skipped.
+    fn halt_height(&self) -> Option<u64> {
+        let governance_halt = self
+            .governance
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|governance| {
+                let halt = self
+                    .storage
+                    .read()
+                    .unwrap()
+                    .read_committed(governance, &state::halt_height_key())
+                    .to_u64()?;
+                (halt != 0 && !self.skipped_halt_heights.contains(&halt)).then_some(halt)
+            });
+
+        match (self.halt_height, governance_halt) {
+            (Some(operator), Some(governance)) => Some(operator.min(governance)),
+            (operator, governance) => operator.or(governance),
+        }
+    }
+
+    /// Receipt of a transaction applied in the block at `height`.
+    fn receipt(&self, tx: &Transaction, execution: &Execution, height: u64) -> Receipt {
+        Receipt {
+            transaction_hash: tx.transaction_hash.clone(),
+            height,
+            sender: tx
+                .signature
+                .as_ref()
+                .map(|signature| signature.sender.clone()),
+            function: match &tx.transaction_type {
+                TransactionType::FunctionExecution { function, .. }
+                | TransactionType::ClassExecution { function, .. } => Some(function.to_string()),
+                TransactionType::ProofVerification { .. } => None,
+                TransactionType::L1Handler(message) => Some(felt_to_hex(&message.selector)),
             },
+            retdata: execution.retdata.clone(),
+            events: execution.events.clone(),
+            gas: execution.gas_used,
+            fee: execution.fee,
         }
     }
 
@@ -193,14 +1624,13 @@ impl StarknetApp {
     /// For details about validator set update semantics see:
     /// https://github.com/tendermint/tendermint/blob/v0.34.x/spec/abci/apps.md#endblock
     fn end_block(&self, _request: request::EndBlock) -> response::EndBlock {
-        unsafe {
-            info!(
-                "Committing block with {} transactions in {} ms. TPS: {}",
-                TRANSACTIONS,
-                (*TIMER).elapsed().as_millis(),
-                (TRANSACTIONS * 1000) as f32 / ((*TIMER).elapsed().as_millis() as f32)
-            );
+        if self.parallelism.is_some() {
+            self.deliver_pending();
         }
+
+        let mut block = self.block.lock().unwrap();
+        block.execution = block.elapsed();
+
         response::EndBlock {
             ..Default::default()
         }
@@ -217,26 +1647,129 @@ impl StarknetApp {
         // in the blockchain transactions (as tendermint already accounts for that with other hashes).
         // https://github.com/tendermint/tendermint/issues/1179
         // https://github.com/tendermint/tendermint/blob/v0.34.x/spec/abci/apps.md#query-proofs
+        let started = Instant::now();
 
+        let (storage_writes, journal) = self
+            .storage
+            .write()
+            .map(|mut storage| {
+                let writes = storage.commit();
+                self.metrics.set_storage_slots(storage.committed_slots());
+                (writes, storage.take_journal())
+            })
+            .unwrap_or_default();
+        let state_diff = self
+            .data_availability
+            .load(Ordering::Relaxed)
+            .then(|| StateDiff {
+                height: self.block.lock().unwrap().height,
+                writes: storage_writes.clone(),
+            });
+        let state_update = self.state_updates.as_ref().map(|_| StateUpdate {
+            height: self.block.lock().unwrap().height,
+            old_root: self.chain.last_block().app_hash,
+            new_root: Vec::new(),
+            writes: storage_writes.clone(),
+        });
+        self.queries.clear();
+        // the transactions left in the mempool are rechecked, and counted again
+        self.pending_senders.lock().unwrap().clear();
+        *self.pending_unsigned.lock().unwrap() = 0;
+
+        // error should be handled here, an empty hash is returned instead
         let app_hash = self
             .hasher
             .lock()
-            .map(|hasher| hasher.clone().finalize().as_slice().to_vec());
+            .map(|mut hasher| {
+                hash_writes(&mut hasher, storage_writes);
+                hasher.clone().finalize().as_slice().to_vec()
+            })
+            .unwrap_or_default();
+
+        // files are written once per block, with a single sync each
+        if let Err(e) = self.programs.flush(self.commit_sync) {
+            warn!("Error writing programs to the program cache: {e}");
+        }
+        let height = self.chain.commit(app_hash.clone(), self.commit_sync);
+        // recorded at the height duplicates are checked against
+        let block_height = self.block.lock().unwrap().height;
+        if let Err(e) = self.seen.commit(block_height, self.commit_sync) {
+            warn!("Error writing seen transactions: {e}");
+        }
+        if let (Some(audit), Some(writes)) = (&self.audit, journal) {
+            let block = BlockAudit {
+                height: height.value(),
+                writes,
+            };
+            if let Err(e) = audit.append(&block, self.commit_sync) {
+                warn!("Error writing the audit log: {e}");
+            }
+        }
+        if let Some(archive) = &self.archive {
+            let block = ArchivedBlock {
+                height: height.value(),
+                txs: std::mem::take(&mut self.block.lock().unwrap().txs),
+                app_hash: app_hash.clone(),
+            };
+            if let Err(e) = archive.write_block(&block, self.commit_sync) {
+                warn!("Error archiving block {height}: {e}");
+            }
+        }
+        // a node that can't record the commitment would fork from the others, so crash instead
+        let state_diff = state_diff.map(|diff| diff.blob().expect("State diffs can be encoded"));
+        if let Some(blob) = &state_diff {
+            *self.da_commitment.lock().unwrap() = Some((blob.height, blob.commitment.clone()));
+        }
+        if let (Some(updates), Some(mut update)) = (&self.state_updates, state_update) {
+            update.new_root = app_hash.clone();
+            let mut updates = updates.lock().unwrap();
+            updates.push_back(update);
+            while updates.len() > self.state_update_history {
+                updates.pop_front();
+            }
+        }
+        *self.last_commit.lock().unwrap() = Some((height, Instant::now()));
 
-        let height = HeightFile::increment();
+        let (summary, receipts) = {
+            let mut block = self.block.lock().unwrap();
+            let summary = BlockSummary {
+                height: height.value(),
+                transactions: block.transactions,
+                gas: block.gas,
+                interval: block.interval,
+                execution: block.execution,
+                commit: started.elapsed(),
+                app_hash: app_hash.clone(),
+                verified_proofs: std::mem::take(&mut block.verified_proofs),
+            };
+            summary.log();
+            self.metrics.observe_block(&summary);
+            (summary, std::mem::take(&mut block.receipts))
+        };
+        if let Some(profiler) = &self.profiler {
+            profiler.finish_block(height.value());
+        }
 
-        info!("Committing height {}", height,);
+        if self
+            .halt_height()
+            .map_or(false, |halt| height.value() >= halt)
+        {
+            info!("Halting at height {height}");
+            self.halted.send_replace(Some(height));
+        }
 
-        match app_hash {
-            Ok(hash) => response::Commit {
-                data: hash.into(),
-                retain_height: Height::default(),
-            },
-            // error should be handled here
-            _ => response::Commit {
-                data: vec![].into(),
-                retain_height: Height::default(),
-            },
+        // nobody may be subscribed, in which case receipts are just dropped
+        for receipt in receipts {
+            let _ = self.receipts.send(receipt);
+        }
+        let _ = self.blocks.send(summary);
+        if let Some(blob) = state_diff {
+            let _ = self.state_diffs.send(blob);
+        }
+
+        response::Commit {
+            data: app_hash.into(),
+            retain_height: Height::default(),
         }
     }
 }
@@ -251,12 +1784,131 @@ impl Service<Request> for StarknetApp {
     }
 
     fn call(&mut self, request: Request) -> Self::Future {
-        info!(?request);
+        let hook = hook_name(&request);
+        let started = Instant::now();
+        let span = self.request_span(&request, hook);
+        let _entered = span.enter();
+        // requests carry whole programs, so they're only logged at the most verbose level
+        trace!(?request);
+
+        match request {
+            // the server keeps forwarding requests while earlier responses are pending, so
+            // DeliverTx responses can wait for the block to be executed at EndBlock
+            Request::DeliverTx(deliver_tx) if self.parallelism.is_some() => {
+                let workers = self.parallelism.as_ref().unwrap().load(Ordering::Relaxed);
+                let receiver = self.queue_deliver_tx(deliver_tx, workers);
+                let metrics = self.metrics.clone();
+                async move {
+                    let response = Response::DeliverTx(receiver.await?);
+                    record_handled(&metrics, hook, started, &response);
+                    Ok(response)
+                }
+                .instrument(span.clone())
+                .boxed()
+            }
+
+            // transactions are checked concurrently on the blocking pool rather than holding up
+            // consensus requests, then admitted in the order they were received
+            Request::CheckTx(check_tx) => {
+                let app = self.clone();
+                let blocking_span = span.clone();
+                let (turn, next_turn) = self.admission_turn();
+                async move {
+                    let permit = app.checks.clone().acquire_owned().await?;
+                    let checker = app.clone();
+                    let checked = tokio::task::spawn_blocking(move || {
+                        let _permit = permit;
+                        let _entered = blocking_span.enter();
+                        checker.check(check_tx)
+                    })
+                    .await?;
+
+                    // the previous request's sender is dropped once it's handled, or cancelled
+                    let _ = turn.await;
+                    let response = Response::CheckTx(app.admit_checked(checked));
+                    drop(next_turn);
+                    record_handled(&app.metrics, hook, started, &response);
+                    Ok(response)
+                }
+                .instrument(span.clone())
+                .boxed()
+            }
+
+            // queries don't depend on each other, so they're executed concurrently on the
+            // blocking pool rather than holding up consensus requests
+            request @ Request::Query(_) => {
+                let app = self.clone();
+                let blocking_span = span.clone();
+                async move {
+                    let permit = app.checks.clone().acquire_owned().await?;
+                    let response = tokio::task::spawn_blocking(move || {
+                        let _permit = permit;
+                        let _entered = blocking_span.enter();
+                        app.respond(request, hook, started)
+                    })
+                    .await??;
+                    Ok(response)
+                }
+                .instrument(span.clone())
+                .boxed()
+            }
+
+            // consensus requests must be executed in order, so they're executed before
+            // returning, but without blocking the other tasks of the runtime
+            request => {
+                let response = block_in_place(|| self.respond(request, hook, started));
+                async move { Ok(response?) }.boxed()
+            }
+        }
+    }
+}
 
+impl StarknetApp {
+    /// Handles a request, recording how long it took since it was received. Errors are
+    /// logged, and returned to the ABCI server, which closes the connection.
+    fn respond(&self, request: Request, hook: &str, started: Instant) -> Result<Response> {
+        let response = self.handle(request).map_err(|e| {
+            error!("Error handling {hook}: {e:#}");
+            e
+        })?;
+        record_handled(&self.metrics, hook, started, &response);
+        Ok(response)
+    }
+
+    /// Span of an ABCI request, carrying its kind, the hash of the transaction it carries as
+    /// computed by Tendermint, and the height it applies to. Its duration is recorded once
+    /// it's handled.
+    fn request_span(&self, request: &Request, hook: &'static str) -> Span {
+        let tx = match request {
+            Request::CheckTx(check_tx) => Some(&check_tx.tx),
+            Request::DeliverTx(deliver_tx) => Some(&deliver_tx.tx),
+            _ => None,
+        }
+        .map(|tx| hex::encode_upper(Sha256::digest(tx)));
+
+        let height = match request {
+            Request::BeginBlock(begin_block) => Some(begin_block.header.height.value()),
+            Request::EndBlock(end_block) => u64::try_from(end_block.height).ok(),
+            Request::Query(query) => Some(query.height.value()),
+            Request::DeliverTx(_) | Request::Commit => Some(self.block.lock().unwrap().height),
+            _ => None,
+        };
+
+        info_span!(
+            "abci",
+            kind = hook,
+            tx = tx.as_deref(),
+            height,
+            duration_ms = field::Empty
+        )
+    }
+
+    fn handle(&self, request: Request) -> Result<Response> {
         let response = match request {
             // handled messages
             Request::Commit => Response::Commit(self.commit()),
             Request::Info(info) => Response::Info(self.info(info)),
+            Request::InitChain(init_chain) => Response::InitChain(self.init_chain(init_chain)?),
             Request::Query(query) => Response::Query(self.query(query)),
             Request::CheckTx(check_tx) => Response::CheckTx(self.check_tx(check_tx)),
             Request::EndBlock(end_block) => Response::EndBlock(self.end_block(end_block)),
@@ -266,7 +1918,6 @@ impl Service<Request> for StarknetApp {
             // unhandled messages
             Request::Flush => Response::Flush,
             Request::Echo(_) => Response::Echo(Default::default()),
-            Request::InitChain(_) => Response::InitChain(Default::default()),
             Request::ListSnapshots => Response::ListSnapshots(Default::default()),
             Request::OfferSnapshot(_) => Response::OfferSnapshot(Default::default()),
             Request::LoadSnapshotChunk(_) => Response::LoadSnapshotChunk(Default::default()),
@@ -277,35 +1928,194 @@ impl Service<Request> for StarknetApp {
                 info: String::from("N/A"),
             }),
         };
+        Ok(response)
+    }
+}
+
+/// Records how long a request took in its span and in the latency histogram of its hook,
+/// logging the response at the most verbose level.
+fn record_handled(metrics: &Metrics, hook: &str, started: Instant, response: &Response) {
+    let elapsed = started.elapsed();
+    metrics.observe_hook(hook, elapsed);
+    let duration_ms = elapsed.as_secs_f64() * 1000.0;
+    Span::current().record("duration_ms", duration_ms);
+    debug!(duration_ms, "Handled {hook}");
+    trace!(?response);
+}
+
+/// Runs CPU-heavy work without blocking the other tasks of the runtime worker it's called
+/// on. Only multi-threaded runtimes can move them to other workers; others just run it.
+fn block_in_place<R>(work: impl FnOnce() -> R) -> R {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(work)
+        }
+        _ => work(),
+    }
+}
+
+/// Name of the ABCI hook handling a request, as reported in metrics.
+fn hook_name(request: &Request) -> &'static str {
+    match request {
+        Request::Echo(_) => "echo",
+        Request::Flush => "flush",
+        Request::Info(_) => "info",
+        Request::SetOption(_) => "set_option",
+        Request::InitChain(_) => "init_chain",
+        Request::Query(_) => "query",
+        Request::BeginBlock(_) => "begin_block",
+        Request::CheckTx(_) => "check_tx",
+        Request::DeliverTx(_) => "deliver_tx",
+        Request::EndBlock(_) => "end_block",
+        Request::Commit => "commit",
+        Request::ListSnapshots => "list_snapshots",
+        Request::OfferSnapshot(_) => "offer_snapshot",
+        Request::LoadSnapshotChunk(_) => "load_snapshot_chunk",
+        Request::ApplySnapshotChunk(_) => "apply_snapshot_chunk",
+    }
+}
+
+/// CheckTx response rejecting a transaction with the given code.
+fn rejection(code: u32, log: String) -> response::CheckTx {
+    response::CheckTx {
+        code: code.into(),
+        info: log.clone(),
+        log,
+        ..Default::default()
+    }
+}
 
-        tracing::info!(?response);
+/// Event indexing a delivered transaction, whether it succeeded or not, so it can be queried
+/// by `app.hash` (see [`Transaction::hash`]), by `app.tx_id`, its trace hash, and by
+/// `app.sender` if signed.
+fn index_event(tx: &Transaction) -> abci::Event {
+    let mut attributes = vec![
+        abci::EventAttribute {
+            index: true,
+            key: "hash".to_string(),
+            value: tx.hash(),
+        },
+        abci::EventAttribute {
+            index: true,
+            key: "tx_id".to_string(),
+            value: tx.transaction_hash.to_string(),
+        },
+    ];
+    if let Some(signature) = &tx.signature {
+        attributes.push(abci::EventAttribute {
+            index: true,
+            key: "sender".to_string(),
+            value: felt_to_hex(&signature.sender),
+        });
+    }
+    abci::Event {
+        kind: "app".to_string(),
+        attributes,
+    }
+}
 
-        async move { Ok(response) }.boxed()
+/// Folds committed storage writes into the running app hash.
+fn hash_writes(hasher: &mut Sha256, storage_writes: StorageWrites) {
+    for ((address, key), value) in storage_writes {
+        hasher.update(address.to_bytes_be());
+        hasher.update(key.to_bytes_be());
+        hasher.update(value.to_bytes_be());
     }
 }
 
-/// Local file used to track the last block height seen by the abci application.
-struct HeightFile;
+/// Parses the address given as query data, in decimal or 0x-prefixed hex.
+fn parse_address(data: &[u8]) -> Result<Felt> {
+    execution::parse_felt(std::str::from_utf8(data)?.trim())
+}
 
-impl HeightFile {
-    fn read_or_create() -> Height {
-        // if height file is missing or unreadable, create a new one from zero height
-        if let Ok(bytes) = std::fs::read(HEIGHT_PATH) {
-            // if contents are not readable, crash intentionally
-            bincode::deserialize(&bytes).expect("Contents of height file are not readable")
-        } else {
-            let height = Height::default();
-            std::fs::write(HEIGHT_PATH, bincode::serialize(&height).unwrap()).unwrap();
-            height
+/// Chain ID, and height and app hash of the last committed block, kept in memory and written
+/// to `abci.height` in the data dir at InitChain and every commit. The node always starts over
+/// from zero height, as the state is kept in memory, so only the chain ID is read back: it's
+/// reported before Tendermint initializes the chain again, which it must do with the same ID.
+#[derive(Debug, Clone)]
+struct ChainMetadata {
+    path: PathBuf,
+    last_block: Arc<Mutex<LastBlock>>,
+    created: Arc<Once>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct LastBlock {
+    height: Height,
+    app_hash: Vec<u8>,
+    /// Chain the node was initialized with.
+    chain_id: Option<String>,
+}
+
+impl ChainMetadata {
+    fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join("abci.height"),
+            last_block: Default::default(),
+            created: Default::default(),
         }
     }
 
-    fn increment() -> Height {
-        // if the file is missing or contents are unexpected, we crash intentionally;
-        let height = bincode::deserialize::<Height>(&std::fs::read(HEIGHT_PATH).unwrap())
-            .unwrap()
-            .increment();
-        std::fs::write(HEIGHT_PATH, bincode::serialize(&height).unwrap()).unwrap();
-        height
+    /// Metadata of the chain in `data_dir`, starting from the chain ID written by a previous
+    /// run, if any.
+    fn open(data_dir: &Path) -> Self {
+        let metadata = Self::new(data_dir);
+        let chain_id = std::fs::read(&metadata.path)
+            .ok()
+            .and_then(|bytes| bincode::deserialize::<LastBlock>(&bytes).ok())
+            .and_then(|last_block| last_block.chain_id);
+        metadata.last_block.lock().unwrap().chain_id = chain_id;
+        metadata
+    }
+
+    fn last_block(&self) -> LastBlock {
+        self.last_block.lock().unwrap().clone()
+    }
+
+    /// Records the chain the node is initialized with. The file is flushed to disk if `sync`
+    /// is set.
+    fn init(&self, chain_id: String, sync: bool) {
+        let mut last_block = self.last_block.lock().unwrap();
+        last_block.chain_id = Some(chain_id);
+
+        // if the file can't be written, we crash intentionally
+        self.write(&last_block, sync)
+            .expect("must be able to write the chain metadata");
+    }
+
+    /// Records the next block as committed with `app_hash`, returning its height. The file
+    /// is flushed to disk if `sync` is set.
+    fn commit(&self, app_hash: Vec<u8>, sync: bool) -> Height {
+        let mut last_block = self.last_block.lock().unwrap();
+        last_block.height = last_block.height.increment();
+        last_block.app_hash = app_hash;
+
+        // if the file can't be written, we crash intentionally
+        self.write(&last_block, sync)
+            .expect("must be able to write the chain metadata");
+        last_block.height
+    }
+
+    /// Writes the file atomically: to a temporary file first, renamed once complete.
+    fn write(&self, last_block: &LastBlock, sync: bool) -> Result<()> {
+        let data_dir = self
+            .path
+            .parent()
+            .expect("metadata file is in the data dir");
+        self.created.call_once(|| {
+            std::fs::create_dir_all(data_dir).expect("must be able to create data dir")
+        });
+
+        let temporary = self.path.with_extension("height.tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(&bincode::serialize(last_block)?)?;
+        if sync {
+            file.sync_all()?;
+        }
+        std::fs::rename(&temporary, &self.path)?;
+        if sync {
+            File::open(data_dir)?.sync_all()?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use color_eyre::eyre::WrapErr;
+use color_eyre::Result;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Name of the block archive in the data dir.
+pub const BLOCK_ARCHIVE_DIR: &str = "blocks";
+
+/// The InitChain request a chain started with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedGenesis {
+    pub chain_id: String,
+    /// `app_state` of the genesis file, as a JSON [`Genesis`](crate::genesis::Genesis).
+    pub app_state: Bytes,
+}
+
+/// A committed block: its transactions as Tendermint delivered them, and the app hash it was
+/// committed with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedBlock {
+    pub height: u64,
+    pub txs: Vec<Bytes>,
+    pub app_hash: Vec<u8>,
+}
+
+/// Directory holding the genesis and every committed block of the chain, bincode-encoded in
+/// `genesis.bin` and `block-<height>.bin`, so the chain can be replayed without Tendermint to
+/// check it's executed deterministically.
+///
+/// As the node replays the chain from genesis when it restarts, the files are written again,
+/// with the same contents unless execution changed.
+#[derive(Debug, Clone)]
+pub struct BlockArchive {
+    dir: PathBuf,
+}
+
+impl BlockArchive {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    pub fn write_genesis(&self, genesis: &ArchivedGenesis, sync: bool) -> Result<()> {
+        self.write(&self.genesis_path(), genesis, sync)
+    }
+
+    pub fn write_block(&self, block: &ArchivedBlock, sync: bool) -> Result<()> {
+        self.write(&self.block_path(block.height), block, sync)
+    }
+
+    pub fn read_genesis(&self) -> Result<ArchivedGenesis> {
+        let path = self.genesis_path();
+        read(&path).wrap_err_with(|| format!("Error reading {}", path.display()))
+    }
+
+    /// Block at `height`, or None if it wasn't archived.
+    pub fn read_block(&self, height: u64) -> Result<Option<ArchivedBlock>> {
+        let path = self.block_path(height);
+        if !path.exists() {
+            return Ok(None);
+        }
+        read(&path)
+            .map(Some)
+            .wrap_err_with(|| format!("Error reading {}", path.display()))
+    }
+
+    fn genesis_path(&self) -> PathBuf {
+        self.dir.join("genesis.bin")
+    }
+
+    fn block_path(&self, height: u64) -> PathBuf {
+        self.dir.join(format!("block-{height}.bin"))
+    }
+
+    /// Writes a file atomically: to a temporary file first, renamed once complete. With
+    /// `sync`, the file is flushed to disk before returning.
+    fn write(&self, path: &Path, value: &impl Serialize, sync: bool) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let temporary = path.with_extension("bin.tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(&bincode::serialize(value)?)?;
+        if sync {
+            file.sync_all()?;
+        }
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+}
+
+fn read<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    Ok(bincode::deserialize(&std::fs::read(path)?)?)
+}
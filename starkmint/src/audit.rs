@@ -0,0 +1,88 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use cairo_felt::Felt;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::execution::felt_to_hex;
+
+/// Name of the audit log in the data dir.
+pub const AUDIT_LOG_FILE: &str = "abci.audit";
+
+/// A storage write of a delivered transaction, with the hashes of the slot's values before
+/// and after it, so the logs of two nodes can be diffed without dumping their state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateWrite {
+    pub transaction_hash: String,
+    pub address: String,
+    pub key: String,
+    pub old_value_hash: String,
+    pub new_value_hash: String,
+}
+
+impl StateWrite {
+    pub fn new(
+        transaction_hash: &str,
+        (address, key): &(Felt, Felt),
+        old: &Felt,
+        new: &Felt,
+    ) -> Self {
+        Self {
+            transaction_hash: transaction_hash.to_string(),
+            address: felt_to_hex(address),
+            key: felt_to_hex(key),
+            old_value_hash: value_hash(old),
+            new_value_hash: value_hash(new),
+        }
+    }
+}
+
+/// The state writes of a committed block, in the order they were applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockAudit {
+    pub height: u64,
+    pub writes: Vec<StateWrite>,
+}
+
+/// Append-only log of the state writes of every block, one JSON record per line, to find the
+/// transaction and slot where validators with mismatching app hashes diverged.
+///
+/// As the node replays the chain from genesis when it restarts, the blocks replayed are
+/// logged again; later records of a height replace earlier ones.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Appends the record of a block to the file. With `sync`, the file is flushed to disk
+    /// before returning.
+    pub fn append(&self, block: &BlockAudit, sync: bool) -> Result<()> {
+        let mut line = serde_json::to_vec(block)?;
+        line.push(b'\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(&line)?;
+        if sync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// Hex-encoded sha256 of a storage value, as hashed into the app hash.
+fn value_hash(value: &Felt) -> String {
+    hex::encode(Sha256::digest(value.to_bytes_be()))
+}
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use clap::Args;
+use color_eyre::Result;
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use starkmint::execution;
+
+use crate::output::Output;
+use crate::{broadcast, build_transaction, BroadcastMode, Context};
+
+#[derive(Debug, Args)]
+pub struct BatchArgs {
+    /// JSON array of `{ "path": ..., "function": ..., "calldata": [...], "repeat": ... }`
+    /// entries. Paths are relative to the manifest.
+    pub manifest: PathBuf,
+
+    /// Number of transactions in flight at once.
+    #[clap(long, default_value_t = 1)]
+    pub concurrency: usize,
+
+    /// How to broadcast each transaction, see `execute --help`.
+    #[clap(long, value_enum, default_value_t = BroadcastMode::Sync)]
+    pub broadcast_mode: BroadcastMode,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestEntry {
+    path: PathBuf,
+    function: String,
+    /// Felts in decimal or 0x-prefixed hex.
+    #[serde(default)]
+    calldata: Vec<String>,
+    #[serde(default = "default_true")]
+    enable_trace: bool,
+    /// Number of transactions to send for this entry.
+    #[serde(default = "default_repeat")]
+    repeat: usize,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_repeat() -> usize {
+    1
+}
+
+/// Outcome of one transaction of the batch.
+#[derive(Debug, Serialize)]
+struct BatchResult {
+    path: PathBuf,
+    function: String,
+    transaction_hash: Option<String>,
+    error: Option<String>,
+}
+
+pub async fn run(args: &BatchArgs, context: &Context) -> Result<Output> {
+    let manifest: Vec<ManifestEntry> = serde_json::from_slice(&std::fs::read(&args.manifest)?)?;
+    let base = args.manifest.parent().unwrap_or(&args.manifest);
+    context.ensure_chain_id().await?;
+
+    let jobs: Vec<(PathBuf, &ManifestEntry)> = manifest
+        .iter()
+        .flat_map(|entry| std::iter::repeat((base.join(&entry.path), entry)).take(entry.repeat))
+        .collect();
+
+    let started = Instant::now();
+    let results: Vec<BatchResult> = stream::iter(jobs)
+        .map(|(path, entry)| async move {
+            let sent = async {
+                let calldata = entry
+                    .calldata
+                    .iter()
+                    .map(|value| execution::parse_felt(value))
+                    .collect::<Result<_>>()?;
+                let transaction =
+                    build_transaction(&path, &entry.function, calldata, entry.enable_trace, true)?;
+                broadcast(
+                    bincode::serialize(&transaction)?,
+                    &context.client()?,
+                    args.broadcast_mode,
+                    &context.retry,
+                )
+                .await?;
+                Ok::<_, color_eyre::Report>(transaction.transaction_hash)
+            }
+            .await;
+
+            let (transaction_hash, error) = match sent {
+                Ok(hash) => (Some(hash), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            BatchResult {
+                path,
+                function: entry.function.clone(),
+                transaction_hash,
+                error,
+            }
+        })
+        .buffer_unordered(args.concurrency.max(1))
+        .collect()
+        .await;
+    let elapsed = started.elapsed();
+
+    let failed = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    let sent = results.len() - failed;
+
+    let mut text = format!(
+        "Sent {sent} transactions, {failed} failed, in {} ms ({:.1} tx/s)",
+        elapsed.as_millis(),
+        sent as f64 / elapsed.as_secs_f64()
+    );
+    for result in results.iter().filter(|result| result.error.is_some()) {
+        text.push_str(&format!(
+            "\n{} {}: {}",
+            result.path.display(),
+            result.function,
+            result.error.as_deref().unwrap_or_default()
+        ));
+    }
+
+    Output::new(
+        text,
+        json!({
+            "sent": sent,
+            "failed": failed,
+            "elapsed_ms": elapsed.as_millis() as u64,
+            "transactions": results,
+        }),
+    )
+}
@@ -0,0 +1,165 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use cairo_felt::Felt;
+use clap::Args;
+use color_eyre::Result;
+use serde_json::json;
+use tokio::sync::Mutex;
+use tokio::time::MissedTickBehavior;
+use uuid::Uuid;
+
+use crate::output::Output;
+use crate::query;
+use crate::{
+    broadcast, build_transaction, exit, parse_felt_arg, BroadcastMode, Context, RetryPolicy,
+};
+
+#[derive(Debug, Args)]
+pub struct BenchArgs {
+    /// Compiled program executed by every transaction, or a `.cairo` source file.
+    #[clap(long)]
+    pub program: PathBuf,
+
+    /// Function to invoke.
+    #[clap(long, default_value = "main")]
+    pub function: String,
+
+    /// Arguments of the function, as felts in decimal or 0x-prefixed hex.
+    #[clap(long, num_args = 1.., value_parser = parse_felt_arg)]
+    pub calldata: Vec<Felt>,
+
+    /// Transactions sent per second.
+    #[clap(long, default_value_t = 100)]
+    pub tps: u32,
+
+    /// How long to sustain the load, e.g. `60s` or `5m`.
+    #[clap(long, default_value = "30s", value_parser = humantime::parse_duration)]
+    pub duration: Duration,
+
+    /// Send the program with every transaction, even if it's already registered on chain.
+    #[clap(long)]
+    pub full_program: bool,
+}
+
+#[derive(Default)]
+struct Stats {
+    accepted: usize,
+    rejected: usize,
+    errors: usize,
+    latencies: Vec<Duration>,
+}
+
+/// Sends transactions at a fixed rate and reports how the node kept up. The program is
+/// executed once to build the transaction; copies with fresh ids are sent, so they are
+/// distinct for the mempool. Once the program is registered on chain, the copies reference
+/// it by class hash instead of carrying it.
+pub async fn run(args: &BenchArgs, context: &Context) -> Result<Output> {
+    context.ensure_chain_id().await?;
+    let mut template = build_transaction(
+        &args.program,
+        &args.function,
+        args.calldata.clone(),
+        false,
+        true,
+    )?;
+
+    let by_class_hash = match template.transaction_type.class_hash() {
+        Some(class_hash) if !args.full_program => {
+            query::is_class_registered(&context.client()?, &class_hash).await?
+        }
+        _ => false,
+    };
+    if by_class_hash {
+        template = template.referencing_class();
+    }
+    let size = bincode::serialized_size(&template)?;
+
+    let stats = Arc::new(Mutex::new(Stats::default()));
+    let no_retries = RetryPolicy {
+        retries: 0,
+        backoff: Duration::ZERO,
+    };
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1) / args.tps.max(1));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Burst);
+
+    let started = Instant::now();
+    let mut tasks = Vec::new();
+    while started.elapsed() < args.duration {
+        interval.tick().await;
+
+        let mut transaction = template.clone();
+        transaction.id = Uuid::new_v4().to_string();
+        let bytes = bincode::serialize(&transaction)?;
+
+        let client = context.client()?;
+        let stats = stats.clone();
+        let no_retries = no_retries.clone();
+        tasks.push(tokio::spawn(async move {
+            let sent = Instant::now();
+            let result = broadcast(bytes, &client, BroadcastMode::Sync, &no_retries).await;
+            let latency = sent.elapsed();
+
+            let mut stats = stats.lock().await;
+            match result {
+                Ok(_) => {
+                    stats.accepted += 1;
+                    stats.latencies.push(latency);
+                }
+                Err(e) if exit::code(&e) == exit::REJECTED => stats.rejected += 1,
+                Err(_) => stats.errors += 1,
+            }
+        }));
+    }
+    for task in tasks {
+        let _ = task.await;
+    }
+    let elapsed = started.elapsed();
+
+    let mut stats = stats.lock().await;
+    stats.latencies.sort();
+    let percentile = |p: f64| -> u128 {
+        match stats.latencies.len() {
+            0 => 0,
+            len => stats.latencies[((len - 1) as f64 * p) as usize].as_millis(),
+        }
+    };
+    let (p50, p95, p99) = (percentile(0.50), percentile(0.95), percentile(0.99));
+    let sent = stats.accepted + stats.rejected + stats.errors;
+    let achieved = stats.accepted as f64 / elapsed.as_secs_f64();
+
+    Output::new(
+        format!(
+            "Sent {sent} transactions in {:.1}s (target {} tx/s)\n\
+             Accepted: {} ({achieved:.1} tx/s)\n\
+             Rejected: {}\n\
+             Errors: {}\n\
+             Latency: p50 {p50} ms, p95 {p95} ms, p99 {p99} ms\n\
+             Transaction size: {size} bytes{}",
+            elapsed.as_secs_f64(),
+            args.tps,
+            stats.accepted,
+            stats.rejected,
+            stats.errors,
+            if by_class_hash {
+                " (program referenced by class hash)"
+            } else {
+                ""
+            },
+        ),
+        json!({
+            "sent": sent,
+            "elapsed_ms": elapsed.as_millis() as u64,
+            "target_tps": args.tps,
+            "achieved_tps": achieved,
+            "accepted": stats.accepted,
+            "rejected": stats.rejected,
+            "errors": stats.errors,
+            "latency_ms": { "p50": p50 as u64, "p95": p95 as u64, "p99": p99 as u64 },
+            "transaction_size": size,
+            "by_class_hash": by_class_hash,
+        }),
+    )
+}
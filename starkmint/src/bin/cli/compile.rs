@@ -0,0 +1,48 @@
+use std::path::Path;
+use std::process::Command;
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use tracing::debug;
+use uuid::Uuid;
+
+/// Compiler invoked on Cairo sources, overridable with the CAIRO_COMPILE env var.
+const DEFAULT_COMPILER: &str = "cairo-compile";
+
+/// Reads the program at `path`. Cairo sources (`.cairo`) are compiled first when `compile`
+/// is set, anything else is expected to be a compiled program.
+pub fn read_program(path: &Path, compile: bool) -> Result<String> {
+    if compile && path.extension().map_or(false, |ext| ext == "cairo") {
+        compile_source(path)
+    } else {
+        Ok(std::fs::read_to_string(path)?)
+    }
+}
+
+/// Compiles a Cairo source file with `cairo-compile`, returning the compiled program.
+fn compile_source(path: &Path) -> Result<String> {
+    let compiler = std::env::var("CAIRO_COMPILE").unwrap_or_else(|_| DEFAULT_COMPILER.to_string());
+    let output = std::env::temp_dir().join(format!("starkmint-{}.json", Uuid::new_v4()));
+
+    debug!("Compiling {} with {compiler}", path.display());
+    let result = Command::new(&compiler)
+        .arg(path)
+        .arg("--output")
+        .arg(&output)
+        .output()
+        .wrap_err_with(|| format!("Error running {compiler}, is cairo-lang installed?"))?;
+
+    if !result.status.success() {
+        bail!(
+            "Error compiling {}:\n{}",
+            path.display(),
+            String::from_utf8_lossy(&result.stderr)
+        );
+    }
+
+    let program = std::fs::read_to_string(&output);
+    let _ = std::fs::remove_file(&output);
+    Ok(program?)
+}
@@ -0,0 +1,31 @@
+use clap::CommandFactory;
+use clap_complete::Shell;
+use color_eyre::Result;
+use serde_json::json;
+
+use crate::output::Output;
+use crate::Cli;
+
+/// Name the CLI is installed as, which completions are registered for.
+const BIN_NAME: &str = "cli";
+
+/// Tab-completion script of the CLI for `shell`.
+pub fn completions(shell: Shell) -> Result<Output> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), BIN_NAME, &mut script);
+
+    let script = String::from_utf8(script)?;
+    Output::new(
+        &script,
+        json!({ "shell": shell.to_string(), "script": script }),
+    )
+}
+
+/// Manual page of the CLI, in roff.
+pub fn manpage() -> Result<Output> {
+    let mut page = Vec::new();
+    clap_mangen::Man::new(Cli::command().name(BIN_NAME)).render(&mut page)?;
+
+    let page = String::from_utf8(page)?;
+    Output::new(&page, json!({ "manpage": page }))
+}
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use color_eyre::{eyre::WrapErr, Result};
+use serde::Deserialize;
+
+/// Defaults read from the CLI configuration file. Flags and environment variables take
+/// precedence over them.
+///
+/// ```toml
+/// url = "http://127.0.0.1:26657"
+/// keystore = "/home/me/.starkmint/keys"
+/// default_key = "alice"
+/// chain_id = "starkmint-devnet"
+/// proxy = "http://proxy.internal:3128"
+///
+/// [headers]
+/// X-Api-Key = "..."
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Tendermint node url.
+    pub url: Option<String>,
+    /// Directory of the local keystore.
+    pub keystore: Option<PathBuf>,
+    /// Key transactions are signed with when `--from` is not given.
+    pub default_key: Option<String>,
+    /// Chain the node must be on for transactions to be sent.
+    pub chain_id: Option<String>,
+    /// Extra HTTP headers sent to the node.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Bearer token sent to the node in the Authorization header.
+    pub bearer_token: Option<String>,
+    /// HTTP proxy requests to the node go through.
+    pub proxy: Option<String>,
+}
+
+impl Config {
+    /// Loads the configuration at `path`, or at `~/.starkmint/config.toml` by default. A
+    /// missing default file is the same as an empty one.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Error reading config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .wrap_err_with(|| format!("Error parsing config file {}", path.display()))
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".starkmint").join("config.toml"))
+}
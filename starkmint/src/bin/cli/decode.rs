@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use clap::Args;
+use color_eyre::{eyre::WrapErr, Result};
+use starkmint::transaction::Transaction;
+
+use crate::output::Output;
+
+#[derive(Debug, Args)]
+pub struct DecodeArgs {
+    /// Transaction bytes as hex (optionally 0x-prefixed), or a file with the raw bytes, e.g.
+    /// one written by `execute --sign-only`.
+    pub transaction: String,
+}
+
+/// Prints the fields of a transaction in the wire format nodes receive.
+pub fn run(args: &DecodeArgs) -> Result<Output> {
+    let bytes = if Path::new(&args.transaction).is_file() {
+        std::fs::read(&args.transaction)?
+    } else {
+        let hex_bytes = args.transaction.trim();
+        hex::decode(hex_bytes.strip_prefix("0x").unwrap_or(hex_bytes))
+            .wrap_err("Expected hex transaction bytes or a file path")?
+    };
+
+    let transaction = decode_transaction(&bytes)?;
+    Output::new(serde_json::to_string_pretty(&transaction)?, &transaction)
+}
+
+/// Deserializes a transaction as sent to the node.
+pub fn decode_transaction(bytes: &[u8]) -> Result<Transaction> {
+    bincode::deserialize(bytes).wrap_err("Bytes are not a bincode-encoded transaction")
+}
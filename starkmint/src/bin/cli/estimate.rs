@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+
+use cairo_felt::Felt;
+use clap::Args;
+use color_eyre::Result;
+use starkmint::execution::FeeEstimate;
+use starkmint::transaction::Transaction;
+
+use crate::output::Output;
+use crate::rpc::NodeClient;
+use crate::{build_transaction, parse_felt_arg, query};
+
+#[derive(Debug, Args)]
+pub struct EstimateArgs {
+    /// Path to the compiled Cairo program, or to a `.cairo` source file.
+    pub path: PathBuf,
+
+    /// Function name from the compiled Cairo program, or its 0x-prefixed selector.
+    pub function_name: String,
+
+    /// Arguments of the function, as felts in decimal or 0x-prefixed hex.
+    #[clap(long, num_args = 1.., value_parser = parse_felt_arg)]
+    pub calldata: Vec<Felt>,
+
+    /// Don't compile `.cairo` files, send them as they are.
+    #[clap(long)]
+    pub no_compile: bool,
+}
+
+/// Executes the transaction on the node against its latest state, without broadcasting it.
+pub async fn run(args: &EstimateArgs, client: &NodeClient) -> Result<Output> {
+    let transaction = build_transaction(
+        &args.path,
+        &args.function_name,
+        args.calldata.clone(),
+        true,
+        !args.no_compile,
+    )?;
+
+    let estimate = estimate(client, &transaction).await?;
+    Output::new(
+        format!("Gas: {}\nFee: {}", estimate.gas, estimate.fee),
+        &estimate,
+    )
+}
+
+/// Gas and fee of a transaction executed on the node against its latest state.
+pub async fn estimate(client: &NodeClient, transaction: &Transaction) -> Result<FeeEstimate> {
+    let value =
+        query::abci_query(client, "/estimate_fee", bincode::serialize(transaction)?).await?;
+    Ok(serde_json::from_slice(&value)?)
+}
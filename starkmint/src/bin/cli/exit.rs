@@ -0,0 +1,48 @@
+//! Process exit codes, so scripts can tell failure classes apart.
+
+use std::fmt;
+
+use color_eyre::Report;
+
+/// The node couldn't be reached or returned an RPC error.
+pub const NETWORK_ERROR: i32 = 2;
+/// The transaction was rejected by CheckTx.
+pub const REJECTED: i32 = 3;
+/// The transaction was included in a block but failed in DeliverTx.
+pub const DELIVERY_FAILED: i32 = 4;
+/// The transaction wasn't included in a block in time.
+pub const TIMEOUT: i32 = 5;
+/// Any other error.
+pub const OTHER: i32 = 1;
+
+/// Failures reported with a dedicated exit code.
+#[derive(Debug)]
+pub enum Failure {
+    Rejected(String),
+    Timeout(String),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Rejected(message) | Failure::Timeout(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Exit code of a failed subcommand.
+pub fn code(error: &Report) -> i32 {
+    for cause in error.chain() {
+        if cause.is::<tendermint_rpc::Error>() {
+            return NETWORK_ERROR;
+        }
+        match cause.downcast_ref::<Failure>() {
+            Some(Failure::Rejected(_)) => return REJECTED,
+            Some(Failure::Timeout(_)) => return TIMEOUT,
+            None => {}
+        }
+    }
+    OTHER
+}
@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+use cairo_felt::Felt;
+use clap::Args;
+use color_eyre::Result;
+use serde_json::json;
+use starkmint::execution::{self, felt_to_hex};
+use starkmint::genesis::{Genesis, GenesisAccount, GenesisParameters};
+use starkmint::state;
+
+use crate::output::Output;
+use crate::{compile, parse_felt_arg};
+
+#[derive(Debug, Args)]
+pub struct GenesisArgs {
+    /// Account funded at genesis, as `<address>=<balance>`. Can be repeated.
+    #[clap(long, value_parser = parse_account)]
+    pub fund: Vec<GenesisAccount>,
+
+    /// Program declared at genesis, compiled or a `.cairo` source file. Can be repeated.
+    #[clap(long)]
+    pub declare: Vec<PathBuf>,
+
+    /// Fee charged per unit of gas, which nodes must be started with (`--gas-price`).
+    #[clap(long, default_value_t = 1)]
+    pub gas_price: u64,
+
+    /// Contract whose storage holds the chain parameters settable by governance, such as the
+    /// height the chain halts at.
+    #[clap(long, value_parser = parse_felt_arg)]
+    pub governance: Option<Felt>,
+
+    /// Account credited with the fees paid by senders, which nodes must be started with
+    /// (`--fee-recipient`). Fees are burned if not given.
+    #[clap(long, value_parser = parse_felt_arg)]
+    pub fee_recipient: Option<Felt>,
+
+    /// Publish the state diff of every block to a data availability layer, recording its
+    /// commitment in the state of the next block. Nodes publish with `--da-layer`.
+    #[clap(long)]
+    pub data_availability: bool,
+
+    /// Public key of the relayer injecting L1 messages as L1 handler transactions, the only
+    /// account allowed to send them. Nodes relay messages with `--l1-rpc`.
+    #[clap(long, value_parser = parse_felt_arg)]
+    pub l1_relayer: Option<Felt>,
+
+    /// Gas a transaction may use, which nodes must be started with (`--max-tx-gas`).
+    /// Executions are bounded to as many steps, and transactions using more fail. Unlimited if
+    /// not given.
+    #[clap(long)]
+    pub max_tx_gas: Option<u64>,
+
+    /// Keep accepting unsigned function and class executions although accounts are funded,
+    /// as on devnets. Otherwise they must be signed once any account is funded.
+    #[clap(long)]
+    pub unsigned_executions: bool,
+
+    /// File the app_state is written to. Printed if not given.
+    #[clap(long)]
+    pub out: Option<PathBuf>,
+}
+
+/// Builds the `app_state` of the Tendermint genesis file.
+pub fn run(args: &GenesisArgs) -> Result<Output> {
+    let classes = args
+        .declare
+        .iter()
+        .map(|path| {
+            let program = compile::read_program(path, true)?;
+            execution::ensure_compatible_artifact(&program)?;
+            Ok(program)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let genesis = Genesis {
+        accounts: args.fund.clone(),
+        classes,
+        parameters: GenesisParameters {
+            gas_price: args.gas_price,
+            governance: args.governance.clone(),
+            fee_recipient: args.fee_recipient.clone(),
+            data_availability: args.data_availability,
+            l1_relayer: args.l1_relayer.clone(),
+            max_tx_gas: args.max_tx_gas,
+            unsigned_executions: args.unsigned_executions,
+        },
+    };
+    let class_hashes: Vec<String> = genesis
+        .classes
+        .iter()
+        .map(|program| felt_to_hex(&state::class_hash(program)))
+        .collect();
+
+    match &args.out {
+        Some(out) => {
+            std::fs::write(out, serde_json::to_string_pretty(&genesis)?)?;
+            Output::new(
+                format!(
+                    "Wrote app_state with {} accounts and {} classes to {}",
+                    genesis.accounts.len(),
+                    genesis.classes.len(),
+                    out.display()
+                ),
+                json!({ "out": out, "class_hashes": class_hashes }),
+            )
+        }
+        None => Output::new(serde_json::to_string_pretty(&genesis)?, &genesis),
+    }
+}
+
+fn parse_account(value: &str) -> Result<GenesisAccount, String> {
+    let (address, balance) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected <address>=<balance>, got `{value}`"))?;
+
+    Ok(GenesisAccount {
+        address: execution::parse_felt(address).map_err(|e| e.to_string())?,
+        balance: execution::parse_felt(balance).map_err(|e| e.to_string())?,
+    })
+}
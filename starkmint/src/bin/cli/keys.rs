@@ -0,0 +1,331 @@
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Subcommand;
+use color_eyre::{
+    eyre::{bail, ensure, eyre, WrapErr},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use starkmint::signature;
+use starknet_crypto::{get_public_key, FieldElement};
+
+use crate::output::Output;
+
+#[derive(Debug, Subcommand)]
+pub enum KeysCommand {
+    /// Generate a new keypair.
+    Generate {
+        /// Name the key is stored under.
+        name: String,
+    },
+
+    /// Import an existing private key.
+    Import {
+        /// Name the key is stored under.
+        name: String,
+
+        /// 0x-prefixed hex private key.
+        private_key: String,
+    },
+
+    /// List the stored keys.
+    List,
+
+    /// Print a stored key.
+    Show {
+        name: String,
+
+        /// Also print the private key.
+        #[arg(long)]
+        private: bool,
+    },
+}
+
+/// Stark-curve keypair stored in the keystore.
+#[derive(Debug, Clone)]
+pub struct Key {
+    pub name: String,
+    pub private_key: FieldElement,
+    pub public_key: FieldElement,
+}
+
+impl Key {
+    fn new(name: String, private_key: FieldElement) -> Self {
+        Self {
+            name,
+            public_key: get_public_key(&private_key),
+            private_key,
+        }
+    }
+}
+
+/// On-disk format of a key. Keys are stored unencrypted, readable only by their owner.
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    private_key: String,
+    public_key: String,
+}
+
+/// Chain ID of devnets started by `starkmint dev` without `--chain-id`.
+pub const DEVNET_CHAIN_ID: &str = "starkmint-dev";
+
+/// Directory holding one `<name>.json` file per key.
+///
+/// With `dev_keys`, the keys of the prefunded accounts of devnets (`starkmint dev`) are built
+/// in as `dev-0`, `dev-1`, etc., unless a stored key has the same name. As anyone can derive
+/// them, they're only resolved when asked for.
+pub struct Keystore {
+    dir: PathBuf,
+    dev_keys: bool,
+}
+
+impl Keystore {
+    /// Opens the keystore at `dir`, or at `~/.starkmint/keys` by default, resolving the
+    /// devnet keys if `dev_keys` is set.
+    pub fn open(dir: Option<&Path>, dev_keys: bool) -> Result<Self> {
+        let dir = match dir {
+            Some(dir) => dir.to_path_buf(),
+            None => std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".starkmint").join("keys"))
+                .ok_or_else(|| eyre!("HOME is not set, pass --keystore"))?,
+        };
+        Ok(Self { dir, dev_keys })
+    }
+
+    pub fn load(&self, name: &str) -> Result<Key> {
+        let path = self.path(name)?;
+        if let Some(index) = dev_key_index(name).filter(|_| !path.exists()) {
+            ensure!(
+                self.dev_keys,
+                "Key {name} not found in keystore. Devnet keys are public, pass --dev to use them"
+            );
+            eprintln!(
+                "Warning: {name} is the public key of devnet account {index}, anyone can sign \
+                 with it"
+            );
+            let private_key = signature::to_field_element(&signature::dev_private_key(index))?;
+            return Ok(Key::new(name.to_string(), private_key));
+        }
+        let file: KeyFile = serde_json::from_slice(
+            &fs::read(&path).wrap_err_with(|| format!("Key {name} not found in keystore"))?,
+        )?;
+
+        let private_key = parse_key(&file.private_key)?;
+        Ok(Key::new(name.to_string(), private_key))
+    }
+
+    pub fn save(&self, key: &Key) -> Result<()> {
+        let path = self.path(&key.name)?;
+        fs::create_dir_all(&self.dir)?;
+
+        // created readable by its owner only, failing if it exists, so the private key is
+        // never readable by others nor written over an existing key
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = match options.open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                bail!("Key {} already exists", key.name)
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let key_file = KeyFile {
+            private_key: format!("{:#x}", key.private_key),
+            public_key: format!("{:#x}", key.public_key),
+        };
+        file.write_all(&serde_json::to_vec_pretty(&key_file)?)?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Result<Vec<Key>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names: Vec<String> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == "json")
+                    .then(|| path.file_stem()?.to_str().map(str::to_string))?
+            })
+            .collect();
+        names.sort();
+
+        names.iter().map(|name| self.load(name)).collect()
+    }
+
+    /// Path of the file of the key, rejecting names that could point outside the keystore.
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        ensure!(
+            !name.is_empty() && !name.contains(['/', '\\']) && !name.contains(".."),
+            "Invalid key name {name:?}: names can't be empty, contain path separators or `..`"
+        );
+        Ok(self.dir.join(format!("{name}.json")))
+    }
+}
+
+/// Index of the dev account a key name refers to, for names like `dev-0`.
+fn dev_key_index(name: &str) -> Option<u32> {
+    name.strip_prefix("dev-")?.parse().ok()
+}
+
+fn parse_key(value: &str) -> Result<FieldElement> {
+    FieldElement::from_hex_be(value).map_err(|_| eyre!("Invalid key: {value}"))
+}
+
+/// Random private key, below 2^250 so it is always smaller than the curve order.
+fn random_private_key() -> FieldElement {
+    let mut bytes: [u8; 32] = rand::random();
+    bytes[0] &= 0x03;
+    FieldElement::from_bytes_be(&bytes).expect("value is below the field prime")
+}
+
+pub fn run(command: &KeysCommand, keystore: &Keystore) -> Result<Output> {
+    match command {
+        KeysCommand::Generate { name } => {
+            let key = Key::new(name.clone(), random_private_key());
+            keystore.save(&key)?;
+            key_output(&key, false)
+        }
+        KeysCommand::Import { name, private_key } => {
+            let key = Key::new(name.clone(), parse_key(private_key)?);
+            keystore.save(&key)?;
+            key_output(&key, false)
+        }
+        KeysCommand::List => {
+            let keys = keystore.list()?;
+
+            let text = keys
+                .iter()
+                .map(|key| format!("{} {:#x}", key.name, key.public_key))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let json: Vec<_> = keys
+                .iter()
+                .map(|key| {
+                    json!({
+                        "name": key.name,
+                        "public_key": format!("{:#x}", key.public_key),
+                    })
+                })
+                .collect();
+            Output::new(text, json)
+        }
+        KeysCommand::Show { name, private } => key_output(&keystore.load(name)?, *private),
+    }
+}
+
+fn key_output(key: &Key, private: bool) -> Result<Output> {
+    let public_key = format!("{:#x}", key.public_key);
+    let private_key = private.then(|| format!("{:#x}", key.private_key));
+
+    let mut text = format!("Name: {}\nPublic key: {public_key}", key.name);
+    if let Some(private_key) = &private_key {
+        text.push_str(&format!("\nPrivate key: {private_key}"));
+    }
+
+    Output::new(
+        text,
+        json!({
+            "name": key.name,
+            "public_key": public_key,
+            "private_key": private_key,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// Keystore in a directory that doesn't exist yet, removed by the caller once done.
+    fn keystore(dev_keys: bool) -> Keystore {
+        let dir = std::env::temp_dir().join(format!("starkmint-keys-{}", Uuid::new_v4()));
+        Keystore::open(Some(&dir), dev_keys).unwrap()
+    }
+
+    #[test]
+    fn saved_keys_are_loaded_back() {
+        let keystore = keystore(false);
+        let key = Key::new("alice".to_string(), random_private_key());
+        keystore.save(&key).unwrap();
+
+        let loaded = keystore.load("alice").unwrap();
+        assert_eq!(loaded.private_key, key.private_key);
+        assert_eq!(loaded.public_key, key.public_key);
+        let names: Vec<_> = keystore
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|key| key.name)
+            .collect();
+        assert_eq!(names, ["alice"]);
+        fs::remove_dir_all(&keystore.dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn key_files_are_readable_by_their_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let keystore = keystore(false);
+        keystore
+            .save(&Key::new("alice".to_string(), random_private_key()))
+            .unwrap();
+
+        let metadata = fs::metadata(keystore.path("alice").unwrap()).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        fs::remove_dir_all(&keystore.dir).unwrap();
+    }
+
+    #[test]
+    fn existing_keys_are_never_overwritten() {
+        let keystore = keystore(false);
+        let key = Key::new("alice".to_string(), random_private_key());
+        keystore.save(&key).unwrap();
+
+        let other = Key::new("alice".to_string(), random_private_key());
+        assert!(keystore.save(&other).is_err());
+        assert_eq!(keystore.load("alice").unwrap().private_key, key.private_key);
+        fs::remove_dir_all(&keystore.dir).unwrap();
+    }
+
+    #[test]
+    fn names_pointing_outside_the_keystore_are_rejected() {
+        let keystore = keystore(false);
+        for name in ["", "../alice", "keys/alice", "keys\\alice", ".."] {
+            let key = Key::new(name.to_string(), random_private_key());
+            assert!(keystore.save(&key).is_err(), "{name:?} was accepted");
+            assert!(keystore.load(name).is_err(), "{name:?} was accepted");
+        }
+        assert!(!keystore.dir.exists());
+    }
+
+    #[test]
+    fn dev_keys_are_only_resolved_when_asked_for() {
+        let private_key = signature::to_field_element(&signature::dev_private_key(1)).unwrap();
+        assert!(keystore(false).load("dev-1").is_err());
+        assert_eq!(
+            keystore(true).load("dev-1").unwrap().private_key,
+            private_key
+        );
+
+        // stored keys take precedence over devnet keys
+        let keystore = keystore(true);
+        let key = Key::new("dev-1".to_string(), random_private_key());
+        keystore.save(&key).unwrap();
+        assert_eq!(keystore.load("dev-1").unwrap().private_key, key.private_key);
+        fs::remove_dir_all(&keystore.dir).unwrap();
+    }
+}
@@ -0,0 +1,912 @@
+use cairo_felt::Felt;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use futures::future::join_all;
+use serde_json::json;
+use starkmint::transaction::{Transaction, TransactionType};
+use starkmint::{execution, signature};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tendermint_rpc::error::ErrorDetail;
+use tendermint_rpc::Client;
+use tracing::{debug, warn};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+mod batch;
+mod bench;
+mod compile;
+mod completions;
+mod config;
+mod decode;
+mod estimate;
+mod exit;
+mod genesis;
+mod keys;
+mod output;
+mod query;
+mod repl;
+mod rpc;
+mod status;
+mod tx;
+mod watch;
+
+use output::{Format, Output};
+
+const LOCAL_SEQUENCER_URL: &str = "http://127.0.0.1:26657";
+
+/// Longest delay between two retries of a broadcast.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Parser)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Output log lines to stdout based on the desired log level (RUST_LOG env var).
+    #[clap(short, long, global = true, default_value_t = false)]
+    pub verbose: bool,
+
+    /// tendermint node url [default: http://127.0.0.1:26657]. Transactions are broadcast to
+    /// every url given (repeated or comma-separated); queries go to the first one.
+    #[clap(
+        short,
+        long,
+        global = true,
+        env = "SEQUENCER_URL",
+        value_delimiter = ','
+    )]
+    pub url: Vec<String>,
+
+    /// Print results as human-readable text or as JSON. Transaction and event searches can
+    /// also be exported as ndjson or csv.
+    #[clap(short, long, alias = "format", global = true, value_enum, default_value_t = Format::Text)]
+    pub output: Format,
+
+    /// Directory of the local keystore [default: ~/.starkmint/keys]
+    #[clap(long, global = true, env = "STARKMINT_KEYSTORE")]
+    pub keystore: Option<PathBuf>,
+
+    /// Number of times a broadcast that failed to reach the node is retried.
+    #[clap(long, global = true, default_value_t = 3)]
+    pub retries: u32,
+
+    /// Milliseconds before the first retry of a broadcast, doubled on every retry up to 30s.
+    #[clap(long, global = true, default_value_t = 500)]
+    pub retry_backoff: u64,
+
+    /// Extra HTTP header sent to the node, as `Name: value`, e.g. for gateways requiring
+    /// authentication. Can be repeated.
+    #[clap(long = "header", global = true, value_parser = rpc::parse_header)]
+    pub headers: Vec<(String, String)>,
+
+    /// Bearer token sent to the node in the Authorization header.
+    #[clap(
+        long,
+        global = true,
+        env = "STARKMINT_BEARER_TOKEN",
+        hide_env_values = true
+    )]
+    pub bearer_token: Option<String>,
+
+    /// HTTP proxy requests to the node go through.
+    #[clap(long, global = true, env = "STARKMINT_PROXY")]
+    pub proxy: Option<String>,
+
+    /// Chain the node must be on. Transactions aren't sent to nodes reporting another chain,
+    /// and are signed for it, or for the node's chain if there's none.
+    #[clap(long, global = true, env = "STARKMINT_CHAIN_ID")]
+    pub chain_id: Option<String>,
+
+    /// Resolve the keys of devnet accounts, `dev-0`, `dev-1`, etc. Implied with the chain ID
+    /// of devnets, `starkmint-dev`.
+    #[clap(long, global = true)]
+    pub dev: bool,
+
+    /// Configuration file with default settings [default: ~/.starkmint/config.toml]
+    #[clap(long, global = true, env = "STARKMINT_CONFIG")]
+    pub config: Option<PathBuf>,
+}
+
+/// Settings shared by subcommands, resolved from flags, environment and configuration file.
+#[derive(Debug)]
+pub struct Context {
+    /// Node queries are sent to.
+    pub url: String,
+    /// Nodes transactions are broadcast to, starting with `url`.
+    pub urls: Vec<String>,
+    pub keystore: Option<PathBuf>,
+    /// Whether devnet keys are resolved, see [`keys::Keystore`].
+    pub dev_keys: bool,
+    pub default_key: Option<String>,
+    pub format: Format,
+    pub retry: RetryPolicy,
+    /// Chain the nodes must be on, if checked, and transactions are signed for.
+    pub chain_id: Option<String>,
+    pub transport: rpc::Transport,
+}
+
+impl Context {
+    fn new(cli: &Cli) -> Result<Self> {
+        let config = config::Config::load(cli.config.as_deref())?;
+
+        let urls = match (cli.url.is_empty(), config.url) {
+            (false, _) => cli.url.clone(),
+            (true, Some(url)) => vec![url],
+            (true, None) => vec![LOCAL_SEQUENCER_URL.to_string()],
+        };
+
+        let mut headers: Vec<(String, String)> = config.headers.into_iter().collect();
+        headers.extend(cli.headers.iter().cloned());
+        if let Some(token) = cli.bearer_token.clone().or(config.bearer_token) {
+            headers.push(("Authorization".to_string(), format!("Bearer {token}")));
+        }
+
+        let chain_id = cli.chain_id.clone().or(config.chain_id);
+
+        Ok(Self {
+            url: urls[0].clone(),
+            urls,
+            keystore: cli.keystore.clone().or(config.keystore),
+            dev_keys: cli.dev || chain_id.as_deref() == Some(keys::DEVNET_CHAIN_ID),
+            default_key: config.default_key,
+            format: cli.output,
+            retry: RetryPolicy {
+                retries: cli.retries,
+                backoff: Duration::from_millis(cli.retry_backoff),
+            },
+            chain_id,
+            transport: rpc::Transport {
+                headers,
+                proxy: cli.proxy.clone().or(config.proxy),
+            },
+        })
+    }
+
+    /// Client of the node queries are sent to.
+    pub fn client(&self) -> Result<rpc::NodeClient> {
+        self.transport.client(&self.url)
+    }
+
+    /// Local keystore.
+    pub fn keystore(&self) -> Result<keys::Keystore> {
+        keys::Keystore::open(self.keystore.as_deref(), self.dev_keys)
+    }
+
+    /// Chain ID reported by the node queries are sent to, which transactions are signed for
+    /// unless one is expected.
+    pub async fn node_chain_id(&self) -> Result<String> {
+        let status = self.client()?.status().await?;
+        Ok(status.node_info.network.to_string())
+    }
+
+    /// Fails if a node transactions are broadcast to reports a different chain than the
+    /// expected one, if any.
+    pub async fn ensure_chain_id(&self) -> Result<()> {
+        let Some(expected) = &self.chain_id else {
+            return Ok(());
+        };
+
+        for url in &self.urls {
+            let status = self.transport.client(url)?.status().await?;
+            let chain_id = status.node_info.network.to_string();
+            if chain_id != *expected {
+                bail!("Node {url} is on chain {chain_id}, not {expected}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Send a transaction executing a function of a compiled Cairo program.
+    Execute(ExecuteArgs),
+
+    /// Send a transaction file written by `execute --sign-only`.
+    Broadcast(BroadcastArgs),
+
+    /// Print the gas and fee a transaction would cost, without sending it.
+    EstimateFee(estimate::EstimateArgs),
+
+    /// Send the transactions listed in a JSON manifest.
+    InvokeBatch(batch::BatchArgs),
+
+    /// Sustain a transaction load against the node and report the achieved throughput.
+    Bench(bench::BenchArgs),
+
+    /// Query the node for transactions.
+    #[command(subcommand)]
+    Query(query::QueryCommand),
+
+    /// Print the chain ID, latest height and application state of the node.
+    Status,
+
+    /// Stream new blocks and transactions from the node.
+    Watch(watch::WatchArgs),
+
+    /// Start an interactive session running subcommands against the node, with history and
+    /// shortcuts: `invoke` for `execute`, `q` for `query`, `tx` for `query tx`, `s` for `status`.
+    Repl,
+
+    /// Print the fields of an encoded transaction, e.g. one found in a mempool or block.
+    Decode(decode::DecodeArgs),
+
+    /// Follow up on transactions that were already sent.
+    #[command(subcommand)]
+    Tx(tx::TxCommand),
+
+    /// Build the app_state of the Tendermint genesis file: funded accounts, declared classes
+    /// and chain parameters.
+    Genesis(genesis::GenesisArgs),
+
+    /// Manage the Stark-curve keypairs of the local keystore.
+    #[command(subcommand)]
+    Keys(keys::KeysCommand),
+
+    /// Print the tab-completion script for a shell, e.g.
+    /// `cli completions bash > /etc/bash_completion.d/cli`.
+    #[command(hide = true)]
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Print the manual page of the CLI, e.g. `cli manpage > /usr/local/share/man/man1/cli.1`.
+    #[command(hide = true)]
+    Manpage,
+}
+
+#[derive(Debug, Args)]
+pub struct ExecuteArgs {
+    /// Path to the compiled Cairo program, or to a `.cairo` source file which is compiled
+    /// with cairo-compile.
+    #[clap()]
+    pub path: PathBuf,
+
+    /// Don't compile `.cairo` files, send them as they are.
+    #[clap(long)]
+    pub no_compile: bool,
+
+    /// Function name from the compiled Cairo program, or its 0x-prefixed selector.
+    #[clap()]
+    pub function_name: String,
+
+    /// Arguments of the function, as felts in decimal or 0x-prefixed hex.
+    #[clap(long, num_args = 1.., value_parser = parse_felt_arg)]
+    pub calldata: Vec<Felt>,
+
+    /// Whether to enable trace on the transaction.
+    #[clap(short, long, global = false, default_value_t = true)]
+    pub enable_trace: bool,
+
+    /// Reference the program by its class hash instead of sending it, which requires it to be
+    /// registered on chain, i.e. to have been executed before.
+    #[clap(long)]
+    pub by_class_hash: bool,
+
+    #[command(flatten)]
+    pub send: SendArgs,
+
+    /// Name of the keystore key to sign the transaction with. Defaults to the configured
+    /// default key, if any.
+    #[clap(long)]
+    pub from: Option<String>,
+
+    /// Address of the account contract sending the transaction, whose `__validate__` entry
+    /// point accepts signatures by the --from key. Defaults to the key's public key, for
+    /// accounts that aren't contracts.
+    #[clap(long, value_parser = parse_felt_arg)]
+    pub account: Option<Felt>,
+
+    /// Name of the keystore key of a paymaster paying the fee and tip in place of the sender,
+    /// e.g. a dapp sponsoring its users' transactions.
+    #[clap(long)]
+    pub paymaster: Option<String>,
+
+    /// Address of the paymaster's account contract, whose `__validate__` entry point accepts
+    /// signatures by the --paymaster key. Defaults to the key's public key.
+    #[clap(long, value_parser = parse_felt_arg, requires = "paymaster")]
+    pub paymaster_account: Option<Felt>,
+
+    /// Nonce of the signed transaction: the number of transactions already applied from
+    /// the sender. Fetched from the node if not given; set it to resubmit a stuck transaction.
+    #[clap(long)]
+    pub nonce: Option<u64>,
+
+    /// Amount of the fee token paid from the sender's balance, or the paymaster's, for the
+    /// transaction to be prioritized in the mempool. Only signed transactions can pay a tip.
+    #[clap(long, default_value_t = 0)]
+    pub tip: u64,
+
+    /// Most the sender, or the paymaster, pays for the gas used by the signed transaction, at
+    /// the chain's gas price. Defaults to twice the node's estimate; the transaction fails if
+    /// it costs more.
+    #[clap(long)]
+    pub max_fee: Option<u64>,
+
+    /// Only build and sign the transaction, writing it to the --out file instead of sending
+    /// it. Requires --nonce, --chain-id and --max-fee for signed transactions, as the node isn't
+    /// contacted.
+    #[clap(long, requires = "out")]
+    pub sign_only: bool,
+
+    /// File the transaction is written to, with --sign-only.
+    #[clap(long)]
+    pub out: Option<PathBuf>,
+
+    /// Build the transaction and print it, with its hash and size, without sending it.
+    #[clap(long, conflicts_with = "sign_only")]
+    pub dry_run: bool,
+}
+
+/// Options for sending a transaction to the node.
+#[derive(Debug, Args)]
+pub struct SendArgs {
+    /// How to broadcast the transaction: `async` doesn't wait for any check, `sync` waits for
+    /// it to be accepted in the mempool and `commit` for it to be included in a block.
+    #[clap(long, value_enum, default_value_t = BroadcastMode::Sync)]
+    pub broadcast_mode: BroadcastMode,
+
+    /// Wait for the transaction to be included in a block and print its receipt.
+    #[clap(long)]
+    pub wait: bool,
+
+    /// Seconds to wait for the transaction to be included, with --wait.
+    #[clap(long, default_value_t = 30)]
+    pub timeout: u64,
+
+    /// Compress the program with zstd, to send large programs in smaller transactions.
+    #[clap(long)]
+    pub compress: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct BroadcastArgs {
+    /// Transaction file written by `execute --sign-only`.
+    pub file: PathBuf,
+
+    #[command(flatten)]
+    pub send: SendArgs,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum BroadcastMode {
+    Async,
+    Sync,
+    Commit,
+}
+
+#[tokio::main()]
+async fn main() {
+    let cli = Cli::parse();
+
+    if cli.verbose {
+        tracing_subscriber::fmt()
+            // Use a more compact, abbreviated log format
+            .compact()
+            .with_env_filter(EnvFilter::from_default_env())
+            // Build and init the subscriber
+            .finish()
+            .init();
+    }
+
+    let result = match Context::new(&cli) {
+        Ok(context) if matches!(cli.command, Command::Repl) => repl::run(&context).await,
+        Ok(context) => execute(&cli.command, &context).await,
+        Err(e) => Err(e),
+    };
+
+    let (exit_code, output) = match result {
+        Ok(output) => (output.exit_code(), output.render(cli.output)),
+        Err(err) => (exit::code(&err), Output::error(err, cli.output)),
+    };
+
+    println!("{output:#}");
+    std::process::exit(exit_code);
+}
+
+async fn execute(command: &Command, context: &Context) -> Result<Output> {
+    match command {
+        Command::Execute(args) => run(args, context).await,
+        Command::Broadcast(args) => broadcast_file(args, context).await,
+        Command::EstimateFee(args) => estimate::run(args, &context.client()?).await,
+        Command::InvokeBatch(args) => batch::run(args, context).await,
+        Command::Bench(args) => bench::run(args, context).await,
+        Command::Query(query) => query::run(query, &context.client()?).await,
+        Command::Status => status::run(&context.client()?).await,
+        Command::Watch(args) => watch::run(args, context).await,
+        Command::Tx(command) => tx::run(command, &context.client()?).await,
+        Command::Decode(args) => decode::run(args),
+        Command::Genesis(args) => genesis::run(args),
+        Command::Keys(keys) => keys::run(keys, &context.keystore()?),
+        Command::Completions { shell } => completions::completions(*shell),
+        Command::Manpage => completions::manpage(),
+        Command::Repl => bail!("The REPL can't be started from a subcommand"),
+    }
+}
+
+async fn run(args: &ExecuteArgs, context: &Context) -> Result<Output> {
+    let mut transaction = build_transaction(
+        &args.path,
+        &args.function_name,
+        args.calldata.clone(),
+        args.enable_trace,
+        !args.no_compile,
+    )?;
+    if args.by_class_hash {
+        transaction = transaction.referencing_class();
+    }
+
+    if let Some(name) = args.from.as_ref().or(context.default_key.as_ref()) {
+        let key = context.keystore()?.load(name)?;
+        let sender = args
+            .account
+            .clone()
+            .unwrap_or_else(|| signature::from_field_element(&key.public_key));
+        let nonce = match args.nonce {
+            Some(nonce) => nonce,
+            None if args.sign_only => bail!("--nonce is required with --sign-only"),
+            None => query::fetch_nonce(&context.client()?, &sender).await?,
+        };
+        let chain_id = match &context.chain_id {
+            Some(chain_id) => chain_id.clone(),
+            None if args.sign_only => bail!("--chain-id is required with --sign-only"),
+            None => context.node_chain_id().await?,
+        };
+        let max_fee = match args.max_fee {
+            Some(max_fee) => max_fee,
+            None if args.sign_only => bail!("--max-fee is required with --sign-only"),
+            None => {
+                let estimate = estimate::estimate(&context.client()?, &transaction).await?;
+                estimate.fee.saturating_mul(2)
+            }
+        };
+        let paymaster = match &args.paymaster {
+            Some(name) => {
+                let key = context.keystore()?.load(name)?;
+                let paymaster = args
+                    .paymaster_account
+                    .clone()
+                    .unwrap_or_else(|| signature::from_field_element(&key.public_key));
+                Some((paymaster, key))
+            }
+            None => None,
+        };
+        transaction.sign_as(
+            &sender,
+            &signature::from_field_element(&key.private_key),
+            &chain_id,
+            nonce,
+            args.tip,
+            max_fee,
+            paymaster.as_ref().map(|(paymaster, _)| paymaster),
+        )?;
+
+        if let Some((paymaster, key)) = &paymaster {
+            transaction.sponsor(
+                paymaster,
+                &signature::from_field_element(&key.private_key),
+                &chain_id,
+            )?;
+        }
+    } else if args.paymaster.is_some() {
+        bail!("Only signed transactions can be sponsored, use --from to sign it");
+    } else if args.account.is_some() {
+        bail!("Transactions sent from an account must be signed, use --from to sign them");
+    } else if args.tip > 0 {
+        bail!("Only signed transactions can pay a tip, use --from to sign it");
+    } else if args.max_fee.is_some() {
+        bail!("Only signed transactions pay fees, use --from to sign it");
+    }
+
+    match &args.out {
+        Some(out) if args.sign_only => {
+            fs::write(out, encode(&transaction, args.send.compress)?)?;
+            Output::new(
+                format!(
+                    "Wrote transaction {} to {}",
+                    transaction.transaction_hash,
+                    out.display()
+                ),
+                json!({
+                    "id": transaction.id,
+                    "transaction_hash": transaction.transaction_hash,
+                    "out": out,
+                }),
+            )
+        }
+        _ if args.dry_run => {
+            let size = encode(&transaction, args.send.compress)?.len();
+            Output::new(
+                format!(
+                    "Transaction {}\nSize: {size} bytes\n{}",
+                    transaction.transaction_hash,
+                    serde_json::to_string_pretty(&transaction)?
+                ),
+                json!({
+                    "transaction_hash": transaction.transaction_hash,
+                    "size": size,
+                    "transaction": transaction,
+                }),
+            )
+        }
+        _ => send_transaction(&transaction, &args.send, context).await,
+    }
+}
+
+/// Sends a transaction previously written with `execute --sign-only`.
+async fn broadcast_file(args: &BroadcastArgs, context: &Context) -> Result<Output> {
+    let transaction: Transaction = bincode::deserialize(&fs::read(&args.file)?)?;
+    send_transaction(&transaction, &args.send, context).await
+}
+
+/// Encodes a transaction to be sent to nodes, compressing its program if `compress` is set.
+fn encode(transaction: &Transaction, compress: bool) -> Result<Vec<u8>> {
+    if compress {
+        transaction.encode_compressed()
+    } else {
+        Ok(bincode::serialize(transaction)?)
+    }
+}
+
+async fn send_transaction(
+    transaction: &Transaction,
+    args: &SendArgs,
+    context: &Context,
+) -> Result<Output> {
+    let transaction_serialized = encode(transaction, args.compress)?;
+
+    context.ensure_chain_id().await?;
+
+    let results = join_all(context.urls.iter().map(|url| async {
+        broadcast(
+            transaction_serialized.clone(),
+            &context.transport.client(url)?,
+            args.broadcast_mode,
+            &context.retry,
+        )
+        .await
+    }))
+    .await;
+
+    // the transaction only needs to reach one node to be gossiped to the others
+    let mut nodes: Vec<(&str, Option<String>)> = Vec::new();
+    let mut committed = None;
+    let mut first_error = None;
+    for (url, result) in context.urls.iter().zip(results) {
+        match result {
+            Ok(receipt) => {
+                nodes.push((url, None));
+                committed = committed.or(receipt);
+            }
+            Err(e) => {
+                nodes.push((url, Some(format!("{e:#}"))));
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    if nodes.iter().all(|(_, error)| error.is_some()) {
+        let error = first_error.expect("at least one node was tried");
+        return Err(error.wrap_err("Error sending out transaction"));
+    }
+
+    let mut sent = format!(
+        "Sent transaction (ID {}) succesfully. Hash: {}",
+        transaction.id,
+        transaction.hash()
+    );
+    if nodes.len() > 1 {
+        for (url, error) in &nodes {
+            match error {
+                Some(error) => sent.push_str(&format!("\n  {url}: failed: {error}")),
+                None => sent.push_str(&format!("\n  {url}: sent")),
+            }
+        }
+    }
+    let nodes: Vec<_> = nodes
+        .into_iter()
+        .map(|(url, error)| json!({ "url": url, "error": error }))
+        .collect();
+
+    let receipt = match committed {
+        Some(receipt) => Some(receipt),
+        None if args.wait => Some(
+            wait_for_receipt(
+                &transaction.hash(),
+                &context.client()?,
+                Duration::from_secs(args.timeout),
+            )
+            .await?,
+        ),
+        None => None,
+    };
+
+    let text = match &receipt {
+        Some(receipt) => format!("{sent}\n{}", receipt.render(output::color())),
+        None => sent,
+    };
+    let failed = receipt.as_ref().map_or(false, |receipt| receipt.code != 0);
+
+    let output = Output::new(
+        text,
+        json!({
+            "id": transaction.id,
+            "hash": transaction.hash(),
+            "transaction_hash": transaction.transaction_hash,
+            "nodes": nodes,
+            "receipt": receipt,
+        }),
+    )?;
+    Ok(if failed {
+        output.with_exit_code(exit::DELIVERY_FAILED)
+    } else {
+        output
+    })
+}
+
+/// Builds a transaction executing a function of the program at `path`, compiling it first
+/// if it's a Cairo source and `compile` is set. The calldata is checked against the
+/// function's arguments when the transaction is hashed.
+pub fn build_transaction(
+    path: &Path,
+    function_name: &str,
+    calldata: Vec<Felt>,
+    enable_trace: bool,
+    compile: bool,
+) -> Result<Transaction> {
+    let program = compile::read_program(path, compile)?;
+
+    let transaction_type = TransactionType::FunctionExecution {
+        program: program.into(),
+        function: function_name.parse()?,
+        program_name: path
+            .file_name()
+            .expect("Error getting file name")
+            .to_string_lossy()
+            .to_string(),
+        enable_trace,
+        calldata,
+    };
+    Transaction::with_type(transaction_type)
+}
+
+fn parse_felt_arg(value: &str) -> Result<Felt, String> {
+    execution::parse_felt(value).map_err(|e| e.to_string())
+}
+
+/// Polls the node until the transaction is included in a block.
+async fn wait_for_receipt(
+    hash: &str,
+    client: &rpc::NodeClient,
+    timeout: Duration,
+) -> Result<query::Receipt> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if let Some(receipt) = query::find_tx(client, hash).await? {
+            return Ok(receipt);
+        }
+        if Instant::now() >= deadline {
+            return Err(exit::Failure::Timeout(format!(
+                "Transaction {hash} not included after {}",
+                humantime::format_duration(timeout)
+            ))
+            .into());
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Broadcasts a transaction, returning its receipt if the mode waits for it to be committed.
+/// Failures to reach the node are retried with exponential backoff; errors returned by the
+/// node, and transactions rejected by CheckTx, are not.
+pub async fn broadcast(
+    transaction: Vec<u8>,
+    client: &rpc::NodeClient,
+    mode: BroadcastMode,
+    retry: &RetryPolicy,
+) -> Result<Option<query::Receipt>> {
+    let mut attempt = 0;
+    let broadcasted = loop {
+        match send(client, transaction.clone(), mode).await {
+            Ok(broadcasted) => break broadcasted,
+            Err(e) if attempt < retry.retries && is_transport_error(&e) => {
+                let delay = retry.delay(attempt);
+                warn!("Error broadcasting transaction, retrying in {delay:?}: {e}");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    match broadcasted {
+        Broadcasted::Sent => Ok(None),
+        Broadcasted::Committed(receipt) => Ok(Some(receipt)),
+        Broadcasted::Checked(tendermint::abci::Code::Ok, _) => Ok(None),
+        Broadcasted::Checked(tendermint::abci::Code::Err(code), log) => Err(
+            exit::Failure::Rejected(format!("Error executing transaction {code}: {log}")).into(),
+        ),
+    }
+}
+
+/// How many times, and how long after, failed broadcasts are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    /// Delay before the first retry, doubled on every following one.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before retrying after `attempt` failed retries, capped at [`MAX_RETRY_DELAY`].
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+/// Whether the request failed to reach the node, or its response to come back, rather than
+/// being answered with an error, so sending it again may succeed.
+fn is_transport_error(e: &tendermint_rpc::Error) -> bool {
+    matches!(
+        e.detail(),
+        ErrorDetail::Io(_)
+            | ErrorDetail::Http(_)
+            | ErrorDetail::Hyper(_)
+            | ErrorDetail::ClientInternal(_)
+    )
+}
+
+enum Broadcasted {
+    Sent,
+    Checked(tendermint::abci::Code, String),
+    Committed(query::Receipt),
+}
+
+async fn send(
+    client: &rpc::NodeClient,
+    transaction: Vec<u8>,
+    mode: BroadcastMode,
+) -> Result<Broadcasted, tendermint_rpc::Error> {
+    match mode {
+        BroadcastMode::Async => {
+            let response = client.broadcast_tx_async(transaction).await?;
+            debug!("Response from broadcast_tx_async: {:?}", response);
+            Ok(Broadcasted::Sent)
+        }
+        BroadcastMode::Sync => {
+            let response = client.broadcast_tx_sync(transaction).await?;
+            debug!("Response from CheckTx: {:?}", response);
+            Ok(Broadcasted::Checked(response.code, response.log))
+        }
+        BroadcastMode::Commit => {
+            let response = client.broadcast_tx_commit(transaction).await?;
+            debug!("Response from broadcast_tx_commit: {:?}", response);
+
+            if response.check_tx.code.is_err() {
+                return Ok(Broadcasted::Checked(
+                    response.check_tx.code,
+                    response.check_tx.log,
+                ));
+            }
+            Ok(Broadcasted::Committed(query::Receipt::new(
+                response.hash.to_string(),
+                response.height,
+                response.deliver_tx,
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// Configuration file with `contents`, removed by the caller once done.
+    fn config_file(contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("starkmint-cli-{}.toml", Uuid::new_v4()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn context(config: &Path, args: &[&str]) -> Context {
+        let config = config.to_str().unwrap();
+        let cli = Cli::try_parse_from(
+            ["cli", "--config", config]
+                .iter()
+                .chain(args)
+                .chain(&["keys", "list"]),
+        )
+        .unwrap();
+        Context::new(&cli).unwrap()
+    }
+
+    const CONFIG: &str = r#"
+        url = "http://node:26657"
+        keystore = "/keys"
+        default_key = "alice"
+        chain_id = "starkmint-dev"
+        bearer_token = "secret"
+        proxy = "http://proxy:3128"
+
+        [headers]
+        X-Api-Key = "key"
+    "#;
+
+    #[test]
+    fn the_configuration_file_fills_in_missing_flags() {
+        let config = config_file(CONFIG);
+        let context = context(&config, &[]);
+
+        assert_eq!(context.urls, ["http://node:26657"]);
+        assert_eq!(context.keystore, Some(PathBuf::from("/keys")));
+        assert_eq!(context.default_key.as_deref(), Some("alice"));
+        assert_eq!(context.chain_id.as_deref(), Some("starkmint-dev"));
+        // the devnet chain ID implies --dev
+        assert!(context.dev_keys);
+        assert_eq!(
+            context.transport.proxy.as_deref(),
+            Some("http://proxy:3128")
+        );
+        assert_eq!(
+            context.transport.headers,
+            [
+                ("X-Api-Key".to_string(), "key".to_string()),
+                ("Authorization".to_string(), "Bearer secret".to_string()),
+            ]
+        );
+        fs::remove_file(config).unwrap();
+    }
+
+    #[test]
+    fn flags_take_precedence_over_the_configuration_file() {
+        let config = config_file(CONFIG);
+        let context = context(
+            &config,
+            &[
+                "--url",
+                "http://a:26657,http://b:26657",
+                "--keystore",
+                "/other-keys",
+                "--chain-id",
+                "starkmint-1",
+                "--bearer-token",
+                "token",
+                "--header",
+                "X-Api-Key: other",
+            ],
+        );
+
+        assert_eq!(context.url, "http://a:26657");
+        assert_eq!(context.urls, ["http://a:26657", "http://b:26657"]);
+        assert_eq!(context.keystore, Some(PathBuf::from("/other-keys")));
+        assert_eq!(context.chain_id.as_deref(), Some("starkmint-1"));
+        assert!(!context.dev_keys);
+        // headers given as flags are sent after, and so override, those of the file
+        assert_eq!(
+            context.transport.headers,
+            [
+                ("X-Api-Key".to_string(), "key".to_string()),
+                ("X-Api-Key".to_string(), "other".to_string()),
+                ("Authorization".to_string(), "Bearer token".to_string()),
+            ]
+        );
+        fs::remove_file(config).unwrap();
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        let config = config_file("urls = [\"http://node:26657\"]");
+        assert!(config::Config::load(Some(&config)).is_err());
+        fs::remove_file(config).unwrap();
+    }
+}
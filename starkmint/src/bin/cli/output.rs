@@ -0,0 +1,136 @@
+use std::fmt::Display;
+
+use clap::ValueEnum;
+use color_eyre::Result;
+use is_terminal::IsTerminal;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+    /// One JSON object per line, for list results.
+    Ndjson,
+    /// Comma-separated values with a header row, for list results.
+    Csv,
+}
+
+/// Whether text output should be colorized: stdout is a terminal and NO_COLOR isn't set.
+pub fn color() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Result of a subcommand, printable for humans or as JSON for scripts.
+#[derive(Debug)]
+pub struct Output {
+    text: String,
+    json: Value,
+    /// Records of list results, rendered one per line in the ndjson and csv formats.
+    rows: Option<Vec<Value>>,
+    exit_code: i32,
+}
+
+impl Output {
+    pub fn new(text: impl Display, json: impl Serialize) -> Result<Self> {
+        Ok(Self {
+            text: text.to_string(),
+            json: serde_json::to_value(json)?,
+            rows: None,
+            exit_code: 0,
+        })
+    }
+
+    /// Sets the records rendered by the ndjson and csv formats. Without them, those formats
+    /// render the JSON result as a single record.
+    pub fn with_rows<T: Serialize>(mut self, rows: &[T]) -> Result<Self> {
+        let rows = rows
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<_, _>>()?;
+        self.rows = Some(rows);
+        Ok(self)
+    }
+
+    /// Reports the subcommand as failed while still printing its result, e.g. the receipt of
+    /// a transaction that failed.
+    pub fn with_exit_code(mut self, exit_code: i32) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.text.clone(),
+            Format::Json => self.json.to_string(),
+            Format::Ndjson => self
+                .rows()
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Format::Csv => to_csv(self.rows()),
+        }
+    }
+
+    fn rows(&self) -> &[Value] {
+        self.rows
+            .as_deref()
+            .unwrap_or(std::slice::from_ref(&self.json))
+    }
+
+    /// Renders an error the way a failed subcommand reports it.
+    pub fn error(error: impl Display, format: Format) -> String {
+        match format {
+            Format::Text | Format::Csv => format!("error: {error:#}"),
+            Format::Json | Format::Ndjson => json!({ "error": format!("{error:#}") }).to_string(),
+        }
+    }
+}
+
+/// Renders records as CSV, with the keys of the first record as columns. Nested values are
+/// written as JSON.
+fn to_csv(rows: &[Value]) -> String {
+    let columns: Vec<&String> = match rows.first() {
+        Some(Value::Object(first)) => first.keys().collect(),
+        _ => return rows.iter().map(csv_field).collect::<Vec<_>>().join("\n"),
+    };
+
+    let header = columns
+        .iter()
+        .map(|column| csv_escape(column))
+        .collect::<Vec<_>>()
+        .join(",");
+    let lines = rows.iter().map(|row| {
+        columns
+            .iter()
+            .map(|column| csv_field(&row[column.as_str()]))
+            .collect::<Vec<_>>()
+            .join(",")
+    });
+
+    std::iter::once(header)
+        .chain(lines)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn csv_field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => csv_escape(value),
+        value => csv_escape(&value.to_string()),
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,493 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use cairo_felt::Felt;
+use clap::Subcommand;
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use starkmint::execution::{self, felt_to_hex};
+use starkmint::state::{self, AccountState};
+use starkmint::transaction::TransactionType;
+use tendermint::abci::{self, response};
+use tendermint::block::Height;
+use tendermint_rpc::endpoint::tx;
+use tendermint_rpc::query::Query;
+use tendermint_rpc::{Client, Order};
+
+use crate::decode;
+use crate::output::{self, Output};
+use crate::rpc::NodeClient;
+
+#[derive(Debug, Subcommand)]
+pub enum QueryCommand {
+    /// Print the receipt of a transaction, given the hash printed when it was sent.
+    Tx {
+        /// Transaction hash.
+        hash: String,
+    },
+
+    /// Print a block and a summary of each of its transactions.
+    Block {
+        /// Block height.
+        height: u64,
+    },
+
+    /// List the transactions that emitted a matching event.
+    Events {
+        /// Event kind, e.g. `function` or `contract_event`.
+        #[arg(long)]
+        kind: String,
+
+        /// Attribute key within the event.
+        #[arg(long)]
+        key: String,
+
+        /// Attribute value to match.
+        #[arg(long)]
+        value: String,
+
+        /// Only include transactions from this block height onwards.
+        #[arg(long)]
+        from_height: Option<u64>,
+
+        /// Page of results to print, starting at 1.
+        #[arg(long, default_value_t = 1)]
+        page: u32,
+
+        /// Number of transactions per page (at most 100).
+        #[arg(long, default_value_t = 30)]
+        per_page: u8,
+    },
+
+    /// Print the nonce and fee-token balance of an account.
+    Account {
+        /// Account address, in decimal or 0x-prefixed hex.
+        address: String,
+    },
+
+    /// Download the compiled program registered under a class hash.
+    Class {
+        /// Class hash, in decimal or 0x-prefixed hex.
+        class_hash: String,
+
+        /// File the program is written to. Printed if not given.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+pub async fn run(command: &QueryCommand, client: &NodeClient) -> Result<Output> {
+    match command {
+        QueryCommand::Tx { hash } => {
+            let receipt = find_tx(client, hash)
+                .await?
+                .ok_or_else(|| eyre!("Transaction {hash} not found"))?;
+            Output::new(receipt.render(output::color()), &receipt)?.with_rows(&[&receipt])
+        }
+        QueryCommand::Block { height } => {
+            let height = Height::try_from(*height)?;
+            let block = client.block(height).await?.block;
+            let results = client.block_results(height).await?.txs_results;
+
+            let transactions: Vec<BlockTx> = block
+                .data
+                .iter()
+                .enumerate()
+                .map(|(index, bytes)| {
+                    let result = results.as_ref().and_then(|results| results.get(index));
+                    BlockTx::new(bytes, result)
+                })
+                .collect();
+
+            let mut lines = vec![
+                format!("Block {}", block.header.height),
+                format!("  Hash:         {}", block.header.hash()),
+                format!("  Time:         {}", block.header.time),
+                format!("  Proposer:     {}", block.header.proposer_address),
+                format!("  Transactions: {}", transactions.len()),
+            ];
+            lines.extend(
+                transactions
+                    .iter()
+                    .map(|transaction| format!("    {transaction}")),
+            );
+            Output::new(
+                lines.join("\n"),
+                json!({
+                    "height": block.header.height.value(),
+                    "hash": block.header.hash().to_string(),
+                    "time": block.header.time.to_string(),
+                    "proposer": block.header.proposer_address.to_string(),
+                    "transactions": transactions,
+                }),
+            )?
+            .with_rows(&transactions)
+        }
+        QueryCommand::Events {
+            kind,
+            key,
+            value,
+            from_height,
+            page,
+            per_page,
+        } => {
+            let mut query = Query::eq(format!("{kind}.{key}"), value.as_str());
+            if let Some(height) = from_height {
+                query = query.and_gte("tx.height", *height);
+            }
+
+            let response = client
+                .tx_search(query, false, *page, *per_page, Order::Ascending)
+                .await?;
+            let per_page = u32::from((*per_page).max(1));
+            let pages = (response.total_count + per_page - 1) / per_page;
+
+            let receipts: Vec<Receipt> = response.txs.into_iter().map(Receipt::from).collect();
+
+            let mut text = format!(
+                "{} matching transactions, page {page} of {pages}",
+                response.total_count
+            );
+            for receipt in &receipts {
+                text.push_str(&format!(
+                    "\n{} height {} code {}",
+                    receipt.hash, receipt.height, receipt.code
+                ));
+            }
+            Output::new(
+                text,
+                json!({
+                    "total_count": response.total_count,
+                    "page": page,
+                    "pages": pages,
+                    "transactions": receipts,
+                }),
+            )?
+            .with_rows(&receipts)
+        }
+        QueryCommand::Account { address } => {
+            let address = execution::parse_felt(address)?;
+            let value = abci_query(client, "/account", felt_to_hex(&address).into_bytes()).await?;
+            let account: AccountState = serde_json::from_slice(&value)?;
+
+            let balance = account.balance.to_str_radix(10);
+            Output::new(
+                format!(
+                    "Account {}\nNonce: {}\nBalance: {balance}",
+                    felt_to_hex(&address),
+                    account.nonce
+                ),
+                json!({
+                    "address": felt_to_hex(&address),
+                    "nonce": account.nonce,
+                    "balance": balance,
+                }),
+            )
+        }
+        QueryCommand::Class { class_hash, out } => {
+            let class_hash = execution::parse_felt(class_hash)?;
+            let value = abci_query(client, "/class", felt_to_hex(&class_hash).into_bytes()).await?;
+            let program = String::from_utf8(value)?;
+
+            // the class hash is the hash of the artifact, so a match proves it's the same code
+            if state::class_hash(&program) != class_hash {
+                bail!("The node returned a program that doesn't match the class hash");
+            }
+
+            match out {
+                Some(out) => {
+                    std::fs::write(out, &program)?;
+                    Output::new(
+                        format!(
+                            "Wrote class {} to {}",
+                            felt_to_hex(&class_hash),
+                            out.display()
+                        ),
+                        json!({ "class_hash": felt_to_hex(&class_hash), "out": out }),
+                    )
+                }
+                None => {
+                    let artifact: serde_json::Value = serde_json::from_str(&program)?;
+                    Output::new(&program, artifact)
+                }
+            }
+        }
+    }
+}
+
+/// Runs an application query, failing if the node rejects it.
+pub async fn abci_query(client: &NodeClient, path: &str, data: Vec<u8>) -> Result<Vec<u8>> {
+    let response = client
+        .abci_query(Some(path.to_string()), data, None, false)
+        .await?;
+
+    if response.code.is_err() {
+        bail!("Query {path} failed: {}", response.log);
+    }
+    Ok(response.value)
+}
+
+/// Nonce of an account as of the node's last committed block.
+pub async fn fetch_nonce(client: &NodeClient, account: &Felt) -> Result<u64> {
+    let value = abci_query(client, "/nonce", felt_to_hex(account).into_bytes()).await?;
+    Ok(serde_json::from_slice(&value)?)
+}
+
+/// Whether a program is registered on chain under the class hash, in which case transactions
+/// can reference it rather than carry it.
+pub async fn is_class_registered(client: &NodeClient, class_hash: &Felt) -> Result<bool> {
+    let response = client
+        .abci_query(
+            Some("/class".to_string()),
+            felt_to_hex(class_hash).into_bytes(),
+            None,
+            false,
+        )
+        .await?;
+    Ok(response.code.is_ok())
+}
+
+/// Looks a transaction up by the `app.hash` event the node indexes it under, whether it
+/// succeeded or failed. A transaction included again fails as a duplicate, so the first
+/// result is the one that was applied.
+pub async fn find_tx(client: &NodeClient, hash: &str) -> Result<Option<Receipt>> {
+    let query = Query::eq("app.hash", hash.trim_start_matches("0x").to_lowercase());
+    let response = client
+        .tx_search(query, false, 1, 1, Order::Ascending)
+        .await?;
+
+    Ok(response.txs.into_iter().next().map(Receipt::from))
+}
+
+/// Outcome of a transaction included in a block.
+#[derive(Debug, Serialize)]
+pub struct Receipt {
+    pub hash: String,
+    pub height: u64,
+    pub code: u32,
+    pub log: String,
+    pub gas_used: i64,
+    /// Values returned by the invoked function.
+    pub retdata: Vec<String>,
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Event {
+    pub kind: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+/// Application-level summary of a transaction in a block.
+#[derive(Debug, Serialize)]
+pub struct BlockTx {
+    /// Application hash of the transaction, or the Tendermint hash if it can't be decoded.
+    pub hash: String,
+    /// `function_execution`, `proof_verification`, or `unknown` if it can't be decoded.
+    pub kind: String,
+    pub program_name: Option<String>,
+    pub function: Option<String>,
+    pub sender: Option<String>,
+    pub code: Option<u32>,
+    pub gas_used: Option<i64>,
+}
+
+impl BlockTx {
+    fn new(bytes: &[u8], result: Option<&response::DeliverTx>) -> Self {
+        let mut summary = Self {
+            hash: hex::encode_upper(Sha256::digest(bytes)),
+            kind: "unknown".to_string(),
+            program_name: None,
+            function: None,
+            sender: None,
+            code: result.map(|result| result.code.value()),
+            gas_used: result.map(|result| result.gas_used),
+        };
+
+        if let Ok(transaction) = decode::decode_transaction(bytes) {
+            summary.hash = transaction.hash();
+            summary.sender = transaction
+                .signature
+                .map(|signature| felt_to_hex(&signature.sender));
+            match transaction.transaction_type {
+                TransactionType::FunctionExecution {
+                    program_name,
+                    function,
+                    ..
+                }
+                | TransactionType::ClassExecution {
+                    program_name,
+                    function,
+                    ..
+                } => {
+                    summary.kind = "function_execution".to_string();
+                    summary.program_name = Some(program_name);
+                    summary.function = Some(function.to_string());
+                }
+                TransactionType::L1Handler(message) => {
+                    summary.kind = "l1_handler".to_string();
+                    summary.program_name = Some(felt_to_hex(&message.to_address));
+                    summary.function = Some(felt_to_hex(&message.selector));
+                }
+                TransactionType::ProofVerification { .. } => {
+                    summary.kind = "proof_verification".to_string();
+                }
+            }
+        }
+        summary
+    }
+}
+
+impl fmt::Display for BlockTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.hash, self.kind)?;
+        if let (Some(program_name), Some(function)) = (&self.program_name, &self.function) {
+            write!(f, " {program_name}:{function}")?;
+        }
+        if let Some(sender) = &self.sender {
+            write!(f, " from {sender}")?;
+        }
+        match self.code {
+            Some(0) => write!(f, " succeeded")?,
+            Some(code) => write!(f, " failed (code {code})")?,
+            None => {}
+        }
+        if let Some(gas_used) = self.gas_used {
+            write!(f, " gas {gas_used}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Receipt {
+    /// Receipt from the DeliverTx result of a transaction included at `height`. `hash` is the
+    /// Tendermint hash, replaced by the application's hash if the transaction could be decoded.
+    pub fn new(hash: String, height: Height, result: response::DeliverTx) -> Self {
+        let events: Vec<Event> = result.events.into_iter().map(Event::from).collect();
+
+        let hash = events
+            .iter()
+            .find(|event| event.kind == "app")
+            .and_then(|event| event.attributes.iter().find(|(key, _)| key == "hash"))
+            .map_or(hash, |(_, value)| value.clone());
+
+        let retdata = events
+            .iter()
+            .find(|event| event.kind == "execution")
+            .and_then(|event| event.attribute("retdata"))
+            .filter(|retdata| !retdata.is_empty())
+            .map(|retdata| retdata.split(',').map(str::to_string).collect())
+            .unwrap_or_default();
+
+        Self {
+            hash,
+            height: height.value(),
+            code: result.code.value(),
+            log: result.log,
+            gas_used: result.gas_used,
+            retdata,
+            events,
+        }
+    }
+
+    /// Formatted summary of the receipt, colorized for terminals if `color` is set.
+    pub fn render(&self, color: bool) -> String {
+        let paint = |text: &str, code: &str| {
+            if color {
+                format!("\x1b[{code}m{text}\x1b[0m")
+            } else {
+                text.to_string()
+            }
+        };
+
+        let status = if self.code == 0 {
+            paint("succeeded", "32")
+        } else {
+            paint(&format!("failed (code {})", self.code), "31")
+        };
+
+        let mut lines = vec![
+            format!("{} {}", paint("Transaction", "1"), self.hash),
+            format!("  Status:        {status}"),
+            format!("  Block:         {}", self.height),
+            format!("  Gas used:      {}", self.gas_used),
+        ];
+        if !self.retdata.is_empty() {
+            lines.push(format!("  Return values: [{}]", self.retdata.join(", ")));
+        }
+        if !self.log.is_empty() {
+            lines.push(format!("  Log:           {}", self.log));
+        }
+
+        let contract_events: Vec<&Event> = self
+            .events
+            .iter()
+            .filter(|event| event.kind == "contract_event")
+            .collect();
+        if !contract_events.is_empty() {
+            lines.push("  Events:".to_string());
+        }
+        for event in contract_events {
+            let values = |key: &str| {
+                event
+                    .attributes
+                    .iter()
+                    .filter(|(k, _)| k == key)
+                    .map(|(_, value)| value.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            lines.push(format!(
+                "    {} keys [{}] data [{}]",
+                paint(&values("from_address"), "36"),
+                values("key"),
+                values("data").replace(',', ", ")
+            ));
+        }
+
+        lines.join("\n")
+    }
+}
+
+impl Event {
+    fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl From<tx::Response> for Receipt {
+    fn from(response: tx::Response) -> Self {
+        Self::new(
+            response.hash.to_string(),
+            response.height,
+            response.tx_result,
+        )
+    }
+}
+
+impl From<abci::Event> for Event {
+    fn from(event: abci::Event) -> Self {
+        Self {
+            kind: event.kind,
+            attributes: event
+                .attributes
+                .into_iter()
+                .map(|attribute| (attribute.key, attribute.value))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Receipt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(false))
+    }
+}
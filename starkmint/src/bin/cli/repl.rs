@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use color_eyre::Result;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use serde_json::json;
+
+use crate::output::Output;
+use crate::{execute, exit, Command, Context};
+
+/// A REPL line: any subcommand, without the global flags set when starting the session.
+#[derive(Debug, Parser)]
+#[command(no_binary_name = true, disable_version_flag = true)]
+struct Line {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Short forms of the most used subcommands.
+const SHORTCUTS: [(&str, &str); 4] = [
+    ("invoke", "execute"),
+    ("q", "query"),
+    ("s", "status"),
+    ("tx", "query tx"),
+];
+
+/// Reads subcommands interactively until EOF or `exit`, reusing the session's settings.
+pub async fn run(context: &Context) -> Result<Output> {
+    let mut editor = DefaultEditor::new()?;
+    let history = history_path();
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    println!(
+        "Connected to {}. Type `help` for the list of commands, `exit` to quit.",
+        context.url
+    );
+
+    loop {
+        let line = match editor.readline("starkmint> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "exit" || line == "quit" {
+            break;
+        }
+        let _ = editor.add_history_entry(line);
+
+        let Some(words) = shlex::split(&expand_shortcut(line)) else {
+            println!("error: unbalanced quotes");
+            continue;
+        };
+
+        match Line::try_parse_from(words) {
+            Ok(Line {
+                command: Command::Repl,
+            }) => println!("error: already in a REPL session"),
+            Ok(Line { command }) => match execute(&command, context).await {
+                Ok(output) => println!("{}", output.render(context.format)),
+                Err(e) => println!(
+                    "{} (exit code {})",
+                    Output::error(&e, context.format),
+                    exit::code(&e)
+                ),
+            },
+            // help and usage errors
+            Err(e) => println!("{e}"),
+        }
+    }
+
+    if let Some(history) = &history {
+        if let Some(dir) = history.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let _ = editor.save_history(history);
+    }
+    Output::new("", json!({}))
+}
+
+fn expand_shortcut(line: &str) -> String {
+    let (first, rest) = line.split_once(' ').unwrap_or((line, ""));
+    match SHORTCUTS.iter().find(|(shortcut, _)| *shortcut == first) {
+        Some((_, command)) => format!("{command} {rest}"),
+        None => line.to_string(),
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".starkmint").join("history"))
+}
@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use color_eyre::{eyre::WrapErr, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use tendermint_rpc::{Client, Error, HttpClient, Response, SimpleRequest};
+
+/// How requests reach the node: extra headers, e.g. to authenticate with a gateway in front
+/// of it, and the proxy they go through.
+#[derive(Debug, Clone, Default)]
+pub struct Transport {
+    pub headers: Vec<(String, String)>,
+    pub proxy: Option<String>,
+}
+
+impl Transport {
+    /// RPC client of the node at `url`.
+    pub fn client(&self, url: &str) -> Result<NodeClient> {
+        // tendermint's client can't send extra headers, requests with them are sent directly
+        if self.headers.is_empty() {
+            let client = match &self.proxy {
+                Some(proxy) => HttpClient::new_with_proxy(url, proxy.as_str())?,
+                None => HttpClient::new(url)?,
+            };
+            return Ok(NodeClient::Http(client));
+        }
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .wrap_err_with(|| format!("Invalid header name {name}"))?,
+                HeaderValue::from_str(value)
+                    .wrap_err_with(|| format!("Invalid value of header {name}"))?,
+            );
+        }
+
+        let mut builder = reqwest::Client::builder().default_headers(headers);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy.as_str())?);
+        }
+        Ok(NodeClient::Gateway {
+            client: builder.build()?,
+            url: url.to_string(),
+        })
+    }
+}
+
+/// Client of the node's RPC.
+#[derive(Debug, Clone)]
+pub enum NodeClient {
+    Http(HttpClient),
+    /// Node behind a gateway that requires extra headers.
+    Gateway {
+        client: reqwest::Client,
+        url: String,
+    },
+}
+
+#[async_trait]
+impl Client for NodeClient {
+    async fn perform<R>(&self, request: R) -> Result<R::Response, Error>
+    where
+        R: SimpleRequest,
+    {
+        match self {
+            NodeClient::Http(client) => client.perform(request).await,
+            NodeClient::Gateway { client, url } => {
+                let response = client
+                    .post(url.as_str())
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(request.into_json())
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .map_err(|e| Error::client_internal(e.to_string()))?;
+                let body = response
+                    .bytes()
+                    .await
+                    .map_err(|e| Error::client_internal(e.to_string()))?;
+
+                R::Response::from_string(body)
+            }
+        }
+    }
+}
+
+/// Parses a `--header` value, given as `Name: value`.
+pub fn parse_header(value: &str) -> Result<(String, String), String> {
+    let (name, value) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected `Name: value`, got `{value}`"))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
@@ -0,0 +1,33 @@
+use color_eyre::Result;
+use serde_json::json;
+use tendermint_rpc::Client;
+
+use crate::output::Output;
+use crate::rpc::NodeClient;
+
+/// Prints the chain the node is on and the state of the application.
+pub async fn run(client: &NodeClient) -> Result<Output> {
+    let status = client.status().await?;
+    let info = client.abci_info().await?;
+
+    let chain_id = status.node_info.network.to_string();
+    let height = info.last_block_height.value();
+    let app_hash = hex::encode(info.last_block_app_hash.as_ref());
+
+    let text = format!(
+        "Chain ID: {chain_id}\nLatest height: {height}\nApp hash: 0x{app_hash}\nApp version: {} ({})\nCatching up: {}",
+        info.app_version, info.version, status.sync_info.catching_up
+    );
+
+    Output::new(
+        text,
+        json!({
+            "chain_id": chain_id,
+            "latest_height": height,
+            "app_hash": format!("0x{app_hash}"),
+            "app_version": info.app_version,
+            "version": info.version,
+            "catching_up": status.sync_info.catching_up,
+        }),
+    )
+}
@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use clap::Subcommand;
+use color_eyre::Result;
+
+use crate::output::{self, Output};
+use crate::rpc::NodeClient;
+use crate::{exit, wait_for_receipt};
+
+#[derive(Debug, Subcommand)]
+pub enum TxCommand {
+    /// Wait for a transaction to be included in a block and print its receipt, e.g. after
+    /// sending it with `--broadcast-mode async`.
+    Wait {
+        /// Transaction hash.
+        hash: String,
+
+        /// How long to wait before giving up, e.g. `60s` or `5m`.
+        #[arg(long, default_value = "60s", value_parser = humantime::parse_duration)]
+        timeout: Duration,
+    },
+}
+
+pub async fn run(command: &TxCommand, client: &NodeClient) -> Result<Output> {
+    match command {
+        TxCommand::Wait { hash, timeout } => {
+            let receipt = wait_for_receipt(hash, client, *timeout).await?;
+            let output = Output::new(receipt.render(output::color()), &receipt)?;
+            Ok(if receipt.code != 0 {
+                output.with_exit_code(exit::DELIVERY_FAILED)
+            } else {
+                output
+            })
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use clap::Args;
+use color_eyre::Result;
+use futures::{stream, StreamExt};
+use serde_json::json;
+use starkmint::execution::felt_to_hex;
+use starkmint::transaction::Transaction;
+use tendermint_rpc::event::{Event, EventData};
+use tendermint_rpc::query::{EventType, Query};
+use tendermint_rpc::{SubscriptionClient, WebSocketClient};
+
+use crate::output::{Format, Output};
+use crate::Context;
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// Only show transactions invoking this function.
+    #[clap(long)]
+    pub function: Option<String>,
+
+    /// Only show transactions signed by this account.
+    #[clap(long)]
+    pub sender: Option<String>,
+
+    /// Don't show new blocks, only transactions.
+    #[clap(long)]
+    pub no_blocks: bool,
+
+    /// Node websocket url [default: the node url, with the ws scheme and /websocket path]
+    #[clap(long)]
+    pub ws_url: Option<String>,
+}
+
+/// Subscribes to the node's events and prints them as they arrive, until the connection is
+/// closed.
+pub async fn run(args: &WatchArgs, context: &Context) -> Result<Output> {
+    let url = args
+        .ws_url
+        .clone()
+        .unwrap_or_else(|| websocket_url(&context.url));
+    let (client, driver) = WebSocketClient::new(url.as_str()).await?;
+    let driver = tokio::spawn(async move { driver.run().await });
+
+    let mut query = Query::from(EventType::Tx);
+    if let Some(function) = &args.function {
+        query = query.and_eq("function.function", function.as_str());
+    }
+    if let Some(sender) = &args.sender {
+        query = query.and_eq("app.sender", sender.as_str());
+    }
+
+    let txs = client.subscribe(query).await?;
+    let mut events = if args.no_blocks {
+        txs.boxed()
+    } else {
+        let blocks = client.subscribe(EventType::NewBlock.into()).await?;
+        stream::select(txs, blocks).boxed()
+    };
+
+    while let Some(event) = events.next().await {
+        if let Some(line) = render(event?, context.format) {
+            println!("{line}");
+        }
+    }
+
+    client.close()?;
+    let _ = driver.await;
+    Output::new("Connection closed", json!({ "closed": true }))
+}
+
+fn render(event: Event, format: Format) -> Option<String> {
+    let output = match event.data {
+        EventData::NewBlock {
+            block: Some(block), ..
+        } => {
+            let height = block.header.height.value();
+            let txs = block.data.len();
+            Output::new(
+                format!("Block {height} with {txs} transactions"),
+                json!({ "type": "block", "height": height, "transactions": txs }),
+            )
+        }
+        EventData::Tx { tx_result } => {
+            let tx: Option<Transaction> = bincode::deserialize(&tx_result.tx).ok();
+
+            let hash = tx.as_ref().map(Transaction::hash);
+            let sender = tx
+                .as_ref()
+                .and_then(|tx| tx.signature.as_ref())
+                .map(|signature| felt_to_hex(&signature.sender));
+            Output::new(
+                format!(
+                    "Transaction {} at height {}",
+                    hash.as_deref().unwrap_or("<undecodable>"),
+                    tx_result.height,
+                ),
+                json!({
+                    "type": "transaction",
+                    "height": tx_result.height,
+                    "transaction_hash": hash,
+                    "sender": sender,
+                }),
+            )
+        }
+        _ => return None,
+    };
+
+    output.ok().map(|output| output.render(format))
+}
+
+/// Websocket endpoint of a node given its http url.
+fn websocket_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    let url = match url.split_once("://") {
+        Some(("https", rest)) => format!("wss://{rest}"),
+        Some((_, rest)) => format!("ws://{rest}"),
+        None => format!("ws://{url}"),
+    };
+    format!("{url}/websocket")
+}
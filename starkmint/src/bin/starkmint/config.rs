@@ -0,0 +1,593 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use color_eyre::{
+    eyre::{ensure, eyre, WrapErr},
+    Result,
+};
+use serde::Deserialize;
+use starkmint::execution::{self, Builtin};
+
+use crate::da::{Celestia, DaLayer};
+use crate::export::ExportSink;
+use crate::logging::{self, LogFormat, LogRotation};
+use crate::settlement;
+
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 26658;
+pub const DEFAULT_GAS_PRICE: u64 = 1;
+pub const DEFAULT_REQUEST_BOUND: usize = 1;
+pub const DEFAULT_MEMPOOL_BUFFER: usize = 10;
+pub const DEFAULT_INFO_BUFFER: usize = 100;
+pub const DEFAULT_INFO_RATE_LIMIT: u64 = 50;
+pub const DEFAULT_QUEUE_ALARM: u8 = 80;
+pub const DEFAULT_READY_MAX_COMMIT_AGE: u64 = 60;
+pub const DEFAULT_TENDERMINT_RPC: &str = "http://127.0.0.1:26657";
+pub const DEFAULT_SETTLEMENT_INTERVAL: u64 = 100;
+pub const DEFAULT_STATE_UPDATE_HISTORY: usize = 1000;
+pub const DEFAULT_EXPORT_SUBJECT: &str = "starkmint.blocks";
+pub const DEFAULT_L1_CONFIRMATIONS: u64 = 6;
+pub const DEFAULT_L1_POLL_INTERVAL: u64 = 12;
+
+/// Value of the setting `name`, which a node with `required_by` set can't run without, so it's
+/// never defaulted: [`Settings::validate`] fails at startup if it's missing.
+pub fn required<T>(value: Option<T>, name: &str, required_by: &str) -> Result<T> {
+    value.ok_or_else(|| eyre!("{required_by} requires {name}"))
+}
+
+/// Node settings, given as flags or in the TOML file passed with `--config`. Flags take
+/// precedence over the file.
+///
+/// ```toml
+/// host = "0.0.0.0"
+/// port = 26658
+/// data_dir = "/var/lib/starkmint"
+/// builtins = ["output", "pedersen", "range_check"]
+/// gas_price = 10
+/// ```
+#[derive(Debug, Clone, Default, Args, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Bind the TCP server to this host, unless --socket is given [default: 127.0.0.1]
+    #[arg(long)]
+    pub host: Option<String>,
+
+    /// Bind the TCP server to this port [default: 26658]
+    #[arg(short, long)]
+    pub port: Option<u16>,
+
+    /// Accept ABCI connections on this unix socket instead of the TCP server, for Tendermint
+    /// configured with `proxy_app = "unix://<path>"`.
+    #[arg(long)]
+    pub socket: Option<PathBuf>,
+
+    /// Directory the node keeps its files in. It's locked while the node runs, so it can't
+    /// be shared by two nodes [default: /tmp/starkmint]
+    #[arg(long)]
+    pub data_dir: Option<PathBuf>,
+
+    /// ID of the chain the node runs. The node refuses to initialize any other chain, and
+    /// reports it to Tendermint in Info.
+    #[arg(long, env = "STARKMINT_CHAIN_ID")]
+    pub chain_id: Option<String>,
+
+    /// Write the node's process ID to this file while it runs.
+    #[arg(long)]
+    pub pid_file: Option<PathBuf>,
+
+    /// Stop the node once the block at this height is committed, e.g. to upgrade the binary
+    /// at the same height as the other validators.
+    #[arg(long)]
+    pub halt_height: Option<u64>,
+
+    /// Heights of halts set by governance that are ignored, so an upgraded node replaying
+    /// the chain doesn't halt again at them.
+    #[arg(long, value_delimiter = ',')]
+    pub skip_halt_heights: Option<Vec<u64>>,
+
+    /// Cairo builtins available to executed programs. Programs requiring any other
+    /// builtin are rejected [default: all]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub builtins: Option<Vec<Builtin>>,
+
+    /// JSON file with the list of hint codes programs may contain, besides syscalls and the
+    /// audited hints of the Cairo common library. Programs containing any other hint are
+    /// rejected.
+    #[arg(long)]
+    pub hint_allowlist: Option<PathBuf>,
+
+    /// Allow programs to contain any hint the node implements, instead of only audited ones.
+    /// Some hints aren't deterministic and could fork validators, so this is only meant for
+    /// devnets [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub allow_all_hints: Option<bool>,
+
+    /// Directory where program artifacts are cached, so they are parsed once and survive
+    /// restarts. Without it, programs are only cached in memory.
+    #[arg(long)]
+    pub program_cache_dir: Option<PathBuf>,
+
+    /// Execute each block's transactions concurrently on this many threads, re-executing
+    /// conflicting transactions serially. Transactions are executed one by one if unset.
+    #[arg(long)]
+    pub parallel_execution: Option<usize>,
+
+    /// Maximum number of CheckTx and Query requests executed concurrently, off the threads
+    /// handling consensus requests [default: number of CPUs]
+    #[arg(long)]
+    pub check_workers: Option<usize>,
+
+    /// Number of query responses cached until the next commit, 0 to disable caching
+    /// [default: 1024]
+    #[arg(long)]
+    pub query_cache_size: Option<usize>,
+
+    /// Number of function executions memoized, so identical calls against unchanged storage
+    /// aren't executed again, 0 to disable memoization [default: 1024]
+    #[arg(long)]
+    pub execution_cache_size: Option<usize>,
+
+    /// Maximum number of transactions a sender may have in the mempool, unsigned transactions
+    /// counting as those of a single sender; transactions beyond it are rejected by CheckTx
+    /// [default: unlimited]
+    #[arg(long)]
+    pub max_pending_per_sender: Option<usize>,
+
+    /// Gas a transaction may use, which must be the chain's `max_tx_gas` genesis parameter:
+    /// DeliverTx executions are bounded to as many steps and fail beyond it, and CheckTx
+    /// rejects transactions expected to use more [default: unlimited]
+    #[arg(long)]
+    pub max_tx_gas: Option<u64>,
+
+    /// Flush the files written at commit to disk before answering Tendermint. Disabling it
+    /// speeds up commits but loses them if the machine crashes [default: true, false for
+    /// devnets]
+    #[arg(long)]
+    pub commit_sync: Option<bool>,
+
+    /// Profile the steps, builtins and wall time of delivered transactions by entry point,
+    /// served by the `/profile` query and logged at every commit [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub profile: Option<bool>,
+
+    /// Append the state writes of every block, with the hashes of the values they replace,
+    /// to `abci.audit` in the data dir, to debug app hash mismatches [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub audit_log: Option<bool>,
+
+    /// Write the genesis and every committed block, with its app hash, to `blocks` in the
+    /// data dir, to be replayed by `starkmint replay` [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub archive_blocks: Option<bool>,
+
+    /// Log what happens inside the Cairo VM as transactions and calls are executed: the
+    /// segments programs are loaded into, the steps they run, the syscalls they invoke and
+    /// why they fail. Executions aren't memoized while traced [default: false]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub trace_execution: Option<bool>,
+
+    /// Price of a unit of gas, used by the `/estimate_fee` query [default: 1]
+    #[arg(long)]
+    pub gas_price: Option<u64>,
+
+    /// Account credited with the fees paid by senders, in decimal or 0x-prefixed hex. Like
+    /// the gas price, it must match the chain's genesis [default: none, fees are burned]
+    #[arg(long)]
+    pub fee_recipient: Option<String>,
+
+    /// Number of requests, from all ABCI connections, queued for the application before
+    /// callers wait. The application handles requests one at a time, so larger values only
+    /// absorb bursts; they don't add concurrency [default: 1]
+    #[arg(long)]
+    pub request_bound: Option<usize>,
+
+    /// Number of CheckTx requests queued before new ones are shed [default: 10]
+    #[arg(long)]
+    pub mempool_buffer: Option<usize>,
+
+    /// Maximum number of CheckTx requests served per second. Unlimited if unset.
+    #[arg(long)]
+    pub mempool_rate_limit: Option<u64>,
+
+    /// Number of Info and Query requests queued before new ones are shed [default: 100]
+    #[arg(long)]
+    pub info_buffer: Option<usize>,
+
+    /// Maximum number of Info and Query requests served per second [default: 50]
+    #[arg(long)]
+    pub info_rate_limit: Option<u64>,
+
+    /// Warn when the CheckTx or Info queue is this full, in percent of its buffer; shed
+    /// requests are always logged [default: 80]
+    #[arg(long)]
+    pub queue_alarm: Option<u8>,
+
+    /// Serve Prometheus metrics (`/metrics`) and health checks (`/healthz`, `/readyz`) over
+    /// HTTP at this address, e.g. `127.0.0.1:9090`.
+    #[arg(long, alias = "metrics-address")]
+    #[serde(alias = "metrics_address")]
+    pub http_address: Option<SocketAddr>,
+
+    /// Longest time, in seconds, since the last commit for the node to be reported as ready
+    /// by `/readyz` [default: 60]
+    #[arg(long)]
+    pub ready_max_commit_age: Option<u64>,
+
+    /// Serve a subset of the Starknet JSON-RPC API at this address, e.g. `127.0.0.1:9545`,
+    /// along with the feeder gateway's `get_block`, `get_transaction` and `get_state_update`
+    /// endpoints under `/feeder_gateway/`.
+    #[arg(long)]
+    pub rpc_address: Option<SocketAddr>,
+
+    /// Number of recent blocks whose state updates are kept in memory for the feeder
+    /// gateway's `get_state_update` [default: 1000]
+    #[arg(long)]
+    pub state_update_history: Option<usize>,
+
+    /// Publish the receipts and contract events of committed transactions over WebSocket at
+    /// this address, e.g. `127.0.0.1:9546`. Clients subscribe by connecting to `/receipts` or
+    /// `/events`, with filters in the query string.
+    #[arg(long)]
+    pub ws_address: Option<SocketAddr>,
+
+    /// Serve application queries (transactions, classes, storage, accounts and blocks) over
+    /// gRPC at this address, e.g. `127.0.0.1:9090`.
+    #[arg(long)]
+    pub grpc_address: Option<SocketAddr>,
+
+    /// RPC endpoint of the Tendermint node, where the Starknet JSON-RPC API and the L1 relayer
+    /// send transactions, and where the API, the gRPC service and the block export read
+    /// transactions and blocks [default: http://127.0.0.1:26657]
+    #[arg(long)]
+    pub tendermint_rpc: Option<String>,
+
+    /// Settle the chain on Ethereum: post the app hash of every `--settlement-interval`
+    /// blocks to the settlement contract, through this Ethereum JSON-RPC endpoint.
+    #[arg(long)]
+    pub settlement_rpc: Option<String>,
+
+    /// Address of the Ethereum contract state roots are settled on, implementing
+    /// `settle(uint256 height, bytes32 stateRoot, bytes32 proofReference)` and
+    /// `latestHeight()`.
+    #[arg(long)]
+    pub settlement_contract: Option<String>,
+
+    /// Ethereum account sending the settlement transactions, which must be unlocked at the
+    /// `--settlement-rpc` endpoint.
+    #[arg(long)]
+    pub settlement_account: Option<String>,
+
+    /// Number of blocks between two settled state roots [default: 100]
+    #[arg(long)]
+    pub settlement_interval: Option<u64>,
+
+    /// Publish the state diff of every block to this data availability layer, on chains
+    /// created with `data_availability` in their genesis parameters.
+    #[arg(long, value_enum)]
+    pub da_layer: Option<DaLayer>,
+
+    /// URL of the data availability layer: the base URL of the blob store, or the RPC
+    /// endpoint of the Celestia node.
+    #[arg(long)]
+    pub da_url: Option<String>,
+
+    /// Hex-encoded, 10-byte ID of the Celestia namespace state diffs are posted under.
+    #[arg(long)]
+    pub da_namespace: Option<String>,
+
+    /// Token authenticating the node with the Celestia node's RPC.
+    #[arg(long, env = "STARKMINT_DA_AUTH_TOKEN", hide_env_values = true)]
+    pub da_auth_token: Option<String>,
+
+    /// Export every committed block, with its decoded transactions and receipts, to this sink
+    /// for external indexers. Blocks are read from Tendermint (`--tendermint-rpc`).
+    #[arg(long, value_enum)]
+    pub export_sink: Option<ExportSink>,
+
+    /// URL of the export sink: the webhook's URL, or the NATS server's, e.g.
+    /// `nats://127.0.0.1:4222`.
+    #[arg(long)]
+    pub export_url: Option<String>,
+
+    /// NATS subject blocks are published on [default: starkmint.blocks]
+    #[arg(long)]
+    pub export_subject: Option<String>,
+
+    /// Relay the messages sent to the chain from L1: watch the messaging contract through
+    /// this Ethereum JSON-RPC endpoint and inject each message as an L1 handler transaction.
+    #[arg(long)]
+    pub l1_rpc: Option<String>,
+
+    /// Address of the Ethereum messaging contract, emitting Starknet's `LogMessageToL2`
+    /// events.
+    #[arg(long)]
+    pub l1_contract: Option<String>,
+
+    /// Private key of the L1 relayer set at genesis, signing the L1 handler transactions.
+    #[arg(long, env = "STARKMINT_L1_RELAYER_KEY", hide_env_values = true)]
+    pub l1_relayer_key: Option<String>,
+
+    /// L1 blocks a message must be buried under before it's relayed [default: 6]
+    #[arg(long)]
+    pub l1_confirmations: Option<u64>,
+
+    /// Seconds between two reads of L1 [default: 12]
+    #[arg(long)]
+    pub l1_poll_interval: Option<u64>,
+
+    /// First L1 block whose messages are relayed, e.g. to catch up after downtime. Relaying
+    /// starts from the next L1 block if not given.
+    #[arg(long)]
+    pub l1_start_block: Option<u64>,
+
+    /// Level of the logs written, e.g. `debug`. Overridden by `--verbose` and `--quiet`
+    /// [default: info]
+    #[arg(long)]
+    pub log_level: Option<String>,
+
+    /// Format of log lines [default: text]
+    #[arg(long, value_enum)]
+    pub log_format: Option<LogFormat>,
+
+    /// Write logs to this file instead of stdout.
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// How often the log file is rotated [default: never]
+    #[arg(long, value_enum)]
+    pub log_rotation: Option<LogRotation>,
+
+    /// Export traces and metrics over OTLP (gRPC) to the collector at this endpoint, e.g.
+    /// `http://127.0.0.1:4317`. Requires a node built with the `otel` feature.
+    #[arg(long)]
+    pub otlp_endpoint: Option<String>,
+}
+
+impl Settings {
+    /// Loads the settings of a configuration file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Error reading config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .wrap_err_with(|| format!("Error parsing config file {}", path.display()))
+    }
+
+    /// Fails if a setting has a value the node can't run with.
+    pub fn validate(&self) -> Result<()> {
+        let queues = [
+            ("request_bound", self.request_bound),
+            ("mempool_buffer", self.mempool_buffer),
+            ("info_buffer", self.info_buffer),
+            ("check_workers", self.check_workers),
+            ("max_pending_per_sender", self.max_pending_per_sender),
+        ];
+        for (name, value) in queues {
+            ensure!(value != Some(0), "{name} must be at least 1");
+        }
+
+        let rates = [
+            ("mempool_rate_limit", self.mempool_rate_limit),
+            ("info_rate_limit", self.info_rate_limit),
+        ];
+        for (name, value) in rates {
+            ensure!(value != Some(0), "{name} must be at least 1");
+        }
+
+        ensure!(
+            self.max_tx_gas != Some(0),
+            "max_tx_gas must be at least 1, or unset for no limit"
+        );
+
+        if let Some(alarm) = self.queue_alarm {
+            ensure!(
+                (1..=100).contains(&alarm),
+                "queue_alarm must be between 1 and 100"
+            );
+        }
+
+        ensure!(
+            cfg!(feature = "otel") || self.otlp_endpoint.is_none(),
+            "otlp_endpoint requires a node built with the otel feature"
+        );
+
+        ensure!(
+            self.settlement_interval != Some(0),
+            "settlement_interval must be at least 1"
+        );
+        if self.settlement_rpc.is_some() {
+            ensure!(
+                self.settlement_contract.is_some() && self.settlement_account.is_some(),
+                "settlement_rpc requires settlement_contract and settlement_account"
+            );
+        }
+        for (name, address) in [
+            ("settlement_contract", &self.settlement_contract),
+            ("settlement_account", &self.settlement_account),
+        ] {
+            if let Some(address) = address {
+                settlement::validate_address(address)
+                    .wrap_err_with(|| format!("Invalid {name}"))?;
+            }
+        }
+
+        if let Some(layer) = self.da_layer {
+            ensure!(self.da_url.is_some(), "da_layer requires da_url");
+            if layer == DaLayer::Celestia {
+                let namespace = self
+                    .da_namespace
+                    .as_deref()
+                    .ok_or_else(|| eyre!("Publishing to Celestia requires da_namespace"))?;
+                Celestia::namespace(namespace).wrap_err("Invalid da_namespace")?;
+            }
+        }
+
+        if self.export_sink.is_some() {
+            ensure!(self.export_url.is_some(), "export_sink requires export_url");
+        }
+
+        ensure!(
+            self.l1_poll_interval != Some(0),
+            "l1_poll_interval must be at least 1"
+        );
+        if self.l1_rpc.is_some() {
+            ensure!(
+                self.l1_contract.is_some() && self.l1_relayer_key.is_some(),
+                "l1_rpc requires l1_contract and l1_relayer_key"
+            );
+        }
+        if let Some(contract) = &self.l1_contract {
+            settlement::validate_address(contract).wrap_err("Invalid l1_contract")?;
+        }
+        if let Some(key) = &self.l1_relayer_key {
+            execution::parse_felt(key).wrap_err("Invalid l1_relayer_key")?;
+        }
+
+        ensure!(
+            !(self.allow_all_hints == Some(true) && self.hint_allowlist.is_some()),
+            "allow_all_hints and hint_allowlist can't be set together"
+        );
+
+        if let Some(recipient) = &self.fee_recipient {
+            execution::parse_felt(recipient).wrap_err("Invalid fee_recipient")?;
+        }
+
+        if let Some(level) = &self.log_level {
+            logging::parse_level(level)?;
+        }
+        Ok(())
+    }
+
+    /// Settings given here, falling back to `defaults` for the ones that aren't.
+    pub fn or(self, defaults: Settings) -> Settings {
+        Settings {
+            host: self.host.or(defaults.host),
+            port: self.port.or(defaults.port),
+            socket: self.socket.or(defaults.socket),
+            data_dir: self.data_dir.or(defaults.data_dir),
+            chain_id: self.chain_id.or(defaults.chain_id),
+            pid_file: self.pid_file.or(defaults.pid_file),
+            halt_height: self.halt_height.or(defaults.halt_height),
+            skip_halt_heights: self.skip_halt_heights.or(defaults.skip_halt_heights),
+            builtins: self.builtins.or(defaults.builtins),
+            hint_allowlist: self.hint_allowlist.or(defaults.hint_allowlist),
+            allow_all_hints: self.allow_all_hints.or(defaults.allow_all_hints),
+            program_cache_dir: self.program_cache_dir.or(defaults.program_cache_dir),
+            parallel_execution: self.parallel_execution.or(defaults.parallel_execution),
+            check_workers: self.check_workers.or(defaults.check_workers),
+            query_cache_size: self.query_cache_size.or(defaults.query_cache_size),
+            execution_cache_size: self.execution_cache_size.or(defaults.execution_cache_size),
+            max_pending_per_sender: self
+                .max_pending_per_sender
+                .or(defaults.max_pending_per_sender),
+            max_tx_gas: self.max_tx_gas.or(defaults.max_tx_gas),
+            commit_sync: self.commit_sync.or(defaults.commit_sync),
+            profile: self.profile.or(defaults.profile),
+            audit_log: self.audit_log.or(defaults.audit_log),
+            archive_blocks: self.archive_blocks.or(defaults.archive_blocks),
+            trace_execution: self.trace_execution.or(defaults.trace_execution),
+            gas_price: self.gas_price.or(defaults.gas_price),
+            fee_recipient: self.fee_recipient.or(defaults.fee_recipient),
+            request_bound: self.request_bound.or(defaults.request_bound),
+            mempool_buffer: self.mempool_buffer.or(defaults.mempool_buffer),
+            mempool_rate_limit: self.mempool_rate_limit.or(defaults.mempool_rate_limit),
+            info_buffer: self.info_buffer.or(defaults.info_buffer),
+            info_rate_limit: self.info_rate_limit.or(defaults.info_rate_limit),
+            queue_alarm: self.queue_alarm.or(defaults.queue_alarm),
+            http_address: self.http_address.or(defaults.http_address),
+            ready_max_commit_age: self.ready_max_commit_age.or(defaults.ready_max_commit_age),
+            rpc_address: self.rpc_address.or(defaults.rpc_address),
+            state_update_history: self.state_update_history.or(defaults.state_update_history),
+            ws_address: self.ws_address.or(defaults.ws_address),
+            grpc_address: self.grpc_address.or(defaults.grpc_address),
+            tendermint_rpc: self.tendermint_rpc.or(defaults.tendermint_rpc),
+            settlement_rpc: self.settlement_rpc.or(defaults.settlement_rpc),
+            settlement_contract: self.settlement_contract.or(defaults.settlement_contract),
+            settlement_account: self.settlement_account.or(defaults.settlement_account),
+            settlement_interval: self.settlement_interval.or(defaults.settlement_interval),
+            da_layer: self.da_layer.or(defaults.da_layer),
+            da_url: self.da_url.or(defaults.da_url),
+            da_namespace: self.da_namespace.or(defaults.da_namespace),
+            da_auth_token: self.da_auth_token.or(defaults.da_auth_token),
+            export_sink: self.export_sink.or(defaults.export_sink),
+            export_url: self.export_url.or(defaults.export_url),
+            export_subject: self.export_subject.or(defaults.export_subject),
+            l1_rpc: self.l1_rpc.or(defaults.l1_rpc),
+            l1_contract: self.l1_contract.or(defaults.l1_contract),
+            l1_relayer_key: self.l1_relayer_key.or(defaults.l1_relayer_key),
+            l1_confirmations: self.l1_confirmations.or(defaults.l1_confirmations),
+            l1_poll_interval: self.l1_poll_interval.or(defaults.l1_poll_interval),
+            l1_start_block: self.l1_start_block.or(defaults.l1_start_block),
+            log_level: self.log_level.or(defaults.log_level),
+            log_format: self.log_format.or(defaults.log_format),
+            log_file: self.log_file.or(defaults.log_file),
+            log_rotation: self.log_rotation.or(defaults.log_rotation),
+            otlp_endpoint: self.otlp_endpoint.or(defaults.otlp_endpoint),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<Settings> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    #[test]
+    fn flags_take_precedence_over_the_configuration_file() {
+        let file = parse(
+            r#"
+            host = "0.0.0.0"
+            port = 26658
+            gas_price = 10
+            builtins = ["output", "pedersen"]
+            "#,
+        )
+        .unwrap();
+        let flags = Settings {
+            port: Some(26659),
+            parallel_execution: Some(4),
+            ..Default::default()
+        };
+
+        let settings = flags.or(file);
+        assert_eq!(settings.host.as_deref(), Some("0.0.0.0"));
+        assert_eq!(settings.port, Some(26659));
+        assert_eq!(settings.gas_price, Some(10));
+        assert_eq!(settings.parallel_execution, Some(4));
+        assert_eq!(
+            settings.builtins,
+            Some(vec![Builtin::Output, Builtin::Pedersen])
+        );
+        assert_eq!(settings.data_dir, None);
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert!(parse("gas-price = 10").is_err());
+        assert!(parse("port = \"26658\"").is_err());
+    }
+
+    #[test]
+    fn settings_the_node_cant_run_with_are_rejected() {
+        assert!(Settings::default().validate().is_ok());
+        for contents in [
+            "mempool_buffer = 0",
+            "max_pending_per_sender = 0",
+            "max_tx_gas = 0",
+            "queue_alarm = 101",
+            "settlement_rpc = \"http://127.0.0.1:8545\"",
+            "l1_rpc = \"http://127.0.0.1:8545\"",
+            "da_layer = \"blob\"",
+            "allow_all_hints = true\nhint_allowlist = \"hints.json\"",
+            "fee_recipient = \"recipient\"",
+        ] {
+            assert!(
+                parse(contents).unwrap().validate().is_err(),
+                "{contents:?} was accepted"
+            );
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Publishing of the state diffs of committed blocks to a data availability layer, on chains
+//! created with `data_availability` in their genesis parameters.
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{bail, ensure, eyre},
+    Result,
+};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use starkmint::da::DataBlob;
+use starkmint::execution::felt_to_hex;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+/// Length in bytes of the ID of a Celestia namespace.
+const NAMESPACE_ID_LEN: usize = 10;
+
+/// Data availability layer state diffs are published to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DaLayer {
+    /// Generic blob store: each blob is `PUT` at `<url>/<commitment>`.
+    Blob,
+    /// Celestia, through the `blob.Submit` JSON-RPC method of a celestia-node.
+    Celestia,
+}
+
+/// Layer blobs are published to, returning a reference to find them there.
+#[async_trait]
+pub trait Publisher: Send + Sync {
+    async fn publish(&self, blob: &DataBlob) -> Result<String>;
+}
+
+/// Content-addressed blob store, such as an object storage bucket behind an HTTP gateway.
+pub struct BlobStore {
+    pub client: reqwest::Client,
+    pub url: String,
+}
+
+#[async_trait]
+impl Publisher for BlobStore {
+    async fn publish(&self, blob: &DataBlob) -> Result<String> {
+        let url = format!(
+            "{}/{}",
+            self.url.trim_end_matches('/'),
+            felt_to_hex(&blob.commitment)
+        );
+        self.client
+            .put(url.as_str())
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(blob.data.to_vec())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(url)
+    }
+}
+
+/// Celestia node, posting blobs under a namespace of the chain.
+pub struct Celestia {
+    pub client: reqwest::Client,
+    pub url: String,
+    /// Version 0 namespace: a zero version byte, 18 zero bytes and the 10-byte ID.
+    pub namespace: [u8; 29],
+    pub auth_token: Option<String>,
+}
+
+impl Celestia {
+    /// Namespace of version 0 with the given hex-encoded ID.
+    pub fn namespace(id: &str) -> Result<[u8; 29]> {
+        let id = hex::decode(id.trim_start_matches("0x"))?;
+        ensure!(
+            id.len() == NAMESPACE_ID_LEN,
+            "Namespace IDs are {NAMESPACE_ID_LEN} bytes, got {}",
+            id.len()
+        );
+        let mut namespace = [0; 29];
+        namespace[29 - NAMESPACE_ID_LEN..].copy_from_slice(&id);
+        Ok(namespace)
+    }
+}
+
+#[async_trait]
+impl Publisher for Celestia {
+    async fn publish(&self, blob: &DataBlob) -> Result<String> {
+        // a negative gas price lets the node estimate it
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "blob.Submit",
+            "params": [
+                [{
+                    "namespace": STANDARD.encode(self.namespace),
+                    "data": STANDARD.encode(blob.data.as_slice()),
+                    "share_version": 0,
+                }],
+                -1.0,
+            ],
+        });
+        let mut builder = self
+            .client
+            .post(self.url.as_str())
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&request)?);
+        if let Some(token) = &self.auth_token {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let body = builder.send().await?.error_for_status()?.bytes().await?;
+
+        let response: Value = serde_json::from_slice(&body)?;
+        if let Some(error) = response.get("error") {
+            bail!("blob.Submit failed: {error}");
+        }
+        let height = response["result"]
+            .as_u64()
+            .ok_or_else(|| eyre!("Unexpected blob.Submit result {}", response["result"]))?;
+        Ok(format!("Celestia height {height}"))
+    }
+}
+
+/// Publishes the state diff of every block as it's committed, until the node stops. Diffs
+/// that fail to be published are logged and skipped, their commitments being recorded on
+/// chain regardless.
+///
+/// The blocks replayed when the node starts are published again: blob stores overwrite the
+/// same blob, while Celestia gets a duplicate, which readers tell apart by its commitment.
+pub async fn publish(publisher: Box<dyn Publisher>, mut blobs: Receiver<DataBlob>) {
+    loop {
+        let blob = match blobs.recv().await {
+            Ok(blob) => blob,
+            Err(RecvError::Lagged(missed)) => {
+                tracing::warn!("Publishing fell behind, {missed} state diffs were skipped");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        match publisher.publish(&blob).await {
+            Ok(reference) => tracing::info!(
+                "Published the state diff of block {} ({} bytes, commitment {}) to {reference}",
+                blob.height,
+                blob.data.len(),
+                felt_to_hex(&blob.commitment)
+            ),
+            Err(e) => tracing::warn!(
+                "Error publishing the state diff of block {}: {e:#}",
+                blob.height
+            ),
+        }
+    }
+}
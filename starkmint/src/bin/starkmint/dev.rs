@@ -0,0 +1,155 @@
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
+use cairo_felt::Felt;
+use clap::Args;
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use starkmint::execution::{self, felt_to_hex};
+use starkmint::genesis::{Genesis, GenesisAccount, GenesisParameters};
+use starkmint::signature;
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tracing::info;
+
+/// Chain ID of dev networks, unless the node is given one.
+pub const DEFAULT_CHAIN_ID: &str = "starkmint-dev";
+
+#[derive(Debug, Args)]
+pub struct DevArgs {
+    /// Tendermint binary the devnet runs.
+    #[arg(long, env = "TENDERMINT_BIN", default_value = "tendermint")]
+    pub tendermint: PathBuf,
+
+    /// Number of prefunded accounts. Their keys are derived from their index, so they're the
+    /// same on every run and must never hold real funds.
+    #[arg(long, default_value_t = 3)]
+    pub accounts: u32,
+
+    /// Balance of each prefunded account.
+    #[arg(long, default_value = "1000000000000000000", value_parser = parse_felt_arg)]
+    pub balance: Felt,
+
+    /// Compiled program declared at genesis. Can be repeated.
+    #[arg(long)]
+    pub declare: Vec<PathBuf>,
+}
+
+/// Node of a devnet, with its home directory under the node's data directory.
+pub struct Devnet {
+    pub home: PathBuf,
+    /// Address of the ABCI server, as `host:port`.
+    pub abci_address: String,
+    pub chain_id: String,
+    pub gas_price: u64,
+    pub fee_recipient: Option<Felt>,
+    pub max_tx_gas: Option<u64>,
+}
+
+impl Devnet {
+    /// Resets the Tendermint home to a fresh chain with the dev accounts funded, then starts
+    /// Tendermint once the ABCI server is reachable. The node is stopped when the returned
+    /// process is dropped.
+    pub async fn start(&self, args: &DevArgs) -> Result<Child> {
+        if !self.home.join("config").join("genesis.json").exists() {
+            self.tendermint(args, &["init"]).await?;
+        }
+        // the application state is in memory, so the chain starts over on every run
+        self.tendermint(args, &["unsafe-reset-all"]).await?;
+
+        let accounts = dev_accounts(args.accounts)?;
+        self.write_genesis(args, &accounts)?;
+
+        for (index, (private_key, address)) in accounts.iter().enumerate() {
+            info!(
+                "Dev account #{index} (cli --from dev-{index} --dev): address {} private key {}",
+                felt_to_hex(address),
+                felt_to_hex(private_key)
+            );
+        }
+
+        while TcpStream::connect(&self.abci_address).await.is_err() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        info!("Starting Tendermint in {}", self.home.display());
+        Command::new(&args.tendermint)
+            .arg("node")
+            .arg("--home")
+            .arg(&self.home)
+            .arg("--proxy_app")
+            .arg(format!("tcp://{}", self.abci_address))
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err_with(|| format!("Error starting {}", args.tendermint.display()))
+    }
+
+    /// Sets the chain ID and app_state of the generated genesis file.
+    fn write_genesis(&self, args: &DevArgs, accounts: &[(Felt, Felt)]) -> Result<()> {
+        let classes = args
+            .declare
+            .iter()
+            .map(std::fs::read_to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        let app_state = Genesis {
+            accounts: accounts
+                .iter()
+                .map(|(_, address)| GenesisAccount {
+                    address: address.clone(),
+                    balance: args.balance.clone(),
+                })
+                .collect(),
+            classes,
+            parameters: GenesisParameters {
+                gas_price: self.gas_price,
+                governance: None,
+                fee_recipient: self.fee_recipient.clone(),
+                data_availability: false,
+                l1_relayer: None,
+                max_tx_gas: self.max_tx_gas,
+                unsigned_executions: true,
+            },
+        };
+
+        let path = self.home.join("config").join("genesis.json");
+        let mut genesis: serde_json::Value = serde_json::from_slice(&std::fs::read(&path)?)?;
+        genesis["chain_id"] = self.chain_id.as_str().into();
+        genesis["app_state"] = serde_json::to_value(&app_state)?;
+        std::fs::write(&path, serde_json::to_string_pretty(&genesis)?)?;
+
+        Ok(())
+    }
+
+    async fn tendermint(&self, args: &DevArgs, command: &[&str]) -> Result<()> {
+        let status = Command::new(&args.tendermint)
+            .args(command)
+            .arg("--home")
+            .arg(&self.home)
+            .stdout(Stdio::null())
+            .status()
+            .await
+            .wrap_err_with(|| format!("Error running {}", args.tendermint.display()))?;
+        if !status.success() {
+            bail!("`tendermint {}` failed with {status}", command.join(" "));
+        }
+        Ok(())
+    }
+}
+
+/// Private keys and addresses (public keys) of the dev accounts.
+fn dev_accounts(count: u32) -> Result<Vec<(Felt, Felt)>> {
+    (0..count)
+        .map(|index| {
+            let private_key = signature::dev_private_key(index);
+            let address = signature::public_key(&private_key)?;
+            Ok((private_key, address))
+        })
+        .collect()
+}
+
+fn parse_felt_arg(value: &str) -> Result<Felt, String> {
+    execution::parse_felt(value).map_err(|e| e.to_string())
+}
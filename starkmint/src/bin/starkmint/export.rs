@@ -0,0 +1,186 @@
+//! Export of committed blocks to external indexers, which receive each block with its decoded
+//! transactions and receipts instead of polling Tendermint and decoding bincode themselves.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use clap::ValueEnum;
+use color_eyre::{eyre::bail, Result};
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use starkmint::metrics::BlockSummary;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tokio::sync::Mutex;
+
+use crate::feeder;
+use crate::gateway::Gateway;
+
+/// Times a block is read from Tendermint before it's skipped, a second apart: the block may
+/// not be served yet when the application commits it.
+const READ_ATTEMPTS: u32 = 5;
+
+/// Sink committed blocks are exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportSink {
+    /// HTTP webhook: each block is `POST`ed as JSON to the URL.
+    Webhook,
+    /// NATS server: each block is published as JSON on a subject.
+    Nats,
+}
+
+/// Destination of exported blocks.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    async fn send(&self, block: &Value) -> Result<()>;
+}
+
+/// Endpoint blocks are `POST`ed to, which must answer with a success status.
+pub struct Webhook {
+    pub client: reqwest::Client,
+    pub url: String,
+}
+
+#[async_trait]
+impl Sink for Webhook {
+    async fn send(&self, block: &Value) -> Result<()> {
+        self.client
+            .post(self.url.as_str())
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(block)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// NATS server, spoken to with the core text protocol. Each publish is followed by a `PING`,
+/// whose `PONG` confirms the server processed it.
+pub struct Nats {
+    /// Address of the server, as `host:port`.
+    address: String,
+    subject: String,
+    connection: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+impl Nats {
+    /// Publisher on `subject` of the server at `url`, e.g. `nats://127.0.0.1:4222`. It
+    /// connects on the first publish.
+    pub fn new(url: &str, subject: String) -> Self {
+        Self {
+            address: url.trim_start_matches("nats://").to_string(),
+            subject,
+            connection: Mutex::new(None),
+        }
+    }
+
+    async fn connect(&self) -> Result<BufReader<TcpStream>> {
+        let mut connection = BufReader::new(TcpStream::connect(&self.address).await?);
+        // the server greets clients with its INFO, then waits for their CONNECT
+        let mut info = String::new();
+        connection.read_line(&mut info).await?;
+        if !info.starts_with("INFO") {
+            bail!("Unexpected greeting from the NATS server: {}", info.trim());
+        }
+        connection
+            .get_mut()
+            .write_all(b"CONNECT {\"verbose\":false,\"pedantic\":false}\r\n")
+            .await?;
+        Ok(connection)
+    }
+
+    async fn publish(&self, connection: &mut BufReader<TcpStream>, payload: &[u8]) -> Result<()> {
+        let header = format!("PUB {} {}\r\n", self.subject, payload.len());
+        let stream = connection.get_mut();
+        stream.write_all(header.as_bytes()).await?;
+        stream.write_all(payload).await?;
+        stream.write_all(b"\r\nPING\r\n").await?;
+
+        // the server's own PINGs are answered while waiting, keeping idle connections alive
+        loop {
+            let mut line = String::new();
+            if connection.read_line(&mut line).await? == 0 {
+                bail!("NATS server closed the connection");
+            }
+            match line.trim_end() {
+                "PONG" => return Ok(()),
+                "PING" => connection.get_mut().write_all(b"PONG\r\n").await?,
+                error if error.starts_with("-ERR") => bail!("NATS server error: {error}"),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for Nats {
+    /// Publishes the block, reconnecting once if the connection was lost, e.g. closed by the
+    /// server while no block was committed.
+    async fn send(&self, block: &Value) -> Result<()> {
+        let payload = serde_json::to_vec(block)?;
+        let mut connection = self.connection.lock().await;
+        if let Some(stream) = connection.as_mut() {
+            match self.publish(stream, &payload).await {
+                Ok(()) => return Ok(()),
+                Err(e) => tracing::debug!("Reconnecting to the NATS server: {e:#}"),
+            }
+        }
+
+        *connection = None;
+        let mut stream = self.connect().await?;
+        self.publish(&mut stream, &payload).await?;
+        *connection = Some(stream);
+        Ok(())
+    }
+}
+
+/// Exports every block as it's committed, until the node stops, in the shape of the feeder
+/// gateway's `get_block` with the app hash as state root. Blocks are read from Tendermint;
+/// those that fail to be read or sent are logged and skipped.
+///
+/// The blocks replayed when the node starts are exported again, so consumers should key
+/// blocks by `block_number`.
+pub async fn export(sink: Box<dyn Sink>, gateway: Gateway, mut blocks: Receiver<BlockSummary>) {
+    loop {
+        let summary = match blocks.recv().await {
+            Ok(summary) => summary,
+            Err(RecvError::Lagged(missed)) => {
+                tracing::warn!("Block export fell behind, {missed} blocks were skipped");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let mut block = match read_block(&gateway, summary.height).await {
+            Ok(block) => block,
+            Err(e) => {
+                tracing::warn!("Error reading block {} to export: {e:#}", summary.height);
+                continue;
+            }
+        };
+        block["state_root"] = json!(feeder::root_hex(&summary.app_hash));
+
+        match sink.send(&block).await {
+            Ok(()) => tracing::debug!("Exported block {}", summary.height),
+            Err(e) => tracing::warn!("Error exporting block {}: {e:#}", summary.height),
+        }
+    }
+}
+
+async fn read_block(gateway: &Gateway, height: u64) -> Result<Value> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match feeder::block(gateway, height).await {
+            Ok(block) => return Ok(block),
+            Err(e) if attempts >= READ_ATTEMPTS => {
+                return Err(e.wrap_err(format!("Gave up after {attempts} attempts")))
+            }
+            Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
+        }
+    }
+}
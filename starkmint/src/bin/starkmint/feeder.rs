@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+
+use cairo_felt::Felt;
+use color_eyre::eyre::eyre;
+use hyper::{Body, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use starkmint::execution::{self, felt_to_hex};
+use starkmint::state;
+use starkmint::transaction::{EntryPoint, Transaction, TransactionType};
+use tendermint::abci::response;
+use tendermint::block::Height;
+use tendermint_rpc::query::Query;
+use tendermint_rpc::{endpoint, Client, Order};
+
+use crate::gateway::{self, Gateway};
+
+/// Path prefix of the feeder gateway endpoints.
+pub const PREFIX: &str = "/feeder_gateway/";
+
+/// Error of a feeder gateway request, with its Starknet error code.
+#[derive(Debug)]
+struct FeederError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl FeederError {
+    fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn block_not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "BLOCK_NOT_FOUND", message)
+    }
+
+    fn malformed(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "MALFORMED_REQUEST", message)
+    }
+
+    fn internal(e: impl std::fmt::Display) -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "INTERNAL_ERROR",
+            format!("Internal error: {e}"),
+        )
+    }
+}
+
+/// Serves the `get_block`, `get_transaction` and `get_state_update` endpoints of the Starknet
+/// feeder gateway, in its JSON shapes, so explorers and indexers built for Starknet can read
+/// the chain. Blocks and transactions are read from Tendermint, state updates from the
+/// application, which keeps those of the most recent blocks.
+pub async fn handle(gateway: &Gateway, request: &Request<Body>) -> Response<Body> {
+    let params: Vec<(&str, &str)> = request
+        .uri()
+        .query()
+        .unwrap_or("")
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| param.split_once('=').unwrap_or((param, "")))
+        .collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(param, _)| *param == name)
+            .map(|(_, value)| *value)
+    };
+
+    let result = match request.uri().path().strip_prefix(PREFIX) {
+        Some("get_block") => get_block(gateway, param("blockNumber")).await,
+        Some("get_transaction") => match param("transactionHash") {
+            Some(hash) => get_transaction(gateway, hash).await,
+            None => Err(FeederError::malformed("transactionHash is required")),
+        },
+        Some("get_state_update") => get_state_update(gateway, param("blockNumber")).await,
+        _ => Err(FeederError::new(
+            StatusCode::NOT_FOUND,
+            "MALFORMED_REQUEST",
+            format!("Unknown endpoint {}", request.uri().path()),
+        )),
+    };
+
+    let (status, body) = match result {
+        Ok(body) => (StatusCode::OK, body),
+        Err(e) => (
+            e.status,
+            json!({
+                "code": format!("StarknetErrorCode.{}", e.code),
+                "message": e.message,
+            }),
+        ),
+    };
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap_or_else(gateway::internal_error)
+}
+
+/// Height given as `blockNumber`, or None for the latest block.
+fn block_number(value: Option<&str>) -> Result<Option<u64>, FeederError> {
+    match value {
+        None | Some("latest") | Some("pending") => Ok(None),
+        Some(number) => number
+            .parse()
+            .map(Some)
+            .map_err(|_| FeederError::malformed(format!("Invalid blockNumber {number}"))),
+    }
+}
+
+async fn fetch_block(
+    gateway: &Gateway,
+    number: Option<u64>,
+) -> Result<endpoint::block::Response, FeederError> {
+    match number {
+        Some(number) => {
+            let height = Height::try_from(number)
+                .map_err(|e| FeederError::malformed(format!("Invalid blockNumber: {e}")))?;
+            gateway
+                .tendermint
+                .block(height)
+                .await
+                .map_err(|e| FeederError::block_not_found(format!("Block {number} not found: {e}")))
+        }
+        None => gateway
+            .tendermint
+            .latest_block()
+            .await
+            .map_err(FeederError::internal),
+    }
+}
+
+async fn get_block(gateway: &Gateway, number: Option<&str>) -> Result<Value, FeederError> {
+    block_json(gateway, block_number(number)?).await
+}
+
+/// Block at `height` in the shape of `get_block`: with its decoded transactions and their
+/// receipts.
+pub async fn block(gateway: &Gateway, height: u64) -> color_eyre::Result<Value> {
+    block_json(gateway, Some(height))
+        .await
+        .map_err(|e| eyre!(e.message))
+}
+
+async fn block_json(gateway: &Gateway, number: Option<u64>) -> Result<Value, FeederError> {
+    let block = fetch_block(gateway, number).await?;
+    let header = &block.block.header;
+    let height = header.height;
+    let results = gateway
+        .tendermint
+        .block_results(height)
+        .await
+        .map_err(FeederError::internal)?
+        .txs_results
+        .unwrap_or_default();
+
+    let mut transactions = Vec::with_capacity(block.block.data.len());
+    let mut receipts = Vec::with_capacity(block.block.data.len());
+    for (index, bytes) in block.block.data.iter().enumerate() {
+        let Ok(transaction) = bincode::deserialize::<Transaction>(bytes) else {
+            continue;
+        };
+        if let Some(result) = results.get(index) {
+            receipts.push(receipt(gateway, index, &transaction, result));
+        }
+        transactions.push(transaction_json(&transaction));
+    }
+
+    Ok(json!({
+        "block_hash": hash_hex(&block.block_id.hash.to_string()),
+        "parent_block_hash": header
+            .last_block_id
+            .as_ref()
+            .map_or_else(|| "0x0".to_string(), |id| hash_hex(&id.hash.to_string())),
+        "block_number": height.value(),
+        "state_root": state_root(gateway, height.value()).await,
+        "status": "ACCEPTED_ON_L2",
+        "timestamp": header.time.unix_timestamp(),
+        "sequencer_address": hash_hex(&header.proposer_address.to_string()),
+        "gas_price": felt_to_hex(&Felt::new(gateway.app.fee(1))),
+        "transactions": transactions,
+        "transaction_receipts": receipts,
+    }))
+}
+
+async fn get_transaction(gateway: &Gateway, hash: &str) -> Result<Value, FeederError> {
+    let response = gateway
+        .tendermint
+        .tx_search(
+            Query::eq("app.hash", hash.trim_start_matches("0x").to_lowercase()),
+            false,
+            1,
+            1,
+            Order::Ascending,
+        )
+        .await
+        .map_err(FeederError::internal)?;
+    let Some(tx) = response.txs.into_iter().next() else {
+        return Ok(json!({ "status": "NOT_RECEIVED", "finality_status": "NOT_RECEIVED" }));
+    };
+    let transaction: Transaction = bincode::deserialize(&tx.tx).map_err(FeederError::internal)?;
+    let block = gateway
+        .tendermint
+        .block(tx.height)
+        .await
+        .map_err(FeederError::internal)?;
+
+    Ok(json!({
+        "status": if tx.tx_result.code.is_ok() { "ACCEPTED_ON_L2" } else { "REJECTED" },
+        "finality_status": "ACCEPTED_ON_L2",
+        "execution_status": execution_status(&tx.tx_result),
+        "block_hash": hash_hex(&block.block_id.hash.to_string()),
+        "block_number": tx.height.value(),
+        "transaction_index": tx.index,
+        "transaction": transaction_json(&transaction),
+    }))
+}
+
+async fn get_state_update(gateway: &Gateway, number: Option<&str>) -> Result<Value, FeederError> {
+    let update = gateway
+        .app
+        .state_update(block_number(number)?)
+        .ok_or_else(|| {
+            FeederError::block_not_found("State updates are only kept for the most recent blocks")
+        })?;
+    let block = fetch_block(gateway, Some(update.height)).await?;
+
+    let mut storage_diffs: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    let mut nonces = BTreeMap::new();
+    for ((address, key), value) in &update.writes {
+        if state::nonce_slot(address).1 == *key {
+            nonces.insert(felt_to_hex(address), felt_to_hex(value));
+        }
+        storage_diffs
+            .entry(felt_to_hex(address))
+            .or_default()
+            .push(json!({ "key": felt_to_hex(key), "value": felt_to_hex(value) }));
+    }
+
+    // contracts aren't deployed nor classes declared, programs live at their class hash
+    Ok(json!({
+        "block_hash": hash_hex(&block.block_id.hash.to_string()),
+        "new_root": root_hex(&update.new_root),
+        "old_root": root_hex(&update.old_root),
+        "state_diff": {
+            "storage_diffs": storage_diffs,
+            "nonces": nonces,
+            "deployed_contracts": [],
+            "old_declared_contracts": [],
+            "declared_classes": [],
+            "replaced_classes": [],
+        },
+    }))
+}
+
+/// App hash after the block at `height`: kept by the application for recent blocks, and in
+/// the header of the next block otherwise.
+pub async fn state_root(gateway: &Gateway, height: u64) -> Value {
+    if let Some(update) = gateway.app.state_update(Some(height)) {
+        return json!(root_hex(&update.new_root));
+    }
+    match Height::try_from(height + 1) {
+        Ok(next) => match gateway.tendermint.block(next).await {
+            Ok(block) => json!(hash_hex(&block.block.header.app_hash.to_string())),
+            Err(_) => Value::Null,
+        },
+        Err(_) => Value::Null,
+    }
+}
+
+/// Transaction in the feeder gateway's shape. Function executions are version 0 invokes of
+/// the contract at the program's class hash.
+pub fn transaction_json(transaction: &Transaction) -> Value {
+    let mut value = match &transaction.transaction_type {
+        TransactionType::FunctionExecution {
+            function, calldata, ..
+        }
+        | TransactionType::ClassExecution {
+            function, calldata, ..
+        } => {
+            let selector = match function {
+                EntryPoint::Name(name) => execution::entry_point_selector(name),
+                EntryPoint::Selector(selector) => selector.clone(),
+            };
+            let contract_address = transaction.transaction_type.class_hash();
+            json!({
+                "type": "INVOKE_FUNCTION",
+                "version": "0x0",
+                "contract_address": contract_address.as_ref().map(felt_to_hex),
+                "entry_point_selector": felt_to_hex(&selector),
+                "calldata": calldata.iter().map(felt_to_hex).collect::<Vec<_>>(),
+            })
+        }
+        TransactionType::L1Handler(message) => json!({
+            "type": "L1_HANDLER",
+            "version": "0x0",
+            "contract_address": felt_to_hex(&message.to_address),
+            "entry_point_selector": felt_to_hex(&message.selector),
+            "nonce": felt_to_hex(&message.nonce),
+            "calldata": std::iter::once(&message.from_address)
+                .chain(&message.payload)
+                .map(felt_to_hex)
+                .collect::<Vec<_>>(),
+        }),
+        TransactionType::ProofVerification { public_inputs, .. } => json!({
+            "type": "PROOF_VERIFICATION",
+            "public_inputs": public_inputs,
+        }),
+    };
+
+    value["transaction_hash"] = json!(format!("0x{}", transaction.hash()));
+    value["max_fee"] = json!("0x0");
+    value["signature"] = json!([]);
+    // L1 handlers are unsigned on Starknet, their nonce being the message's
+    if matches!(transaction.transaction_type, TransactionType::L1Handler(_)) {
+        return value;
+    }
+    if let Some(signature) = &transaction.signature {
+        value["sender_address"] = json!(felt_to_hex(&signature.sender));
+        value["nonce"] = json!(felt_to_hex(&Felt::new(signature.nonce)));
+        value["signature"] = json!([felt_to_hex(&signature.r), felt_to_hex(&signature.s)]);
+    }
+    value
+}
+
+fn receipt(
+    gateway: &Gateway,
+    index: usize,
+    transaction: &Transaction,
+    result: &response::DeliverTx,
+) -> Value {
+    let mut receipt = json!({
+        "transaction_index": index,
+        "transaction_hash": format!("0x{}", transaction.hash()),
+        "actual_fee": felt_to_hex(&Felt::new(gateway::actual_fee(result))),
+        "execution_status": execution_status(result),
+        "events": gateway::contract_events(result),
+        "l2_to_l1_messages": [],
+    });
+    if result.code.is_err() {
+        receipt["revert_error"] = json!(result.log);
+    }
+    receipt
+}
+
+fn execution_status(result: &response::DeliverTx) -> &'static str {
+    if result.code.is_ok() {
+        "SUCCEEDED"
+    } else {
+        "REVERTED"
+    }
+}
+
+/// App hash as a 0x-prefixed hex string, `0x0` before the first block.
+pub fn root_hex(app_hash: &[u8]) -> String {
+    if app_hash.is_empty() {
+        "0x0".to_string()
+    } else {
+        format!("0x{}", hex::encode(app_hash))
+    }
+}
+
+/// Hash printed by Tendermint, in uppercase hex, as a 0x-prefixed lowercase one.
+pub fn hash_hex(hash: &str) -> String {
+    format!("0x{}", hash.to_lowercase())
+}
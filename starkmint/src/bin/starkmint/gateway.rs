@@ -0,0 +1,399 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use cairo_felt::Felt;
+use color_eyre::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use starkmint::app::StarknetApp;
+use starkmint::execution::{felt_to_hex, parse_felt};
+use starkmint::transaction::Transaction;
+use tendermint::abci::response;
+use tendermint_rpc::query::Query;
+use tendermint_rpc::{Client, HttpClient, Order};
+
+use crate::feeder;
+
+/// Subset of the Starknet JSON-RPC API, so Starknet tooling can talk to the node. Calls and
+/// nonces are served from the application's committed state, while transactions are sent to
+/// and receipts read from Tendermint. The methods read by starknet-rs providers are served too,
+/// see [`Gateway::respond_provider`], and the feeder gateway endpoints along with them, see
+/// [`feeder::handle`].
+pub struct Gateway {
+    pub app: StarknetApp,
+    /// Client of the Tendermint node's RPC.
+    pub tendermint: HttpClient,
+}
+
+/// Error of a JSON-RPC request, with the codes of the JSON-RPC and Starknet specs.
+#[derive(Debug)]
+pub struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_params(e: impl std::fmt::Display) -> Self {
+        Self::new(-32602, format!("Invalid params: {e}"))
+    }
+
+    pub fn internal(e: impl std::fmt::Display) -> Self {
+        Self::new(-32603, format!("Internal error: {e}"))
+    }
+}
+
+/// Parameters of `starknet_call`.
+#[derive(Deserialize)]
+struct CallParams {
+    request: FunctionCall,
+    block_id: Value,
+}
+
+#[derive(Deserialize)]
+struct FunctionCall {
+    contract_address: String,
+    entry_point_selector: String,
+    calldata: Vec<String>,
+}
+
+/// Parameters of `starknet_getNonce`.
+#[derive(Deserialize)]
+struct NonceParams {
+    block_id: Value,
+    contract_address: String,
+}
+
+/// Parameters of `starknet_getTransactionReceipt`.
+#[derive(Deserialize)]
+struct ReceiptParams {
+    transaction_hash: String,
+}
+
+/// Parameters of `starknet_addInvokeTransaction`.
+#[derive(Deserialize)]
+struct InvokeParams {
+    invoke_transaction: InvokeTransaction,
+}
+
+/// Version 0 invoke transaction, calling a function of a deployed contract directly.
+#[derive(Deserialize)]
+struct InvokeTransaction {
+    contract_address: String,
+    entry_point_selector: String,
+    calldata: Vec<String>,
+    #[serde(default)]
+    signature: Vec<String>,
+}
+
+/// Serves the gateway at `address`, taking JSON-RPC requests POSTed to `/`.
+pub async fn serve(address: SocketAddr, gateway: Gateway) -> Result<()> {
+    let gateway = Arc::new(gateway);
+    let make_service = make_service_fn(move |_| {
+        let gateway = gateway.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let gateway = gateway.clone();
+                async move { Ok::<_, Infallible>(gateway.handle(request).await) }
+            }))
+        }
+    });
+
+    tracing::info!("Serving the Starknet JSON-RPC API on http://{address}");
+    Server::try_bind(&address)?.serve(make_service).await?;
+    Ok(())
+}
+
+impl Gateway {
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        if request.method() == Method::GET && request.uri().path().starts_with(feeder::PREFIX) {
+            return feeder::handle(self, &request).await;
+        }
+        if request.method() != Method::POST {
+            return Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .body(Body::empty())
+                .unwrap_or_else(internal_error);
+        }
+
+        let body = match hyper::body::to_bytes(request.into_body()).await {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::debug!("Error reading JSON-RPC request: {e}");
+                return Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(Body::empty())
+                    .unwrap_or_else(internal_error);
+            }
+        };
+
+        let response = match serde_json::from_slice::<Value>(&body) {
+            Ok(Value::Array(requests)) => {
+                let mut responses = Vec::with_capacity(requests.len());
+                for request in requests {
+                    responses.push(self.respond(request).await);
+                }
+                Value::Array(responses)
+            }
+            Ok(request) => self.respond(request).await,
+            Err(e) => error_response(
+                Value::Null,
+                RpcError::new(-32700, format!("Parse error: {e}")),
+            ),
+        };
+
+        Response::builder()
+            .header("Content-Type", "application/json")
+            .body(Body::from(response.to_string()))
+            .unwrap_or_else(internal_error)
+    }
+
+    /// Response to a single JSON-RPC request.
+    async fn respond(&self, request: Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return error_response(id, RpcError::new(-32600, "Invalid request"));
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "starknet_call" => self.call(params),
+            "starknet_getNonce" => self.get_nonce(params),
+            "starknet_getTransactionReceipt" => self.get_transaction_receipt(params).await,
+            "starknet_addInvokeTransaction" => self.add_invoke_transaction(params).await,
+            method => match self.respond_provider(method, params).await {
+                Some(result) => result,
+                None => Err(RpcError::new(-32601, format!("Method {method} not found"))),
+            },
+        };
+
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, e),
+        }
+    }
+
+    fn call(&self, params: Value) -> Result<Value, RpcError> {
+        let params: CallParams = parse_params(params, &["request", "block_id"])?;
+        ensure_latest(&params.block_id)?;
+        let call = params.request;
+
+        let transaction_type = self
+            .app
+            .invoke(
+                &parse_rpc_felt(&call.contract_address)?,
+                &parse_rpc_felt(&call.entry_point_selector)?,
+                parse_calldata(&call.calldata)?,
+            )
+            .map_err(|_| RpcError::new(20, "Contract not found"))?;
+        let retdata = self
+            .app
+            .call(&transaction_type)
+            .map_err(|e| RpcError::new(40, format!("Contract error: {e}")))?;
+
+        Ok(json!(retdata.iter().map(felt_to_hex).collect::<Vec<_>>()))
+    }
+
+    fn get_nonce(&self, params: Value) -> Result<Value, RpcError> {
+        let params: NonceParams = parse_params(params, &["block_id", "contract_address"])?;
+        ensure_latest(&params.block_id)?;
+
+        let nonce = self
+            .app
+            .committed_nonce(&parse_rpc_felt(&params.contract_address)?);
+        Ok(json!(felt_to_hex(&Felt::new(nonce))))
+    }
+
+    async fn get_transaction_receipt(&self, params: Value) -> Result<Value, RpcError> {
+        let params: ReceiptParams = parse_params(params, &["transaction_hash"])?;
+        let hash = params
+            .transaction_hash
+            .trim_start_matches("0x")
+            .to_lowercase();
+
+        let response = self
+            .tendermint
+            .tx_search(Query::eq("app.hash", hash), false, 1, 1, Order::Ascending)
+            .await
+            .map_err(RpcError::internal)?;
+        let tx = response
+            .txs
+            .into_iter()
+            .next()
+            .ok_or_else(|| RpcError::new(29, "Transaction hash not found"))?;
+        let block = self
+            .tendermint
+            .block(tx.height)
+            .await
+            .map_err(RpcError::internal)?;
+
+        Ok(self.receipt(
+            &params.transaction_hash,
+            tx.height.value(),
+            &block.block_id.hash.to_string(),
+            tx.tx_result,
+        ))
+    }
+
+    /// Starknet receipt of a transaction delivered in a block.
+    fn receipt(
+        &self,
+        transaction_hash: &str,
+        block_number: u64,
+        block_hash: &str,
+        result: response::DeliverTx,
+    ) -> Value {
+        let status = if result.code.is_ok() {
+            "ACCEPTED_ON_L2"
+        } else {
+            "REJECTED"
+        };
+
+        json!({
+            "transaction_hash": transaction_hash,
+            "actual_fee": felt_to_hex(&Felt::new(actual_fee(&result))),
+            "status": status,
+            "block_hash": format!("0x{}", block_hash.to_lowercase()),
+            "block_number": block_number,
+            "type": "INVOKE",
+            "messages_sent": [],
+            "events": contract_events(&result),
+        })
+    }
+
+    async fn add_invoke_transaction(&self, params: Value) -> Result<Value, RpcError> {
+        let params: InvokeParams = parse_params(params, &["invoke_transaction"])?;
+        let invoke = params.invoke_transaction;
+        // transaction hashes are computed by the node, so they can't be signed beforehand
+        if !invoke.signature.is_empty() {
+            return Err(RpcError::invalid_params(
+                "signed transactions must be sent with the starkmint CLI",
+            ));
+        }
+
+        let transaction_type = self
+            .app
+            .invoke(
+                &parse_rpc_felt(&invoke.contract_address)?,
+                &parse_rpc_felt(&invoke.entry_point_selector)?,
+                parse_calldata(&invoke.calldata)?,
+            )
+            .map_err(|_| RpcError::new(20, "Contract not found"))?;
+        // the contract's program is registered, so it's referenced rather than sent
+        let transaction = Transaction::with_type(transaction_type)
+            .map_err(|e| RpcError::new(40, format!("Contract error: {e}")))?
+            .referencing_class();
+
+        let response = self
+            .tendermint
+            .broadcast_tx_sync(bincode::serialize(&transaction).map_err(RpcError::internal)?)
+            .await
+            .map_err(RpcError::internal)?;
+        if response.code.is_err() {
+            return Err(RpcError::new(
+                40,
+                format!("Transaction rejected: {}", response.log),
+            ));
+        }
+
+        Ok(json!({ "transaction_hash": format!("0x{}", transaction.hash()) }))
+    }
+}
+
+/// Contract events of a delivered transaction, in the Starknet shape.
+pub fn contract_events(result: &response::DeliverTx) -> Vec<Value> {
+    result
+        .events
+        .iter()
+        .filter(|event| event.kind == "contract_event")
+        .map(|event| {
+            let values = |key: &str| {
+                event
+                    .attributes
+                    .iter()
+                    .filter(|attribute| attribute.key == key)
+                    .flat_map(|attribute| attribute.value.split(','))
+                    .filter(|value| !value.is_empty())
+                    .collect::<Vec<_>>()
+            };
+            json!({
+                "from_address": values("from_address").first(),
+                "keys": values("key"),
+                "data": values("data"),
+            })
+        })
+        .collect()
+}
+
+/// Fee charged for a delivered transaction, as recorded by its `execution` event. Zero if it
+/// wasn't applied.
+pub fn actual_fee(result: &response::DeliverTx) -> u64 {
+    result
+        .events
+        .iter()
+        .filter(|event| event.kind == "execution")
+        .flat_map(|event| &event.attributes)
+        .find(|attribute| attribute.key == "fee")
+        .and_then(|attribute| attribute.value.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Deserializes the params of a request, given by position or by name. `names` are the
+/// names of the positional params, in order.
+pub fn parse_params<T: for<'de> Deserialize<'de>>(
+    params: Value,
+    names: &[&str],
+) -> Result<T, RpcError> {
+    let params = match params {
+        Value::Array(values) => Value::Object(
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .zip(values)
+                .collect(),
+        ),
+        params => params,
+    };
+    serde_json::from_value(params).map_err(RpcError::invalid_params)
+}
+
+/// Fails unless the block id refers to the latest state, the only one the node keeps.
+pub fn ensure_latest(block_id: &Value) -> Result<(), RpcError> {
+    match block_id.as_str() {
+        Some("latest") | Some("pending") => Ok(()),
+        _ => Err(RpcError::new(24, "Block not found")),
+    }
+}
+
+pub fn parse_rpc_felt(value: &str) -> Result<Felt, RpcError> {
+    parse_felt(value).map_err(RpcError::invalid_params)
+}
+
+fn parse_calldata(calldata: &[String]) -> Result<Vec<Felt>, RpcError> {
+    calldata.iter().map(|value| parse_rpc_felt(value)).collect()
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+/// Empty 500 response, for responses that couldn't be built, e.g. because of an invalid header.
+pub fn internal_error(e: hyper::http::Error) -> Response<Body> {
+    tracing::error!("Error building an HTTP response: {e}");
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    response
+}
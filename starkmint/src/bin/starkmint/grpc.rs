@@ -0,0 +1,200 @@
+use std::net::SocketAddr;
+
+use cairo_felt::Felt;
+use color_eyre::Result;
+use sha2::{Digest, Sha256};
+use starkmint::app::StarknetApp;
+use starkmint::execution::{felt_to_hex, parse_felt};
+use starkmint::transaction::Transaction;
+use tendermint::abci;
+use tendermint::block::Height;
+use tendermint_rpc::query::Query as TxQuery;
+use tendermint_rpc::{Client, HttpClient, Order};
+use tonic::{Request, Response, Status};
+
+use proto::query_server::{Query, QueryServer};
+
+pub mod proto {
+    tonic::include_proto!("starkmint.query.v1");
+}
+
+/// gRPC service of the application's queries, defined in `proto/starkmint/query/v1`. State
+/// is read from the application, while transactions and blocks are read from Tendermint.
+pub struct QueryService {
+    pub app: StarknetApp,
+    /// Client of the Tendermint node's RPC.
+    pub tendermint: HttpClient,
+}
+
+/// Serves the query service at `address`.
+pub async fn serve(address: SocketAddr, service: QueryService) -> Result<()> {
+    tracing::info!("Serving gRPC queries on {address}");
+    tonic::transport::Server::builder()
+        .add_service(QueryServer::new(service))
+        .serve(address)
+        .await?;
+    Ok(())
+}
+
+#[tonic::async_trait]
+impl Query for QueryService {
+    async fn get_transaction(
+        &self,
+        request: Request<proto::GetTransactionRequest>,
+    ) -> Result<Response<proto::Transaction>, Status> {
+        let hash = request.into_inner().hash;
+        let response = self
+            .tendermint
+            .tx_search(
+                TxQuery::eq("app.hash", hash.trim_start_matches("0x").to_lowercase()),
+                false,
+                1,
+                1,
+                Order::Ascending,
+            )
+            .await
+            .map_err(unavailable)?;
+        let tx = response
+            .txs
+            .into_iter()
+            .next()
+            .ok_or_else(|| Status::not_found(format!("Transaction {hash} not found")))?;
+
+        let result = tx.tx_result;
+        let retdata = result
+            .events
+            .iter()
+            .filter(|event| event.kind == "execution")
+            .flat_map(|event| &event.attributes)
+            .filter(|attribute| attribute.key == "retdata")
+            .flat_map(|attribute| attribute.value.split(','))
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        Ok(Response::new(proto::Transaction {
+            hash,
+            height: tx.height.value(),
+            code: result.code.value(),
+            log: result.log,
+            gas_used: result.gas_used.max(0) as u64,
+            retdata,
+            events: result.events.into_iter().map(proto::Event::from).collect(),
+        }))
+    }
+
+    async fn get_class(
+        &self,
+        request: Request<proto::GetClassRequest>,
+    ) -> Result<Response<proto::Class>, Status> {
+        let class_hash = parse_felt_param("class_hash", &request.into_inner().class_hash)?;
+        let program = self.app.contract_class(&class_hash).ok_or_else(|| {
+            Status::not_found(format!("Class {} not found", felt_to_hex(&class_hash)))
+        })?;
+
+        Ok(Response::new(proto::Class {
+            class_hash: felt_to_hex(&class_hash),
+            program,
+        }))
+    }
+
+    async fn get_storage(
+        &self,
+        request: Request<proto::GetStorageRequest>,
+    ) -> Result<Response<proto::StorageValue>, Status> {
+        let request = request.into_inner();
+        let contract_address = parse_felt_param("contract_address", &request.contract_address)?;
+        let key = parse_felt_param("key", &request.key)?;
+
+        let value = self.app.committed_storage(&contract_address, &key);
+        Ok(Response::new(proto::StorageValue {
+            value: felt_to_hex(&value),
+        }))
+    }
+
+    async fn get_account(
+        &self,
+        request: Request<proto::GetAccountRequest>,
+    ) -> Result<Response<proto::Account>, Status> {
+        let address = parse_felt_param("address", &request.into_inner().address)?;
+        let account = self.app.committed_account(&address);
+
+        Ok(Response::new(proto::Account {
+            address: felt_to_hex(&address),
+            nonce: account.nonce,
+            balance: felt_to_hex(&account.balance),
+        }))
+    }
+
+    async fn get_block(
+        &self,
+        request: Request<proto::GetBlockRequest>,
+    ) -> Result<Response<proto::Block>, Status> {
+        let block = match request.into_inner().height {
+            Some(height) => {
+                let height = Height::try_from(height)
+                    .map_err(|e| Status::invalid_argument(format!("Invalid height: {e}")))?;
+                self.tendermint.block(height).await
+            }
+            None => self.tendermint.latest_block().await,
+        }
+        .map_err(unavailable)?;
+        let height = block.block.header.height;
+        let results = self
+            .tendermint
+            .block_results(height)
+            .await
+            .map_err(unavailable)?
+            .txs_results
+            .unwrap_or_default();
+
+        let transactions: Vec<proto::BlockTransaction> = block
+            .block
+            .data
+            .iter()
+            .enumerate()
+            .map(|(index, bytes)| {
+                let result = results.get(index);
+                proto::BlockTransaction {
+                    hash: bincode::deserialize::<Transaction>(bytes)
+                        .map(|transaction| transaction.hash())
+                        .unwrap_or_else(|_| hex::encode_upper(Sha256::digest(bytes))),
+                    code: result.map_or(0, |result| result.code.value()),
+                    gas_used: result.map_or(0, |result| result.gas_used.max(0) as u64),
+                }
+            })
+            .collect();
+
+        Ok(Response::new(proto::Block {
+            height: height.value(),
+            hash: block.block_id.hash.to_string(),
+            time: block.block.header.time.to_rfc3339(),
+            gas_used: transactions.iter().map(|tx| tx.gas_used).sum(),
+            transactions,
+        }))
+    }
+}
+
+impl From<abci::Event> for proto::Event {
+    fn from(event: abci::Event) -> Self {
+        Self {
+            kind: event.kind,
+            attributes: event
+                .attributes
+                .into_iter()
+                .map(|attribute| proto::Attribute {
+                    key: attribute.key,
+                    value: attribute.value,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn parse_felt_param(name: &str, value: &str) -> Result<Felt, Status> {
+    parse_felt(value).map_err(|e| Status::invalid_argument(format!("Invalid {name}: {e}")))
+}
+
+fn unavailable(e: tendermint_rpc::Error) -> Status {
+    Status::unavailable(format!("Error querying Tendermint: {e}"))
+}
@@ -0,0 +1,96 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use color_eyre::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use serde_json::json;
+use starkmint::app::StarknetApp;
+use tokio::net::TcpStream;
+
+/// What the node's HTTP endpoints report on.
+pub struct Endpoints {
+    pub app: StarknetApp,
+    /// Address of the ABCI server, as `host:port`.
+    pub abci_address: String,
+    /// Longest time since the last commit for the node to be ready.
+    pub max_commit_age: Duration,
+}
+
+/// Serves the node's metrics at `/metrics`, in the Prometheus text format, and its health
+/// checks at `/healthz` (the application is up) and `/readyz` (it's also reachable by
+/// Tendermint and committing blocks).
+pub async fn serve(address: SocketAddr, endpoints: Endpoints) -> Result<()> {
+    let endpoints = Arc::new(endpoints);
+    let make_service = make_service_fn(move |_| {
+        let endpoints = endpoints.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let endpoints = endpoints.clone();
+                async move { Ok::<_, Infallible>(endpoints.handle(request).await) }
+            }))
+        }
+    });
+
+    tracing::info!("Serving metrics and health checks on http://{address}");
+    Server::try_bind(&address)?.serve(make_service).await?;
+    Ok(())
+}
+
+impl Endpoints {
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        match request.uri().path() {
+            "/metrics" => Response::builder()
+                .header("Content-Type", "text/plain; version=0.0.4")
+                .body(Body::from(self.app.metrics().encode()))
+                .unwrap(),
+            "/healthz" => self.healthz(),
+            "/readyz" => self.readyz().await,
+            _ => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .unwrap(),
+        }
+    }
+
+    fn healthz(&self) -> Response<Body> {
+        let health = self.app.health();
+        check_response(
+            health.storage_available,
+            json!({ "storage": health.storage_available }),
+        )
+    }
+
+    async fn readyz(&self) -> Response<Body> {
+        let health = self.app.health();
+        let abci_listening = TcpStream::connect(&self.abci_address).await.is_ok();
+        let commit_age = health.last_commit.map(|(_, time)| time.elapsed());
+        let committing = commit_age.map_or(false, |age| age <= self.max_commit_age);
+
+        check_response(
+            health.storage_available && abci_listening && committing,
+            json!({
+                "storage": health.storage_available,
+                "abci": abci_listening,
+                "height": health.last_commit.map(|(height, _)| height.value()),
+                "seconds_since_commit": commit_age.map(|age| age.as_secs()),
+            }),
+        )
+    }
+}
+
+/// Response of a health check: 200 if it passed, 503 otherwise, with the checked conditions.
+fn check_response(passed: bool, checks: serde_json::Value) -> Response<Body> {
+    let status = if passed {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(checks.to_string()))
+        .unwrap()
+}
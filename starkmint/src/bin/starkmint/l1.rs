@@ -0,0 +1,234 @@
+//! Ingestion of the messages sent to the chain from L1: a watcher reads them from the events of
+//! an Ethereum messaging contract and injects them as L1 handler transactions, signed by the
+//! chain's L1 relayer.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use cairo_felt::Felt;
+use color_eyre::{
+    eyre::{bail, ensure, eyre},
+    Result,
+};
+use num_traits::{ToPrimitive, Zero};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use starkmint::app::StarknetApp;
+use starkmint::execution::felt_to_hex;
+use starkmint::messaging::L1Message;
+use starkmint::signature;
+use starkmint::transaction::Transaction;
+use tendermint_rpc::{Client, HttpClient};
+
+use crate::settlement;
+
+/// Event emitted by Starknet-compatible messaging contracts for every message sent to L2.
+const LOG_MESSAGE_TO_L2: &str = "LogMessageToL2(address,uint256,uint256,uint256[],uint256,uint256)";
+
+/// Where messages sent from L1 are read from.
+#[async_trait]
+pub trait MessageSource: Send + Sync {
+    /// Latest L1 block whose messages can be ingested.
+    async fn latest_block(&self) -> Result<u64>;
+
+    /// Messages sent in the L1 blocks from `from` to `to`, both included, in the order they
+    /// were sent.
+    async fn messages(&self, from: u64, to: u64) -> Result<Vec<L1Message>>;
+}
+
+/// Messaging contract on Ethereum, whose `LogMessageToL2` events are read over JSON-RPC.
+pub struct Ethereum {
+    pub client: reqwest::Client,
+    /// Ethereum JSON-RPC endpoint.
+    pub rpc: String,
+    /// Address of the messaging contract.
+    pub contract: String,
+    /// Blocks a message must be buried under before it's ingested, so it isn't reorged out.
+    pub confirmations: u64,
+}
+
+#[async_trait]
+impl MessageSource for Ethereum {
+    async fn latest_block(&self) -> Result<u64> {
+        let result =
+            settlement::request(&self.client, &self.rpc, "eth_blockNumber", json!([])).await?;
+        Ok(quantity(&result)?.saturating_sub(self.confirmations))
+    }
+
+    async fn messages(&self, from: u64, to: u64) -> Result<Vec<L1Message>> {
+        let filter = json!({
+            "address": self.contract,
+            "fromBlock": format!("{from:#x}"),
+            "toBlock": format!("{to:#x}"),
+            "topics": [format!("0x{}", hex::encode(Keccak256::digest(LOG_MESSAGE_TO_L2)))],
+        });
+        let logs =
+            settlement::request(&self.client, &self.rpc, "eth_getLogs", json!([filter])).await?;
+        let logs = logs
+            .as_array()
+            .ok_or_else(|| eyre!("Unexpected eth_getLogs result {logs}"))?;
+
+        // logs are returned in the order they were emitted
+        logs.iter().map(decode_log).collect()
+    }
+}
+
+/// Message of a `LogMessageToL2` log: the sender, recipient and selector are its indexed
+/// topics, and its data the payload, nonce and fee, ABI-encoded.
+fn decode_log(log: &Value) -> Result<L1Message> {
+    let topics: Vec<Felt> = log["topics"]
+        .as_array()
+        .ok_or_else(|| eyre!("Log without topics {log}"))?
+        .iter()
+        .map(|topic| {
+            let topic = topic
+                .as_str()
+                .ok_or_else(|| eyre!("Invalid topic {topic}"))?;
+            Ok(Felt::from_bytes_be(&bytes(topic)?))
+        })
+        .collect::<Result<_>>()?;
+    ensure!(topics.len() == 4, "Unexpected topics of log {log}");
+
+    let data = bytes(log["data"].as_str().unwrap_or_default())?;
+    let words: Vec<Felt> = data.chunks(32).map(Felt::from_bytes_be).collect();
+    ensure!(words.len() >= 4, "Unexpected data of log {log}");
+
+    // the payload is dynamic: its offset in bytes is given first, and it starts with its length
+    let offset = abi_usize(&words[0])?;
+    ensure!(offset % 32 == 0, "Unaligned payload in log {log}");
+    let start = offset / 32 + 1;
+    let len = abi_usize(
+        words
+            .get(start - 1)
+            .ok_or_else(|| eyre!("Payload out of bounds in log {log}"))?,
+    )?;
+    let payload = words
+        .get(start..start.saturating_add(len))
+        .ok_or_else(|| eyre!("Payload out of bounds in log {log}"))?;
+
+    Ok(L1Message {
+        from_address: topics[1].clone(),
+        to_address: topics[2].clone(),
+        selector: topics[3].clone(),
+        payload: payload.to_vec(),
+        nonce: words[1].clone(),
+    })
+}
+
+/// Offset or length of an ABI-encoded value.
+fn abi_usize(word: &Felt) -> Result<usize> {
+    word.to_usize()
+        .ok_or_else(|| eyre!("ABI offset or length {word} is too large"))
+}
+
+fn bytes(value: &str) -> Result<Vec<u8>> {
+    Ok(hex::decode(value.trim_start_matches("0x"))?)
+}
+
+/// Number given as a 0x-prefixed hex quantity.
+fn quantity(value: &Value) -> Result<u64> {
+    value
+        .as_str()
+        .and_then(|value| u64::from_str_radix(value.trim_start_matches("0x"), 16).ok())
+        .ok_or_else(|| eyre!("Unexpected quantity {value}"))
+}
+
+/// Watches L1 for messages and injects each one as an L1 handler transaction, until the node
+/// stops.
+pub struct Watcher {
+    pub source: Box<dyn MessageSource>,
+    pub app: StarknetApp,
+    /// Client of the Tendermint node's RPC, where transactions are sent.
+    pub tendermint: HttpClient,
+    /// Private key of the chain's L1 relayer, signing the transactions.
+    pub relayer_key: Felt,
+    /// Time between two reads of L1.
+    pub poll_interval: Duration,
+    /// First L1 block whose messages are ingested, the next one to be read if not given.
+    pub start_block: Option<u64>,
+}
+
+impl Watcher {
+    /// Injects the messages of every L1 block as it's read. Messages already handled, e.g.
+    /// when blocks are read again after a restart, are skipped; messages that fail to be
+    /// injected are logged and skipped too.
+    pub async fn run(self) -> Result<()> {
+        let relayer = signature::public_key(&self.relayer_key)?;
+        let mut next_block = match self.start_block {
+            Some(block) => block,
+            None => self.source.latest_block().await? + 1,
+        };
+        tracing::info!(
+            "Relaying L1 messages from block {next_block} as {}",
+            felt_to_hex(&relayer)
+        );
+
+        // nonces of transactions still pending are ahead of the committed one
+        let mut next_nonce = 0;
+        loop {
+            tokio::time::sleep(self.poll_interval).await;
+            let latest = match self.source.latest_block().await {
+                Ok(latest) if latest >= next_block => latest,
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::warn!("Error reading the latest L1 block: {e:#}");
+                    continue;
+                }
+            };
+            let messages = match self.source.messages(next_block, latest).await {
+                Ok(messages) => messages,
+                Err(e) => {
+                    tracing::warn!(
+                        "Error reading L1 messages of blocks {next_block} to {latest}: {e:#}"
+                    );
+                    continue;
+                }
+            };
+
+            for message in messages {
+                let (address, key) = message.slot();
+                if !self.app.committed_storage(&address, &key).is_zero() {
+                    continue;
+                }
+                let hash = hex::encode(message.hash());
+                next_nonce = next_nonce.max(self.app.committed_nonce(&relayer));
+                match self.inject(message, next_nonce).await {
+                    Ok(transaction) => {
+                        next_nonce += 1;
+                        tracing::info!("Injected L1 message 0x{hash} in transaction {transaction}");
+                    }
+                    Err(e) => {
+                        // resynchronized with the committed nonce on the next message
+                        next_nonce = 0;
+                        tracing::warn!("Error injecting L1 message 0x{hash}: {e:#}");
+                    }
+                }
+            }
+            next_block = latest + 1;
+        }
+    }
+
+    /// Sends the L1 handler transaction of a message, returning its hash.
+    async fn inject(&self, message: L1Message, nonce: u64) -> Result<String> {
+        let program = self
+            .app
+            .contract_class(&message.to_address)
+            .ok_or_else(|| eyre!("Contract {} not found", felt_to_hex(&message.to_address)))?;
+        let chain_id = self
+            .app
+            .chain_id()
+            .ok_or_else(|| eyre!("The chain isn't initialized yet"))?;
+        let mut transaction = Transaction::l1_handler(message, &program)?;
+        // L1 handlers are paid for on L1, so they pay no fee
+        transaction.sign(&self.relayer_key, &chain_id, nonce, 0, 0)?;
+
+        let response = self
+            .tendermint
+            .broadcast_tx_sync(bincode::serialize(&transaction)?)
+            .await?;
+        if response.code.is_err() {
+            bail!("Transaction rejected: {}", response.log);
+        }
+        Ok(transaction.transaction_hash)
+    }
+}
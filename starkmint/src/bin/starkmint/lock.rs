@@ -0,0 +1,68 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use fs2::FileExt;
+
+/// Exclusive lock on the data directory, so two nodes never share it. The lock is released
+/// by the OS when the process exits, even if it crashes, so a leftover lock file is never
+/// mistaken for a running node.
+pub struct DataDirLock {
+    _file: File,
+}
+
+impl DataDirLock {
+    pub fn acquire(data_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = data_dir.join("LOCK");
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+            .wrap_err_with(|| format!("Error opening {}", path.display()))?;
+
+        if file.try_lock_exclusive().is_err() {
+            let mut owner = String::new();
+            let _ = file.read_to_string(&mut owner);
+            bail!(
+                "Data directory {} is in use by another node (pid {})",
+                data_dir.display(),
+                owner.trim()
+            );
+        }
+
+        // the lock file records the owner, for the error above
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// File holding the node's process ID while it runs, for init systems and scripts. It's
+/// removed when the node shuts down.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create(path: &Path) -> Result<Self> {
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .wrap_err_with(|| format!("Error writing PID file {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
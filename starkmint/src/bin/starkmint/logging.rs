@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, Registry};
+
+/// Handle changing the log level of the running node.
+pub type LevelHandle = reload::Handle<LevelFilter, Registry>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per line, for log aggregators.
+    Json,
+}
+
+/// How often the log file is rotated. Rotated files get the date (and hour) as suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    Daily,
+}
+
+/// Installs the global logger, writing to `file` if given or to stdout otherwise. Logs are
+/// written to files from a background thread, which flushes them until the returned guard
+/// is dropped. Spans are also exported to the OTLP collector at `otlp_endpoint`, if any.
+pub fn init(
+    level: LevelFilter,
+    format: LogFormat,
+    file: Option<&Path>,
+    rotation: LogRotation,
+    otlp_endpoint: Option<&str>,
+) -> Result<(Option<WorkerGuard>, LevelHandle)> {
+    let (writer, guard) = match file {
+        Some(path) => {
+            let directory = path.parent().unwrap_or_else(|| Path::new("."));
+            let file_name = path
+                .file_name()
+                .ok_or_else(|| eyre!("Invalid log file {}", path.display()))?;
+            let rotation = match rotation {
+                LogRotation::Never => Rotation::NEVER,
+                LogRotation::Hourly => Rotation::HOURLY,
+                LogRotation::Daily => Rotation::DAILY,
+            };
+
+            let appender = RollingFileAppender::new(rotation, directory, file_name);
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        None => (BoxMakeWriter::new(std::io::stdout), None),
+    };
+
+    let (filter, handle) = reload::Layer::new(level);
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(file.is_none())
+        .with_writer(writer);
+    #[cfg(feature = "otel")]
+    let otel = otlp_endpoint
+        .map(crate::telemetry::tracing_layer)
+        .transpose()?;
+    // settings with an endpoint are rejected without the feature
+    #[cfg(not(feature = "otel"))]
+    let otel = {
+        debug_assert!(otlp_endpoint.is_none());
+        None::<tracing_subscriber::layer::Identity>
+    };
+    let registry = tracing_subscriber::registry().with(filter).with(otel);
+    match format {
+        LogFormat::Text => registry.with(layer).init(),
+        LogFormat::Json => registry.with(layer.json()).init(),
+    }
+
+    Ok((guard, handle))
+}
+
+/// Parses a log level such as `info` or `debug`.
+pub fn parse_level(level: &str) -> Result<LevelFilter> {
+    level
+        .parse()
+        .map_err(|_| eyre!("Invalid log level {level}"))
+}
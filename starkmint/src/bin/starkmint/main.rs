@@ -0,0 +1,613 @@
+use starkmint::app::{self, StarknetApp};
+use starkmint::archive::BLOCK_ARCHIVE_DIR;
+use starkmint::audit::AUDIT_LOG_FILE;
+use starkmint::cache::ProgramCache;
+use starkmint::execution::{self, Builtin, ExecutionConfig, HintPolicy};
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use tendermint_rpc::HttpClient;
+use tokio::signal::unix::{signal, SignalKind};
+use tower::ServiceBuilder;
+use tower_abci::{split, Server};
+use tracing_subscriber::filter::LevelFilter;
+
+mod config;
+mod da;
+mod dev;
+mod export;
+mod feeder;
+mod gateway;
+mod grpc;
+mod http;
+mod l1;
+mod lock;
+mod logging;
+mod provider;
+mod queue;
+mod ratelimit;
+mod replay;
+mod settlement;
+#[cfg(feature = "otel")]
+mod telemetry;
+mod unix;
+mod ws;
+
+use config::Settings;
+use da::DaLayer;
+use export::ExportSink;
+use lock::{DataDirLock, PidFile};
+use logging::{LevelHandle, LogFormat, LogRotation};
+use queue::QueueMetricsLayer;
+use ratelimit::RateLimitLayer;
+use unix::UnixServer;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    settings: Settings,
+
+    /// TOML file with node settings. Flags take precedence over it. On SIGHUP, the file is
+    /// read again and the log level, rate limits and execution workers are updated.
+    #[arg(long, env = "STARKMINT_NODE_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Increase output logging verbosity to DEBUG level.
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Suppress all output logging (overrides --verbose).
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a local devnet: the node and a Tendermint node managed by it, on a fresh chain
+    /// with prefunded accounts. Tendermint's files are kept in `<data-dir>/devnet`.
+    Dev(dev::DevArgs),
+    /// Re-execute the blocks archived by a node started with `--archive-blocks`, from genesis,
+    /// and check their app hashes match the recorded ones, e.g. to verify an upgraded node
+    /// executes the chain as before. The node's data dir is left untouched.
+    Replay(replay::ReplayArgs),
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut cli: Cli = Cli::parse();
+    let command = cli.command.take();
+    let settings = cli.settings()?;
+    let mempool_rate_limit = Arc::new(AtomicU64::new(mempool_rate(&settings)));
+    let info_rate_limit = Arc::new(AtomicU64::new(info_rate(&settings)));
+
+    // kept alive so buffered logs are written to the file until the node exits
+    let (_log_guard, log_handle) = logging::init(
+        cli.log_level(&settings)?,
+        settings.log_format.unwrap_or(LogFormat::Text),
+        settings.log_file.as_deref(),
+        settings.log_rotation.unwrap_or(LogRotation::Never),
+        settings.otlp_endpoint.as_deref(),
+    )?;
+
+    let hint_policy = match (settings.allow_all_hints, settings.hint_allowlist) {
+        (Some(true), _) => {
+            tracing::warn!("Programs may contain any hint, including nondeterministic ones");
+            HintPolicy::Builtin
+        }
+        (_, Some(path)) => {
+            let allowed: HashSet<String> = serde_json::from_slice(&std::fs::read(path)?)?;
+            HintPolicy::Allowlist(allowed)
+        }
+        _ => HintPolicy::default(),
+    };
+
+    let execution = ExecutionConfig {
+        builtins: settings.builtins.unwrap_or_else(|| Builtin::ALL.to_vec()),
+        hint_policy,
+        gas_price: settings.gas_price.unwrap_or(config::DEFAULT_GAS_PRICE),
+        fee_recipient: settings
+            .fee_recipient
+            .as_deref()
+            .map(execution::parse_felt)
+            .transpose()?,
+        trace_execution: settings.trace_execution.unwrap_or(false),
+        max_steps: None,
+        max_tx_gas: settings.max_tx_gas,
+        proof_verifier: None,
+    };
+
+    // replays run with their own temporary data dir, so they can run next to the node
+    if let Some(Command::Replay(args)) = command {
+        let workers = settings.parallel_execution;
+        return tokio::task::spawn_blocking(move || replay::replay(&args, execution, workers))
+            .await?;
+    }
+
+    let gas_price = execution.gas_price;
+    let fee_recipient = execution.fee_recipient.clone();
+    let max_tx_gas = execution.max_tx_gas;
+    let programs = ProgramCache::new(settings.program_cache_dir)?.with_execution_memo(
+        settings
+            .execution_cache_size
+            .unwrap_or(app::DEFAULT_EXECUTION_CACHE_SIZE),
+    );
+
+    let data_dir = settings
+        .data_dir
+        .unwrap_or_else(|| app::DEFAULT_DATA_DIR.into());
+    let _lock = DataDirLock::acquire(&data_dir)?;
+    let _pid_file = settings
+        .pid_file
+        .as_deref()
+        .map(PidFile::create)
+        .transpose()?;
+
+    // devnets are created with a chain ID even when none is given
+    let chain_id = match &command {
+        Some(Command::Dev(_)) => Some(
+            settings
+                .chain_id
+                .clone()
+                .unwrap_or_else(|| dev::DEFAULT_CHAIN_ID.to_string()),
+        ),
+        None | Some(Command::Replay(_)) => settings.chain_id.clone(),
+    };
+
+    // Construct our ABCI application.
+    let mut service = StarknetApp::new()
+        .with_execution_config(execution)
+        .with_program_cache(programs)
+        .with_data_dir(&data_dir);
+    if let Some(workers) = settings.parallel_execution {
+        service = service.with_parallel_execution(workers);
+    }
+    if let Some(workers) = settings.check_workers {
+        service = service.with_check_workers(workers);
+    }
+    if let Some(capacity) = settings.query_cache_size {
+        service = service.with_query_cache(capacity);
+    }
+    if let Some(limit) = settings.max_pending_per_sender {
+        service = service.with_max_pending_per_sender(limit);
+    }
+    // devnets start from a fresh chain every time, so commits needn't survive a crash
+    let commit_sync = settings
+        .commit_sync
+        .unwrap_or(!matches!(command, Some(Command::Dev(_))));
+    service = service.with_commit_sync(commit_sync);
+    if settings.profile.unwrap_or(false) {
+        service = service.with_profiler();
+    }
+    // the feeder gateway serves the state updates of recent blocks
+    if settings.rpc_address.is_some() {
+        service = service.with_state_update_history(
+            settings
+                .state_update_history
+                .unwrap_or(config::DEFAULT_STATE_UPDATE_HISTORY),
+        );
+    }
+    if settings.audit_log.unwrap_or(false) {
+        service = service.with_audit_log(&data_dir.join(AUDIT_LOG_FILE));
+    }
+    if settings.archive_blocks.unwrap_or(false) {
+        service = service.with_block_archive(&data_dir.join(BLOCK_ARCHIVE_DIR));
+    }
+    if let Some(chain_id) = &chain_id {
+        service = service.with_chain_id(chain_id.clone());
+    }
+    if let Some(height) = settings.halt_height {
+        service = service.with_halt_height(height);
+    }
+    if let Some(heights) = settings.skip_halt_heights {
+        service = service.with_skipped_halt_heights(heights);
+    }
+    let mut halted = service.halted();
+    #[cfg(feature = "otel")]
+    let metrics_export = settings
+        .otlp_endpoint
+        .as_deref()
+        .map(|endpoint| telemetry::export_metrics(endpoint, service.metrics()))
+        .transpose()?;
+
+    let host = settings
+        .host
+        .unwrap_or_else(|| config::DEFAULT_HOST.to_string());
+    let port = settings.port.unwrap_or(config::DEFAULT_PORT);
+    let buffers = Buffers {
+        requests: settings
+            .request_bound
+            .unwrap_or(config::DEFAULT_REQUEST_BOUND),
+        mempool: settings
+            .mempool_buffer
+            .unwrap_or(config::DEFAULT_MEMPOOL_BUFFER),
+        mempool_rate_limit: mempool_rate_limit.clone(),
+        info: settings.info_buffer.unwrap_or(config::DEFAULT_INFO_BUFFER),
+        info_rate_limit: info_rate_limit.clone(),
+        alarm: settings.queue_alarm.unwrap_or(config::DEFAULT_QUEUE_ALARM),
+    };
+
+    let reloader = Reloader {
+        log_level: log_handle,
+        mempool_rate_limit,
+        info_rate_limit,
+        app: service.clone(),
+    };
+    tokio::spawn(reloader.run(cli));
+
+    if let Some(address) = settings.http_address {
+        let endpoints = http::Endpoints {
+            app: service.clone(),
+            abci_address: format!("{}:{}", host, port),
+            max_commit_age: Duration::from_secs(
+                settings
+                    .ready_max_commit_age
+                    .unwrap_or(config::DEFAULT_READY_MAX_COMMIT_AGE),
+            ),
+        };
+        tokio::spawn(async move {
+            if let Err(e) = http::serve(address, endpoints).await {
+                tracing::error!("Error serving HTTP endpoints: {e}");
+            }
+        });
+    }
+
+    let tendermint = HttpClient::new(
+        settings
+            .tendermint_rpc
+            .as_deref()
+            .unwrap_or(config::DEFAULT_TENDERMINT_RPC),
+    )?;
+
+    if let Some(address) = settings.rpc_address {
+        let gateway = gateway::Gateway {
+            app: service.clone(),
+            tendermint: tendermint.clone(),
+        };
+        tokio::spawn(async move {
+            if let Err(e) = gateway::serve(address, gateway).await {
+                tracing::error!("Error serving the Starknet JSON-RPC API: {e}");
+            }
+        });
+    }
+
+    if let Some(address) = settings.ws_address {
+        let app = service.clone();
+        tokio::spawn(async move {
+            if let Err(e) = ws::serve(address, app).await {
+                tracing::error!("Error serving WebSocket subscriptions: {e}");
+            }
+        });
+    }
+
+    if let Some(address) = settings.grpc_address {
+        let queries = grpc::QueryService {
+            app: service.clone(),
+            tendermint: tendermint.clone(),
+        };
+        tokio::spawn(async move {
+            if let Err(e) = grpc::serve(address, queries).await {
+                tracing::error!("Error serving gRPC queries: {e}");
+            }
+        });
+    }
+
+    if let Some(rpc) = settings.settlement_rpc {
+        let settlement = settlement::Settlement {
+            rpc,
+            contract: config::required(
+                settings.settlement_contract,
+                "settlement_contract",
+                "settlement_rpc",
+            )?,
+            account: config::required(
+                settings.settlement_account,
+                "settlement_account",
+                "settlement_rpc",
+            )?,
+            interval: settings
+                .settlement_interval
+                .unwrap_or(config::DEFAULT_SETTLEMENT_INTERVAL),
+        };
+        let blocks = service.subscribe_blocks();
+        tokio::spawn(async move {
+            if let Err(e) = settlement.run(blocks).await {
+                tracing::error!("Error settling the chain on Ethereum: {e:#}");
+            }
+        });
+    }
+
+    if let Some(layer) = settings.da_layer {
+        let client = reqwest::Client::new();
+        let url = config::required(settings.da_url, "da_url", "da_layer")?;
+        let publisher: Box<dyn da::Publisher> = match layer {
+            DaLayer::Blob => Box::new(da::BlobStore { client, url }),
+            DaLayer::Celestia => Box::new(da::Celestia {
+                client,
+                url,
+                namespace: da::Celestia::namespace(&config::required(
+                    settings.da_namespace,
+                    "da_namespace",
+                    "Publishing to Celestia",
+                )?)?,
+                auth_token: settings.da_auth_token,
+            }),
+        };
+        tokio::spawn(da::publish(publisher, service.subscribe_state_diffs()));
+    }
+
+    if let Some(rpc) = settings.l1_rpc {
+        let watcher = l1::Watcher {
+            source: Box::new(l1::Ethereum {
+                client: reqwest::Client::new(),
+                rpc,
+                contract: config::required(settings.l1_contract, "l1_contract", "l1_rpc")?,
+                confirmations: settings
+                    .l1_confirmations
+                    .unwrap_or(config::DEFAULT_L1_CONFIRMATIONS),
+            }),
+            app: service.clone(),
+            tendermint: tendermint.clone(),
+            relayer_key: execution::parse_felt(&config::required(
+                settings.l1_relayer_key,
+                "l1_relayer_key",
+                "l1_rpc",
+            )?)?,
+            poll_interval: Duration::from_secs(
+                settings
+                    .l1_poll_interval
+                    .unwrap_or(config::DEFAULT_L1_POLL_INTERVAL),
+            ),
+            start_block: settings.l1_start_block,
+        };
+        tokio::spawn(async move {
+            if let Err(e) = watcher.run().await {
+                tracing::error!("Error relaying L1 messages: {e:#}");
+            }
+        });
+    }
+
+    if let Some(sink) = settings.export_sink {
+        let url = config::required(settings.export_url, "export_url", "export_sink")?;
+        let sink: Box<dyn export::Sink> = match sink {
+            ExportSink::Webhook => Box::new(export::Webhook {
+                client: reqwest::Client::new(),
+                url,
+            }),
+            ExportSink::Nats => Box::new(export::Nats::new(
+                &url,
+                settings
+                    .export_subject
+                    .unwrap_or_else(|| config::DEFAULT_EXPORT_SUBJECT.to_string()),
+            )),
+        };
+        let gateway = gateway::Gateway {
+            app: service.clone(),
+            tendermint: tendermint.clone(),
+        };
+        tokio::spawn(export::export(sink, gateway, service.subscribe_blocks()));
+    }
+
+    let node = async {
+        match &command {
+            Some(Command::Dev(args)) => {
+                let devnet = dev::Devnet {
+                    home: data_dir.join("devnet"),
+                    abci_address: format!("{}:{}", host, port),
+                    chain_id: chain_id.clone().unwrap_or_default(),
+                    gas_price,
+                    fee_recipient,
+                    max_tx_gas,
+                };
+
+                tokio::select! {
+                    result = exec(&host, port, settings.socket, buffers, service) => result,
+                    status = async {
+                        devnet.start(args).await?.wait().await.map_err(|e| eyre!(e))
+                    } => Err(eyre!("Tendermint exited with {}", status?)),
+                }
+            }
+            None | Some(Command::Replay(_)) => {
+                exec(&host, port, settings.socket, buffers, service).await
+            }
+        }
+    };
+
+    // returning drops the data directory lock and removes the PID file
+    let result = tokio::select! {
+        result = node => result,
+        _ = shutdown_signal() => {
+            tracing::info!("Shutting down");
+            Ok(())
+        }
+        _ = halted.changed() => {
+            tracing::info!("Shutting down after the halt height");
+            Ok(())
+        }
+    };
+
+    #[cfg(feature = "otel")]
+    telemetry::shutdown(metrics_export);
+    result
+}
+
+impl Cli {
+    /// Settings given as flags, falling back to the `--config` file.
+    fn settings(&self) -> Result<Settings> {
+        let settings = match &self.config {
+            Some(path) => self.settings.clone().or(Settings::load(path)?),
+            None => self.settings.clone(),
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Level of the logs written: `--quiet` and `--verbose` take precedence over the setting.
+    fn log_level(&self, settings: &Settings) -> Result<LevelFilter> {
+        if self.quiet {
+            Ok(LevelFilter::OFF)
+        } else if self.verbose {
+            Ok(LevelFilter::DEBUG)
+        } else {
+            settings
+                .log_level
+                .as_deref()
+                .map_or(Ok(LevelFilter::INFO), logging::parse_level)
+        }
+    }
+}
+
+/// CheckTx requests served per second; unlimited if no limit is set.
+fn mempool_rate(settings: &Settings) -> u64 {
+    settings.mempool_rate_limit.unwrap_or(u64::MAX)
+}
+
+fn info_rate(settings: &Settings) -> u64 {
+    settings
+        .info_rate_limit
+        .unwrap_or(config::DEFAULT_INFO_RATE_LIMIT)
+}
+
+/// Settings that can be changed while the node runs, by sending it SIGHUP.
+struct Reloader {
+    log_level: LevelHandle,
+    mempool_rate_limit: Arc<AtomicU64>,
+    info_rate_limit: Arc<AtomicU64>,
+    app: StarknetApp,
+}
+
+impl Reloader {
+    /// Reloads the settings on each SIGHUP, from the `--config` file merged under the flags.
+    async fn run(self, cli: Cli) {
+        let mut hangup = signal(SignalKind::hangup()).expect("must be able to handle SIGHUP");
+        while hangup.recv().await.is_some() {
+            match self.reload(&cli) {
+                Ok(()) => tracing::info!(
+                    "Reloaded the log level, rate limits and execution workers; \
+                     other settings take effect on restart"
+                ),
+                Err(e) => tracing::error!("Error reloading settings: {e:#}"),
+            }
+        }
+    }
+
+    fn reload(&self, cli: &Cli) -> Result<()> {
+        let settings = cli.settings()?;
+        self.log_level.reload(cli.log_level(&settings)?)?;
+        self.mempool_rate_limit
+            .store(mempool_rate(&settings), Ordering::Relaxed);
+        self.info_rate_limit
+            .store(info_rate(&settings), Ordering::Relaxed);
+        if let Some(workers) = settings.parallel_execution {
+            self.app.set_parallel_workers(workers);
+        }
+        Ok(())
+    }
+}
+
+/// Resolves once the node is asked to stop, with SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("must be able to handle SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate.recv() => {},
+    }
+}
+
+/// Sizes and rate limits of the request queues in front of the application.
+struct Buffers {
+    /// Requests queued for the application, from all connections.
+    requests: usize,
+    mempool: usize,
+    /// CheckTx requests served per second, changed on reload.
+    mempool_rate_limit: Arc<AtomicU64>,
+    info: usize,
+    /// Info requests served per second, changed on reload.
+    info_rate_limit: Arc<AtomicU64>,
+    /// Percentage of a buffer past which a warning is logged.
+    alarm: u8,
+}
+
+impl Buffers {
+    /// Number of requests in a queue of `buffer` requests past which a warning is logged.
+    fn alarm(&self, buffer: usize) -> usize {
+        (buffer * self.alarm as usize / 100).max(1)
+    }
+}
+
+async fn exec(
+    host: &str,
+    port: u16,
+    socket: Option<PathBuf>,
+    buffers: Buffers,
+    service: StarknetApp,
+) -> Result<()> {
+    let metrics = service.metrics();
+    let mempool_queue = QueueMetricsLayer::new(
+        "mempool",
+        metrics.clone(),
+        buffers.mempool,
+        buffers.alarm(buffers.mempool),
+    );
+    let info_queue =
+        QueueMetricsLayer::new("info", metrics, buffers.info, buffers.alarm(buffers.info));
+
+    // Split it into components.
+    let (consensus, mempool, snapshot, info) = split::service(service, buffers.requests);
+
+    // Customize request behavior for each category -- for instance, apply load-shedding only
+    // to mempool and info requests, but not to consensus requests.
+    let mempool = ServiceBuilder::new()
+        .layer(mempool_queue)
+        .load_shed()
+        .buffer(buffers.mempool)
+        .layer(RateLimitLayer::new(buffers.mempool_rate_limit))
+        .service(mempool);
+    let info = ServiceBuilder::new()
+        .layer(info_queue)
+        .load_shed()
+        .buffer(buffers.info)
+        .layer(RateLimitLayer::new(buffers.info_rate_limit))
+        .service(info);
+
+    // Hand those components to the ABCI server, on the unix socket if there's one
+    match socket {
+        Some(path) => {
+            let server = UnixServer {
+                consensus,
+                mempool,
+                info,
+                snapshot,
+            };
+            server.listen(&path).await.wrap_err_with(|| {
+                format!("Failed to serve ABCI on the unix socket {}", path.display())
+            })
+        }
+        None => {
+            tracing::info!("ABCI server listening on {}::{}", host, port);
+            Server::builder()
+                .consensus(consensus)
+                .snapshot(snapshot)
+                .mempool(mempool)
+                .info(info)
+                .finish()
+                .ok_or_else(|| eyre!("The ABCI server is missing a service"))?
+                .listen(format!("{}:{}", host, port))
+                .await
+                .map_err(|e| eyre!(e))
+                .wrap_err_with(|| format!("Failed to serve ABCI on {}:{}", host, port))
+        }
+    }
+}
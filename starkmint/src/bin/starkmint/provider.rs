@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use starkmint::execution::felt_to_hex;
+use starkmint::state;
+use starkmint::transaction::Transaction;
+use tendermint::block::Height;
+use tendermint_rpc::query::Query;
+use tendermint_rpc::{endpoint, Client, Order};
+
+use crate::feeder;
+use crate::gateway::{ensure_latest, parse_params, parse_rpc_felt, Gateway, RpcError};
+
+/// Parameters of the methods taking only a block id.
+#[derive(Deserialize)]
+struct BlockParams {
+    block_id: Value,
+}
+
+/// Parameters of `starknet_getStorageAt`.
+#[derive(Deserialize)]
+struct StorageParams {
+    contract_address: String,
+    key: String,
+    block_id: Value,
+}
+
+/// Parameters of `starknet_getClassHashAt`.
+#[derive(Deserialize)]
+struct ClassHashParams {
+    block_id: Value,
+    contract_address: String,
+}
+
+/// Parameters of `starknet_getTransactionByHash`.
+#[derive(Deserialize)]
+struct TransactionParams {
+    transaction_hash: String,
+}
+
+/// Parameters of `starknet_getTransactionByBlockIdAndIndex`.
+#[derive(Deserialize)]
+struct TransactionIndexParams {
+    block_id: Value,
+    index: usize,
+}
+
+/// Methods of the Starknet JSON-RPC API read by the `Provider` of starknet-rs, so Rust code
+/// written against it can target the node through a `JsonRpcClient` unmodified. Blocks are
+/// read from Tendermint and identified by number or tag; state is only kept as of the latest
+/// block.
+impl Gateway {
+    /// Response to the provider methods, or None if `method` isn't one of them.
+    pub async fn respond_provider(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Option<Result<Value, RpcError>> {
+        let result = match method {
+            "starknet_chainId" => self.chain_id(),
+            "starknet_syncing" => Ok(json!(false)),
+            "starknet_blockNumber" => self.block_number().await,
+            "starknet_blockHashAndNumber" => self.block_hash_and_number().await,
+            "starknet_getBlockWithTxHashes" => self.get_block(params, false).await,
+            "starknet_getBlockWithTxs" => self.get_block(params, true).await,
+            "starknet_getBlockTransactionCount" => self.get_block_transaction_count(params).await,
+            "starknet_getStateUpdate" => self.get_state_update(params).await,
+            "starknet_getStorageAt" => self.get_storage_at(params),
+            "starknet_getClassHashAt" => self.get_class_hash_at(params),
+            "starknet_getTransactionByHash" => self.get_transaction_by_hash(params).await,
+            "starknet_getTransactionByBlockIdAndIndex" => {
+                self.get_transaction_by_block_id_and_index(params).await
+            }
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    /// Chain ID as a felt, i.e. the ASCII bytes of the Tendermint chain ID.
+    fn chain_id(&self) -> Result<Value, RpcError> {
+        let chain_id = self
+            .app
+            .chain_id()
+            .ok_or_else(|| RpcError::internal("the chain isn't initialized"))?;
+        Ok(json!(format!("0x{}", hex::encode(chain_id))))
+    }
+
+    async fn block_number(&self) -> Result<Value, RpcError> {
+        let block = self.block(None).await?;
+        Ok(json!(block.block.header.height.value()))
+    }
+
+    async fn block_hash_and_number(&self) -> Result<Value, RpcError> {
+        let block = self.block(None).await?;
+        Ok(json!({
+            "block_hash": feeder::hash_hex(&block.block_id.hash.to_string()),
+            "block_number": block.block.header.height.value(),
+        }))
+    }
+
+    /// Block with the hashes of its transactions, or the transactions themselves.
+    async fn get_block(&self, params: Value, with_txs: bool) -> Result<Value, RpcError> {
+        let params: BlockParams = parse_params(params, &["block_id"])?;
+        let block = self.block(block_number(&params.block_id)?).await?;
+        let header = &block.block.header;
+
+        let transactions: Vec<Value> = decode_transactions(&block)
+            .map(|transaction| {
+                if with_txs {
+                    transaction_json(&transaction)
+                } else {
+                    json!(format!("0x{}", transaction.hash()))
+                }
+            })
+            .collect();
+
+        Ok(json!({
+            "status": "ACCEPTED_ON_L2",
+            "block_hash": feeder::hash_hex(&block.block_id.hash.to_string()),
+            "parent_hash": header
+                .last_block_id
+                .as_ref()
+                .map_or_else(|| "0x0".to_string(), |id| feeder::hash_hex(&id.hash.to_string())),
+            "block_number": header.height.value(),
+            "new_root": feeder::state_root(self, header.height.value()).await,
+            "timestamp": header.time.unix_timestamp(),
+            "sequencer_address": feeder::hash_hex(&header.proposer_address.to_string()),
+            "transactions": transactions,
+        }))
+    }
+
+    async fn get_block_transaction_count(&self, params: Value) -> Result<Value, RpcError> {
+        let params: BlockParams = parse_params(params, &["block_id"])?;
+        let block = self.block(block_number(&params.block_id)?).await?;
+        Ok(json!(decode_transactions(&block).count()))
+    }
+
+    async fn get_state_update(&self, params: Value) -> Result<Value, RpcError> {
+        let params: BlockParams = parse_params(params, &["block_id"])?;
+        let update = self
+            .app
+            .state_update(block_number(&params.block_id)?)
+            .ok_or_else(|| {
+                RpcError::new(
+                    24,
+                    "Block not found: state updates are only kept for the most recent blocks",
+                )
+            })?;
+        let block = self.block(Some(update.height)).await?;
+
+        let mut storage_diffs: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+        let mut nonces = Vec::new();
+        for ((address, key), value) in &update.writes {
+            if state::nonce_slot(address).1 == *key {
+                nonces.push(json!({
+                    "contract_address": felt_to_hex(address),
+                    "nonce": felt_to_hex(value),
+                }));
+            }
+            storage_diffs
+                .entry(felt_to_hex(address))
+                .or_default()
+                .push(json!({ "key": felt_to_hex(key), "value": felt_to_hex(value) }));
+        }
+
+        Ok(json!({
+            "block_hash": feeder::hash_hex(&block.block_id.hash.to_string()),
+            "new_root": feeder::root_hex(&update.new_root),
+            "old_root": feeder::root_hex(&update.old_root),
+            "state_diff": {
+                "storage_diffs": storage_diffs
+                    .into_iter()
+                    .map(|(address, entries)| json!({
+                        "address": address,
+                        "storage_entries": entries,
+                    }))
+                    .collect::<Vec<_>>(),
+                "nonces": nonces,
+                "deployed_contracts": [],
+                "declared_contract_hashes": [],
+                "declared_classes": [],
+                "deprecated_declared_classes": [],
+                "replaced_classes": [],
+            },
+        }))
+    }
+
+    fn get_storage_at(&self, params: Value) -> Result<Value, RpcError> {
+        let params: StorageParams = parse_params(params, &["contract_address", "key", "block_id"])?;
+        ensure_latest(&params.block_id)?;
+
+        let value = self.app.committed_storage(
+            &parse_rpc_felt(&params.contract_address)?,
+            &parse_rpc_felt(&params.key)?,
+        );
+        Ok(json!(felt_to_hex(&value)))
+    }
+
+    /// Class hash of a contract, which is its address since programs live at their class hash.
+    fn get_class_hash_at(&self, params: Value) -> Result<Value, RpcError> {
+        let params: ClassHashParams = parse_params(params, &["block_id", "contract_address"])?;
+        ensure_latest(&params.block_id)?;
+
+        let address = parse_rpc_felt(&params.contract_address)?;
+        match self.app.contract_class(&address) {
+            Some(_) => Ok(json!(felt_to_hex(&address))),
+            None => Err(RpcError::new(20, "Contract not found")),
+        }
+    }
+
+    async fn get_transaction_by_hash(&self, params: Value) -> Result<Value, RpcError> {
+        let params: TransactionParams = parse_params(params, &["transaction_hash"])?;
+        let hash = params
+            .transaction_hash
+            .trim_start_matches("0x")
+            .to_lowercase();
+
+        let response = self
+            .tendermint
+            .tx_search(Query::eq("app.hash", hash), false, 1, 1, Order::Ascending)
+            .await
+            .map_err(RpcError::internal)?;
+        let tx = response
+            .txs
+            .into_iter()
+            .next()
+            .ok_or_else(|| RpcError::new(29, "Transaction hash not found"))?;
+        let transaction: Transaction = bincode::deserialize(&tx.tx).map_err(RpcError::internal)?;
+
+        Ok(transaction_json(&transaction))
+    }
+
+    async fn get_transaction_by_block_id_and_index(
+        &self,
+        params: Value,
+    ) -> Result<Value, RpcError> {
+        let params: TransactionIndexParams = parse_params(params, &["block_id", "index"])?;
+        let block = self.block(block_number(&params.block_id)?).await?;
+
+        let transaction = decode_transactions(&block)
+            .nth(params.index)
+            .ok_or_else(|| RpcError::new(27, "Invalid transaction index in a block"))?;
+        Ok(transaction_json(&transaction))
+    }
+
+    /// Block at `number`, or the latest block.
+    async fn block(&self, number: Option<u64>) -> Result<endpoint::block::Response, RpcError> {
+        match number {
+            Some(number) => {
+                let height =
+                    Height::try_from(number).map_err(|_| RpcError::new(24, "Block not found"))?;
+                self.tendermint
+                    .block(height)
+                    .await
+                    .map_err(|_| RpcError::new(24, "Block not found"))
+            }
+            None => self
+                .tendermint
+                .latest_block()
+                .await
+                .map_err(RpcError::internal),
+        }
+    }
+}
+
+/// Height a block id refers to, or None for the latest block. Blocks are only looked up by
+/// number: Tendermint indexes them by height.
+fn block_number(block_id: &Value) -> Result<Option<u64>, RpcError> {
+    if ensure_latest(block_id).is_ok() {
+        return Ok(None);
+    }
+    block_id
+        .get("block_number")
+        .and_then(Value::as_u64)
+        .map(Some)
+        .ok_or_else(|| RpcError::new(24, "Block not found"))
+}
+
+/// Transactions of a block, skipping those that fail to decode as Tendermint delivers them
+/// anyway.
+fn decode_transactions(
+    block: &endpoint::block::Response,
+) -> impl Iterator<Item = Transaction> + '_ {
+    block
+        .block
+        .data
+        .iter()
+        .filter_map(|bytes| bincode::deserialize(bytes).ok())
+}
+
+/// Transaction in the JSON-RPC shape, which only differs from the feeder gateway's in the
+/// name of the invoke type.
+fn transaction_json(transaction: &Transaction) -> Value {
+    let mut value = feeder::transaction_json(transaction);
+    if value["type"] == "INVOKE_FUNCTION" {
+        value["type"] = json!("INVOKE");
+    }
+    value
+}
@@ -0,0 +1,158 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use starkmint::metrics::Metrics;
+use tower::load_shed::error::Overloaded;
+use tower::{BoxError, Layer, Service};
+
+/// Telemetry of a request queue in front of the application, wrapping its `load_shed` layer:
+/// the number of requests queued or being served, and those shed because the queue was full,
+/// are exported as metrics. Warnings are logged when the queue fills up past `alarm` requests
+/// and when requests start being shed, so operators know to tune its buffer size.
+#[derive(Debug, Clone)]
+pub struct QueueMetricsLayer {
+    queue: Arc<Queue>,
+}
+
+impl QueueMetricsLayer {
+    /// Telemetry of the queue serving `connection` requests, holding up to `buffer` requests.
+    pub fn new(
+        connection: &'static str,
+        metrics: Arc<Metrics>,
+        buffer: usize,
+        alarm: usize,
+    ) -> Self {
+        Self {
+            queue: Arc::new(Queue {
+                connection,
+                metrics,
+                buffer,
+                alarm,
+                depth: AtomicUsize::new(0),
+                alarmed: AtomicBool::new(false),
+                shed: AtomicU64::new(0),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for QueueMetricsLayer {
+    type Service = QueueMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        QueueMetrics {
+            inner,
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Queue {
+    connection: &'static str,
+    metrics: Arc<Metrics>,
+    buffer: usize,
+    alarm: usize,
+    /// Requests queued or being served.
+    depth: AtomicUsize,
+    /// Whether the depth is past the alarm, so the warning is logged once until it drains.
+    alarmed: AtomicBool,
+    /// Requests shed since the last one served.
+    shed: AtomicU64,
+}
+
+impl Queue {
+    fn push(&self) {
+        let depth = self.depth.fetch_add(1, Ordering::Relaxed) + 1;
+        self.metrics.set_queue_depth(self.connection, depth);
+
+        if depth >= self.alarm && !self.alarmed.swap(true, Ordering::Relaxed) {
+            tracing::warn!(
+                "{} queue at {depth} of {} requests, consider raising {}_buffer or adding \
+                 capacity",
+                self.connection,
+                self.buffer,
+                self.connection
+            );
+        }
+    }
+
+    fn pop(&self, shed: bool) {
+        let depth = self.depth.fetch_sub(1, Ordering::Relaxed) - 1;
+        self.metrics.set_queue_depth(self.connection, depth);
+
+        // half the alarm, so a queue hovering around it doesn't flood the logs
+        if depth <= self.alarm / 2 && self.alarmed.swap(false, Ordering::Relaxed) {
+            tracing::info!("{} queue drained to {depth} requests", self.connection);
+        }
+
+        if shed {
+            self.metrics.observe_shed_request(self.connection);
+            if self.shed.fetch_add(1, Ordering::Relaxed) == 0 {
+                tracing::warn!(
+                    "{} queue full, shedding requests; consider raising {}_buffer",
+                    self.connection,
+                    self.connection
+                );
+            }
+        } else {
+            let shed = self.shed.swap(0, Ordering::Relaxed);
+            if shed > 0 {
+                tracing::info!(
+                    "{} requests served again after shedding {shed}",
+                    self.connection
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueMetrics<S> {
+    inner: S,
+    queue: Arc<Queue>,
+}
+
+impl<S, R> Service<R> for QueueMetrics<S>
+where
+    S: Service<R, Error = BoxError>,
+    S::Future: Send + 'static,
+    S::Response: 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        self.queue.push();
+        let mut pending = Pending {
+            queue: self.queue.clone(),
+            shed: false,
+        };
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await;
+            pending.shed = matches!(&response, Err(e) if e.is::<Overloaded>());
+            response
+        })
+    }
+}
+
+/// Request counted in the queue until it's served, or dropped with its connection.
+struct Pending {
+    queue: Arc<Queue>,
+    shed: bool,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.queue.pop(self.shed);
+    }
+}
@@ -0,0 +1,82 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::time::{Instant, Sleep};
+use tower::{Layer, Service};
+
+/// Rate limit whose rate, in requests per second, can be changed while the node runs. Like
+/// tower's `RateLimit`, the service isn't ready while the rate is exceeded, so the requests
+/// queue up in front of it.
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    rate: Arc<AtomicU64>,
+}
+
+impl RateLimitLayer {
+    pub fn new(rate: Arc<AtomicU64>) -> Self {
+        Self { rate }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            rate: self.rate.clone(),
+            until: Instant::now(),
+            remaining: 0,
+            sleep: None,
+        }
+    }
+}
+
+pub struct RateLimit<S> {
+    inner: S,
+    rate: Arc<AtomicU64>,
+    /// End of the current one-second window.
+    until: Instant,
+    /// Requests left in the current window.
+    remaining: u64,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S, R> Service<R> for RateLimit<S>
+where
+    S: Service<R>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        loop {
+            if let Some(sleep) = &mut self.sleep {
+                if sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.sleep = None;
+            }
+
+            let now = Instant::now();
+            if now >= self.until {
+                self.until = now + Duration::from_secs(1);
+                self.remaining = self.rate.load(Ordering::Relaxed);
+            }
+            if self.remaining > 0 {
+                return self.inner.poll_ready(cx);
+            }
+            self.sleep = Some(Box::pin(tokio::time::sleep_until(self.until)));
+        }
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        self.remaining = self.remaining.saturating_sub(1);
+        self.inner.call(request)
+    }
+}
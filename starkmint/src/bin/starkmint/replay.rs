@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use clap::Args;
+use color_eyre::eyre::{bail, ensure, eyre, WrapErr};
+use color_eyre::Result;
+use starkmint::app::StarknetApp;
+use starkmint::archive::BlockArchive;
+use starkmint::execution::ExecutionConfig;
+use starkmint::genesis::Genesis;
+use starkmint::local::LocalChain;
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// Block archive to replay, i.e. the `blocks` directory in the data dir of a node started
+    /// with `--archive-blocks`.
+    #[arg(long)]
+    pub blocks: PathBuf,
+}
+
+/// Re-executes the archived blocks in order, from genesis, with the node's execution settings,
+/// until the first block that wasn't archived. Fails at the first block whose app hash differs
+/// from the recorded one. Blocks are executed by a [`LocalChain`] keeping its data in a
+/// temporary directory, removed once the replay ends.
+pub fn replay(
+    args: &ReplayArgs,
+    execution: ExecutionConfig,
+    parallel_execution: Option<usize>,
+) -> Result<()> {
+    let data_dir = std::env::temp_dir().join(format!("starkmint-replay-{}", Uuid::new_v4()));
+    let result = replay_in(args, execution, parallel_execution, &data_dir);
+    let _ = std::fs::remove_dir_all(&data_dir);
+    result
+}
+
+fn replay_in(
+    args: &ReplayArgs,
+    execution: ExecutionConfig,
+    parallel_execution: Option<usize>,
+    data_dir: &Path,
+) -> Result<()> {
+    let archive = BlockArchive::new(&args.blocks);
+    let genesis = archive.read_genesis()?;
+    let app_state = Genesis::from_app_state(&genesis.app_state)?;
+    // the chain would fail to initialize otherwise
+    app_state
+        .ensure_parameters(&execution)
+        .wrap_err("The node's settings don't match the archived chain")?;
+
+    let mut app = StarknetApp::new()
+        .with_execution_config(execution)
+        .with_chain_id(genesis.chain_id.clone())
+        .with_data_dir(data_dir);
+    if let Some(workers) = parallel_execution {
+        app = app.with_parallel_execution(workers);
+    }
+    let mut chain = LocalChain::new(app, genesis.chain_id.clone(), &app_state)
+        .map_err(|e| eyre!(e))
+        .wrap_err("The app refused to initialize the archived chain")?;
+    info!(
+        "Replaying chain {} from {}",
+        genesis.chain_id,
+        args.blocks.display()
+    );
+
+    let started = Instant::now();
+    let mut transactions = 0;
+    while let Some(block) = archive.read_block(chain.height() + 1)? {
+        transactions += block.txs.len();
+        let result = chain.execute_block(block.txs);
+        if result.app_hash != block.app_hash {
+            bail!(
+                "App hash mismatch at height {}: {} was recorded, but replaying gives {}",
+                block.height,
+                hex::encode(&block.app_hash),
+                hex::encode(&result.app_hash)
+            );
+        }
+    }
+    ensure!(
+        chain.height() > 0,
+        "No block archived in {}",
+        args.blocks.display()
+    );
+
+    info!(
+        "Replayed {} blocks and {transactions} transactions in {:?}: every app hash matches",
+        chain.height(),
+        started.elapsed()
+    );
+    Ok(())
+}
@@ -0,0 +1,205 @@
+//! Settlement of the chain on Ethereum: every few blocks, the app hash is posted to a
+//! settlement contract, along with a reference to the last verified proof, so the chain's
+//! state can be checked against L1.
+
+use color_eyre::{
+    eyre::{bail, ensure, eyre, WrapErr},
+    Result,
+};
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use starkmint::metrics::BlockSummary;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+/// Function of the settlement contract recording the state root of a block.
+const SETTLE_FUNCTION: &str = "settle(uint256,bytes32,bytes32)";
+
+/// View function of the settlement contract returning the height of the last settled block.
+const LATEST_HEIGHT_FUNCTION: &str = "latestHeight()";
+
+/// Settlement contract on Ethereum and how state roots are posted to it.
+///
+/// Transactions are sent with `eth_sendTransaction`, so the account must be unlocked at the
+/// RPC endpoint, e.g. by a signer such as Clef running in front of the Ethereum node.
+#[derive(Debug, Clone)]
+pub struct Settlement {
+    /// Ethereum JSON-RPC endpoint.
+    pub rpc: String,
+    /// Address of the settlement contract.
+    pub contract: String,
+    /// Address of the account posting state roots.
+    pub account: String,
+    /// Number of blocks between two settled state roots.
+    pub interval: u64,
+}
+
+impl Settlement {
+    /// Posts the state root of every `interval`-th block as it's committed, until the node
+    /// stops. Blocks already settled, e.g. replayed when the node starts, are skipped; roots
+    /// that fail to be posted are skipped too, as the next one commits to the whole state.
+    pub async fn run(self, mut blocks: Receiver<BlockSummary>) -> Result<()> {
+        let client = reqwest::Client::new();
+        let mut settled = self
+            .latest_height(&client)
+            .await
+            .wrap_err("Error reading the last settled height")?;
+        tracing::info!(
+            "Settling state roots on contract {} every {} blocks, last settled height {settled}",
+            self.contract,
+            self.interval
+        );
+
+        // the proof referenced is the last one verified, which may be in an earlier block
+        let mut proof = None;
+        loop {
+            let block = match blocks.recv().await {
+                Ok(block) => block,
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!("Settlement fell behind, {missed} blocks were skipped");
+                    continue;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            };
+            if let Some(hash) = block.verified_proofs.last() {
+                proof = Some(hash.clone());
+            }
+            if block.height % self.interval != 0 || block.height <= settled {
+                continue;
+            }
+
+            match self.settle(&client, &block, proof.as_deref()).await {
+                Ok(transaction) => {
+                    settled = block.height;
+                    tracing::info!(
+                        "Posted the state root of block {} in L1 transaction {transaction}",
+                        block.height
+                    );
+                }
+                Err(e) => tracing::warn!(
+                    "Error settling the state root of block {}: {e:#}",
+                    block.height
+                ),
+            }
+        }
+    }
+
+    /// Sends the transaction settling a block, returning its hash.
+    async fn settle(
+        &self,
+        client: &reqwest::Client,
+        block: &BlockSummary,
+        proof: Option<&str>,
+    ) -> Result<String> {
+        let proof = match proof {
+            Some(hash) => word(&hex::decode(hash)?)?,
+            None => [0; 32],
+        };
+        let mut data = selector(SETTLE_FUNCTION).to_vec();
+        data.extend(word(&block.height.to_be_bytes())?);
+        data.extend(word(&block.app_hash)?);
+        data.extend(proof);
+
+        let transaction = json!({
+            "from": self.account,
+            "to": self.contract,
+            "data": format!("0x{}", hex::encode(data)),
+        });
+        let hash = self
+            .request(client, "eth_sendTransaction", json!([transaction]))
+            .await?;
+        hash.as_str()
+            .map(str::to_string)
+            .ok_or_else(|| eyre!("Unexpected transaction hash {hash}"))
+    }
+
+    /// Height of the last block settled on the contract.
+    async fn latest_height(&self, client: &reqwest::Client) -> Result<u64> {
+        let call = json!({
+            "to": self.contract,
+            "data": format!("0x{}", hex::encode(selector(LATEST_HEIGHT_FUNCTION))),
+        });
+        let result = self
+            .request(client, "eth_call", json!([call, "latest"]))
+            .await?;
+        let bytes = result
+            .as_str()
+            .and_then(|result| hex::decode(result.trim_start_matches("0x")).ok())
+            .ok_or_else(|| eyre!("Unexpected result {result}"))?;
+        ensure!(bytes.len() == 32, "Unexpected result {result}");
+
+        let (high, low) = bytes.split_at(24);
+        ensure!(
+            high.iter().all(|byte| *byte == 0),
+            "Settled height {result} is too large"
+        );
+        Ok(u64::from_be_bytes(low.try_into()?))
+    }
+
+    async fn request(
+        &self,
+        client: &reqwest::Client,
+        method: &str,
+        params: Value,
+    ) -> Result<Value> {
+        request(client, &self.rpc, method, params).await
+    }
+}
+
+/// Result of a call to an Ethereum JSON-RPC endpoint.
+pub async fn request(
+    client: &reqwest::Client,
+    rpc: &str,
+    method: &str,
+    params: Value,
+) -> Result<Value> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    });
+    let body = client
+        .post(rpc)
+        .header(CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&request)?)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)?
+        .bytes()
+        .await?;
+
+    let mut response: Value = serde_json::from_slice(&body)?;
+    if let Some(error) = response.get("error") {
+        bail!("{method} failed: {error}");
+    }
+    Ok(response["result"].take())
+}
+
+/// First 4 bytes of the keccak256 of a function's signature, identifying it in calldata.
+fn selector(function: &str) -> [u8; 4] {
+    let digest = Keccak256::digest(function.as_bytes());
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+/// ABI word of a value of up to 32 big-endian bytes, left-padded with zeros.
+fn word(value: &[u8]) -> Result<[u8; 32]> {
+    ensure!(
+        value.len() <= 32,
+        "{} bytes don't fit in a word",
+        value.len()
+    );
+    let mut word = [0; 32];
+    word[32 - value.len()..].copy_from_slice(value);
+    Ok(word)
+}
+
+/// Fails unless `address` is a 0x-prefixed, 20-byte Ethereum address.
+pub fn validate_address(address: &str) -> Result<()> {
+    let bytes = address
+        .strip_prefix("0x")
+        .and_then(|hex| hex::decode(hex).ok())
+        .ok_or_else(|| eyre!("{address} is not a 0x-prefixed hex address"))?;
+    ensure!(bytes.len() == 20, "{address} is not a 20-byte address");
+    Ok(())
+}
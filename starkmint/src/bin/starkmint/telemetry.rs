@@ -0,0 +1,159 @@
+//! OpenTelemetry export of traces and metrics over OTLP, for nodes built with the `otel`
+//! feature.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use color_eyre::Result;
+use opentelemetry::metrics::ObservableGauge;
+use opentelemetry::sdk::export::metrics::aggregation::cumulative_temporality_selector;
+use opentelemetry::sdk::metrics::controllers::BasicController;
+use opentelemetry::sdk::metrics::selectors;
+use opentelemetry::sdk::trace::{self, Tracer};
+use opentelemetry::sdk::Resource;
+use opentelemetry::{global, runtime, Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use prometheus::proto::MetricType;
+use starkmint::metrics::Metrics;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// How often metrics are exported.
+const METRICS_PERIOD: Duration = Duration::from_secs(10);
+
+/// Exported metrics by name, mirroring the Prometheus ones.
+type Gauges = HashMap<String, ObservableGauge<f64>>;
+
+fn resource() -> Resource {
+    Resource::new([KeyValue::new("service.name", "starkmint")])
+}
+
+/// Layer exporting spans, such as those of ABCI requests, to the OTLP collector at
+/// `endpoint`. Spans are exported in batches from a background task.
+pub fn tracing_layer<S>(endpoint: &str) -> Result<OpenTelemetryLayer<S, Tracer>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(trace::config().with_resource(resource()))
+        .install_batch(runtime::Tokio)?;
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Exports the node's metrics to the OTLP collector at `endpoint` every [`METRICS_PERIOD`],
+/// until [`shutdown`] is called. They mirror the Prometheus metrics, as gauges: counters and
+/// gauges by their name, and histograms by their `_sum` and `_count`.
+pub fn export_metrics(endpoint: &str, metrics: Arc<Metrics>) -> Result<BasicController> {
+    let controller = opentelemetry_otlp::new_pipeline()
+        .metrics(
+            selectors::simple::inexpensive(),
+            cumulative_temporality_selector(),
+            runtime::Tokio,
+        )
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_resource(resource())
+        .with_period(METRICS_PERIOD)
+        .build()?;
+    global::set_meter_provider(controller.clone());
+    let meter = global::meter("starkmint");
+
+    let gauges: Arc<Mutex<Gauges>> = Default::default();
+    {
+        let metrics = metrics.clone();
+        let gauges = gauges.clone();
+        meter.register_callback(move |cx| {
+            let gauges = gauges.lock().unwrap();
+            for (name, labels, value) in samples(&metrics) {
+                if let Some(gauge) = gauges.get(&name) {
+                    gauge.observe(cx, value, &labels);
+                }
+            }
+        })?;
+    }
+
+    // metrics with labels only appear once observed, and instruments can't be created
+    // while they're being collected, so they're created as metrics show up
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(METRICS_PERIOD);
+        loop {
+            interval.tick().await;
+            let new: Vec<String> = {
+                let gauges = gauges.lock().unwrap();
+                samples(&metrics)
+                    .into_iter()
+                    .map(|(name, _, _)| name)
+                    .filter(|name| !gauges.contains_key(name))
+                    .collect()
+            };
+            for name in new {
+                let gauge = meter.f64_observable_gauge(name.clone()).init();
+                gauges.lock().unwrap().insert(name, gauge);
+            }
+        }
+    });
+
+    Ok(controller)
+}
+
+/// Current value of every Prometheus metric, by name and labels.
+fn samples(metrics: &Metrics) -> Vec<(String, Vec<KeyValue>, f64)> {
+    let mut samples = Vec::new();
+    for family in metrics.gather() {
+        let name = family.get_name();
+        for metric in family.get_metric() {
+            let labels: Vec<KeyValue> = metric
+                .get_label()
+                .iter()
+                .map(|label| {
+                    KeyValue::new(label.get_name().to_string(), label.get_value().to_string())
+                })
+                .collect();
+
+            match family.get_field_type() {
+                MetricType::COUNTER => {
+                    samples.push((name.to_string(), labels, metric.get_counter().get_value()))
+                }
+                MetricType::GAUGE => {
+                    samples.push((name.to_string(), labels, metric.get_gauge().get_value()))
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    samples.push((
+                        format!("{name}_sum"),
+                        labels.clone(),
+                        histogram.get_sample_sum(),
+                    ));
+                    samples.push((
+                        format!("{name}_count"),
+                        labels,
+                        histogram.get_sample_count() as f64,
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+    samples
+}
+
+/// Exports the spans and metrics not exported yet, before the node exits.
+pub fn shutdown(metrics: Option<BasicController>) {
+    if let Some(controller) = metrics {
+        if let Err(e) = controller.stop(&Context::current()) {
+            tracing::warn!("Error exporting metrics: {e}");
+        }
+    }
+    global::shutdown_tracer_provider();
+}
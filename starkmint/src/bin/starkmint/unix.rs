@@ -0,0 +1,193 @@
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+
+use color_eyre::{
+    eyre::{ensure, eyre, WrapErr},
+    Result,
+};
+use futures::future::{self, BoxFuture};
+use futures::stream::FuturesOrdered;
+use futures::{FutureExt, StreamExt, TryFutureExt};
+use prost::Message;
+use tendermint::abci::{
+    ConsensusRequest, ConsensusResponse, InfoRequest, InfoResponse, MempoolRequest,
+    MempoolResponse, MethodKind, Request, Response, SnapshotRequest, SnapshotResponse,
+};
+use tendermint_proto::abci as pb;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tower::{BoxError, Service, ServiceExt};
+
+/// Largest ABCI message read from a connection, that of a block at Tendermint's maximum block
+/// size, so a corrupted length can't make the node allocate arbitrary amounts of memory.
+const MAX_MESSAGE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// ABCI server on a unix socket, for Tendermint configured with `proxy_app = "unix://<path>"`.
+/// tower-abci only listens on TCP, so connections are served here, with the same services and
+/// protocol as [`tower_abci::Server`]: requests and responses are length-delimited protobuf
+/// messages, each request is handled by the service of its category, and responses are
+/// written in the order of the requests.
+#[derive(Clone)]
+pub struct UnixServer<C, M, I, S> {
+    pub consensus: C,
+    pub mempool: M,
+    pub info: I,
+    pub snapshot: S,
+}
+
+impl<C, M, I, S> UnixServer<C, M, I, S>
+where
+    C: Service<ConsensusRequest, Response = ConsensusResponse, Error = BoxError>
+        + Send
+        + Clone
+        + 'static,
+    C::Future: Send + 'static,
+    M: Service<MempoolRequest, Response = MempoolResponse, Error = BoxError>
+        + Send
+        + Clone
+        + 'static,
+    M::Future: Send + 'static,
+    I: Service<InfoRequest, Response = InfoResponse, Error = BoxError> + Send + Clone + 'static,
+    I::Future: Send + 'static,
+    S: Service<SnapshotRequest, Response = SnapshotResponse, Error = BoxError>
+        + Send
+        + Clone
+        + 'static,
+    S::Future: Send + 'static,
+{
+    /// Accepts connections on the unix socket at `path`, each served with its own clones of
+    /// the services.
+    pub async fn listen(self, path: &Path) -> Result<()> {
+        // a socket left behind by a previous run would make bind fail
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if metadata.file_type().is_socket() {
+                std::fs::remove_file(path).wrap_err_with(|| {
+                    format!("Failed to remove the stale unix socket {}", path.display())
+                })?;
+            }
+        }
+        let listener = UnixListener::bind(path)
+            .wrap_err_with(|| format!("Failed to bind the unix socket {}", path.display()))?;
+        tracing::info!("ABCI server listening on {}", path.display());
+
+        loop {
+            let (stream, _) = listener
+                .accept()
+                .await
+                .wrap_err("Failed to accept a connection on the unix socket")?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.serve(stream).await {
+                    tracing::error!("ABCI connection on the unix socket failed: {e:#}");
+                }
+            });
+        }
+    }
+
+    async fn serve(mut self, stream: UnixStream) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+
+        // requests are read by their own task, so a partially read request is never dropped
+        // when a response becomes ready first
+        let (sender, mut requests) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(reader);
+            loop {
+                let request = read_request(&mut reader).await;
+                let last = !matches!(request, Ok(Some(_)));
+                if sender.send(request).await.is_err() || last {
+                    break;
+                }
+            }
+        });
+
+        let mut responses = FuturesOrdered::new();
+        loop {
+            tokio::select! {
+                request = requests.recv() => match request.unwrap_or(Ok(None))? {
+                    Some(request) => responses.push_back(self.call(request).await?),
+                    None => return Ok(()),
+                },
+                Some(response) = responses.next() => {
+                    let response = pb::Response::from(response.map_err(|e| eyre!(e))?);
+                    writer.write_all(&response.encode_length_delimited_to_vec()).await?;
+                }
+            }
+        }
+    }
+
+    /// Hands the request to the service of its category once it's ready, returning the
+    /// future of its response. Flushes are answered once the previous responses are written.
+    async fn call(
+        &mut self,
+        request: Request,
+    ) -> Result<BoxFuture<'static, Result<Response, BoxError>>> {
+        let response = match request.kind() {
+            MethodKind::Consensus => {
+                let request = request
+                    .try_into()
+                    .map_err(|e| eyre!("Invalid ABCI consensus request: {e}"))?;
+                let service = self.consensus.ready().await.map_err(|e| eyre!(e))?;
+                service.call(request).map_ok(Response::from).boxed()
+            }
+            MethodKind::Mempool => {
+                let request = request
+                    .try_into()
+                    .map_err(|e| eyre!("Invalid ABCI mempool request: {e}"))?;
+                let service = self.mempool.ready().await.map_err(|e| eyre!(e))?;
+                service.call(request).map_ok(Response::from).boxed()
+            }
+            MethodKind::Info => {
+                let request = request
+                    .try_into()
+                    .map_err(|e| eyre!("Invalid ABCI info request: {e}"))?;
+                let service = self.info.ready().await.map_err(|e| eyre!(e))?;
+                service.call(request).map_ok(Response::from).boxed()
+            }
+            MethodKind::Snapshot => {
+                let request = request
+                    .try_into()
+                    .map_err(|e| eyre!("Invalid ABCI snapshot request: {e}"))?;
+                let service = self.snapshot.ready().await.map_err(|e| eyre!(e))?;
+                service.call(request).map_ok(Response::from).boxed()
+            }
+            MethodKind::Flush => future::ready(Ok(Response::Flush)).boxed(),
+        };
+        Ok(response)
+    }
+}
+
+/// Reads a request prefixed with its length as a varint, or None if the connection was
+/// closed.
+async fn read_request(reader: &mut BufReader<OwnedReadHalf>) -> Result<Option<Request>> {
+    let mut length = 0_u64;
+    let mut shift = 0;
+    loop {
+        let byte = match reader.read_u8().await {
+            Ok(byte) => byte,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && shift == 0 => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        length |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        ensure!(shift < 64, "Invalid ABCI message length");
+    }
+    ensure!(
+        length <= MAX_MESSAGE_SIZE,
+        "ABCI message of {length} bytes is over the limit of {MAX_MESSAGE_SIZE}"
+    );
+
+    let mut message = vec![0; length as usize];
+    reader.read_exact(&mut message).await?;
+    let request = pb::Request::decode(message.as_slice())?;
+    Request::try_from(request)
+        .map(Some)
+        .map_err(|e| eyre!("Invalid ABCI request: {e}"))
+}
@@ -0,0 +1,169 @@
+use std::net::SocketAddr;
+
+use cairo_felt::Felt;
+use color_eyre::{eyre::bail, Result};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use starkmint::app::StarknetApp;
+use starkmint::execution::{parse_felt, Receipt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+
+/// What a WebSocket client is subscribed to, given by the path and query string it connects
+/// with. Every filter given must match.
+#[derive(Debug)]
+enum Subscription {
+    /// `/receipts?sender=<address>&function=<name>`: receipts of applied transactions.
+    Receipts {
+        sender: Option<Felt>,
+        function: Option<String>,
+    },
+    /// `/events?from_address=<address>&key=<key>`: contract events, along with the hash and
+    /// height of their transaction. `key` may be repeated; events must have all of them.
+    Events {
+        from_address: Option<Felt>,
+        keys: Vec<Felt>,
+    },
+}
+
+/// Serves subscriptions to the receipts and events of committed transactions at `address`.
+pub async fn serve(address: SocketAddr, app: StarknetApp) -> Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    tracing::info!("Serving WebSocket subscriptions on ws://{address}");
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let receipts = app.subscribe_receipts();
+        tokio::spawn(async move {
+            if let Err(e) = subscribe(stream, receipts).await {
+                tracing::debug!("WebSocket subscription of {peer} closed: {e}");
+            }
+        });
+    }
+}
+
+/// Sends a client the receipts or events it subscribed to, until it disconnects.
+async fn subscribe(stream: TcpStream, mut receipts: Receiver<Receipt>) -> Result<()> {
+    let mut target = None;
+    let record_target = |request: &Request, response: Response| -> Result<_, ErrorResponse> {
+        target = Some(request.uri().clone());
+        Ok(response)
+    };
+    let socket = tokio_tungstenite::accept_hdr_async(stream, record_target).await?;
+    let (mut sink, mut stream) = socket.split();
+
+    let target = target.unwrap_or_default();
+    let subscription = match Subscription::parse(target.path(), target.query().unwrap_or("")) {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            let close = CloseFrame {
+                code: CloseCode::Policy,
+                reason: e.to_string().into(),
+            };
+            sink.send(Message::Close(Some(close))).await?;
+            return Ok(());
+        }
+    };
+
+    loop {
+        tokio::select! {
+            receipt = receipts.recv() => match receipt {
+                Ok(receipt) => {
+                    for message in subscription.messages(&receipt) {
+                        sink.send(Message::Text(message.to_string())).await?;
+                    }
+                }
+                // a slow client misses receipts rather than holding them back for others
+                Err(RecvError::Lagged(missed)) => {
+                    let error = json!({ "error": format!("{missed} receipts missed") });
+                    sink.send(Message::Text(error.to_string())).await?;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            message = stream.next() => match message {
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                // pings are answered while reading, other messages are ignored
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+            },
+        }
+    }
+}
+
+impl Subscription {
+    fn parse(path: &str, query: &str) -> Result<Self> {
+        let params = query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| param.split_once('=').unwrap_or((param, "")));
+
+        let mut subscription = match path {
+            "/receipts" => Subscription::Receipts {
+                sender: None,
+                function: None,
+            },
+            "/events" => Subscription::Events {
+                from_address: None,
+                keys: Vec::new(),
+            },
+            path => bail!("Unknown subscription {path}, expected /receipts or /events"),
+        };
+
+        for (name, value) in params {
+            match (&mut subscription, name) {
+                (Subscription::Receipts { sender, .. }, "sender") => {
+                    *sender = Some(parse_felt(value)?)
+                }
+                (Subscription::Receipts { function, .. }, "function") => {
+                    *function = Some(value.to_string())
+                }
+                (Subscription::Events { from_address, .. }, "from_address") => {
+                    *from_address = Some(parse_felt(value)?)
+                }
+                (Subscription::Events { keys, .. }, "key") => keys.push(parse_felt(value)?),
+                (_, name) => bail!("Unknown filter {name} for {path}"),
+            }
+        }
+        Ok(subscription)
+    }
+
+    /// Messages to send for a committed receipt, if it matches the filters.
+    fn messages(&self, receipt: &Receipt) -> Vec<Value> {
+        match self {
+            Subscription::Receipts { sender, function } => {
+                let matches = sender
+                    .as_ref()
+                    .map_or(true, |sender| receipt.sender.as_ref() == Some(sender))
+                    && function
+                        .as_ref()
+                        .map_or(true, |function| receipt.function.as_ref() == Some(function));
+                if matches {
+                    vec![json!(receipt)]
+                } else {
+                    Vec::new()
+                }
+            }
+            Subscription::Events { from_address, keys } => receipt
+                .events
+                .iter()
+                .filter(|event| {
+                    from_address
+                        .as_ref()
+                        .map_or(true, |address| &event.from_address == address)
+                        && keys.iter().all(|key| event.keys.contains(key))
+                })
+                .map(|event| {
+                    json!({
+                        "transaction_hash": receipt.transaction_hash,
+                        "height": receipt.height,
+                        "event": event,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
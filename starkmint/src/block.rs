@@ -0,0 +1,478 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use cairo_felt::Felt;
+use color_eyre::eyre::ensure;
+use color_eyre::Result;
+use num_traits::Zero;
+use tracing::debug;
+
+use crate::account;
+use crate::cache::ProgramCache;
+use crate::execution::{self, ExecutionConfig};
+use crate::state::{self, ClassRegistry, ContractStorage, StorageWrites, TxStorage};
+use crate::transaction::{Execution, Transaction, TransactionType};
+
+/// Outcome of delivering a transaction: its execution if it was applied, or `None` if its hash
+/// doesn't match its execution. The storage writes of the execution have been applied.
+pub type Delivery = Result<Option<Execution>>;
+
+/// Executes a transaction and applies it to the block's state.
+pub fn deliver(
+    tx: &Transaction,
+    config: &ExecutionConfig,
+    storage: &mut ContractStorage,
+    classes: &mut ClassRegistry,
+    programs: &ProgramCache,
+) -> Delivery {
+    let execution =
+        tx.transaction_type
+            .execute(config, TxStorage::new(storage), classes, programs)?;
+    apply(tx, execution, config, storage, classes, programs)
+}
+
+/// Delivers a batch of transactions, executing them concurrently on up to `workers` threads.
+///
+/// Every transaction first runs optimistically against the state at the start of the batch.
+/// Results are then validated in block order: a transaction that read a slot written by an
+/// earlier transaction of the batch, or that failed (e.g. calling a contract registered earlier
+/// in the batch), is re-executed serially against the up-to-date state. The outcome is the same
+/// as delivering the transactions one by one.
+pub fn deliver_parallel(
+    txs: &[Transaction],
+    config: &ExecutionConfig,
+    storage: &mut ContractStorage,
+    classes: &mut ClassRegistry,
+    programs: &ProgramCache,
+    workers: usize,
+) -> Vec<Delivery> {
+    let speculative = execute_concurrently(txs, config, storage, classes, programs, workers);
+    deliver_speculated(txs, speculative, config, storage, classes, programs)
+}
+
+/// Delivers a batch of transactions given their optimistic executions against the state at
+/// the start of the batch, in the same order. Executions are validated and applied as in
+/// [`deliver_parallel`].
+pub fn deliver_speculated(
+    txs: &[Transaction],
+    speculative: Vec<Result<Execution>>,
+    config: &ExecutionConfig,
+    storage: &mut ContractStorage,
+    classes: &mut ClassRegistry,
+    programs: &ProgramCache,
+) -> Vec<Delivery> {
+    let mut written = BTreeSet::new();
+    let mut reexecuted = 0;
+
+    let deliveries = txs
+        .iter()
+        .zip(speculative)
+        .map(|(tx, execution)| -> Delivery {
+            let execution = match execution {
+                Ok(execution) if execution.storage_reads.is_disjoint(&written) => execution,
+                _ => {
+                    reexecuted += 1;
+                    tx.transaction_type.execute(
+                        config,
+                        TxStorage::new(storage),
+                        classes,
+                        programs,
+                    )?
+                }
+            };
+
+            let applied = apply(tx, execution, config, storage, classes, programs)?;
+            if let Some(execution) = &applied {
+                written.extend(execution.storage_writes.keys().cloned());
+            }
+            Ok(applied)
+        })
+        .collect();
+
+    debug!(
+        "Delivered {} transactions in parallel, {} re-executed serially",
+        txs.len(),
+        reexecuted
+    );
+    deliveries
+}
+
+fn execute_concurrently(
+    txs: &[Transaction],
+    config: &ExecutionConfig,
+    storage: &ContractStorage,
+    classes: &ClassRegistry,
+    programs: &ProgramCache,
+    workers: usize,
+) -> Vec<Result<Execution>> {
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<Result<Execution>>>> =
+        txs.iter().map(|_| Mutex::new(None)).collect();
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, txs.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(tx) = txs.get(i) else { break };
+
+                let execution =
+                    tx.transaction_type
+                        .execute(config, TxStorage::new(storage), classes, programs);
+                *results[i].lock().unwrap() = Some(execution);
+            });
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|result| {
+            result
+                .into_inner()
+                .unwrap()
+                .expect("every transaction is executed")
+        })
+        .collect()
+}
+
+/// Applies an execution to the block's state if it matches the transaction hash and used at
+/// most the chain's `max_tx_gas`. Executed programs are registered so other contracts can call
+/// them. Unsigned function and class executions are rejected on chains requiring signatures.
+/// Signed transactions must be validated by their sender's account against the block's state
+/// (see [`account::validate`]), their call is dispatched to the sender's account contract if
+/// it has one (see [`account::execute`]), and the sender's nonce is incremented. The fee, the
+/// gas used at the chain's gas price, which may not exceed the signed max fee, and the tip are
+/// moved from the balance of their payer, the sender or its paymaster, to the fee recipient's,
+/// if the chain has one. L1 handler transactions must be sent by the chain's L1 relayer, pay no
+/// fee as they're paid for on L1, and their message is marked handled.
+fn apply(
+    tx: &Transaction,
+    mut execution: Execution,
+    config: &ExecutionConfig,
+    storage: &mut ContractStorage,
+    classes: &mut ClassRegistry,
+    programs: &ProgramCache,
+) -> Delivery {
+    if execution.hash != tx.transaction_hash {
+        return Ok(None);
+    }
+    if tx.signature.is_none() && tx.transaction_type.is_execution() {
+        let (address, key) = state::signatures_required_slot();
+        ensure!(
+            storage.read(&address, &key).is_zero(),
+            "Executions must be signed on this chain, as it funds accounts"
+        );
+    }
+
+    // the nonce, fee and tip are staged first, as the execution's writes override them
+    let mut writes = StorageWrites::new();
+    if let TransactionType::L1Handler(message) = &tx.transaction_type {
+        let (address, key) = state::l1_relayer_slot();
+        let relayer = storage.read(&address, &key);
+        ensure!(
+            !relayer.is_zero()
+                && tx.signature.as_ref().map(|signature| &signature.sender) == Some(&relayer),
+            "L1 handler transactions must be sent by the chain's L1 relayer"
+        );
+        let (address, key) = message.slot();
+        ensure!(
+            storage.read(&address, &key).is_zero(),
+            "L1 message 0x{} was already handled",
+            hex::encode(message.hash())
+        );
+        writes.insert((address, key), Felt::new(1));
+    }
+    if let Some(signature) = &tx.signature {
+        account::validate(
+            tx,
+            signature,
+            config,
+            TxStorage::new(storage),
+            classes,
+            programs,
+        )?;
+        let dispatch = account::execute(
+            tx,
+            signature,
+            config,
+            TxStorage::new(storage),
+            classes,
+            programs,
+        )?;
+        if let Some(mut dispatch) = dispatch {
+            // the account's writes and events come first, the call's override them
+            dispatch
+                .storage_writes
+                .append(&mut execution.storage_writes);
+            execution.storage_writes = dispatch.storage_writes;
+            dispatch.events.append(&mut execution.events);
+            execution.events = dispatch.events;
+            execution.storage_reads.append(&mut dispatch.storage_reads);
+            execution.gas_used += dispatch.gas_used;
+            execution.resources.add(&dispatch.resources);
+        }
+    }
+    if let Some(limit) = config.max_tx_gas {
+        ensure!(
+            execution.gas_used <= limit,
+            "Transaction used {} gas, over the limit of {limit}",
+            execution.gas_used
+        );
+    }
+
+    if let Some(signature) = &tx.signature {
+        let expected = storage.nonce(&signature.sender);
+        ensure!(
+            signature.nonce == expected,
+            "Invalid nonce {}, expected {expected}",
+            signature.nonce
+        );
+
+        let fee = match &tx.transaction_type {
+            TransactionType::L1Handler(_) => 0,
+            _ => execution.gas_used.saturating_mul(config.gas_price),
+        };
+        ensure!(
+            fee <= signature.max_fee,
+            "Fee {fee} exceeds the max fee {}",
+            signature.max_fee
+        );
+        let payer = signature.payer();
+        let balance = storage.balance(payer);
+        let charge = fee.saturating_add(signature.tip);
+        ensure!(
+            balance >= Felt::new(charge),
+            "Insufficient balance {balance} for fee {fee} and tip {}",
+            signature.tip
+        );
+
+        writes.insert(
+            state::nonce_slot(&signature.sender),
+            Felt::new(expected + 1),
+        );
+        if charge > 0 {
+            writes.insert(state::balance_slot(payer), balance - Felt::new(charge));
+            // the payer may be the recipient, so its balance is read back from the writes
+            if let Some(recipient) = &config.fee_recipient {
+                let slot = state::balance_slot(recipient);
+                let recipient_balance = writes
+                    .get(&slot)
+                    .cloned()
+                    .unwrap_or_else(|| storage.balance(recipient));
+                writes.insert(slot, recipient_balance + Felt::new(charge));
+            }
+        }
+        execution.fee = fee;
+        execution.events.push(execution::fee_transfer_event(
+            payer,
+            config.fee_recipient.as_ref(),
+            charge,
+        ));
+    }
+
+    writes.extend(execution.storage_writes.clone());
+    storage.apply_transaction(&tx.transaction_hash, writes);
+    if let TransactionType::FunctionExecution { program, .. } = &tx.transaction_type {
+        classes.register(program);
+    }
+    Ok(Some(execution))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messaging::L1Message;
+    use crate::signature;
+    use crate::transaction::EntryPoint;
+
+    const CHAIN_ID: &str = "starkmint-test";
+
+    const FIBONACCI: &str = include_str!("../../examples/programs/fibonacci.json");
+
+    /// Example contract writing its storage, see `examples/programs/syscalls.cairo`.
+    const SYSCALLS: &str = include_str!("../../examples/programs/syscalls.json");
+
+    /// Account credited with the fees.
+    const FEE_RECIPIENT: u64 = 99;
+
+    /// Private key and address of a native account.
+    fn account(index: u32) -> (Felt, Felt) {
+        let private_key = signature::dev_private_key(index);
+        let address = signature::public_key(&private_key).unwrap();
+        (private_key, address)
+    }
+
+    /// State of a chain recording `CHAIN_ID` at genesis, whose L1 relayer is the account at
+    /// index 2.
+    fn genesis() -> ContractStorage {
+        let mut storage = ContractStorage::default();
+        storage.apply(StorageWrites::from([
+            (state::chain_id_slot(), signature::chain_id_hash(CHAIN_ID)),
+            (state::l1_relayer_slot(), account(2).1),
+        ]));
+        storage
+    }
+
+    fn config() -> ExecutionConfig {
+        ExecutionConfig {
+            fee_recipient: Some(Felt::new(FEE_RECIPIENT)),
+            ..Default::default()
+        }
+    }
+
+    fn fibonacci() -> Transaction {
+        Transaction::with_type(TransactionType::FunctionExecution {
+            program: FIBONACCI.into(),
+            function: EntryPoint::Name("main".to_string()),
+            program_name: "fibonacci.json".to_string(),
+            enable_trace: false,
+            calldata: Vec::new(),
+        })
+        .unwrap()
+    }
+
+    fn deliver(tx: &Transaction, storage: &mut ContractStorage) -> Delivery {
+        let mut classes = ClassRegistry::default();
+        classes.register(SYSCALLS);
+        super::deliver(
+            tx,
+            &config(),
+            storage,
+            &mut classes,
+            &ProgramCache::default(),
+        )
+    }
+
+    /// Transaction handling `message`, whose payload is written by the example contract at
+    /// the key of the L1 sender, sent by the account at `index`.
+    fn l1_handler(message: &L1Message, index: u32, nonce: u64) -> Transaction {
+        let mut tx = Transaction::l1_handler(message.clone(), SYSCALLS).unwrap();
+        tx.sign(&account(index).0, CHAIN_ID, nonce, 0, 0).unwrap();
+        tx
+    }
+
+    fn message(nonce: u64) -> L1Message {
+        L1Message {
+            from_address: Felt::new(0xabc),
+            to_address: state::class_hash(SYSCALLS),
+            selector: execution::entry_point_selector("write"),
+            payload: vec![Felt::new(42)],
+            nonce: Felt::new(nonce),
+        }
+    }
+
+    #[test]
+    fn paymasters_pay_the_fee_and_tip_of_the_transactions_they_sponsor() {
+        let (sender_key, sender) = account(0);
+        let (paymaster_key, paymaster) = account(1);
+        let mut storage = genesis();
+        storage.set_balance(&paymaster, Felt::new(1_000_000));
+
+        let mut tx = fibonacci();
+        tx.sign_as(
+            &sender,
+            &sender_key,
+            CHAIN_ID,
+            0,
+            5,
+            100_000,
+            Some(&paymaster),
+        )
+        .unwrap();
+        tx.sponsor(&paymaster, &paymaster_key, CHAIN_ID).unwrap();
+        let execution = deliver(&tx, &mut storage).unwrap().unwrap();
+
+        let charge = execution.fee + 5;
+        assert_eq!(execution.fee, execution.gas_used);
+        assert_eq!(storage.balance(&paymaster), Felt::new(1_000_000 - charge));
+        assert_eq!(storage.balance(&sender), Felt::new(0));
+        assert_eq!(
+            storage.balance(&Felt::new(FEE_RECIPIENT)),
+            Felt::new(charge)
+        );
+        assert_eq!(storage.nonce(&sender), 1);
+        assert_eq!(storage.nonce(&paymaster), 0);
+        let event = execution.events.last().unwrap();
+        assert_eq!(event.data[0], paymaster);
+    }
+
+    #[test]
+    fn sponsorships_must_be_signed_and_paid_for() {
+        let (sender_key, sender) = account(0);
+        let (paymaster_key, paymaster) = account(1);
+        let mut storage = genesis();
+        storage.set_balance(&sender, Felt::new(1_000_000));
+
+        // the sender signed for a paymaster that didn't sponsor the transaction
+        let mut unsponsored = fibonacci();
+        unsponsored
+            .sign_as(
+                &sender,
+                &sender_key,
+                CHAIN_ID,
+                0,
+                0,
+                100_000,
+                Some(&paymaster),
+            )
+            .unwrap();
+        assert!(deliver(&unsponsored, &mut storage).is_err());
+
+        // the paymaster can't cover the max fee
+        let mut sponsored = fibonacci();
+        sponsored
+            .sign_as(
+                &sender,
+                &sender_key,
+                CHAIN_ID,
+                0,
+                0,
+                100_000,
+                Some(&paymaster),
+            )
+            .unwrap();
+        sponsored
+            .sponsor(&paymaster, &paymaster_key, CHAIN_ID)
+            .unwrap();
+        assert!(deliver(&sponsored, &mut storage).is_err());
+        assert_eq!(storage.nonce(&sender), 0);
+
+        storage.set_balance(&paymaster, Felt::new(1_000_000));
+        assert!(deliver(&sponsored, &mut storage).unwrap().is_some());
+    }
+
+    #[test]
+    fn l1_handlers_execute_their_message_once_for_free() {
+        let relayer = account(2).1;
+        let mut storage = genesis();
+
+        let execution = deliver(&l1_handler(&message(0), 2, 0), &mut storage)
+            .unwrap()
+            .unwrap();
+        assert_eq!(execution.fee, 0);
+        let contract = state::class_hash(SYSCALLS);
+        assert_eq!(storage.read(&contract, &Felt::new(0xabc)), Felt::new(42));
+        let (address, key) = message(0).slot();
+        assert_eq!(storage.read(&address, &key), Felt::new(1));
+        assert_eq!(storage.nonce(&relayer), 1);
+
+        // handled already
+        assert!(deliver(&l1_handler(&message(0), 2, 1), &mut storage).is_err());
+        // the same payload sent again is another message
+        assert!(deliver(&l1_handler(&message(1), 2, 1), &mut storage)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn l1_handlers_must_be_sent_by_the_relayer() {
+        let mut storage = genesis();
+        assert!(deliver(&l1_handler(&message(0), 0, 0), &mut storage).is_err());
+
+        let unsigned = Transaction::l1_handler(message(0), SYSCALLS).unwrap();
+        assert!(deliver(&unsigned, &mut storage).is_err());
+
+        let (address, key) = message(0).slot();
+        assert_eq!(storage.read(&address, &key), Felt::new(0));
+    }
+}
@@ -0,0 +1,619 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use cairo_felt::Felt;
+use cairo_vm::types::program::Program;
+use color_eyre::Result;
+use tracing::{debug, info, warn};
+
+use crate::execution::{self, felt_to_hex};
+use crate::state::{self, TxStorage};
+use crate::transaction::{EntryPoint, Execution};
+
+/// Parsed programs keyed by class hash, so each program is parsed once instead of on every
+/// invocation. When a directory is configured, program artifacts are also kept on disk and
+/// parsed back into memory at startup. They're written to disk together at commit, see
+/// [`ProgramCache::flush`].
+///
+/// The results of recent executions of the programs can also be kept, see
+/// [`ProgramCache::with_execution_memo`].
+#[derive(Clone, Debug, Default)]
+pub struct ProgramCache {
+    programs: Arc<Mutex<HashMap<Felt, Arc<Program>>>>,
+    dir: Option<PathBuf>,
+    /// Artifacts of the programs parsed since the last flush, to be written to `dir`.
+    unsaved: Arc<Mutex<Vec<(Felt, String)>>>,
+    executions: Arc<Mutex<HashMap<Invocation, MemoizedExecution>>>,
+    /// Number of executions memoized, none by default.
+    execution_capacity: usize,
+}
+
+/// Function invoked by a transaction, and with which arguments.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Invocation {
+    pub class_hash: Felt,
+    pub function: EntryPoint,
+    pub calldata: Vec<Felt>,
+    pub enable_trace: bool,
+}
+
+/// Result of an execution, and the values of the storage slots it read.
+#[derive(Debug)]
+struct MemoizedExecution {
+    execution: Execution,
+    reads: Vec<((Felt, Felt), Felt)>,
+}
+
+impl ProgramCache {
+    pub fn new(dir: Option<PathBuf>) -> Result<Self> {
+        let mut programs = HashMap::new();
+
+        if let Some(dir) = &dir {
+            std::fs::create_dir_all(dir)?;
+
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                // artifacts are written to temporary files first, left over on a crash
+                if path
+                    .extension()
+                    .map_or(true, |extension| extension != "json")
+                {
+                    continue;
+                }
+                let program = std::fs::read_to_string(path)?;
+                let parsed = Program::from_reader(program.as_bytes(), None)?;
+                programs.insert(state::class_hash(&program), Arc::new(parsed));
+            }
+            info!("Loaded {} cached programs", programs.len());
+        }
+
+        Ok(Self {
+            programs: Arc::new(Mutex::new(programs)),
+            dir,
+            ..Default::default()
+        })
+    }
+
+    /// Memoizes the results of up to `capacity` distinct function executions, so identical
+    /// ones (e.g. repeated simulations, or copies of a benchmark transaction) aren't executed
+    /// again. Once full, the memoized executions are dropped.
+    pub fn with_execution_memo(mut self, capacity: usize) -> Self {
+        self.execution_capacity = capacity;
+        self
+    }
+
+    pub fn memoizes_executions(&self) -> bool {
+        self.execution_capacity > 0
+    }
+
+    /// Result of an identical earlier execution, if every storage slot it read still has the
+    /// same value in `storage`, which makes it the same execution. Classes don't need to be
+    /// checked: the program under a class hash never changes, and classes are never removed.
+    pub fn memoized(&self, invocation: &Invocation, storage: &TxStorage<'_>) -> Option<Execution> {
+        let executions = self.executions.lock().unwrap();
+        let memoized = executions.get(invocation)?;
+        memoized
+            .reads
+            .iter()
+            .all(|((address, key), value)| &storage.peek(address, key) == value)
+            .then(|| memoized.execution.clone())
+    }
+
+    /// Gas used by an identical earlier execution, whether or not it's still valid.
+    pub fn memoized_gas(&self, invocation: &Invocation) -> Option<u64> {
+        self.executions
+            .lock()
+            .unwrap()
+            .get(invocation)
+            .map(|memoized| memoized.execution.gas_used)
+    }
+
+    /// Memoizes the result of an execution against `storage`.
+    pub fn memoize(&self, invocation: Invocation, execution: &Execution, storage: &TxStorage<'_>) {
+        let reads = execution
+            .storage_reads
+            .iter()
+            .map(|(address, key)| ((address.clone(), key.clone()), storage.peek(address, key)))
+            .collect();
+
+        let mut executions = self.executions.lock().unwrap();
+        if executions.len() >= self.execution_capacity {
+            executions.clear();
+        }
+        executions.insert(
+            invocation,
+            MemoizedExecution {
+                execution: execution.clone(),
+                reads,
+            },
+        );
+    }
+
+    /// Returns the parsed program with the given class hash, if cached.
+    pub fn get(&self, class_hash: &Felt) -> Option<Arc<Program>> {
+        self.programs.lock().unwrap().get(class_hash).cloned()
+    }
+
+    /// Returns the parsed program with the given class hash, parsing `program` on a miss.
+    /// Programs seen for the first time must be compatible with the executor.
+    pub fn get_or_parse(&self, class_hash: &Felt, program: &str) -> Result<Arc<Program>> {
+        if let Some(parsed) = self.get(class_hash) {
+            return Ok(parsed);
+        }
+
+        debug!("Program cache miss for class {}", felt_to_hex(class_hash));
+        execution::ensure_compatible_artifact(program)?;
+        let parsed = Arc::new(Program::from_reader(program.as_bytes(), None)?);
+
+        if self.dir.is_some() {
+            self.unsaved
+                .lock()
+                .unwrap()
+                .push((class_hash.clone(), program.to_string()));
+        }
+
+        self.programs
+            .lock()
+            .unwrap()
+            .insert(class_hash.clone(), parsed.clone());
+        Ok(parsed)
+    }
+
+    /// Writes the artifacts of the programs parsed since the last flush to the cache
+    /// directory, if any. With `sync`, they're flushed to disk before returning, so they
+    /// survive a crash of the machine.
+    ///
+    /// Artifacts are written to a temporary file renamed once complete, so a crash never
+    /// leaves a truncated artifact behind.
+    pub fn flush(&self, sync: bool) -> Result<()> {
+        let unsaved = std::mem::take(&mut *self.unsaved.lock().unwrap());
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        let mut written = 0;
+        for (class_hash, program) in unsaved {
+            let path = dir.join(format!("{}.json", felt_to_hex(&class_hash)));
+            if path.exists() {
+                continue;
+            }
+
+            let temporary = path.with_extension("json.tmp");
+            let mut file = File::create(&temporary)?;
+            file.write_all(program.as_bytes())?;
+            if sync {
+                file.sync_all()?;
+            }
+            std::fs::rename(temporary, path)?;
+            written += 1;
+        }
+
+        // renames are only durable once the directory itself is synced
+        if sync && written > 0 {
+            File::open(dir)?.sync_all()?;
+        }
+        if written > 0 {
+            debug!("Wrote {written} programs to the program cache");
+        }
+        Ok(())
+    }
+}
+
+/// Path, data and height of a query.
+type QueryKey = (String, Bytes, u64);
+
+/// Responses of recent queries, so clients repeating the same queries don't execute them
+/// against storage every time. Queries are answered from the last committed state, so the
+/// cache is cleared at every commit. At most `capacity` responses are kept; queries aren't
+/// cached once it's full.
+#[derive(Clone, Debug, Default)]
+pub struct QueryCache {
+    responses: Arc<Mutex<QueryResponses>>,
+    capacity: usize,
+}
+
+#[derive(Debug, Default)]
+struct QueryResponses {
+    responses: HashMap<QueryKey, Bytes>,
+    /// Number of times the cache was cleared, so responses computed against state that has
+    /// been committed over since aren't cached.
+    generation: u64,
+}
+
+impl QueryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            responses: Default::default(),
+            capacity,
+        }
+    }
+
+    /// Returns the cached response of a query, or the generation of the cache to insert the
+    /// response with once computed.
+    pub fn get(&self, path: &str, data: &Bytes, height: u64) -> Result<Bytes, u64> {
+        let responses = self.responses.lock().unwrap();
+        responses
+            .responses
+            .get(&(path.to_string(), data.clone(), height))
+            .cloned()
+            .ok_or(responses.generation)
+    }
+
+    /// Caches the response of a query, unless the cache was cleared since `generation` or
+    /// is full.
+    pub fn insert(&self, path: &str, data: &Bytes, height: u64, generation: u64, value: Bytes) {
+        let mut responses = self.responses.lock().unwrap();
+        if responses.generation == generation && responses.responses.len() < self.capacity {
+            responses
+                .responses
+                .insert((path.to_string(), data.clone(), height), value);
+        }
+    }
+
+    /// Drops every cached response, once the state they were computed from has changed.
+    pub fn clear(&self) {
+        let mut responses = self.responses.lock().unwrap();
+        responses.responses.clear();
+        responses.generation += 1;
+    }
+}
+
+/// Number of blocks a transaction can't be included again for, once included.
+pub const SEEN_TRANSACTIONS_WINDOW: u64 = 1000;
+
+/// Digests (see [`Transaction::digest`](crate::transaction::Transaction::digest)) of the
+/// transactions included in the last [`SEEN_TRANSACTIONS_WINDOW`] blocks, so the same
+/// transaction can't be executed twice.
+///
+/// Every node rejects the same duplicates as long as it delivers the same blocks, since a
+/// transaction is only rejected for having been included in an earlier block of the window.
+/// When a data directory is configured, the digests of each block are appended to a file at
+/// commit and read back at startup, so transactions committed before a restart are rejected
+/// by CheckTx while the node catches up.
+#[derive(Clone, Debug, Default)]
+pub struct SeenTransactions {
+    seen: Arc<Mutex<Seen>>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    /// Height of the block each transaction was included in.
+    committed: HashMap<[u8; 32], u64>,
+    /// Transactions of the block being executed.
+    block: HashSet<[u8; 32]>,
+    /// Blocks appended to the file since it was last rewritten.
+    logged_blocks: u64,
+}
+
+/// Digests of the transactions included in a block, as appended to the file.
+type BlockDigests = (u64, Vec<[u8; 32]>);
+
+impl SeenTransactions {
+    /// Transactions seen by a node keeping its files in `data_dir`, read from its file if
+    /// any. The file is only used for CheckTx, so if it can't be read the node starts over
+    /// with a warning.
+    pub fn open(data_dir: &Path) -> Self {
+        let path = data_dir.join("abci.seen");
+        let seen = match Self::read(&path) {
+            Ok(seen) => seen,
+            Err(e) => {
+                warn!(
+                    "Error reading seen transactions from {}: {e}",
+                    path.display()
+                );
+                Default::default()
+            }
+        };
+        Self {
+            seen: Arc::new(Mutex::new(seen)),
+            path: Some(path),
+        }
+    }
+
+    fn read(path: &Path) -> Result<Seen> {
+        let mut seen = Seen::default();
+        let Ok(file) = File::open(path) else {
+            return Ok(seen);
+        };
+
+        // a record cut short by a crash ends the file
+        let mut reader = BufReader::new(file);
+        while let Ok((height, digests)) = bincode::deserialize_from::<_, BlockDigests>(&mut reader)
+        {
+            seen.committed
+                .extend(digests.into_iter().map(|digest| (digest, height)));
+            seen.logged_blocks += 1;
+        }
+        Ok(seen)
+    }
+
+    /// Whether the transaction was included in a recent block or the block being executed,
+    /// for CheckTx.
+    pub fn contains(&self, digest: &[u8; 32]) -> bool {
+        let seen = self.seen.lock().unwrap();
+        seen.committed.contains_key(digest) || seen.block.contains(digest)
+    }
+
+    /// Records a transaction delivered in the block at `height`, unless it's a duplicate of
+    /// one included earlier in the block or in the window, in which case it returns false.
+    pub fn deliver(&self, digest: [u8; 32], height: u64) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        let included = seen.committed.get(&digest).map_or(false, |&included| {
+            included < height && included + SEEN_TRANSACTIONS_WINDOW > height
+        });
+        !included && seen.block.insert(digest)
+    }
+
+    /// Records the transactions of the block committed at `height`, forgetting those that
+    /// left the window, and appends them to the file. With `sync`, the file is flushed to
+    /// disk before returning.
+    pub fn commit(&self, height: u64, sync: bool) -> Result<()> {
+        let mut seen = self.seen.lock().unwrap();
+        let block: Vec<_> = std::mem::take(&mut seen.block).into_iter().collect();
+        seen.committed
+            .extend(block.iter().map(|digest| (*digest, height)));
+        seen.committed
+            .retain(|_, included| *included + SEEN_TRANSACTIONS_WINDOW > height);
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if block.is_empty() {
+            return Ok(());
+        }
+        // blocks that left the window stay in the file, so it's rewritten once it holds
+        // twice as many blocks
+        seen.logged_blocks += 1;
+        if seen.logged_blocks > 2 * SEEN_TRANSACTIONS_WINDOW {
+            return self.rewrite(&mut seen, path, sync);
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(&bincode::serialize(&(height, block))?)?;
+        if sync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the file with the transactions in the window, atomically.
+    fn rewrite(&self, seen: &mut Seen, path: &Path, sync: bool) -> Result<()> {
+        let mut blocks: HashMap<u64, Vec<[u8; 32]>> = HashMap::new();
+        for (digest, height) in &seen.committed {
+            blocks.entry(*height).or_default().push(*digest);
+        }
+
+        let temporary = path.with_extension("seen.tmp");
+        let mut file = File::create(&temporary)?;
+        for block in &blocks {
+            file.write_all(&bincode::serialize(&block)?)?;
+        }
+        if sync {
+            file.sync_all()?;
+        }
+        std::fs::rename(temporary, path)?;
+        if sync {
+            if let Some(dir) = path.parent() {
+                File::open(dir)?.sync_all()?;
+            }
+        }
+
+        seen.logged_blocks = blocks.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::execution::ExecutionConfig;
+    use crate::state::{ClassRegistry, ContractStorage, StorageWrites};
+    use crate::transaction::TransactionType;
+
+    /// Example contract reading and writing its storage, see `examples/programs/syscalls.cairo`.
+    const PROGRAM: &str = include_str!("../../examples/programs/syscalls.json");
+
+    /// Empty directory, removed by the caller once done.
+    fn temporary_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("starkmint-cache-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Runs `read(key)` of the example contract against `storage`, memoized in `programs`.
+    fn read(programs: &ProgramCache, storage: &ContractStorage, key: u64) -> Execution {
+        TransactionType::FunctionExecution {
+            program: PROGRAM.into(),
+            function: EntryPoint::Name("read".to_string()),
+            program_name: "syscalls.json".to_string(),
+            enable_trace: false,
+            calldata: vec![Felt::new(key)],
+        }
+        .execute(
+            &ExecutionConfig::default(),
+            TxStorage::new(storage),
+            &ClassRegistry::default(),
+            programs,
+        )
+        .unwrap()
+    }
+
+    fn invocation(key: u64) -> Invocation {
+        Invocation {
+            class_hash: state::class_hash(PROGRAM),
+            function: EntryPoint::Name("read".to_string()),
+            calldata: vec![Felt::new(key)],
+            enable_trace: false,
+        }
+    }
+
+    #[test]
+    fn programs_are_parsed_once_and_kept_on_disk() {
+        let dir = temporary_dir();
+        let class_hash = state::class_hash(PROGRAM);
+
+        let programs = ProgramCache::new(Some(dir.clone())).unwrap();
+        assert!(programs.get(&class_hash).is_none());
+        let parsed = programs.get_or_parse(&class_hash, PROGRAM).unwrap();
+        assert!(Arc::ptr_eq(&parsed, &programs.get(&class_hash).unwrap()));
+
+        // artifacts are only written at flush, and read back at startup
+        assert!(ProgramCache::new(Some(dir.clone()))
+            .unwrap()
+            .get(&class_hash)
+            .is_none());
+        programs.flush(true).unwrap();
+        assert!(ProgramCache::new(Some(dir.clone()))
+            .unwrap()
+            .get(&class_hash)
+            .is_some());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn incompatible_programs_are_not_cached() {
+        let programs = ProgramCache::default();
+        let sierra = r#"{"sierra_program": []}"#;
+        let class_hash = state::class_hash(sierra);
+        assert!(programs.get_or_parse(&class_hash, sierra).is_err());
+        assert!(programs.get(&class_hash).is_none());
+    }
+
+    #[test]
+    fn memoized_executions_are_valid_until_a_slot_they_read_changes() {
+        let programs = ProgramCache::default().with_execution_memo(16);
+        let contract = state::class_hash(PROGRAM);
+        let mut storage = ContractStorage::default();
+
+        let execution = read(&programs, &storage, 5);
+        assert_eq!(
+            programs.memoized_gas(&invocation(5)),
+            Some(execution.gas_used)
+        );
+        assert!(programs
+            .memoized(&invocation(5), &TxStorage::new(&storage))
+            .is_some());
+        assert!(programs
+            .memoized(&invocation(6), &TxStorage::new(&storage))
+            .is_none());
+
+        // writes to other slots don't invalidate it
+        storage.apply(StorageWrites::from([(
+            (contract.clone(), Felt::new(6)),
+            Felt::new(1),
+        )]));
+        assert!(programs
+            .memoized(&invocation(5), &TxStorage::new(&storage))
+            .is_some());
+
+        storage.apply(StorageWrites::from([(
+            (contract, Felt::new(5)),
+            Felt::new(1),
+        )]));
+        assert!(programs
+            .memoized(&invocation(5), &TxStorage::new(&storage))
+            .is_none());
+        // the gas is still known, as an estimate
+        assert_eq!(
+            programs.memoized_gas(&invocation(5)),
+            Some(execution.gas_used)
+        );
+    }
+
+    #[test]
+    fn memoized_executions_are_dropped_once_full() {
+        let storage = ContractStorage::default();
+        let disabled = ProgramCache::default();
+        read(&disabled, &storage, 5);
+        assert!(disabled.memoized_gas(&invocation(5)).is_none());
+
+        let programs = ProgramCache::default().with_execution_memo(2);
+        for key in 1..=3 {
+            read(&programs, &storage, key);
+        }
+        assert!(programs.memoized_gas(&invocation(1)).is_none());
+        assert!(programs.memoized_gas(&invocation(3)).is_some());
+    }
+
+    #[test]
+    fn query_responses_are_cached_until_cleared() {
+        let cache = QueryCache::new(1);
+        let data = Bytes::from_static(b"data");
+        let response = Bytes::from_static(b"response");
+
+        let generation = cache.get("/class", &data, 1).unwrap_err();
+        cache.insert("/class", &data, 1, generation, response.clone());
+        assert_eq!(cache.get("/class", &data, 1), Ok(response.clone()));
+        assert!(cache.get("/class", &data, 2).is_err());
+
+        // full
+        cache.insert("/account", &data, 1, generation, response.clone());
+        assert!(cache.get("/account", &data, 1).is_err());
+
+        // responses computed before a commit aren't cached after it
+        let stale = cache.get("/account", &data, 1).unwrap_err();
+        cache.clear();
+        assert!(cache.get("/class", &data, 1).is_err());
+        cache.insert("/account", &data, 1, stale, response.clone());
+        assert!(cache.get("/account", &data, 1).is_err());
+        let generation = cache.get("/account", &data, 1).unwrap_err();
+        cache.insert("/account", &data, 1, generation, response.clone());
+        assert_eq!(cache.get("/account", &data, 1), Ok(response));
+    }
+
+    #[test]
+    fn seen_transactions_are_rejected_within_the_window() {
+        let seen = SeenTransactions::default();
+        let digest = [1; 32];
+
+        assert!(seen.deliver(digest, 1));
+        assert!(seen.contains(&digest));
+        assert!(!seen.deliver(digest, 1));
+        seen.commit(1, false).unwrap();
+
+        assert!(!seen.deliver(digest, 2));
+        assert!(!seen.deliver(digest, SEEN_TRANSACTIONS_WINDOW));
+        seen.commit(SEEN_TRANSACTIONS_WINDOW, false).unwrap();
+        assert!(seen.deliver(digest, SEEN_TRANSACTIONS_WINDOW + 1));
+    }
+
+    #[test]
+    fn seen_transactions_are_read_back_after_a_restart() {
+        let dir = temporary_dir();
+        let (first, second) = ([1; 32], [2; 32]);
+
+        let seen = SeenTransactions::open(&dir);
+        assert!(seen.deliver(first, 1));
+        seen.commit(1, true).unwrap();
+        assert!(seen.deliver(second, 2));
+        seen.commit(2, true).unwrap();
+
+        let reopened = SeenTransactions::open(&dir);
+        assert!(reopened.contains(&first));
+        assert!(reopened.contains(&second));
+        assert!(!reopened.deliver(first, 3));
+
+        // a record cut short by a crash is skipped
+        let path = dir.join("abci.seen");
+        let length = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(length - 1)
+            .unwrap();
+        let truncated = SeenTransactions::open(&dir);
+        assert!(truncated.contains(&first));
+        assert!(!truncated.contains(&second));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
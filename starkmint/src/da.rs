@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use cairo_felt::Felt;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::state::StorageWrites;
+
+/// zstd level state diffs are compressed with.
+const COMPRESSION_LEVEL: i32 = 19;
+
+/// Storage writes committed by a block, published to a data availability layer so the
+/// chain's state can be rebuilt without its nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDiff {
+    pub height: u64,
+    pub writes: StorageWrites,
+}
+
+impl StateDiff {
+    /// Blob published for the diff, and its commitment, recorded in the state of the next
+    /// block (see [`da_commitment_slot`](crate::state::da_commitment_slot)).
+    ///
+    /// The commitment is the sha256 of the bincode-encoded diff, truncated to 250 bits so it
+    /// fits in a felt. It doesn't depend on the compression, so every node records the same
+    /// one; the blob is the encoded diff compressed with zstd.
+    pub fn blob(&self) -> Result<DataBlob> {
+        let encoded = bincode::serialize(self)?;
+        let mut digest = Sha256::digest(&encoded);
+        digest[0] &= 0x03;
+
+        Ok(DataBlob {
+            height: self.height,
+            commitment: Felt::from_bytes_be(&digest),
+            data: Arc::new(zstd::encode_all(encoded.as_slice(), COMPRESSION_LEVEL)?),
+        })
+    }
+
+    /// Decodes a published blob.
+    pub fn from_blob(data: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(&zstd::decode_all(data)?)?)
+    }
+}
+
+/// Compressed state diff of a committed block, to be published.
+#[derive(Debug, Clone)]
+pub struct DataBlob {
+    pub height: u64,
+    pub commitment: Felt,
+    pub data: Arc<Vec<u8>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(value: u64) -> StateDiff {
+        StateDiff {
+            height: 3,
+            writes: StorageWrites::from([((Felt::new(1), Felt::new(2)), Felt::new(value))]),
+        }
+    }
+
+    #[test]
+    fn blobs_decode_to_their_diff() {
+        let blob = diff(4).blob().unwrap();
+        assert_eq!(blob.height, 3);
+        assert_eq!(StateDiff::from_blob(&blob.data).unwrap(), diff(4));
+        assert!(StateDiff::from_blob(b"not zstd").is_err());
+    }
+
+    #[test]
+    fn commitments_are_the_truncated_hash_of_the_encoded_diff() {
+        let blob = diff(4).blob().unwrap();
+        let mut digest = Sha256::digest(bincode::serialize(&diff(4)).unwrap());
+        digest[0] &= 0x03;
+        assert_eq!(blob.commitment, Felt::from_bytes_be(&digest));
+        assert_eq!(diff(4).blob().unwrap().commitment, blob.commitment);
+        assert_ne!(diff(5).blob().unwrap().commitment, blob.commitment);
+    }
+}
@@ -0,0 +1,561 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+use cairo_felt::Felt;
+use cairo_vm::hint_processor::hint_processor_definition::HintProcessor;
+use cairo_vm::types::program::Program;
+use cairo_vm::types::relocatable::MaybeRelocatable;
+use cairo_vm::vm::errors::vm_errors::VirtualMachineError;
+use cairo_vm::vm::runners::cairo_runner::{CairoRunner, ExecutionResources};
+use cairo_vm::vm::vm_core::VirtualMachine;
+use clap::ValueEnum;
+use color_eyre::eyre::{bail, ensure, eyre, ContextCompat};
+use color_eyre::Result;
+use num_traits::{Num, Zero};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::proof::ProofVerifier;
+use crate::syscalls::SYSCALL_HINTS;
+use crate::transaction::EntryPoint;
+
+/// Cairo builtins that can be made available to executed programs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Builtin {
+    #[value(name = "output")]
+    Output,
+    #[value(name = "pedersen")]
+    Pedersen,
+    #[value(name = "range_check")]
+    RangeCheck,
+    #[value(name = "ecdsa")]
+    Ecdsa,
+    #[value(name = "bitwise")]
+    Bitwise,
+    #[value(name = "ec_op")]
+    EcOp,
+    #[value(name = "poseidon")]
+    Poseidon,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 7] = [
+        Builtin::Output,
+        Builtin::Pedersen,
+        Builtin::RangeCheck,
+        Builtin::Ecdsa,
+        Builtin::Bitwise,
+        Builtin::EcOp,
+        Builtin::Poseidon,
+    ];
+
+    /// Name of the builtin as it appears in a compiled program's `builtins` list.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Output => "output",
+            Builtin::Pedersen => "pedersen",
+            Builtin::RangeCheck => "range_check",
+            Builtin::Ecdsa => "ecdsa",
+            Builtin::Bitwise => "bitwise",
+            Builtin::EcOp => "ec_op",
+            Builtin::Poseidon => "poseidon",
+        }
+    }
+
+    /// Gas charged per instance of the builtin used by an execution.
+    pub fn gas_cost(&self) -> u64 {
+        match self {
+            Builtin::Output => 0,
+            Builtin::Pedersen => 32,
+            Builtin::RangeCheck => 16,
+            Builtin::Ecdsa => 2048,
+            Builtin::Bitwise => 64,
+            Builtin::EcOp => 1024,
+            Builtin::Poseidon => 32,
+        }
+    }
+}
+
+impl fmt::Display for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Hint codes of the Cairo common library audited to be deterministic: those of `alloc`,
+/// `memcpy`, `pow` and the `math` module, which only compute values from their arguments.
+/// Listed in `audited_hints.json`, in the format of the node's `--hint-allowlist` files.
+pub static AUDITED_HINTS: Lazy<HashSet<String>> = Lazy::new(|| {
+    serde_json::from_str(include_str!("audited_hints.json"))
+        .expect("the audited hints are a JSON list of hint codes")
+});
+
+/// Which hints a program may contain to be executed during consensus, in DeliverTx as well
+/// as in CheckTx.
+#[derive(Clone, Debug)]
+pub enum HintPolicy {
+    /// Any hint the hint processor implements. Some aren't deterministic and could make
+    /// validators diverge, so this is only meant for devnets.
+    Builtin,
+    /// Only syscall hints, the [`AUDITED_HINTS`] and the listed hint codes. Programs
+    /// containing any other hint are rejected before they run, so they can't make validators
+    /// diverge.
+    Allowlist(HashSet<String>),
+}
+
+impl Default for HintPolicy {
+    /// Only syscall hints and the audited hints.
+    fn default() -> Self {
+        HintPolicy::Allowlist(HashSet::new())
+    }
+}
+
+impl HintPolicy {
+    pub fn allows(&self, code: &str) -> bool {
+        match self {
+            HintPolicy::Builtin => true,
+            HintPolicy::Allowlist(allowed) => {
+                SYSCALL_HINTS.contains(&code)
+                    || AUDITED_HINTS.contains(code)
+                    || allowed.contains(code)
+            }
+        }
+    }
+}
+
+/// Node-level settings applied to every Cairo execution.
+#[derive(Clone, Debug)]
+pub struct ExecutionConfig {
+    /// Builtins programs are allowed to use.
+    pub builtins: Vec<Builtin>,
+    /// Hints programs are allowed to contain.
+    pub hint_policy: HintPolicy,
+    /// Price of a unit of gas, used to estimate transaction fees.
+    pub gas_price: u64,
+    /// Account credited with the fees paid by senders, which are burned if there's none.
+    pub fee_recipient: Option<Felt>,
+    /// Whether executions log what happens in the VM: the segments programs are loaded
+    /// into, the steps they run and the syscalls they invoke.
+    pub trace_execution: bool,
+    /// Steps an execution may run, including those of the calls it makes, if bounded.
+    /// Bounded executions fail with [`StepLimitExceeded`] once they've run them all, and
+    /// aren't memoized, as an execution memoized by one node could exceed the bound on another.
+    pub max_steps: Option<usize>,
+    /// Gas a transaction may use, a chain parameter set at genesis. As a step costs a unit of
+    /// gas, executions are bounded to as many steps, see [`Self::step_limit`], and DeliverTx
+    /// fails transactions using more gas, so over-budget transactions fail on every node.
+    pub max_tx_gas: Option<u64>,
+    /// Verifier of the proofs of `ProofVerification` transactions, which fail if there's none.
+    pub proof_verifier: Option<Arc<dyn ProofVerifier>>,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            builtins: Builtin::ALL.to_vec(),
+            hint_policy: HintPolicy::default(),
+            gas_price: 1,
+            fee_recipient: None,
+            trace_execution: false,
+            max_steps: None,
+            max_tx_gas: None,
+            proof_verifier: None,
+        }
+    }
+}
+
+impl ExecutionConfig {
+    /// Steps an execution may run: `max_steps` if set, or else as many as the gas of
+    /// `max_tx_gas`.
+    pub fn step_limit(&self) -> Option<usize> {
+        self.max_steps.or_else(|| {
+            self.max_tx_gas
+                .map(|gas| usize::try_from(gas).unwrap_or(usize::MAX))
+        })
+    }
+
+    /// Fails if the program requires a builtin that is not enabled on this node.
+    pub fn ensure_builtins_supported(&self, program: &Program) -> Result<()> {
+        let unsupported: Vec<&str> = program
+            .builtins
+            .iter()
+            .map(String::as_str)
+            .filter(|name| !self.builtins.iter().any(|b| b.name() == *name))
+            .collect();
+
+        if !unsupported.is_empty() {
+            bail!(
+                "Program requires unsupported builtins: {}",
+                unsupported.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Fails if the program contains a hint that the hint policy doesn't allow.
+    pub fn ensure_hints_allowed(&self, program: &Program) -> Result<()> {
+        let disallowed = program
+            .hints
+            .values()
+            .flatten()
+            .find(|hint| !self.hint_policy.allows(&hint.code));
+
+        if let Some(hint) = disallowed {
+            bail!("Program contains a disallowed hint: {}", hint.code);
+        }
+
+        Ok(())
+    }
+
+    /// Runs every static check a program must pass before being executed.
+    pub fn ensure_program_supported(&self, program: &Program) -> Result<()> {
+        self.ensure_builtins_supported(program)?;
+        self.ensure_hints_allowed(program)
+    }
+}
+
+/// Field prime of the programs the executor can run.
+pub const CAIRO_PRIME: &str = "0x800000000000011000000000000000000000000000000000000000000000001";
+
+/// Major version of the Cairo compiler whose output the executor can run.
+pub const SUPPORTED_COMPILER_MAJOR: u64 = 0;
+
+/// Fails if a compiled program artifact was not produced by a compiler the executor supports,
+/// so it is rejected when first submitted rather than failing once executed.
+pub fn ensure_compatible_artifact(program: &str) -> Result<()> {
+    let artifact: serde_json::Value =
+        serde_json::from_str(program).map_err(|e| eyre!("Program is not valid JSON: {e}"))?;
+
+    ensure!(
+        artifact.get("sierra_program").is_none(),
+        "Sierra (Cairo 1) classes are not supported, only Cairo 0 compiled programs"
+    );
+
+    let prime = artifact.get("prime").and_then(|prime| prime.as_str());
+    ensure!(
+        prime == Some(CAIRO_PRIME),
+        "Unsupported program prime: {}",
+        prime.unwrap_or("missing")
+    );
+
+    // programs compiled before the version was recorded are assumed to be Cairo 0
+    if let Some(version) = artifact.get("compiler_version").and_then(|v| v.as_str()) {
+        let major = version
+            .split('.')
+            .next()
+            .and_then(|major| major.parse::<u64>().ok());
+        ensure!(
+            major == Some(SUPPORTED_COMPILER_MAJOR),
+            "Unsupported compiler version {version}, expected {SUPPORTED_COMPILER_MAJOR}.x"
+        );
+    }
+
+    Ok(())
+}
+
+/// Steps run between two checks of the step budget of a bounded execution.
+const STEP_CHUNK: usize = 10_000;
+
+/// Error of an execution that ran more steps than [`ExecutionConfig::step_limit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepLimitExceeded(pub usize);
+
+impl fmt::Display for StepLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Execution exceeded the limit of {} steps", self.0)
+    }
+}
+
+impl std::error::Error for StepLimitExceeded {}
+
+/// Steps left to a bounded execution, shared by the runs of the function it executes and of
+/// the calls it makes.
+#[derive(Debug)]
+pub struct StepBudget {
+    max_steps: usize,
+    left: Cell<usize>,
+}
+
+impl StepBudget {
+    pub fn new(max_steps: usize) -> Self {
+        Self {
+            max_steps,
+            left: Cell::new(max_steps),
+        }
+    }
+
+    /// Whether every step was used, in which case the execution failed for lack of steps.
+    pub fn exhausted(&self) -> bool {
+        self.left.get() == 0
+    }
+
+    pub fn error(&self) -> StepLimitExceeded {
+        StepLimitExceeded(self.max_steps)
+    }
+
+    fn spend(&self, steps: usize) {
+        self.left.set(self.left.get().saturating_sub(steps));
+    }
+}
+
+/// Runs the function at `entrypoint` with the given stack of arguments, as
+/// `CairoRunner::run_from_entrypoint` does, but by chunks of steps taken from `budget`, which
+/// is shared with the calls the function makes. Fails once the budget is used up, with the
+/// function still running.
+pub fn run_bounded(
+    runner: &mut CairoRunner,
+    entrypoint: usize,
+    args: Vec<MaybeRelocatable>,
+    budget: &StepBudget,
+    vm: &mut VirtualMachine,
+    hint_processor: &mut dyn HintProcessor,
+) -> Result<()> {
+    let end =
+        runner.initialize_function_entrypoint(vm, entrypoint, args, MaybeRelocatable::from(0))?;
+    runner.initialize_vm(vm)?;
+
+    // calls made by the function spend the budget as well, so it's checked after each chunk
+    loop {
+        let steps = budget.left.get().min(STEP_CHUNK);
+        match runner.run_for_steps(steps, vm, hint_processor) {
+            // the function returned before the chunk was over
+            Err(VirtualMachineError::EndOfProgram(remaining)) => {
+                budget.spend(steps - remaining);
+                break;
+            }
+            Ok(()) => {
+                budget.spend(steps);
+                if *vm.get_pc() == end {
+                    break;
+                }
+                if budget.exhausted() {
+                    return Err(budget.error().into());
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    runner.end_run(true, false, vm, hint_processor)?;
+    Ok(())
+}
+
+/// Gas consumed by a run: one unit per step plus the cost of every builtin instance used.
+pub fn gas_cost(resources: &ExecutionResources) -> u64 {
+    let builtins: u64 = resources
+        .builtin_instance_counter
+        .iter()
+        .map(|(name, count)| {
+            let name = name.strip_suffix("_builtin").unwrap_or(name);
+            let cost = Builtin::ALL
+                .iter()
+                .find(|builtin| builtin.name() == name)
+                .map_or(0, Builtin::gas_cost);
+            cost * *count as u64
+        })
+        .sum();
+
+    resources.n_steps as u64 + builtins
+}
+
+/// Cheap estimate of the gas used by invoking a program with `calldata`, without running it:
+/// a step per word of the program's bytecode and per calldata value, and an instance of each
+/// builtin it uses. Programs that loop or call other contracts use more.
+pub fn estimate_gas(program: &Program, calldata: &[Felt]) -> u64 {
+    let builtins: u64 = program
+        .builtins
+        .iter()
+        .filter_map(|name| Builtin::ALL.iter().find(|builtin| builtin.name() == *name))
+        .map(Builtin::gas_cost)
+        .sum();
+
+    (program.data.len() + calldata.len()) as u64 + builtins
+}
+
+/// Steps and builtin instances used by an execution, including the contracts it called.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resources {
+    pub steps: u64,
+    /// Instances used of each builtin, by builtin name.
+    pub builtins: BTreeMap<String, u64>,
+}
+
+impl Resources {
+    /// Adds the resources used by a run.
+    pub fn add_run(&mut self, resources: &ExecutionResources) {
+        self.steps += resources.n_steps as u64;
+        for (name, count) in &resources.builtin_instance_counter {
+            let name = name.strip_suffix("_builtin").unwrap_or(name);
+            *self.builtins.entry(name.to_string()).or_default() += *count as u64;
+        }
+    }
+
+    /// Adds the resources used by another execution, e.g. a nested call.
+    pub fn add(&mut self, other: &Resources) {
+        self.steps += other.steps;
+        for (name, count) in &other.builtins {
+            *self.builtins.entry(name.clone()).or_default() += count;
+        }
+    }
+}
+
+/// Estimated cost of a transaction, as returned by the `/estimate_fee` query.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    pub gas: u64,
+    pub fee: u64,
+}
+
+/// Would-be receipt of a transaction, as returned by the `/simulate` query.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Simulation {
+    pub transaction_hash: String,
+    pub retdata: Vec<Felt>,
+    pub events: Vec<ContractEvent>,
+    pub gas: u64,
+    pub fee: u64,
+}
+
+/// Outcome of a transaction applied in a committed block, as published to subscribers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub transaction_hash: String,
+    pub height: u64,
+    /// Account that sent the transaction, if it was signed.
+    pub sender: Option<Felt>,
+    /// Invoked function, for function executions.
+    pub function: Option<String>,
+    pub retdata: Vec<Felt>,
+    pub events: Vec<ContractEvent>,
+    pub gas: u64,
+    pub fee: u64,
+}
+
+/// Event emitted by a contract through the `emit_event` syscall.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractEvent {
+    pub from_address: Felt,
+    pub keys: Vec<Felt>,
+    pub data: Vec<Felt>,
+}
+
+/// Event recording the fee and tip paid by the payer of a transaction, emitted by the chain
+/// itself (from address zero) like the `Transfer` events of Starknet's fee token: its key is
+/// the selector of `Transfer` and its data the payer, the recipient (zero if the fee is burned)
+/// and the amount.
+pub fn fee_transfer_event(sender: &Felt, recipient: Option<&Felt>, amount: u64) -> ContractEvent {
+    ContractEvent {
+        from_address: Felt::zero(),
+        keys: vec![entry_point_selector("Transfer")],
+        data: vec![
+            sender.clone(),
+            recipient.cloned().unwrap_or_else(Felt::zero),
+            Felt::new(amount),
+        ],
+    }
+}
+
+/// `0x`-prefixed hex representation of a felt.
+pub fn felt_to_hex(felt: &Felt) -> String {
+    format!("0x{}", felt.to_str_radix(16))
+}
+
+/// Felt as a 32-byte big-endian word.
+pub fn felt_to_word(felt: &Felt) -> [u8; 32] {
+    let bytes = felt.to_bytes_be();
+    let mut word = [0; 32];
+    word[32 - bytes.len()..].copy_from_slice(&bytes);
+    word
+}
+
+/// Parses a felt given in decimal or as `0x`-prefixed hex.
+pub fn parse_felt(value: &str) -> Result<Felt> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => Felt::from_str_radix(hex, 16),
+        None => Felt::from_str_radix(value, 10),
+    };
+    parsed.map_err(|_| eyre!("Invalid felt: {value}"))
+}
+
+/// Starknet entry point selector of a function: its keccak256 truncated to 250 bits.
+pub fn entry_point_selector(function: &str) -> Felt {
+    let mut digest = Keccak256::digest(function.as_bytes());
+    digest[0] &= 0x03;
+    Felt::from_bytes_be(&digest)
+}
+
+/// Finds the `__main__` function whose selector matches, returning its name and pc.
+pub fn find_entrypoint(program: &Program, selector: &Felt) -> Option<(String, usize)> {
+    program.identifiers.iter().find_map(|(name, identifier)| {
+        let function = name.strip_prefix("__main__.")?;
+        let pc = identifier.pc?;
+
+        (identifier.type_.as_deref() == Some("function")
+            && entry_point_selector(function) == *selector)
+            .then(|| (function.to_string(), pc))
+    })
+}
+
+/// Pc of a `__main__` function.
+pub fn entrypoint_pc(program: &Program, function: &str) -> Result<usize> {
+    program
+        .identifiers
+        .get(&format!("__main__.{function}"))
+        .filter(|identifier| identifier.type_.as_deref() == Some("function"))
+        .and_then(|identifier| identifier.pc)
+        .with_context(|| format!("Entry point {function} not found in program"))
+}
+
+/// Resolves an entry point given by name or selector against the program's functions,
+/// returning the function name and its pc.
+pub fn resolve_entrypoint(program: &Program, entry_point: &EntryPoint) -> Result<(String, usize)> {
+    match entry_point {
+        EntryPoint::Name(function) => {
+            entrypoint_pc(program, function).map(|pc| (function.clone(), pc))
+        }
+        EntryPoint::Selector(selector) => find_entrypoint(program, selector)
+            .with_context(|| format!("No entry point with selector {}", felt_to_hex(selector))),
+    }
+}
+
+/// Number of explicit arguments of a `__main__` function, according to its `Args` struct.
+pub fn args_size(program: &Program, function: &str) -> usize {
+    struct_size(program, &format!("__main__.{function}.Args"))
+}
+
+/// Number of values returned by a `__main__` function, according to its `Return` struct.
+pub fn return_size(program: &Program, function: &str) -> usize {
+    struct_size(program, &format!("__main__.{function}.Return"))
+}
+
+fn struct_size(program: &Program, name: &str) -> usize {
+    program
+        .identifiers
+        .get(name)
+        .and_then(|identifier| identifier.members.as_ref())
+        .map_or(0, |members| members.len())
+}
+
+/// Checks that an invocation can run without executing it: the entry point exists and the
+/// calldata matches its arguments. Returns the resolved function name and pc.
+pub fn validate_invocation(
+    program: &Program,
+    entry_point: &EntryPoint,
+    calldata: &[Felt],
+) -> Result<(String, usize)> {
+    let (function, pc) = resolve_entrypoint(program, entry_point)?;
+
+    let expected = args_size(program, &function);
+    ensure!(
+        calldata.len() == expected,
+        "Function {function} takes {expected} arguments, got {}",
+        calldata.len()
+    );
+
+    Ok((function, pc))
+}
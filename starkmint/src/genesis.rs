@@ -0,0 +1,156 @@
+use cairo_felt::Felt;
+use color_eyre::eyre::{ensure, WrapErr};
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::execution::{self, felt_to_hex, ExecutionConfig};
+use crate::signature;
+use crate::state::{self, ClassRegistry, ContractStorage, StorageWrites};
+
+/// Initial state of the chain, given as the `app_state` of the Tendermint genesis file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Genesis {
+    /// Accounts funded at genesis.
+    #[serde(default)]
+    pub accounts: Vec<GenesisAccount>,
+    /// Compiled programs declared at genesis, so they can be called before being executed.
+    #[serde(default)]
+    pub classes: Vec<String>,
+    /// Chain parameters, which every node must be configured with.
+    #[serde(default)]
+    pub parameters: GenesisParameters,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisAccount {
+    pub address: Felt,
+    pub balance: Felt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisParameters {
+    /// Fee charged per unit of gas.
+    pub gas_price: u64,
+    /// Contract whose storage holds the chain parameters settable by governance, such as the
+    /// height to halt at (see [`halt_height_key`](crate::state::halt_height_key)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub governance: Option<Felt>,
+    /// Account credited with the fees paid by senders. They're burned if there's none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_recipient: Option<Felt>,
+    /// Whether the state diff of every block is published to a data availability layer, its
+    /// commitment being recorded in the state of the next block.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub data_availability: bool,
+    /// Public key of the relayer injecting the messages sent from L1 as L1 handler
+    /// transactions, the only account allowed to send them. L1 messages can't be handled if
+    /// there's none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub l1_relayer: Option<Felt>,
+    /// Gas a transaction may use. Executions are bounded to as many steps, and transactions
+    /// using more fail, see [`ExecutionConfig::max_tx_gas`]. Unlimited if there's none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tx_gas: Option<u64>,
+    /// Whether function and class executions may still be sent unsigned on a chain funding
+    /// accounts, executed on behalf of no account. Meant for devnets: otherwise, once accounts
+    /// are funded, every execution must be signed and pay for its gas. Chains without funded
+    /// accounts accept unsigned executions either way.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unsigned_executions: bool,
+}
+
+impl Default for GenesisParameters {
+    fn default() -> Self {
+        Self {
+            gas_price: 1,
+            governance: None,
+            fee_recipient: None,
+            data_availability: false,
+            l1_relayer: None,
+            max_tx_gas: None,
+            unsigned_executions: false,
+        }
+    }
+}
+
+impl Genesis {
+    /// Parses the `app_state` of the genesis file. An empty one is an empty genesis.
+    pub fn from_app_state(app_state: &[u8]) -> Result<Self> {
+        if app_state.is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_slice(app_state).wrap_err("Invalid genesis app_state")
+    }
+
+    /// Funds the accounts, registers the classes and records the chain ID, the L1 relayer and
+    /// whether unsigned executions are rejected. Balances are staged in `storage` like the
+    /// writes of a block, to be committed with it.
+    pub fn apply(
+        &self,
+        chain_id: &str,
+        storage: &mut ContractStorage,
+        classes: &mut ClassRegistry,
+    ) -> Result<()> {
+        let slot = state::chain_id_slot();
+        storage.apply(StorageWrites::from([(
+            slot,
+            signature::chain_id_hash(chain_id),
+        )]));
+        for account in &self.accounts {
+            storage.set_balance(&account.address, account.balance.clone());
+        }
+        for program in &self.classes {
+            execution::ensure_compatible_artifact(program)?;
+            classes.register(program);
+        }
+        if let Some(relayer) = &self.parameters.l1_relayer {
+            let slot = state::l1_relayer_slot();
+            storage.apply(StorageWrites::from([(slot, relayer.clone())]));
+        }
+        if self.signatures_required() {
+            let slot = state::signatures_required_slot();
+            storage.apply(StorageWrites::from([(slot, Felt::new(1))]));
+        }
+        Ok(())
+    }
+
+    /// Whether function and class executions must be signed: the chain funds accounts and
+    /// isn't a devnet accepting unsigned executions.
+    pub fn signatures_required(&self) -> bool {
+        !self.accounts.is_empty() && !self.parameters.unsigned_executions
+    }
+
+    /// Fails unless the node runs with the chain's parameters.
+    pub fn ensure_parameters(&self, config: &ExecutionConfig) -> Result<()> {
+        ensure!(
+            self.parameters.gas_price == config.gas_price,
+            "The genesis gas price is {} but the node runs with {}",
+            self.parameters.gas_price,
+            config.gas_price
+        );
+        ensure!(
+            self.parameters.fee_recipient == config.fee_recipient,
+            "The genesis fee recipient is {} but the node runs with {}",
+            fee_recipient(self.parameters.fee_recipient.as_ref()),
+            fee_recipient(config.fee_recipient.as_ref())
+        );
+        ensure!(
+            self.parameters.max_tx_gas == config.max_tx_gas,
+            "The genesis max_tx_gas is {} but the node runs with {}",
+            max_tx_gas(self.parameters.max_tx_gas),
+            max_tx_gas(config.max_tx_gas)
+        );
+        Ok(())
+    }
+}
+
+fn fee_recipient(recipient: Option<&Felt>) -> String {
+    recipient.map_or_else(|| "none".to_string(), felt_to_hex)
+}
+
+fn max_tx_gas(limit: Option<u64>) -> String {
+    limit.map_or_else(|| "unlimited".to_string(), |limit| limit.to_string())
+}
@@ -1,2 +1,20 @@
+pub mod account;
 pub mod app;
+pub mod archive;
+pub mod audit;
+pub mod block;
+pub mod cache;
+pub mod da;
+pub mod execution;
+pub mod genesis;
+pub mod local;
+pub mod messaging;
+pub mod metrics;
+pub mod profiler;
+pub mod proof;
+pub mod signature;
+pub mod state;
+pub mod syscalls;
+#[cfg(feature = "testkit")]
+pub mod testkit;
 pub mod transaction;
@@ -0,0 +1,242 @@
+//! In-process chain: a [`LocalChain`] drives a [`StarknetApp`] with the InitChain, BeginBlock,
+//! DeliverTx, EndBlock and Commit requests Tendermint would send, so whole blocks can be
+//! executed without running a Tendermint node, e.g. to replay archived blocks. The test
+//! harness of the `testkit` feature is built on it.
+
+use bytes::Bytes;
+use futures::future::join_all;
+use tendermint::abci::request::{self, Request};
+use tendermint::abci::types::CommitInfo;
+use tendermint::abci::{response, Response};
+use tendermint::block::{self, header::Version, Header, Height};
+use tendermint::consensus::{self, params::ValidatorParams};
+use tendermint::{account, evidence, public_key, AppHash, Hash, Time};
+use tokio::runtime::Runtime;
+use tower::Service;
+use tower_abci::BoxError;
+
+use crate::app::StarknetApp;
+use crate::genesis::Genesis;
+
+/// Chain run by a single app, which plays the part of Tendermint: blocks are made of the
+/// transactions they're given, in order, and committed right away. The app keeps its data in
+/// the data dir it was configured with.
+///
+/// Requests go through the app's [`Service`] implementation like those of the ABCI server, so
+/// apps configured for parallel execution are driven as they would be by Tendermint. Methods
+/// panic if the app fails to handle a request, as it would crash a node.
+pub struct LocalChain {
+    app: StarknetApp,
+    runtime: Runtime,
+    chain_id: String,
+    /// Genesis the chain was initialized with, as the `app_state` of InitChain.
+    app_state: Bytes,
+    /// Height of the last committed block, 0 before the first one.
+    height: u64,
+    /// App hash of the last committed block, empty before the first one.
+    app_hash: Vec<u8>,
+}
+
+/// Outcome of a block executed by a [`LocalChain`].
+#[derive(Debug, Clone)]
+pub struct BlockResult {
+    pub height: u64,
+    /// Responses to the DeliverTx of the block's transactions, in order.
+    pub deliveries: Vec<response::DeliverTx>,
+    /// App hash the block was committed with.
+    pub app_hash: Vec<u8>,
+}
+
+impl BlockResult {
+    /// Panics unless every transaction of the block was delivered successfully.
+    pub fn assert_delivered(&self) {
+        for (index, delivery) in self.deliveries.iter().enumerate() {
+            assert!(
+                delivery.code.is_ok(),
+                "Transaction {index} of block {} failed with code {}: {}",
+                self.height,
+                delivery.code.value(),
+                delivery.log
+            );
+        }
+    }
+}
+
+impl LocalChain {
+    /// Chain `chain_id` initialized with `genesis`, run by `app`. Fails if the app refuses to
+    /// initialize the chain, e.g. as its data dir holds another one.
+    pub fn new(app: StarknetApp, chain_id: String, genesis: &Genesis) -> Result<Self, BoxError> {
+        let mut chain = Self {
+            app,
+            runtime: Runtime::new()?,
+            chain_id,
+            app_state: serde_json::to_vec(genesis)?.into(),
+            height: 0,
+            app_hash: Vec::new(),
+        };
+        chain.init_chain()?;
+        Ok(chain)
+    }
+
+    /// Replaces the app running the chain, then initializes the chain again as Tendermint does
+    /// when a node restarts. The blocks committed before must be executed again.
+    pub fn restart(&mut self, app: StarknetApp) -> Result<(), BoxError> {
+        self.app = app;
+        self.height = 0;
+        self.app_hash = Vec::new();
+        self.init_chain()
+    }
+
+    /// App run by the chain, whose committed state can be read directly.
+    pub fn app(&self) -> &StarknetApp {
+        &self.app
+    }
+
+    /// ID of the chain, as sent in InitChain and the blocks' headers.
+    pub fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+
+    /// Height of the last committed block, 0 before the first one.
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// App hash of the last committed block, empty before the first one.
+    pub fn app_hash(&self) -> &[u8] {
+        &self.app_hash
+    }
+
+    /// Executes and commits a block made of `txs` as they would be included by Tendermint,
+    /// i.e. without being checked first.
+    pub fn execute_block(&mut self, txs: Vec<Bytes>) -> BlockResult {
+        let height = self.height + 1;
+        self.begin_block(height);
+
+        // in parallel execution mode, DeliverTx responses are only sent once EndBlock is
+        // handled, so they're awaited after it
+        let deliveries: Vec<_> = txs
+            .into_iter()
+            .map(|tx| self.app.call(Request::DeliverTx(request::DeliverTx { tx })))
+            .collect();
+        let end_block = self.app.call(Request::EndBlock(request::EndBlock {
+            height: height as i64,
+        }));
+        let deliveries = self.runtime.block_on(async {
+            let deliveries = join_all(deliveries).await;
+            end_block.await.expect("the app must handle EndBlock");
+            deliveries
+        });
+        let deliveries = deliveries
+            .into_iter()
+            .map(
+                |response| match response.expect("the app must handle DeliverTx") {
+                    Response::DeliverTx(response) => response,
+                    response => panic!("Unexpected response to DeliverTx: {response:?}"),
+                },
+            )
+            .collect();
+
+        let app_hash = self.commit();
+        BlockResult {
+            height,
+            deliveries,
+            app_hash,
+        }
+    }
+
+    /// Sends a request to the app and waits for its response.
+    pub fn call(&mut self, request: Request) -> Response {
+        let response = self.app.call(request);
+        self.runtime
+            .block_on(response)
+            .expect("the app must handle the request")
+    }
+
+    /// Sends requests to the app without waiting for the previous responses, as Tendermint
+    /// does for CheckTx, so the app handles them concurrently. Returns the responses in order.
+    pub fn call_concurrently(&mut self, requests: Vec<Request>) -> Vec<Response> {
+        let responses: Vec<_> = requests
+            .into_iter()
+            .map(|request| self.app.call(request))
+            .collect();
+        self.runtime
+            .block_on(join_all(responses))
+            .into_iter()
+            .map(|response| response.expect("the app must handle the request"))
+            .collect()
+    }
+
+    fn init_chain(&mut self) -> Result<(), BoxError> {
+        let request = request::InitChain {
+            time: Time::unix_epoch(),
+            chain_id: self.chain_id.clone(),
+            consensus_params: consensus_params(),
+            validators: Vec::new(),
+            app_state_bytes: self.app_state.clone(),
+            initial_height: Height::from(1_u32),
+        };
+        let response = self.app.call(Request::InitChain(request));
+        self.runtime.block_on(response)?;
+        Ok(())
+    }
+
+    fn begin_block(&mut self, height: u64) {
+        let header = Header {
+            version: Version { block: 11, app: 1 },
+            chain_id: self.chain_id.parse().expect("the chain ID must be valid"),
+            height: Height::try_from(height).expect("the height must be valid"),
+            time: Time::unix_epoch(),
+            last_block_id: None,
+            last_commit_hash: None,
+            data_hash: None,
+            validators_hash: Hash::None,
+            next_validators_hash: Hash::None,
+            consensus_hash: Hash::None,
+            app_hash: AppHash::try_from(self.app_hash.clone()).unwrap_or_default(),
+            last_results_hash: None,
+            evidence_hash: None,
+            proposer_address: account::Id::new([0; 20]),
+        };
+        let request = request::BeginBlock {
+            hash: Hash::None,
+            header,
+            last_commit_info: CommitInfo {
+                round: block::Round::default(),
+                votes: Vec::new(),
+            },
+            byzantine_validators: Vec::new(),
+        };
+        self.call(Request::BeginBlock(request));
+    }
+
+    fn commit(&mut self) -> Vec<u8> {
+        let app_hash = match self.call(Request::Commit) {
+            Response::Commit(response) => response.data.to_vec(),
+            response => panic!("Unexpected response to Commit: {response:?}"),
+        };
+        self.height += 1;
+        self.app_hash = app_hash.clone();
+        app_hash
+    }
+}
+
+/// Consensus parameters of local chains, which the app doesn't read.
+fn consensus_params() -> consensus::Params {
+    consensus::Params {
+        block: block::Size {
+            max_bytes: 22_020_096,
+            max_gas: -1,
+            time_iota_ms: 1000,
+        },
+        evidence: evidence::Params {
+            max_age_num_blocks: 100_000,
+            max_age_duration: evidence::Duration(std::time::Duration::from_secs(48 * 60 * 60)),
+            max_bytes: 1_048_576,
+        },
+        validator: ValidatorParams {
+            pub_key_types: vec![public_key::Algorithm::Ed25519],
+        },
+        version: None,
+    }
+}
@@ -0,0 +1,64 @@
+use cairo_felt::Felt;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::execution::{felt_to_hex, felt_to_word};
+use crate::state;
+use crate::transaction::{EntryPoint, TransactionType};
+
+/// Message sent to the chain from L1, by a call to the messaging contract's
+/// `sendMessageToL2(toAddress, selector, payload)`, which emits it as a `LogMessageToL2` event.
+/// It's handled by an L1 handler transaction calling `selector` of the contract at
+/// `to_address` with the L1 sender followed by the payload.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct L1Message {
+    /// Ethereum address of the contract that sent the message.
+    pub from_address: Felt,
+    /// Contract handling the message, i.e. the class hash of its program.
+    pub to_address: Felt,
+    /// Selector of the handler entry point.
+    pub selector: Felt,
+    pub payload: Vec<Felt>,
+    /// Nonce assigned by the messaging contract, so identical messages hash differently.
+    pub nonce: Felt,
+}
+
+impl L1Message {
+    /// Hash of the message, as computed by Starknet's core contract: the keccak256 of the
+    /// sender, recipient, nonce, selector, payload length and payload, as 32-byte words.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        for value in [
+            &self.from_address,
+            &self.to_address,
+            &self.nonce,
+            &self.selector,
+            &Felt::new(self.payload.len() as u64),
+        ]
+        .into_iter()
+        .chain(&self.payload)
+        {
+            hasher.update(felt_to_word(value));
+        }
+        hasher.finalize().into()
+    }
+
+    /// Storage slot recording that the message was handled, see [`state::l1_message_slot`].
+    pub fn slot(&self) -> (Felt, Felt) {
+        state::l1_message_slot(&self.hash())
+    }
+
+    /// Function execution handling the message, against the program registered at
+    /// `to_address`.
+    pub fn handler_call(&self) -> TransactionType {
+        TransactionType::ClassExecution {
+            class_hash: self.to_address.clone(),
+            function: EntryPoint::Selector(self.selector.clone()),
+            program_name: felt_to_hex(&self.to_address),
+            enable_trace: true,
+            calldata: std::iter::once(self.from_address.clone())
+                .chain(self.payload.iter().cloned())
+                .collect(),
+        }
+    }
+}
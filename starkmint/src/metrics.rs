@@ -0,0 +1,233 @@
+use std::fmt;
+use std::time::Duration;
+
+use prometheus::proto::MetricFamily;
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+
+/// Prometheus metrics of the application.
+pub struct Metrics {
+    registry: Registry,
+    /// Delivered transactions, by result: `applied`, `invalid` or `failed`.
+    transactions: IntCounterVec,
+    /// Seconds between the start of consecutive blocks.
+    block_interval: Histogram,
+    block_transactions: Histogram,
+    block_gas: Histogram,
+    /// Transactions per second of the last block, from its begin_block to its end_block.
+    tps: Gauge,
+    /// Seconds spent in each phase of a block: `execution` or `commit`.
+    block_phase_duration: HistogramVec,
+    /// Number of committed storage slots.
+    storage_slots: IntGauge,
+    /// Seconds spent handling requests, by ABCI hook, from the time they're received until
+    /// their response is ready. Parallel DeliverTx responses are only ready once the block is
+    /// executed at EndBlock, and CheckTx and Query ones may wait for a free worker.
+    hook_duration: HistogramVec,
+    /// Requests queued or being served, by connection: `mempool` or `info`.
+    queue_depth: IntGaugeVec,
+    /// Requests shed because their queue was full, by connection.
+    shed_requests: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let transactions = IntCounterVec::new(
+            Opts::new("starkmint_transactions_total", "Delivered transactions"),
+            &["result"],
+        )
+        .unwrap();
+        let block_interval = Histogram::with_opts(HistogramOpts::new(
+            "starkmint_block_interval_seconds",
+            "Time between the start of consecutive blocks",
+        ))
+        .unwrap();
+        let block_transactions = Histogram::with_opts(
+            HistogramOpts::new("starkmint_block_transactions", "Transactions per block")
+                .buckets(prometheus::exponential_buckets(1.0, 4.0, 8).unwrap()),
+        )
+        .unwrap();
+        let block_gas = Histogram::with_opts(
+            HistogramOpts::new("starkmint_block_gas", "Gas used per block")
+                .buckets(prometheus::exponential_buckets(1000.0, 10.0, 8).unwrap()),
+        )
+        .unwrap();
+        let tps = Gauge::new("starkmint_tps", "Transactions per second of the last block").unwrap();
+        let block_phase_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "starkmint_block_phase_duration_seconds",
+                "Time spent executing and committing blocks",
+            ),
+            &["phase"],
+        )
+        .unwrap();
+        let storage_slots =
+            IntGauge::new("starkmint_storage_slots", "Committed storage slots").unwrap();
+        let hook_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "starkmint_abci_hook_duration_seconds",
+                "Time spent handling ABCI requests",
+            )
+            // from 100us, as most CheckTx and DeliverTx requests are handled in under 5ms
+            .buckets(prometheus::exponential_buckets(0.0001, 4.0, 10).unwrap()),
+            &["hook"],
+        )
+        .unwrap();
+        let queue_depth = IntGaugeVec::new(
+            Opts::new(
+                "starkmint_queue_depth",
+                "Requests queued for the application or being served",
+            ),
+            &["connection"],
+        )
+        .unwrap();
+        let shed_requests = IntCounterVec::new(
+            Opts::new(
+                "starkmint_shed_requests_total",
+                "Requests shed because their queue was full",
+            ),
+            &["connection"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(transactions.clone())).unwrap();
+        registry.register(Box::new(block_interval.clone())).unwrap();
+        registry
+            .register(Box::new(block_transactions.clone()))
+            .unwrap();
+        registry.register(Box::new(block_gas.clone())).unwrap();
+        registry.register(Box::new(tps.clone())).unwrap();
+        registry
+            .register(Box::new(block_phase_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(storage_slots.clone())).unwrap();
+        registry.register(Box::new(hook_duration.clone())).unwrap();
+        registry.register(Box::new(queue_depth.clone())).unwrap();
+        registry.register(Box::new(shed_requests.clone())).unwrap();
+
+        Self {
+            registry,
+            transactions,
+            block_interval,
+            block_transactions,
+            block_gas,
+            tps,
+            block_phase_duration,
+            storage_slots,
+            hook_duration,
+            queue_depth,
+            shed_requests,
+        }
+    }
+
+    /// Current value of every metric.
+    pub fn gather(&self) -> Vec<MetricFamily> {
+        self.registry.gather()
+    }
+
+    /// Metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.gather(), &mut buffer)
+            .expect("metrics must be encodable");
+        String::from_utf8(buffer).expect("metrics must be UTF-8")
+    }
+
+    pub fn observe_transaction(&self, result: &str) {
+        self.transactions.with_label_values(&[result]).inc();
+    }
+
+    /// Records a block once it's committed.
+    pub fn observe_block(&self, block: &BlockSummary) {
+        if let Some(interval) = block.interval {
+            self.block_interval.observe(interval.as_secs_f64());
+        }
+        self.block_transactions.observe(block.transactions as f64);
+        self.block_gas.observe(block.gas as f64);
+        if let Some(tps) = block.tps() {
+            self.tps.set(tps);
+        }
+        self.block_phase_duration
+            .with_label_values(&["execution"])
+            .observe(block.execution.as_secs_f64());
+        self.block_phase_duration
+            .with_label_values(&["commit"])
+            .observe(block.commit.as_secs_f64());
+    }
+
+    pub fn set_storage_slots(&self, slots: usize) {
+        self.storage_slots.set(slots as i64);
+    }
+
+    pub fn observe_hook(&self, hook: &str, duration: Duration) {
+        self.hook_duration
+            .with_label_values(&[hook])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn set_queue_depth(&self, connection: &str, depth: usize) {
+        self.queue_depth
+            .with_label_values(&[connection])
+            .set(depth as i64);
+    }
+
+    pub fn observe_shed_request(&self, connection: &str) {
+        self.shed_requests.with_label_values(&[connection]).inc();
+    }
+}
+
+/// Summary of a committed block, logged and recorded as metrics once per block.
+#[derive(Debug, Clone)]
+pub struct BlockSummary {
+    pub height: u64,
+    pub transactions: usize,
+    pub gas: u64,
+    /// Time since the previous block started, unless this is the first since startup.
+    pub interval: Option<Duration>,
+    /// Time from begin_block to end_block, delivering the block's transactions.
+    pub execution: Duration,
+    /// Time spent in commit, persisting and hashing the block's writes.
+    pub commit: Duration,
+    pub app_hash: Vec<u8>,
+    /// Hashes of the block's transactions whose proofs were verified.
+    pub verified_proofs: Vec<String>,
+}
+
+impl BlockSummary {
+    /// Transactions executed per second, unless the block took no measurable time.
+    pub fn tps(&self) -> Option<f64> {
+        (!self.execution.is_zero()).then(|| self.transactions as f64 / self.execution.as_secs_f64())
+    }
+
+    /// Logs the summary as a single structured record.
+    pub fn log(&self) {
+        tracing::info!(
+            height = self.height,
+            transactions = self.transactions,
+            gas = self.gas,
+            interval_ms = self.interval.map(|interval| interval.as_millis() as u64),
+            execution_ms = self.execution.as_millis() as u64,
+            commit_ms = self.commit.as_millis() as u64,
+            tps = self.tps(),
+            app_hash = %hex::encode_upper(&self.app_hash),
+            "Committed block {}",
+            self.height
+        );
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
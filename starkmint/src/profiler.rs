@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use cairo_felt::Felt;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::execution::{felt_to_hex, Resources};
+use crate::transaction::{Execution, TransactionType};
+
+/// Number of entry points logged after each block, most expensive first.
+const LOGGED_ENTRY_POINTS: usize = 3;
+
+/// Class hash and function name of an entry point.
+type EntryPointKey = (Felt, String);
+
+/// Aggregated cost of the executions of an entry point, as returned by the `/profile` query.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryPointProfile {
+    pub class_hash: Felt,
+    pub function: String,
+    pub executions: u64,
+    /// Steps and builtins used by all the executions, including nested calls.
+    pub resources: Resources,
+    pub gas: u64,
+    /// Total wall time of the executions, in microseconds.
+    pub total_time_us: u64,
+    /// Longest wall time of an execution, in microseconds.
+    pub max_time_us: u64,
+}
+
+impl EntryPointProfile {
+    fn new((class_hash, function): EntryPointKey) -> Self {
+        Self {
+            class_hash,
+            function,
+            executions: 0,
+            resources: Resources::default(),
+            gas: 0,
+            total_time_us: 0,
+            max_time_us: 0,
+        }
+    }
+
+    fn record(&mut self, execution: &Execution) {
+        let time_us = execution.duration.as_micros() as u64;
+        self.executions += 1;
+        self.resources.add(&execution.resources);
+        self.gas += execution.gas_used;
+        self.total_time_us += time_us;
+        self.max_time_us = self.max_time_us.max(time_us);
+    }
+}
+
+/// Opt-in profile of the transactions delivered by the node, aggregated by entry point so
+/// developers can see which are expensive. The profile since startup is returned by the
+/// `/profile` query, and that of each block is logged at commit.
+///
+/// Only function executions are profiled; memoized executions take next to no time.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    entry_points: Mutex<HashMap<EntryPointKey, EntryPointProfile>>,
+    block: Mutex<HashMap<EntryPointKey, EntryPointProfile>>,
+}
+
+impl Profiler {
+    pub fn record(&self, transaction_type: &TransactionType, execution: &Execution) {
+        let function = match transaction_type {
+            TransactionType::FunctionExecution { function, .. }
+            | TransactionType::ClassExecution { function, .. } => function.to_string(),
+            TransactionType::L1Handler(message) => felt_to_hex(&message.selector),
+            TransactionType::ProofVerification { .. } => return,
+        };
+        let Some(class_hash) = transaction_type.class_hash() else {
+            return;
+        };
+
+        debug!(
+            "Executed {function} of class {} in {:?}: {} steps, {} gas",
+            felt_to_hex(&class_hash),
+            execution.duration,
+            execution.resources.steps,
+            execution.gas_used
+        );
+
+        let key = (class_hash, function);
+        for profiles in [&self.entry_points, &self.block] {
+            profiles
+                .lock()
+                .unwrap()
+                .entry(key.clone())
+                .or_insert_with(|| EntryPointProfile::new(key.clone()))
+                .record(execution);
+        }
+    }
+
+    /// Profile of every entry point executed since startup, the most expensive first.
+    pub fn report(&self) -> Vec<EntryPointProfile> {
+        sorted(
+            self.entry_points
+                .lock()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Logs the most expensive entry points of the block, and starts profiling the next one.
+    pub fn finish_block(&self, height: u64) {
+        let block = std::mem::take(&mut *self.block.lock().unwrap());
+        for profile in sorted(block.into_values().collect())
+            .iter()
+            .take(LOGGED_ENTRY_POINTS)
+        {
+            info!(
+                "Block {height} profile: {} of class {} executed {} times in {}us \
+                 (at most {}us), {} steps, {} gas",
+                profile.function,
+                felt_to_hex(&profile.class_hash),
+                profile.executions,
+                profile.total_time_us,
+                profile.max_time_us,
+                profile.resources.steps,
+                profile.gas
+            );
+        }
+    }
+}
+
+/// Sorts profiles by total wall time, the longest first.
+fn sorted(mut profiles: Vec<EntryPointProfile>) -> Vec<EntryPointProfile> {
+    profiles.sort_by(|a, b| b.total_time_us.cmp(&a.total_time_us));
+    profiles
+}
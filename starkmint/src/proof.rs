@@ -0,0 +1,12 @@
+use color_eyre::Result;
+
+/// Verifies STARK proofs carried by `ProofVerification` transactions, see
+/// [`ExecutionConfig::proof_verifier`](crate::execution::ExecutionConfig::proof_verifier).
+/// No verifier ships with the node yet: until one is configured, nodes reject these
+/// transactions in CheckTx, and fail them in DeliverTx.
+///
+/// Implementations must be deterministic: every validator runs the verifier on
+/// the same inputs, and a transaction whose proof is rejected fails.
+pub trait ProofVerifier: std::fmt::Debug + Send + Sync {
+    fn verify(&self, proof: &[u8], public_inputs: &[String]) -> Result<()>;
+}
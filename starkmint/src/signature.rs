@@ -0,0 +1,451 @@
+use cairo_felt::Felt;
+use color_eyre::eyre::{ensure, eyre};
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use starknet_crypto::{rfc6979_generate_k, FieldElement};
+
+use crate::execution::felt_to_word;
+
+/// Stark-curve signature of a transaction by its sender.
+///
+/// The sender is either the address of an account contract, which validates the signature
+/// itself, or a public key, whose signature is verified natively, see
+/// [`crate::account::validate`].
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct TxSignature {
+    pub sender: Felt,
+    /// Number of transactions the sender had applied before this one.
+    pub nonce: u64,
+    pub r: Felt,
+    pub s: Felt,
+    /// Amount of the fee token paid by the sender, or its paymaster, for the transaction to
+    /// be prioritized in the mempool.
+    pub tip: u64,
+    /// Most the sender, or its paymaster, pays for the gas used by the transaction, at the
+    /// chain's gas price. The transaction fails if it costs more.
+    pub max_fee: u64,
+    /// Account paying the fee and tip in place of the sender, if any.
+    pub paymaster: Option<PaymasterSignature>,
+}
+
+impl TxSignature {
+    /// Account paying the fee and tip: the paymaster if there's one, the sender otherwise.
+    pub fn payer(&self) -> &Felt {
+        self.paymaster
+            .as_ref()
+            .map_or(&self.sender, |paymaster| &paymaster.paymaster)
+    }
+}
+
+/// Signature of an account sponsoring a transaction, e.g. a dapp paying for its users, over
+/// the sender's signed message (see [`paymaster_message_hash`]). Like senders, paymasters are
+/// public keys or account contracts.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PaymasterSignature {
+    pub paymaster: Felt,
+    pub r: Felt,
+    pub s: Felt,
+}
+
+/// Domain of the messages signed by senders, so they can't be mistaken for other messages
+/// signed with the same keys.
+const TRANSACTION_DOMAIN: &[u8] = b"starkmint:transaction:v1";
+
+/// Call made by a signed transaction, as covered by its sender's signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignedCall<'a> {
+    /// Call of the entry point with this selector of the program registered as `class_hash`.
+    Invoke {
+        class_hash: Felt,
+        selector: Felt,
+        calldata: &'a [Felt],
+    },
+    /// Handling of the L1 message with this hash, which commits to its recipient, handler,
+    /// payload and nonce.
+    L1Handler { message_hash: [u8; 32] },
+    /// Verification of a proof against its public inputs.
+    ProofVerification {
+        proof: &'a [u8],
+        public_inputs: &'a [String],
+    },
+}
+
+/// Content of a transaction covered by its sender's signature, see [`message_hash`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTransaction<'a> {
+    /// Chain the transaction is sent to, as hashed by [`chain_id_hash`], so it can't be
+    /// replayed on another chain.
+    pub chain_id: Felt,
+    /// Id of the transaction, which its receipt hash commits to along with its execution
+    /// hash, so it can't be broadcast again under another id.
+    pub id: &'a str,
+    /// Hash of the transaction's execution.
+    pub transaction_hash: &'a str,
+    pub call: SignedCall<'a>,
+}
+
+/// Chain ID as signed in transactions: its sha256 truncated to 250 bits, as chain IDs may be
+/// longer than a felt.
+pub fn chain_id_hash(chain_id: &str) -> Felt {
+    let mut digest = Sha256::digest(chain_id.as_bytes());
+    digest[0] &= 0x03;
+    Felt::from_bytes_be(&digest)
+}
+
+/// Message signed by the sender: the sha256 of a canonical encoding of the transaction, its
+/// sender, nonce, tip and max fee, and the paymaster paying them if there's one, truncated to
+/// 250 bits so it fits in a felt. Felts are encoded as 32-byte words and variable-length values
+/// are prefixed with their length, so no two transactions encode the same.
+pub fn message_hash(
+    tx: &SignedTransaction,
+    sender: &Felt,
+    nonce: u64,
+    tip: u64,
+    max_fee: u64,
+    paymaster: Option<&Felt>,
+) -> Felt {
+    let mut hasher = Sha256::new();
+    hasher.update(TRANSACTION_DOMAIN);
+    hasher.update(felt_to_word(&tx.chain_id));
+    update_bytes(&mut hasher, tx.id.as_bytes());
+    update_bytes(&mut hasher, tx.transaction_hash.as_bytes());
+    match &tx.call {
+        SignedCall::Invoke {
+            class_hash,
+            selector,
+            calldata,
+        } => {
+            hasher.update([0]);
+            hasher.update(felt_to_word(class_hash));
+            hasher.update(felt_to_word(selector));
+            hasher.update((calldata.len() as u64).to_be_bytes());
+            for value in *calldata {
+                hasher.update(felt_to_word(value));
+            }
+        }
+        SignedCall::L1Handler { message_hash } => {
+            hasher.update([1]);
+            hasher.update(message_hash);
+        }
+        SignedCall::ProofVerification {
+            proof,
+            public_inputs,
+        } => {
+            hasher.update([2]);
+            update_bytes(&mut hasher, proof);
+            hasher.update((public_inputs.len() as u64).to_be_bytes());
+            for input in *public_inputs {
+                update_bytes(&mut hasher, input.as_bytes());
+            }
+        }
+    }
+    hasher.update(felt_to_word(sender));
+    hasher.update(nonce.to_be_bytes());
+    hasher.update(tip.to_be_bytes());
+    hasher.update(max_fee.to_be_bytes());
+    match paymaster {
+        Some(paymaster) => {
+            hasher.update([1]);
+            hasher.update(felt_to_word(paymaster));
+        }
+        None => hasher.update([0]),
+    }
+
+    let mut digest = hasher.finalize();
+    digest[0] &= 0x03;
+    Felt::from_bytes_be(&digest)
+}
+
+/// Message signed by a paymaster: the sha256 of the sender's message and the paymaster,
+/// truncated like it. It commits to the whole transaction, its tip and max fee, so the
+/// sponsorship can't be reused for another transaction or to pay more.
+pub fn paymaster_message_hash(
+    tx: &SignedTransaction,
+    signature: &TxSignature,
+    paymaster: &Felt,
+) -> Felt {
+    let message = message_hash(
+        tx,
+        &signature.sender,
+        signature.nonce,
+        signature.tip,
+        signature.max_fee,
+        Some(paymaster),
+    );
+    let mut hasher = Sha256::new();
+    hasher.update(felt_to_word(&message));
+    hasher.update(felt_to_word(paymaster));
+
+    let mut digest = hasher.finalize();
+    digest[0] &= 0x03;
+    Felt::from_bytes_be(&digest)
+}
+
+/// Signs a transaction with the sender's private key, the sender being its public key.
+pub fn sign(
+    private_key: &Felt,
+    tx: &SignedTransaction,
+    nonce: u64,
+    tip: u64,
+    max_fee: u64,
+) -> Result<TxSignature> {
+    let sender = public_key(private_key)?;
+    sign_as(sender, private_key, tx, nonce, tip, max_fee, None)
+}
+
+/// Signs a transaction with a private key on behalf of `sender`, e.g. an account contract
+/// whose `__validate__` entry point checks signatures by that key. The signature covers the
+/// paymaster paying the fee and tip if there's one, which must then [`sponsor`] the
+/// transaction.
+pub fn sign_as(
+    sender: Felt,
+    private_key: &Felt,
+    tx: &SignedTransaction,
+    nonce: u64,
+    tip: u64,
+    max_fee: u64,
+    paymaster: Option<&Felt>,
+) -> Result<TxSignature> {
+    let (r, s) = sign_message(
+        private_key,
+        &message_hash(tx, &sender, nonce, tip, max_fee, paymaster),
+    )?;
+    Ok(TxSignature {
+        sender,
+        nonce,
+        r,
+        s,
+        tip,
+        max_fee,
+        paymaster: None,
+    })
+}
+
+/// Signs a transaction already signed by its sender, as `paymaster`, with a private key its
+/// account accepts.
+pub fn sponsor(
+    paymaster: Felt,
+    private_key: &Felt,
+    tx: &SignedTransaction,
+    signature: &TxSignature,
+) -> Result<PaymasterSignature> {
+    let (r, s) = sign_message(
+        private_key,
+        &paymaster_message_hash(tx, signature, &paymaster),
+    )?;
+    Ok(PaymasterSignature { paymaster, r, s })
+}
+
+/// Feeds a variable-length value to a hasher, prefixed with its length.
+fn update_bytes(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_be_bytes());
+    hasher.update(bytes);
+}
+
+fn sign_message(private_key: &Felt, message: &Felt) -> Result<(Felt, Felt)> {
+    let private = to_field_element(private_key)?;
+    let message = to_field_element(message)?;
+
+    let k = rfc6979_generate_k(&message, &private, None);
+    let signature = starknet_crypto::sign(&private, &message, &k)
+        .map_err(|e| eyre!("Error signing transaction: {e}"))?;
+    Ok((
+        from_field_element(&signature.r),
+        from_field_element(&signature.s),
+    ))
+}
+
+/// Fails unless `(r, s)` is a signature of the message by the private key of `public_key`.
+pub fn verify(public_key: &Felt, message: &Felt, r: &Felt, s: &Felt) -> Result<()> {
+    let valid = starknet_crypto::verify(
+        &to_field_element(public_key)?,
+        &to_field_element(message)?,
+        &to_field_element(r)?,
+        &to_field_element(s)?,
+    )
+    .unwrap_or(false);
+    ensure!(valid, "Invalid transaction signature");
+
+    Ok(())
+}
+
+/// Private key of the dev account at `index`, derived from the index so devnets and the CLI
+/// agree on it without sharing any file. Dev keys are public, so they must never hold real
+/// funds.
+pub fn dev_private_key(index: u32) -> Felt {
+    let mut seed = Sha256::digest(format!("starkmint-dev-{index}"));
+    seed[0] &= 0x03;
+    Felt::from_bytes_be(&seed)
+}
+
+/// Public key of a private key, which is also the address of its account.
+pub fn public_key(private_key: &Felt) -> Result<Felt> {
+    let private = to_field_element(private_key)?;
+    Ok(from_field_element(&starknet_crypto::get_public_key(
+        &private,
+    )))
+}
+
+pub fn to_field_element(felt: &Felt) -> Result<FieldElement> {
+    let bytes = felt.to_bytes_be();
+    let mut padded = [0u8; 32];
+    padded[32 - bytes.len()..].copy_from_slice(&bytes);
+
+    FieldElement::from_bytes_be(&padded).map_err(|_| eyre!("Felt out of range"))
+}
+
+pub fn from_field_element(element: &FieldElement) -> Felt {
+    Felt::from_bytes_be(&element.to_bytes_be())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAIN_ID: &str = "starkmint-test";
+
+    fn invoke(calldata: &[Felt]) -> SignedTransaction<'_> {
+        SignedTransaction {
+            chain_id: chain_id_hash(CHAIN_ID),
+            id: "3f1e2c4a-5b6d-4e7f-8a9b-0c1d2e3f4a5b",
+            transaction_hash: "8f434346648f6b96df89dda901c5176b",
+            call: SignedCall::Invoke {
+                class_hash: Felt::new(10),
+                selector: Felt::new(20),
+                calldata,
+            },
+        }
+    }
+
+    fn verification<'a>(proof: &'a [u8], public_inputs: &'a [String]) -> SignedTransaction<'a> {
+        SignedTransaction {
+            chain_id: chain_id_hash(CHAIN_ID),
+            id: "",
+            transaction_hash: "",
+            call: SignedCall::ProofVerification {
+                proof,
+                public_inputs,
+            },
+        }
+    }
+
+    #[test]
+    fn changing_any_signed_field_invalidates_the_signature() {
+        let private_key = dev_private_key(0);
+        let sender = public_key(&private_key).unwrap();
+        let paymaster = Felt::new(30);
+        let calldata = [Felt::new(1), Felt::new(2)];
+        let tx = invoke(&calldata);
+        let signature = sign_as(
+            sender.clone(),
+            &private_key,
+            &tx,
+            1,
+            5,
+            100,
+            Some(&paymaster),
+        )
+        .unwrap();
+
+        let verify_message = |message: Felt| verify(&sender, &message, &signature.r, &signature.s);
+        verify_message(message_hash(&tx, &sender, 1, 5, 100, Some(&paymaster))).unwrap();
+
+        let other_calldata = [Felt::new(1), Felt::new(3)];
+        let longer_calldata = [Felt::new(1), Felt::new(2), Felt::new(0)];
+        let mut altered = vec![
+            SignedTransaction {
+                chain_id: chain_id_hash("another-chain"),
+                ..tx.clone()
+            },
+            SignedTransaction {
+                id: "3f1e2c4a-5b6d-4e7f-8a9b-0c1d2e3f4a5c",
+                ..tx.clone()
+            },
+            SignedTransaction {
+                transaction_hash: "8f434346648f6b96df89dda901c5176c",
+                ..tx.clone()
+            },
+            invoke(&other_calldata),
+            invoke(&longer_calldata),
+            invoke(&calldata[..1]),
+        ];
+        for (class_hash, selector) in [
+            (Felt::new(11), Felt::new(20)),
+            (Felt::new(10), Felt::new(21)),
+        ] {
+            altered.push(SignedTransaction {
+                call: SignedCall::Invoke {
+                    class_hash,
+                    selector,
+                    calldata: &calldata,
+                },
+                ..tx.clone()
+            });
+        }
+        for tx in &altered {
+            assert!(
+                verify_message(message_hash(tx, &sender, 1, 5, 100, Some(&paymaster))).is_err()
+            );
+        }
+
+        let other = Felt::new(31);
+        for message in [
+            message_hash(&tx, &other, 1, 5, 100, Some(&paymaster)),
+            message_hash(&tx, &sender, 2, 5, 100, Some(&paymaster)),
+            message_hash(&tx, &sender, 1, 6, 100, Some(&paymaster)),
+            message_hash(&tx, &sender, 1, 5, 101, Some(&paymaster)),
+            message_hash(&tx, &sender, 1, 5, 100, Some(&other)),
+            message_hash(&tx, &sender, 1, 5, 100, None),
+        ] {
+            assert!(verify_message(message).is_err());
+        }
+    }
+
+    #[test]
+    fn changing_a_proof_or_its_inputs_invalidates_the_signature() {
+        let private_key = dev_private_key(0);
+        let sender = public_key(&private_key).unwrap();
+        let inputs = ["1".to_string(), "2".to_string()];
+        let tx = verification(b"proof", &inputs);
+        let signature = sign(&private_key, &tx, 0, 0, 0).unwrap();
+        let verify_tx = |tx: &SignedTransaction| {
+            let message = message_hash(tx, &sender, 0, 0, 0, None);
+            verify(&sender, &message, &signature.r, &signature.s)
+        };
+        verify_tx(&tx).unwrap();
+
+        assert!(verify_tx(&verification(b"proog", &inputs)).is_err());
+        assert!(verify_tx(&verification(b"proof", &inputs[..1])).is_err());
+        // the same bytes, split differently between the proof and its inputs
+        assert!(verify_tx(&verification(b"proof1", &inputs[1..])).is_err());
+    }
+
+    #[test]
+    fn paymasters_sign_for_the_whole_transaction() {
+        let (sender_key, paymaster_key) = (dev_private_key(0), dev_private_key(1));
+        let paymaster = public_key(&paymaster_key).unwrap();
+        let calldata = [Felt::new(1)];
+        let tx = invoke(&calldata);
+        let signature = sign(&sender_key, &tx, 0, 5, 100).unwrap();
+        let sponsorship = sponsor(paymaster.clone(), &paymaster_key, &tx, &signature).unwrap();
+
+        let verify_message =
+            |message: Felt| verify(&paymaster, &message, &sponsorship.r, &sponsorship.s);
+        verify_message(paymaster_message_hash(&tx, &signature, &paymaster)).unwrap();
+
+        let higher_tip = TxSignature {
+            tip: 6,
+            ..signature.clone()
+        };
+        assert!(verify_message(paymaster_message_hash(&tx, &higher_tip, &paymaster)).is_err());
+        let higher_max_fee = TxSignature {
+            max_fee: 101,
+            ..signature.clone()
+        };
+        assert!(verify_message(paymaster_message_hash(&tx, &higher_max_fee, &paymaster)).is_err());
+        let other_calldata = [Felt::new(2)];
+        let other_tx = invoke(&other_calldata);
+        assert!(verify_message(paymaster_message_hash(&other_tx, &signature, &paymaster)).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use cairo_felt::Felt;
+use num_traits::{ToPrimitive, Zero};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::audit::StateWrite;
+use crate::execution;
+
+/// Storage writes keyed by (contract address, storage key), ordered so they hash deterministically.
+pub type StorageWrites = BTreeMap<(Felt, Felt), Felt>;
+
+/// Storage slots read by a transaction, keyed like [`StorageWrites`].
+pub type StorageReads = BTreeSet<(Felt, Felt)>;
+
+/// Class hash of a program: the sha256 of its compiled JSON, truncated to 250 bits so it fits
+/// in a felt. Each program acts as a singleton contract deployed at its class hash, so this is
+/// also the address under which its storage lives.
+pub fn class_hash(program: &str) -> Felt {
+    let mut digest = Sha256::digest(program.as_bytes());
+    digest[0] &= 0x03;
+    Felt::from_bytes_be(&digest)
+}
+
+/// Storage key of an account's nonce, kept under the account's address like contract storage
+/// so it is committed and hashed along with it: the sn_keccak of `nonce`. Computed once, as
+/// every storage write checks it.
+static NONCE_KEY: Lazy<Felt> = Lazy::new(|| execution::entry_point_selector("nonce"));
+
+/// Storage key of an account's fee-token balance, kept like its nonce.
+static BALANCE_KEY: Lazy<Felt> = Lazy::new(|| execution::entry_point_selector("balance"));
+
+/// Whether `key` holds the nonce or balance of an account. Contracts can't write these keys,
+/// even under their own address: an account contract could otherwise reset its nonce to replay
+/// transactions, or mint itself the balance to pay tips.
+pub fn is_account_key(key: &Felt) -> bool {
+    *key == *NONCE_KEY || *key == *BALANCE_KEY
+}
+
+/// Storage slot of an account's nonce.
+pub fn nonce_slot(account: &Felt) -> (Felt, Felt) {
+    (account.clone(), NONCE_KEY.clone())
+}
+
+/// Storage slot of an account's fee-token balance.
+pub fn balance_slot(account: &Felt) -> (Felt, Felt) {
+    (account.clone(), BALANCE_KEY.clone())
+}
+
+/// Storage key, under the governance contract set at genesis, of the height the chain halts
+/// at once committed: the sn_keccak of `halt_height`. The chain doesn't halt while it's zero.
+pub fn halt_height_key() -> Felt {
+    execution::entry_point_selector("halt_height")
+}
+
+/// Storage slot of the data availability commitment of the state diff of the block at
+/// `height` (see [`StateDiff::blob`](crate::da::StateDiff::blob)), recorded by the next block
+/// on chains publishing their state diffs. Commitments are kept under the reserved address
+/// 0x2, keyed by height, like Starknet keeps block hashes under 0x1.
+pub fn da_commitment_slot(height: u64) -> (Felt, Felt) {
+    (Felt::new(2), Felt::new(height))
+}
+
+/// Storage slot of the public key of the L1 relayer set at genesis, the only sender of L1
+/// handler transactions. L1 messaging state is kept under the reserved address 0x3.
+pub fn l1_relayer_slot() -> (Felt, Felt) {
+    (Felt::new(3), execution::entry_point_selector("l1_relayer"))
+}
+
+/// Storage slot of the chain ID, as hashed by
+/// [`chain_id_hash`](crate::signature::chain_id_hash), which transaction signatures commit to.
+/// Set at genesis, under the reserved address 0x4.
+pub fn chain_id_slot() -> (Felt, Felt) {
+    (Felt::new(4), execution::entry_point_selector("chain_id"))
+}
+
+/// Storage slot recording, as one, that the chain rejects unsigned function and class
+/// executions, see
+/// [`Genesis::signatures_required`](crate::genesis::Genesis::signatures_required). Set at
+/// genesis, under the reserved address 0x4.
+pub fn signatures_required_slot() -> (Felt, Felt) {
+    (
+        Felt::new(4),
+        execution::entry_point_selector("signatures_required"),
+    )
+}
+
+/// Storage slot recording that the L1 message with this hash was handled, so it's handled
+/// once: set to one by its L1 handler transaction. Keyed by the hash truncated to 250 bits.
+pub fn l1_message_slot(message_hash: &[u8; 32]) -> (Felt, Felt) {
+    let mut key = *message_hash;
+    key[0] &= 0x03;
+    (Felt::new(3), Felt::from_bytes_be(&key))
+}
+
+/// Nonce and fee-token balance of an account, as returned by the `/account` query.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountState {
+    pub nonce: u64,
+    pub balance: Felt,
+}
+
+/// Programs that have been executed on chain, by contract address, so other contracts can call them.
+#[derive(Debug, Default, Clone)]
+pub struct ClassRegistry {
+    classes: HashMap<Felt, String>,
+}
+
+impl ClassRegistry {
+    /// Registers the program under its contract address, returning the address.
+    pub fn register(&mut self, program: &str) -> Felt {
+        let address = class_hash(program);
+        self.classes
+            .entry(address.clone())
+            .or_insert_with(|| program.to_string());
+        address
+    }
+
+    pub fn get(&self, address: &Felt) -> Option<&str> {
+        self.classes.get(address).map(String::as_str)
+    }
+}
+
+/// Contract storage of the application.
+///
+/// Writes made by a transaction are first staged in a [`TxStorage`]; if the transaction succeeds
+/// they are merged into the pending block writes, which only become committed state at commit.
+#[derive(Debug, Default, Clone)]
+pub struct ContractStorage {
+    committed: HashMap<(Felt, Felt), Felt>,
+    pending: StorageWrites,
+    /// Writes of the transactions applied since the journal was last taken, if kept.
+    journal: Option<Vec<StateWrite>>,
+}
+
+impl ContractStorage {
+    /// Keeps a journal of the writes of the transactions delivered from now on, for the audit
+    /// log. The storage is kept as is.
+    pub fn keep_journal(&mut self) {
+        self.journal.get_or_insert_with(Vec::new);
+    }
+
+    /// Reads a storage value as seen by the block being executed. Unset keys read as zero.
+    pub fn read(&self, address: &Felt, key: &Felt) -> Felt {
+        let slot = (address.clone(), key.clone());
+        self.pending
+            .get(&slot)
+            .or_else(|| self.committed.get(&slot))
+            .cloned()
+            .unwrap_or_else(Felt::zero)
+    }
+
+    /// Reads a storage value as of the last committed block, ignoring the current block's writes.
+    pub fn read_committed(&self, address: &Felt, key: &Felt) -> Felt {
+        self.committed
+            .get(&(address.clone(), key.clone()))
+            .cloned()
+            .unwrap_or_else(Felt::zero)
+    }
+
+    /// Number of transactions applied from an account, including those of the current block.
+    pub fn nonce(&self, account: &Felt) -> u64 {
+        self.read(account, &NONCE_KEY).to_u64().unwrap_or(u64::MAX)
+    }
+
+    /// Number of transactions applied from an account as of the last committed block.
+    pub fn committed_nonce(&self, account: &Felt) -> u64 {
+        self.read_committed(account, &NONCE_KEY)
+            .to_u64()
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Fee-token balance of an account, including the current block's changes.
+    pub fn balance(&self, account: &Felt) -> Felt {
+        self.read(account, &BALANCE_KEY)
+    }
+
+    /// Stages an account's fee-token balance into the current block.
+    pub fn set_balance(&mut self, account: &Felt, balance: Felt) {
+        self.pending.insert(balance_slot(account), balance);
+    }
+
+    /// Nonce and balance of an account as of the last committed block.
+    pub fn committed_account(&self, account: &Felt) -> AccountState {
+        AccountState {
+            nonce: self.committed_nonce(account),
+            balance: self.read_committed(account, &BALANCE_KEY),
+        }
+    }
+
+    /// Number of storage slots in committed state.
+    pub fn committed_slots(&self) -> usize {
+        self.committed.len()
+    }
+
+    /// Stages the writes of a successful transaction into the current block.
+    pub fn apply(&mut self, writes: StorageWrites) {
+        self.pending.extend(writes);
+    }
+
+    /// Stages the writes of a delivered transaction like [`ContractStorage::apply`], recording
+    /// them in the journal if one is kept.
+    pub fn apply_transaction(&mut self, transaction_hash: &str, writes: StorageWrites) {
+        if self.journal.is_some() {
+            let entries: Vec<_> = writes
+                .iter()
+                .map(|(slot, new)| {
+                    StateWrite::new(transaction_hash, slot, &self.read(&slot.0, &slot.1), new)
+                })
+                .collect();
+            if let Some(journal) = &mut self.journal {
+                journal.extend(entries);
+            }
+        }
+        self.apply(writes);
+    }
+
+    /// Writes recorded in the journal since it was last taken, if one is kept.
+    pub fn take_journal(&mut self) -> Option<Vec<StateWrite>> {
+        self.journal.as_mut().map(std::mem::take)
+    }
+
+    /// Moves the block's writes into committed state, returning them.
+    pub fn commit(&mut self) -> StorageWrites {
+        let writes = std::mem::take(&mut self.pending);
+        self.committed.extend(
+            writes
+                .iter()
+                .map(|(slot, value)| (slot.clone(), value.clone())),
+        );
+        writes
+    }
+}
+
+/// Per-transaction view over [`ContractStorage`] that buffers the transaction's own writes
+/// and records the slots it reads, for conflict detection.
+#[derive(Debug)]
+pub struct TxStorage<'a> {
+    base: &'a ContractStorage,
+    committed_only: bool,
+    writes: StorageWrites,
+    reads: StorageReads,
+}
+
+impl<'a> TxStorage<'a> {
+    /// View over the state of the block being executed.
+    pub fn new(base: &'a ContractStorage) -> Self {
+        Self {
+            base,
+            committed_only: false,
+            writes: StorageWrites::new(),
+            reads: StorageReads::new(),
+        }
+    }
+
+    /// View over the last committed state, for executions that are never applied.
+    pub fn committed(base: &'a ContractStorage) -> Self {
+        Self {
+            committed_only: true,
+            ..Self::new(base)
+        }
+    }
+
+    pub fn read(&mut self, address: &Felt, key: &Felt) -> Felt {
+        let slot = (address.clone(), key.clone());
+        let value = self
+            .writes
+            .get(&slot)
+            .cloned()
+            .unwrap_or_else(|| self.peek(address, key));
+
+        self.reads.insert(slot);
+        value
+    }
+
+    /// Reads a value of the underlying state, ignoring the writes staged in this view and
+    /// without recording the read.
+    pub fn peek(&self, address: &Felt, key: &Felt) -> Felt {
+        if self.committed_only {
+            self.base.read_committed(address, key)
+        } else {
+            self.base.read(address, key)
+        }
+    }
+
+    /// Fresh view over the same state, without the writes and reads of this one.
+    pub fn view(&self) -> TxStorage<'a> {
+        TxStorage {
+            committed_only: self.committed_only,
+            ..TxStorage::new(self.base)
+        }
+    }
+
+    pub fn write(&mut self, address: Felt, key: Felt, value: Felt) {
+        self.writes.insert((address, key), value);
+    }
+
+    pub fn into_parts(self) -> (StorageWrites, StorageReads) {
+        (self.writes, self.reads)
+    }
+
+    /// Hands the staged writes and recorded reads over to a nested call. They must be given
+    /// back with [`TxStorage::join`] once the call returns.
+    pub fn fork(&mut self) -> TxStorage<'a> {
+        TxStorage {
+            base: self.base,
+            committed_only: self.committed_only,
+            writes: std::mem::take(&mut self.writes),
+            reads: std::mem::take(&mut self.reads),
+        }
+    }
+
+    /// Takes back the staged writes and recorded reads, including those of the nested call.
+    pub fn join(&mut self, nested: TxStorage<'a>) {
+        self.writes = nested.writes;
+        self.reads = nested.reads;
+    }
+}
@@ -0,0 +1,567 @@
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use cairo_felt::Felt;
+use cairo_vm::{
+    hint_processor::{
+        builtin_hint_processor::{
+            builtin_hint_processor_definition::{BuiltinHintProcessor, HintProcessorData},
+            hint_utils::get_ptr_from_var_name,
+        },
+        hint_processor_definition::{HintProcessor, HintReference},
+    },
+    serde::deserialize_program::ApTracking,
+    types::{
+        exec_scope::ExecutionScopes,
+        program::Program,
+        relocatable::{MaybeRelocatable, Relocatable},
+    },
+    vm::{
+        errors::{hint_errors::HintError, vm_errors::VirtualMachineError},
+        runners::cairo_runner::{CairoArg, CairoRunner, ExecutionResources},
+        vm_core::VirtualMachine,
+    },
+};
+use num_traits::ToPrimitive;
+use tracing::info;
+use tracing::span::EnteredSpan;
+
+use crate::cache::ProgramCache;
+use crate::execution::{self, ContractEvent, ExecutionConfig, Resources, StepBudget};
+use crate::state::{self, ClassRegistry, StorageReads, StorageWrites, TxStorage};
+
+const STORAGE_READ_HINT: &str =
+    "syscall_handler.storage_read(segments=segments, syscall_ptr=ids.syscall_ptr)";
+const STORAGE_WRITE_HINT: &str =
+    "syscall_handler.storage_write(segments=segments, syscall_ptr=ids.syscall_ptr)";
+const CALL_CONTRACT_HINT: &str =
+    "syscall_handler.call_contract(segments=segments, syscall_ptr=ids.syscall_ptr)";
+const EMIT_EVENT_HINT: &str =
+    "syscall_handler.emit_event(segments=segments, syscall_ptr=ids.syscall_ptr)";
+
+/// Hints served by [`SyscallHandler`].
+pub const SYSCALL_HINTS: [&str; 4] = [
+    STORAGE_READ_HINT,
+    STORAGE_WRITE_HINT,
+    CALL_CONTRACT_HINT,
+    EMIT_EVENT_HINT,
+];
+
+/// Maximum nesting of `call_contract` syscalls within a transaction.
+const MAX_CALL_DEPTH: usize = 10;
+
+/// Hint processor serving Starknet syscalls against the application's contract storage.
+/// Any hint that is not a syscall is delegated to the builtin hint processor.
+///
+/// Syscall structs follow the layout of `starkware.starknet.common.syscalls`:
+/// `StorageRead { selector, address, value }` and `StorageWrite { selector, address, value }`,
+/// where `value` is the response for reads and the request for writes, and
+/// `CallContract { selector, contract_address, function_selector, calldata_size, calldata,
+/// retdata_size, retdata }` and `EmitEvent { selector, keys_len, keys, data_len, data }`.
+pub struct SyscallHandler<'a> {
+    builtin: BuiltinHintProcessor,
+    config: &'a ExecutionConfig,
+    storage: TxStorage<'a>,
+    classes: &'a ClassRegistry,
+    programs: &'a ProgramCache,
+    contract_address: Felt,
+    events: Vec<ContractEvent>,
+    /// Gas consumed by nested calls.
+    gas_used: u64,
+    /// Resources used by nested calls.
+    resources: Resources,
+    depth: usize,
+    /// Steps left to the executed transaction, shared with nested calls, if it's bounded.
+    steps: Option<Rc<StepBudget>>,
+}
+
+impl<'a> SyscallHandler<'a> {
+    pub fn new(
+        config: &'a ExecutionConfig,
+        storage: TxStorage<'a>,
+        classes: &'a ClassRegistry,
+        programs: &'a ProgramCache,
+        contract_address: Felt,
+    ) -> Self {
+        Self {
+            builtin: BuiltinHintProcessor::new_empty(),
+            config,
+            storage,
+            classes,
+            programs,
+            contract_address,
+            events: Vec::new(),
+            gas_used: 0,
+            resources: Resources::default(),
+            depth: 0,
+            steps: config.step_limit().map(|max| Rc::new(StepBudget::new(max))),
+        }
+    }
+
+    /// Storage writes staged, storage slots read and events emitted by the executed
+    /// transaction, including those of nested calls.
+    pub fn finish(self) -> (StorageWrites, StorageReads, Vec<ContractEvent>) {
+        let (writes, reads) = self.storage.into_parts();
+        (writes, reads, self.events)
+    }
+
+    /// Gas consumed by the contracts called from the executed transaction.
+    pub fn gas_used(&self) -> u64 {
+        self.gas_used
+    }
+
+    /// Resources used by the contracts called from the executed transaction.
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    /// Steps left to the executed transaction, if it's bounded, see
+    /// [`ExecutionConfig::step_limit`].
+    pub fn step_budget(&self) -> Option<Rc<StepBudget>> {
+        self.steps.clone()
+    }
+
+    /// Span of the execution of `function` by this handler's contract, if executions are
+    /// traced. Nested calls are traced in nested spans.
+    pub fn trace_span(&self, function: &str) -> Option<EnteredSpan> {
+        self.config.trace_execution.then(|| {
+            tracing::info_span!(
+                "cairo",
+                contract = %execution::felt_to_hex(&self.contract_address),
+                function,
+                depth = self.depth
+            )
+            .entered()
+        })
+    }
+
+    /// Logs the segments a program was loaded into, if executions are traced.
+    pub fn trace_loaded(&self, program: &Program, args: usize) {
+        if self.config.trace_execution {
+            info!(
+                bytecode = program.data.len(),
+                builtins = ?program.builtins,
+                args,
+                "Loaded program, execution and builtin segments, and syscall segment"
+            );
+        }
+    }
+
+    /// Logs why a run failed, if executions are traced.
+    pub fn trace_failure(&self, e: &impl Display) {
+        if self.config.trace_execution {
+            info!("Execution failed: {e}");
+        }
+    }
+
+    /// Logs the resources used by a run, excluding nested calls, if executions are traced.
+    pub fn trace_resources(&self, resources: &ExecutionResources) {
+        if self.config.trace_execution {
+            info!(
+                steps = resources.n_steps,
+                memory_holes = resources.n_memory_holes,
+                builtins = ?resources.builtin_instance_counter,
+                "Used resources"
+            );
+        }
+    }
+
+    fn storage_read(
+        &mut self,
+        vm: &mut VirtualMachine,
+        hint_data: &HintProcessorData,
+    ) -> Result<(), HintError> {
+        let syscall_ptr = get_ptr_from_var_name(
+            "syscall_ptr",
+            vm,
+            &hint_data.ids_data,
+            &hint_data.ap_tracking,
+        )?;
+        let key = vm.get_integer(&(syscall_ptr + 1))?.into_owned();
+        let value = self.storage.read(&self.contract_address, &key);
+        if self.config.trace_execution {
+            info!(%key, %value, "storage_read");
+        }
+
+        vm.insert_value(&(syscall_ptr + 2), value)?;
+        Ok(())
+    }
+
+    fn storage_write(
+        &mut self,
+        vm: &mut VirtualMachine,
+        hint_data: &HintProcessorData,
+    ) -> Result<(), HintError> {
+        let syscall_ptr = get_ptr_from_var_name(
+            "syscall_ptr",
+            vm,
+            &hint_data.ids_data,
+            &hint_data.ap_tracking,
+        )?;
+        let key = vm.get_integer(&(syscall_ptr + 1))?.into_owned();
+        let value = vm.get_integer(&(syscall_ptr + 2))?.into_owned();
+        if self.config.trace_execution {
+            info!(%key, %value, "storage_write");
+        }
+        if state::is_account_key(&key) {
+            return Err(HintError::CustomHint(format!(
+                "storage_write to key {} is forbidden, it holds an account's nonce or balance",
+                execution::felt_to_hex(&key)
+            )));
+        }
+
+        self.storage
+            .write(self.contract_address.clone(), key, value);
+        Ok(())
+    }
+
+    fn call_contract(
+        &mut self,
+        vm: &mut VirtualMachine,
+        hint_data: &HintProcessorData,
+    ) -> Result<(), HintError> {
+        let syscall_ptr = get_ptr_from_var_name(
+            "syscall_ptr",
+            vm,
+            &hint_data.ids_data,
+            &hint_data.ap_tracking,
+        )?;
+        let callee_address = vm.get_integer(&(syscall_ptr + 1))?.into_owned();
+        let selector = vm.get_integer(&(syscall_ptr + 2))?.into_owned();
+        let calldata = read_felts(vm, &(syscall_ptr + 3), &(syscall_ptr + 4))?;
+        if self.config.trace_execution {
+            info!(
+                contract = %execution::felt_to_hex(&callee_address),
+                selector = %execution::felt_to_hex(&selector),
+                calldata = calldata.len(),
+                "call_contract"
+            );
+        }
+
+        let retdata = self.execute_call(callee_address, &selector, calldata)?;
+
+        let retdata_ptr = vm.add_memory_segment();
+        for (i, value) in retdata.iter().enumerate() {
+            vm.insert_value(&(retdata_ptr + i), value.clone())?;
+        }
+        vm.insert_value(&(syscall_ptr + 5), Felt::new(retdata.len()))?;
+        vm.insert_value(&(syscall_ptr + 6), retdata_ptr)?;
+        Ok(())
+    }
+
+    fn emit_event(
+        &mut self,
+        vm: &mut VirtualMachine,
+        hint_data: &HintProcessorData,
+    ) -> Result<(), HintError> {
+        let syscall_ptr = get_ptr_from_var_name(
+            "syscall_ptr",
+            vm,
+            &hint_data.ids_data,
+            &hint_data.ap_tracking,
+        )?;
+        let keys = read_felts(vm, &(syscall_ptr + 1), &(syscall_ptr + 2))?;
+        let data = read_felts(vm, &(syscall_ptr + 3), &(syscall_ptr + 4))?;
+        if self.config.trace_execution {
+            info!(keys = keys.len(), data = data.len(), "emit_event");
+        }
+
+        self.events.push(ContractEvent {
+            from_address: self.contract_address.clone(),
+            keys,
+            data,
+        });
+        Ok(())
+    }
+
+    /// Runs the callee in its own VM, sharing this transaction's staged storage.
+    /// The callee receives a fresh syscall pointer followed by the calldata.
+    fn execute_call(
+        &mut self,
+        address: Felt,
+        selector: &Felt,
+        calldata: Vec<Felt>,
+    ) -> Result<Vec<Felt>, HintError> {
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(call_error("Maximum call depth exceeded"));
+        }
+
+        let program_str = self.classes.get(&address).ok_or_else(|| {
+            call_error(format!(
+                "No contract at address {}",
+                execution::felt_to_hex(&address)
+            ))
+        })?;
+        let program = self
+            .programs
+            .get_or_parse(&address, program_str)
+            .map_err(call_error)?;
+        self.config
+            .ensure_program_supported(&program)
+            .map_err(call_error)?;
+
+        let (function, entrypoint) = execution::find_entrypoint(&program, selector)
+            .ok_or_else(|| call_error("Entry point not found in callee"))?;
+
+        let mut callee = SyscallHandler {
+            builtin: BuiltinHintProcessor::new_empty(),
+            config: self.config,
+            storage: self.storage.fork(),
+            classes: self.classes,
+            programs: self.programs,
+            contract_address: address,
+            events: Vec::new(),
+            gas_used: 0,
+            resources: Resources::default(),
+            depth: self.depth + 1,
+            steps: self.steps.clone(),
+        };
+        let _span = callee.trace_span(&function);
+
+        let mut vm = VirtualMachine::new(false);
+        let mut cairo_runner = CairoRunner::new(&program, "all", false).map_err(call_error)?;
+        cairo_runner
+            .initialize_builtins(&mut vm)
+            .map_err(call_error)?;
+        cairo_runner.initialize_segments(&mut vm, None);
+        let syscall_ptr = vm.add_memory_segment();
+
+        let stack: Vec<MaybeRelocatable> = std::iter::once(MaybeRelocatable::from(syscall_ptr))
+            .chain(calldata.into_iter().map(MaybeRelocatable::from))
+            .collect();
+        callee.trace_loaded(&program, stack.len());
+
+        let result = match callee.step_budget() {
+            Some(budget) => execution::run_bounded(
+                &mut cairo_runner,
+                entrypoint,
+                stack,
+                &budget,
+                &mut vm,
+                &mut callee,
+            )
+            .map_err(|e| {
+                callee.trace_failure(&e);
+                call_error(e)
+            }),
+            None => {
+                let args: Vec<CairoArg> = stack.into_iter().map(CairoArg::from).collect();
+                let args: Vec<&CairoArg> = args.iter().collect();
+                cairo_runner
+                    .run_from_entrypoint(entrypoint, &args, false, &mut vm, &mut callee)
+                    .map_err(|e| {
+                        callee.trace_failure(&e);
+                        call_error(e)
+                    })
+            }
+        };
+
+        // the staged writes are handed back even if the call failed, the whole
+        // transaction is discarded in that case anyway
+        self.storage.join(callee.storage);
+        result?;
+        self.events.extend(callee.events);
+
+        let resources = cairo_runner
+            .get_execution_resources(&vm)
+            .map_err(call_error)?;
+        callee.trace_resources(&resources);
+        self.gas_used += execution::gas_cost(&resources) + callee.gas_used;
+        self.resources.add_run(&resources);
+        self.resources.add(&callee.resources);
+
+        vm.get_return_values(execution::return_size(&program, &function))
+            .map_err(call_error)?
+            .into_iter()
+            .map(|value| match value {
+                MaybeRelocatable::Int(value) => Ok(value),
+                MaybeRelocatable::RelocatableValue(_) => {
+                    Err(call_error("Callee returned a pointer"))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Reads an array passed as a `(len, ptr)` pair of struct members.
+fn read_felts(
+    vm: &VirtualMachine,
+    len_addr: &Relocatable,
+    ptr_addr: &Relocatable,
+) -> Result<Vec<Felt>, HintError> {
+    let len = vm
+        .get_integer(len_addr)?
+        .to_usize()
+        .ok_or(HintError::BigintToUsizeFail)?;
+    let ptr = vm.get_relocatable(ptr_addr)?;
+
+    (0..len)
+        .map(|i| vm.get_integer(&(ptr + i)).map(Cow::into_owned))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(HintError::from)
+}
+
+fn call_error(e: impl Display) -> HintError {
+    HintError::CustomHint(format!("call_contract failed: {e}"))
+}
+
+impl HintProcessor for SyscallHandler<'_> {
+    fn execute_hint(
+        &mut self,
+        vm: &mut VirtualMachine,
+        exec_scopes: &mut ExecutionScopes,
+        hint_data: &Box<dyn Any>,
+        constants: &HashMap<String, Felt>,
+    ) -> Result<(), HintError> {
+        let data = hint_data
+            .downcast_ref::<HintProcessorData>()
+            .ok_or(HintError::WrongHintData)?;
+
+        match data.code.as_str() {
+            STORAGE_READ_HINT => self.storage_read(vm, data),
+            STORAGE_WRITE_HINT => self.storage_write(vm, data),
+            CALL_CONTRACT_HINT => self.call_contract(vm, data),
+            EMIT_EVENT_HINT => self.emit_event(vm, data),
+            _ => self
+                .builtin
+                .execute_hint(vm, exec_scopes, hint_data, constants),
+        }
+    }
+
+    fn compile_hint(
+        &self,
+        hint_code: &str,
+        ap_tracking_data: &ApTracking,
+        reference_ids: &HashMap<String, usize>,
+        references: &HashMap<usize, HintReference>,
+    ) -> Result<Box<dyn Any>, VirtualMachineError> {
+        self.builtin
+            .compile_hint(hint_code, ap_tracking_data, reference_ids, references)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color_eyre::Result;
+
+    use super::*;
+    use crate::state::ContractStorage;
+    use crate::transaction::{EntryPoint, Execution, TransactionType};
+
+    /// Example contract making every syscall, see `examples/programs/syscalls.cairo`.
+    const PROGRAM: &str = include_str!("../../examples/programs/syscalls.json");
+
+    /// Executes `function` of the example contract against `storage`. The contract is
+    /// registered, so it can call itself.
+    fn execute(
+        storage: &ContractStorage,
+        function: &str,
+        calldata: Vec<Felt>,
+    ) -> Result<Execution> {
+        let mut classes = ClassRegistry::default();
+        classes.register(PROGRAM);
+        TransactionType::FunctionExecution {
+            program: PROGRAM.into(),
+            function: EntryPoint::Name(function.to_string()),
+            program_name: "syscalls.json".to_string(),
+            enable_trace: false,
+            calldata,
+        }
+        .execute(
+            &ExecutionConfig::default(),
+            TxStorage::new(storage),
+            &classes,
+            &ProgramCache::default(),
+        )
+    }
+
+    #[test]
+    fn storage_syscalls_stage_writes_and_record_reads() {
+        let contract = state::class_hash(PROGRAM);
+        let mut storage = ContractStorage::default();
+        storage.apply(StorageWrites::from([(
+            (contract.clone(), Felt::new(5)),
+            Felt::new(7),
+        )]));
+
+        let read = execute(&storage, "read", vec![Felt::new(5)]).unwrap();
+        assert!(read.storage_writes.is_empty());
+        assert!(read
+            .storage_reads
+            .contains(&(contract.clone(), Felt::new(5))));
+
+        let write = execute(&storage, "write", vec![Felt::new(6), Felt::new(8)]).unwrap();
+        assert_eq!(
+            write.storage_writes,
+            StorageWrites::from([((contract.clone(), Felt::new(6)), Felt::new(8))])
+        );
+        // the writes are only staged, the caller applies them
+        assert_eq!(storage.read(&contract, &Felt::new(6)), Felt::new(0));
+    }
+
+    #[test]
+    fn storage_writes_to_account_keys_are_forbidden() {
+        let storage = ContractStorage::default();
+        for key in ["nonce", "balance"] {
+            let key = execution::entry_point_selector(key);
+            assert!(execute(&storage, "write", vec![key, Felt::new(1)]).is_err());
+        }
+    }
+
+    #[test]
+    fn events_are_emitted_from_the_contract() {
+        let execution = execute(
+            &ContractStorage::default(),
+            "emit",
+            vec![Felt::new(1), Felt::new(2)],
+        )
+        .unwrap();
+        assert_eq!(
+            execution.events,
+            vec![ContractEvent {
+                from_address: state::class_hash(PROGRAM),
+                keys: vec![Felt::new(1)],
+                data: vec![Felt::new(2)],
+            }]
+        );
+    }
+
+    #[test]
+    fn called_contracts_share_the_transaction_writes_events_and_gas() {
+        let contract = state::class_hash(PROGRAM);
+        let storage = ContractStorage::default();
+        let call = |function: &str| {
+            let selector = execution::entry_point_selector(function);
+            let calldata = vec![contract.clone(), selector, Felt::new(3), Felt::new(4)];
+            execute(&storage, "call", calldata).unwrap()
+        };
+
+        let write = call("write");
+        assert_eq!(
+            write.storage_writes,
+            StorageWrites::from([((contract.clone(), Felt::new(3)), Felt::new(4))])
+        );
+        let direct = execute(&storage, "write", vec![Felt::new(3), Felt::new(4)]).unwrap();
+        assert!(write.gas_used > direct.gas_used);
+
+        let emit = call("emit");
+        assert_eq!(emit.events.len(), 1);
+        assert_eq!(emit.events[0].from_address, contract);
+    }
+
+    #[test]
+    fn calls_to_unknown_contracts_or_entry_points_fail() {
+        let contract = state::class_hash(PROGRAM);
+        let storage = ContractStorage::default();
+        let write = execution::entry_point_selector("write");
+        let missing = execution::entry_point_selector("missing");
+
+        let unknown_contract = vec![Felt::new(1234), write, Felt::new(3), Felt::new(4)];
+        assert!(execute(&storage, "call", unknown_contract).is_err());
+        let unknown_entry_point = vec![contract, missing, Felt::new(3), Felt::new(4)];
+        assert!(execute(&storage, "call", unknown_entry_point).is_err());
+    }
+}
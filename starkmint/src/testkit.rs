@@ -0,0 +1,197 @@
+//! In-process harness for end-to-end tests: a [`TestChain`] runs a [`LocalChain`] in a temporary
+//! data dir, so whole blocks can be executed and their state and app hashes asserted on without
+//! running a Tendermint node.
+//!
+//! Tests build a chain from a genesis, execute blocks of transactions with
+//! [`TestChain::execute_block`], then compare the committed state and app hashes with the
+//! expected ones, e.g. those of a previous run to check execution is deterministic.
+
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use cairo_felt::Felt;
+use tendermint::abci::request::{self, CheckTxKind, Request};
+use tendermint::abci::{response, Response};
+use tower_abci::BoxError;
+use uuid::Uuid;
+
+use crate::app::StarknetApp;
+use crate::execution::{felt_to_hex, ExecutionConfig};
+use crate::genesis::Genesis;
+pub use crate::local::BlockResult;
+use crate::local::LocalChain;
+use crate::transaction::Transaction;
+
+/// Chain ID of test chains, unless their app is configured with one.
+pub const DEFAULT_CHAIN_ID: &str = "starkmint-test";
+
+/// [`LocalChain`] keeping its data in a temporary directory, removed when the chain is dropped.
+/// Methods panic if the app fails to handle a request, as it would crash a node.
+pub struct TestChain {
+    chain: LocalChain,
+    data_dir: PathBuf,
+    /// Transactions of the committed blocks, replayed when the app is restarted.
+    blocks: Vec<Vec<Bytes>>,
+}
+
+impl TestChain {
+    /// Chain initialized with `genesis`, run by an app with the default settings apart from
+    /// the chain parameters of the genesis.
+    pub fn new(genesis: &Genesis) -> Self {
+        let execution = ExecutionConfig {
+            gas_price: genesis.parameters.gas_price,
+            fee_recipient: genesis.parameters.fee_recipient.clone(),
+            max_tx_gas: genesis.parameters.max_tx_gas,
+            ..Default::default()
+        };
+        Self::with_app(StarknetApp::new().with_execution_config(execution), genesis)
+    }
+
+    /// Chain initialized with `genesis`, run by `app`. Its data directory is replaced by a
+    /// temporary one.
+    pub fn with_app(app: StarknetApp, genesis: &Genesis) -> Self {
+        let data_dir = std::env::temp_dir().join(format!("starkmint-testkit-{}", Uuid::new_v4()));
+        let chain_id = app
+            .chain_id()
+            .unwrap_or_else(|| DEFAULT_CHAIN_ID.to_string());
+        let chain = LocalChain::new(app.with_data_dir(&data_dir), chain_id, genesis)
+            .expect("the app must handle InitChain");
+        Self {
+            chain,
+            data_dir,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Restarts the chain with `app`, which reads the data directory of the previous one, like
+    /// Tendermint restarts a node: the chain is initialized again, then its blocks are
+    /// replayed. Fails if the app refuses to initialize the chain; panics if a replayed block
+    /// is committed with another app hash.
+    pub fn restart(&mut self, app: StarknetApp) -> Result<(), BoxError> {
+        let app_hash = self.chain.app_hash().to_vec();
+        self.chain.restart(app.with_data_dir(&self.data_dir))?;
+
+        for txs in std::mem::take(&mut self.blocks) {
+            self.execute_raw_block(txs);
+        }
+        self.assert_app_hash(&app_hash);
+        Ok(())
+    }
+
+    /// Directory the app keeps its files in, e.g. to start another app from them.
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// App run by the chain, whose committed state can be read directly.
+    pub fn app(&self) -> &StarknetApp {
+        self.chain.app()
+    }
+
+    /// Height of the last committed block, 0 before the first one.
+    pub fn height(&self) -> u64 {
+        self.chain.height()
+    }
+
+    /// App hash of the last committed block, empty before the first one.
+    pub fn app_hash(&self) -> &[u8] {
+        self.chain.app_hash()
+    }
+
+    /// Checks a transaction as if it was just submitted to the mempool.
+    pub fn check_tx(&mut self, tx: &Transaction) -> response::CheckTx {
+        self.check_raw_tx(encode(tx))
+    }
+
+    /// Checks bytes as if they were just submitted to the mempool, e.g. to test how the app
+    /// handles malformed transactions.
+    pub fn check_raw_tx(&mut self, tx: Bytes) -> response::CheckTx {
+        match self.chain.call(check_tx_request(tx)) {
+            Response::CheckTx(response) => response,
+            response => panic!("Unexpected response to CheckTx: {response:?}"),
+        }
+    }
+
+    /// Checks transactions submitted at once, as Tendermint sends CheckTx requests without
+    /// waiting for the previous responses, so the app checks them concurrently. Returns the
+    /// responses in order.
+    pub fn check_txs(&mut self, txs: &[Transaction]) -> Vec<response::CheckTx> {
+        let requests = txs.iter().map(|tx| check_tx_request(encode(tx))).collect();
+        self.chain
+            .call_concurrently(requests)
+            .into_iter()
+            .map(|response| match response {
+                Response::CheckTx(response) => response,
+                response => panic!("Unexpected response to CheckTx: {response:?}"),
+            })
+            .collect()
+    }
+
+    /// Executes and commits a block made of `txs`, in order.
+    pub fn execute_block(&mut self, txs: &[Transaction]) -> BlockResult {
+        self.execute_raw_block(txs.iter().map(encode).collect())
+    }
+
+    /// Executes and commits a block made of `txs` as they would be included by Tendermint,
+    /// i.e. without being checked first.
+    pub fn execute_raw_block(&mut self, txs: Vec<Bytes>) -> BlockResult {
+        self.blocks.push(txs.clone());
+        self.chain.execute_block(txs)
+    }
+
+    /// Panics unless the committed value of a storage slot is `expected`.
+    pub fn assert_storage(&self, contract_address: &Felt, key: &Felt, expected: &Felt) {
+        let value = self.chain.app().committed_storage(contract_address, key);
+        assert_eq!(
+            &value,
+            expected,
+            "Storage slot {} of {} is {}, expected {}",
+            felt_to_hex(key),
+            felt_to_hex(contract_address),
+            felt_to_hex(&value),
+            felt_to_hex(expected)
+        );
+    }
+
+    /// Panics unless the committed nonce of an account is `expected`.
+    pub fn assert_nonce(&self, account: &Felt, expected: u64) {
+        let nonce = self.chain.app().committed_nonce(account);
+        assert_eq!(
+            nonce,
+            expected,
+            "Nonce of {} is {nonce}, expected {expected}",
+            felt_to_hex(account)
+        );
+    }
+
+    /// Panics unless the last block was committed with `expected` as app hash.
+    pub fn assert_app_hash(&self, expected: &[u8]) {
+        assert_eq!(
+            self.app_hash(),
+            expected,
+            "App hash at height {} is {}, expected {}",
+            self.height(),
+            hex::encode(self.app_hash()),
+            hex::encode(expected)
+        );
+    }
+}
+
+impl Drop for TestChain {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+fn encode(tx: &Transaction) -> Bytes {
+    bincode::serialize(tx)
+        .expect("transactions can be encoded")
+        .into()
+}
+
+fn check_tx_request(tx: Bytes) -> Request {
+    Request::CheckTx(request::CheckTx {
+        tx,
+        kind: CheckTxKind::New,
+    })
+}
@@ -1,9 +1,19 @@
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use cairo_felt::Felt;
 use cairo_vm::{
-    hint_processor::builtin_hint_processor::builtin_hint_processor_definition::BuiltinHintProcessor,
-    types::{program::Program, relocatable::MaybeRelocatable},
-    vm::{runners::cairo_runner::CairoRunner, vm_core::VirtualMachine},
+    types::relocatable::MaybeRelocatable,
+    vm::{
+        runners::cairo_runner::{CairoArg, CairoRunner},
+        vm_core::VirtualMachine,
+    },
 };
-use color_eyre::eyre::{ensure, ContextCompat};
+use color_eyre::eyre::{ensure, eyre, WrapErr};
 use color_eyre::Result;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -11,21 +21,106 @@ use sha2::{Digest, Sha256};
 use tracing::info;
 use uuid::Uuid;
 
+use crate::cache::{Invocation, ProgramCache};
+use crate::execution::{self, ContractEvent, ExecutionConfig, Resources};
+use crate::messaging::L1Message;
+use crate::signature::{self, SignedCall, SignedTransaction, TxSignature};
+use crate::state::{self, ClassRegistry, ContractStorage, StorageReads, StorageWrites, TxStorage};
+use crate::syscalls::SyscallHandler;
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Transaction {
     pub id: String,
     pub transaction_hash: String, // this acts
     pub transaction_type: TransactionType,
+    /// Signature of the sender, for transactions sent from an account.
+    pub signature: Option<TxSignature>,
 }
 
+/// Function to invoke, given by name or by its Starknet selector.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+pub enum EntryPoint {
+    Name(String),
+    Selector(Felt),
+}
+
+impl EntryPoint {
+    /// Selector of the function, as the names of functions are resolved to theirs.
+    pub fn selector(&self) -> Felt {
+        match self {
+            EntryPoint::Name(name) => execution::entry_point_selector(name),
+            EntryPoint::Selector(selector) => selector.clone(),
+        }
+    }
+}
+
+impl FromStr for EntryPoint {
+    type Err = color_eyre::Report;
+
+    /// `0x`-prefixed values are selectors, anything else is a function name.
+    fn from_str(s: &str) -> Result<Self> {
+        if s.starts_with("0x") {
+            execution::parse_felt(s).map(EntryPoint::Selector)
+        } else {
+            Ok(EntryPoint::Name(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for EntryPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryPoint::Name(name) => f.write_str(name),
+            EntryPoint::Selector(selector) => f.write_str(&execution::felt_to_hex(selector)),
+        }
+    }
+}
+
+/// Largest program a compressed payload may decompress to, so a small transaction can't make
+/// nodes allocate arbitrary amounts of memory.
+pub const MAX_PROGRAM_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Most calldata values a function execution may carry.
+pub const MAX_CALLDATA_LEN: usize = 4096;
+
+/// zstd level programs are compressed with.
+const COMPRESSION_LEVEL: i32 = 19;
+
+/// Type and payload of a transaction. Programs can also be sent compressed, in which case
+/// they're decompressed as the transaction is decoded.
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(try_from = "WireType", into = "WireType")]
 pub enum TransactionType {
     FunctionExecution {
-        program: String,
-        function: String,
+        /// Compiled program JSON. Shared so that cloning a transaction, or queueing it for
+        /// parallel execution, doesn't copy the program.
+        program: Arc<str>,
+        function: EntryPoint,
+        program_name: String,
+        enable_trace: bool,
+        /// Explicit arguments of the function. The syscall pointer is passed before them.
+        calldata: Vec<Felt>,
+    },
+    ProofVerification {
+        /// Encoded the same way as a `Vec<u8>`, but decoded as a single buffer.
+        proof: Bytes,
+        public_inputs: Vec<String>,
+    },
+    /// Function execution of a program already registered on chain, referenced by its class
+    /// hash instead of being sent in full. It hashes the same as the equivalent
+    /// `FunctionExecution`, since the program itself isn't hashed, only its trace. Declared
+    /// last so the encoding of the other variants is unchanged.
+    ClassExecution {
+        class_hash: Felt,
+        function: EntryPoint,
         program_name: String,
         enable_trace: bool,
+        calldata: Vec<Felt>,
     },
+    /// Handling of a message sent from L1, executed like the [`L1Message::handler_call`]
+    /// function execution, which it hashes the same as. Only the L1 relayer set at genesis may
+    /// send it, and each message is handled once.
+    L1Handler(L1Message),
 }
 
 impl Transaction {
@@ -34,9 +129,219 @@ impl Transaction {
             transaction_hash: transaction_type.compute_and_hash()?,
             transaction_type,
             id: Uuid::new_v4().to_string(),
+            signature: None,
         })
     }
 
+    /// Transaction handling an L1 message, hashed against `program`, the program registered at
+    /// the message's recipient.
+    pub fn l1_handler(message: L1Message, program: &str) -> Result<Transaction> {
+        let TransactionType::ClassExecution {
+            function,
+            program_name,
+            enable_trace,
+            calldata,
+            ..
+        } = message.handler_call()
+        else {
+            unreachable!("L1 handlers are class executions");
+        };
+        let execution = TransactionType::FunctionExecution {
+            program: program.into(),
+            function,
+            program_name,
+            enable_trace,
+            calldata,
+        };
+
+        Ok(Transaction {
+            transaction_hash: execution.compute_and_hash()?,
+            transaction_type: TransactionType::L1Handler(message),
+            id: Uuid::new_v4().to_string(),
+            signature: None,
+        })
+    }
+
+    /// Content of the transaction its sender signs for the chain `chain_id` (see
+    /// [`signature::chain_id_hash`]): its id, the hash of its execution and the call it makes,
+    /// so everything its receipt hash commits to is signed.
+    pub fn signed_content(&self, chain_id: Felt) -> SignedTransaction<'_> {
+        let call = match &self.transaction_type {
+            TransactionType::FunctionExecution {
+                program,
+                function,
+                calldata,
+                ..
+            } => SignedCall::Invoke {
+                class_hash: state::class_hash(program),
+                selector: function.selector(),
+                calldata,
+            },
+            TransactionType::ClassExecution {
+                class_hash,
+                function,
+                calldata,
+                ..
+            } => SignedCall::Invoke {
+                class_hash: class_hash.clone(),
+                selector: function.selector(),
+                calldata,
+            },
+            TransactionType::ProofVerification {
+                proof,
+                public_inputs,
+            } => SignedCall::ProofVerification {
+                proof,
+                public_inputs,
+            },
+            TransactionType::L1Handler(message) => SignedCall::L1Handler {
+                message_hash: message.hash(),
+            },
+        };
+        SignedTransaction {
+            chain_id,
+            id: &self.id,
+            transaction_hash: &self.transaction_hash,
+            call,
+        }
+    }
+
+    /// Signs the transaction for the chain `chain_id` as the account owning `private_key`, at
+    /// the given nonce, paying `tip` for it to be prioritized and at most `max_fee` for its gas.
+    pub fn sign(
+        &mut self,
+        private_key: &Felt,
+        chain_id: &str,
+        nonce: u64,
+        tip: u64,
+        max_fee: u64,
+    ) -> Result<()> {
+        let content = self.signed_content(signature::chain_id_hash(chain_id));
+        self.signature = Some(signature::sign(private_key, &content, nonce, tip, max_fee)?);
+        Ok(())
+    }
+
+    /// Signs the transaction for the chain `chain_id` on behalf of an account contract, with a
+    /// key its `__validate__` entry point accepts. If the fee and tip are paid by a paymaster,
+    /// it must then [`sponsor`](Self::sponsor) the transaction.
+    #[allow(clippy::too_many_arguments)]
+    pub fn sign_as(
+        &mut self,
+        account: &Felt,
+        private_key: &Felt,
+        chain_id: &str,
+        nonce: u64,
+        tip: u64,
+        max_fee: u64,
+        paymaster: Option<&Felt>,
+    ) -> Result<()> {
+        let content = self.signed_content(signature::chain_id_hash(chain_id));
+        self.signature = Some(signature::sign_as(
+            account.clone(),
+            private_key,
+            &content,
+            nonce,
+            tip,
+            max_fee,
+            paymaster,
+        )?);
+        Ok(())
+    }
+
+    /// Has `paymaster` pay the fee and tip of the transaction signed for the chain `chain_id`
+    /// in place of its sender, signing with a key its account accepts. The sender must have
+    /// signed it with this paymaster.
+    pub fn sponsor(&mut self, paymaster: &Felt, private_key: &Felt, chain_id: &str) -> Result<()> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or_else(|| eyre!("Only signed transactions can be sponsored"))?;
+        let content = self.signed_content(signature::chain_id_hash(chain_id));
+        let sponsorship = signature::sponsor(paymaster.clone(), private_key, &content, signature)?;
+        if let Some(signature) = self.signature.as_mut() {
+            signature.paymaster = Some(sponsorship);
+        }
+        Ok(())
+    }
+
+    /// Tip paid by the sender or its paymaster, zero for unsigned transactions.
+    pub fn tip(&self) -> u64 {
+        self.signature.as_ref().map_or(0, |signature| signature.tip)
+    }
+
+    /// Digest identifying the transaction by its id and hash, so the same transaction can't
+    /// be included twice, while the same call can still be sent again as a new transaction.
+    /// Both are signed, so only the sender of a signed transaction can send it under another
+    /// id.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        // prefixed with its length, so no other id and hash hash the same
+        hasher.update((self.id.len() as u64).to_be_bytes());
+        hasher.update(&self.id);
+        hasher.update(&self.transaction_hash);
+        hasher.finalize().into()
+    }
+
+    /// Hash identifying the transaction on chain, the hex of its [`digest`](Self::digest).
+    /// Unlike `transaction_hash`, two transactions making the same call hash differently, so
+    /// this is the hash receipts are indexed under (as `app.hash`, whether the transaction
+    /// succeeded or not) and the one the Starknet APIs report.
+    pub fn hash(&self) -> String {
+        hex::encode(self.digest())
+    }
+
+    /// Equivalent transaction referencing its program by class hash, to be sent once the
+    /// program is registered on chain. The hash, and so the signature, are unchanged.
+    pub fn referencing_class(mut self) -> Transaction {
+        if let TransactionType::FunctionExecution {
+            program,
+            function,
+            program_name,
+            enable_trace,
+            calldata,
+        } = self.transaction_type
+        {
+            self.transaction_type = TransactionType::ClassExecution {
+                class_hash: state::class_hash(&program),
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            };
+        }
+        self
+    }
+
+    /// Encodes the transaction with its program, if any, compressed with zstd. Nodes decode
+    /// it back to the same transaction.
+    pub fn encode_compressed(&self) -> Result<Vec<u8>> {
+        let TransactionType::FunctionExecution {
+            program,
+            function,
+            program_name,
+            enable_trace,
+            calldata,
+        } = &self.transaction_type
+        else {
+            return Ok(bincode::serialize(self)?);
+        };
+
+        let program = zstd::encode_all(program.as_bytes(), COMPRESSION_LEVEL)?;
+        let transaction = CompressedTransaction {
+            id: &self.id,
+            transaction_hash: &self.transaction_hash,
+            transaction_type: WireType::CompressedFunctionExecution {
+                program: program.into(),
+                function: function.clone(),
+                program_name: program_name.clone(),
+                enable_trace: *enable_trace,
+                calldata: calldata.clone(),
+            },
+            signature: &self.signature,
+        };
+        Ok(bincode::serialize(&transaction)?)
+    }
+
     /// Verify that the transaction id is consistent with its contents, by checking its sha256 hash.
     pub fn verify(&self) -> Result<()> {
         ensure!(
@@ -48,45 +353,229 @@ impl Transaction {
     }
 }
 
+/// Outcome of executing a transaction against the application state.
+#[derive(Clone, Debug)]
+pub struct Execution {
+    /// Hash of the execution, which must match the transaction hash.
+    pub hash: String,
+    /// Storage writes staged by the transaction, to be applied if it is accepted.
+    pub storage_writes: StorageWrites,
+    /// Storage slots read by the transaction, used to detect conflicts between transactions
+    /// executed concurrently.
+    pub storage_reads: StorageReads,
+    /// Events emitted by the transaction and the contracts it called.
+    pub events: Vec<ContractEvent>,
+    /// Values returned by the invoked function.
+    pub retdata: Vec<Felt>,
+    /// Gas consumed by the execution, including nested calls.
+    pub gas_used: u64,
+    /// Fee charged for the gas used, at the chain's gas price, once the transaction is
+    /// applied. Zero for unsigned and L1 handler transactions, which no one pays for on L2.
+    pub fee: u64,
+    /// Steps and builtins used by the execution, including nested calls.
+    pub resources: Resources,
+    /// Time spent executing the transaction, which is next to nothing when memoized.
+    pub duration: Duration,
+}
+
 impl TransactionType {
+    /// Executes the transaction with every builtin enabled and hashes the result.
     pub fn compute_and_hash(&self) -> Result<String> {
+        self.compute_and_hash_with(&ExecutionConfig::default())
+    }
+
+    /// Executes the transaction under the given node configuration, against empty storage,
+    /// and hashes the result.
+    pub fn compute_and_hash_with(&self, config: &ExecutionConfig) -> Result<String> {
+        self.execute(
+            config,
+            TxStorage::new(&ContractStorage::default()),
+            &ClassRegistry::default(),
+            &ProgramCache::default(),
+        )
+        .map(|execution| execution.hash)
+    }
+
+    /// Executes the transaction against the given view of contract storage, resolving called
+    /// contracts from `classes`. Storage is not modified; the writes are returned so the caller
+    /// decides whether to apply them. Parsed programs are taken from (and added to) `programs`.
+    ///
+    /// Note the hash covers the register trace, so storage values only affect it through
+    /// control flow.
+    ///
+    /// If `programs` memoizes executions, the result of an identical earlier function
+    /// execution is returned instead when it's still valid, see [`ProgramCache::memoized`].
+    pub fn execute(
+        &self,
+        config: &ExecutionConfig,
+        storage: TxStorage<'_>,
+        classes: &ClassRegistry,
+        programs: &ProgramCache,
+    ) -> Result<Execution> {
+        let started = Instant::now();
+        let mut execution = self.memoized_run(config, storage, classes, programs)?;
+        execution.duration = started.elapsed();
+        Ok(execution)
+    }
+
+    fn memoized_run(
+        &self,
+        config: &ExecutionConfig,
+        storage: TxStorage<'_>,
+        classes: &ClassRegistry,
+        programs: &ProgramCache,
+    ) -> Result<Execution> {
+        // memoized executions aren't run, so there'd be nothing to trace, and a bounded
+        // execution must run to know whether it stays within its steps. Those only bounded by
+        // `max_tx_gas` are memoized: the bound is the same on every node, and DeliverTx checks
+        // their gas against it
+        if !programs.memoizes_executions() || config.trace_execution || config.max_steps.is_some() {
+            return self.run(config, storage, classes, programs);
+        }
+        let invocation = match self {
+            TransactionType::FunctionExecution {
+                function,
+                enable_trace,
+                calldata,
+                ..
+            }
+            | TransactionType::ClassExecution {
+                function,
+                enable_trace,
+                calldata,
+                ..
+            } => Invocation {
+                class_hash: self.program(classes)?.0,
+                function: function.clone(),
+                calldata: calldata.clone(),
+                enable_trace: *enable_trace,
+            },
+            TransactionType::ProofVerification { .. } => {
+                return self.run(config, storage, classes, programs)
+            }
+            TransactionType::L1Handler(message) => {
+                return message
+                    .handler_call()
+                    .memoized_run(config, storage, classes, programs)
+            }
+        };
+
+        if let Some(execution) = programs.memoized(&invocation, &storage) {
+            return Ok(execution);
+        }
+        let view = storage.view();
+        let execution = self.run(config, storage, classes, programs)?;
+        programs.memoize(invocation, &execution, &view);
+        Ok(execution)
+    }
+
+    fn run(
+        &self,
+        config: &ExecutionConfig,
+        storage: TxStorage<'_>,
+        classes: &ClassRegistry,
+        programs: &ProgramCache,
+    ) -> Result<Execution> {
         let mut hasher = Sha256::new();
+        let mut storage_writes = StorageWrites::new();
+        let mut storage_reads = StorageReads::new();
+        let mut events = Vec::new();
+        let mut retdata = Vec::new();
+        let mut resources = Resources::default();
+        let gas_used;
 
         match self {
             TransactionType::FunctionExecution {
-                program: program_str,
                 function,
-                program_name: _,
                 enable_trace: execute_trace,
+                calldata,
+                ..
+            }
+            | TransactionType::ClassExecution {
+                function,
+                enable_trace: execute_trace,
+                calldata,
+                ..
             } => {
-                let program = Program::from_reader(program_str.as_bytes(), None)?;
+                let (class_hash, program_str) = self.program(classes)?;
+                let program = programs.get_or_parse(&class_hash, program_str)?;
+                config.ensure_program_supported(&program)?;
+                let (function, entrypoint) =
+                    execution::validate_invocation(&program, function, calldata)?;
+
                 let mut vm = VirtualMachine::new(*execute_trace);
 
                 let mut cairo_runner = CairoRunner::new(&program, "all", false)?;
 
-                let mut hint_processor = BuiltinHintProcessor::new_empty();
-
-                let entrypoint = program
-                    .identifiers
-                    .get(&format!("__main__.{function}"))
-                    .and_then(|x| x.pc)
-                    .context("Error geting entrypoint function")?;
+                let mut hint_processor =
+                    SyscallHandler::new(config, storage, classes, programs, class_hash);
+                let _span = hint_processor.trace_span(&function);
 
                 cairo_runner.initialize_builtins(&mut vm)?;
                 cairo_runner.initialize_segments(&mut vm, None);
+                let syscall_ptr = vm.add_memory_segment();
+
+                let stack: Vec<MaybeRelocatable> =
+                    std::iter::once(MaybeRelocatable::from(syscall_ptr))
+                        .chain(calldata.iter().cloned().map(MaybeRelocatable::from))
+                        .collect();
+
+                hint_processor.trace_loaded(&program, stack.len());
+
+                match hint_processor.step_budget() {
+                    Some(budget) => execution::run_bounded(
+                        &mut cairo_runner,
+                        entrypoint,
+                        stack,
+                        &budget,
+                        &mut vm,
+                        &mut hint_processor,
+                    )
+                    .map_err(|e| {
+                        hint_processor.trace_failure(&e);
+                        // calls that ran out of steps fail with a hint error
+                        if budget.exhausted() {
+                            budget.error().into()
+                        } else {
+                            e
+                        }
+                    })?,
+                    None => {
+                        let args: Vec<CairoArg> = stack.into_iter().map(CairoArg::from).collect();
+                        cairo_runner
+                            .run_from_entrypoint(
+                                entrypoint,
+                                &args.iter().collect::<Vec<_>>(),
+                                false,
+                                &mut vm,
+                                &mut hint_processor,
+                            )
+                            .map_err(|e| {
+                                hint_processor.trace_failure(&e);
+                                e
+                            })?
+                    }
+                }
+
+                retdata = vm
+                    .get_return_values(execution::return_size(&program, &function))?
+                    .into_iter()
+                    .map(|value| match value {
+                        MaybeRelocatable::Int(value) => Ok(value),
+                        MaybeRelocatable::RelocatableValue(_) => {
+                            Err(eyre!("Function {function} returned a pointer"))
+                        }
+                    })
+                    .collect::<Result<_>>()?;
 
-                cairo_runner.run_from_entrypoint(
-                    entrypoint,
-                    &[
-                        &MaybeRelocatable::from(2).into(),
-                        &MaybeRelocatable::from((2, 0)).into(),
-                    ],
-                    false,
-                    &mut vm,
-                    &mut hint_processor,
-                )?;
                 cairo_runner.relocate(&mut vm).unwrap();
 
+                let run = cairo_runner.get_execution_resources(&vm)?;
+                hint_processor.trace_resources(&run);
+                gas_used = execution::gas_cost(&run) + hint_processor.gas_used();
+                resources.add_run(&run);
+                resources.add(hint_processor.resources());
+
                 let trace = cairo_runner.relocated_trace;
 
                 match trace {
@@ -98,10 +587,293 @@ impl TransactionType {
                     None => info!("Trace not enabled, not executing/hashing"),
                 }
                 hasher.update(function);
+
+                (storage_writes, storage_reads, events) = hint_processor.finish();
+            }
+            TransactionType::ProofVerification {
+                proof,
+                public_inputs,
+            } => {
+                config
+                    .proof_verifier
+                    .as_ref()
+                    .ok_or_else(|| {
+                        eyre!("No STARK verifier is configured, proofs can't be verified")
+                    })?
+                    .verify(proof, public_inputs)
+                    .wrap_err("Invalid proof")?;
+
+                hasher.update(proof);
+                for input in public_inputs {
+                    hasher.update(input);
+                }
+
+                // verification cost grows with the size of the proof
+                gas_used = proof.len() as u64;
+            }
+            TransactionType::L1Handler(message) => {
+                return message
+                    .handler_call()
+                    .run(config, storage, classes, programs)
             }
         }
 
         let hash = hasher.finalize().as_slice().to_owned();
-        Ok(hex::encode(hash))
+        Ok(Execution {
+            hash: hex::encode(hash),
+            storage_writes,
+            storage_reads,
+            events,
+            retdata,
+            gas_used,
+            fee: 0,
+            resources,
+            duration: Duration::ZERO,
+        })
+    }
+
+    /// Whether the transaction is a function or class execution, which must be signed on
+    /// chains funding accounts.
+    pub fn is_execution(&self) -> bool {
+        matches!(
+            self,
+            TransactionType::FunctionExecution { .. } | TransactionType::ClassExecution { .. }
+        )
+    }
+
+    /// Class hash of the program executed by the transaction, if it executes one.
+    pub fn class_hash(&self) -> Option<Felt> {
+        match self {
+            TransactionType::FunctionExecution { program, .. } => Some(state::class_hash(program)),
+            TransactionType::ClassExecution { class_hash, .. } => Some(class_hash.clone()),
+            TransactionType::ProofVerification { .. } => None,
+            TransactionType::L1Handler(message) => Some(message.to_address.clone()),
+        }
+    }
+
+    /// Class hash and compiled program executed by the transaction, looking up programs
+    /// referenced by class hash in `classes`.
+    fn program<'a>(&'a self, classes: &'a ClassRegistry) -> Result<(Felt, &'a str)> {
+        match self {
+            TransactionType::FunctionExecution { program, .. } => {
+                Ok((state::class_hash(program), program))
+            }
+            TransactionType::ClassExecution { class_hash, .. }
+            | TransactionType::L1Handler(L1Message {
+                to_address: class_hash,
+                ..
+            }) => {
+                let program = classes.get(class_hash).ok_or_else(|| {
+                    eyre!("Class {} not found", execution::felt_to_hex(class_hash))
+                })?;
+                Ok((class_hash.clone(), program))
+            }
+            TransactionType::ProofVerification { .. } => {
+                Err(eyre!("Proof verifications don't execute a program"))
+            }
+        }
+    }
+}
+
+/// Encoding of [`TransactionType`], which also has variants for compressed payloads. The
+/// variants of both are declared in the same order, except for `L1Handler`, declared after
+/// `CompressedFunctionExecution` so the encoding of the other variants is unchanged.
+#[derive(Serialize, Deserialize)]
+enum WireType {
+    FunctionExecution {
+        program: Arc<str>,
+        function: EntryPoint,
+        program_name: String,
+        enable_trace: bool,
+        calldata: Vec<Felt>,
+    },
+    ProofVerification {
+        proof: Bytes,
+        public_inputs: Vec<String>,
+    },
+    ClassExecution {
+        class_hash: Felt,
+        function: EntryPoint,
+        program_name: String,
+        enable_trace: bool,
+        calldata: Vec<Felt>,
+    },
+    /// `FunctionExecution` with a zstd-compressed program.
+    CompressedFunctionExecution {
+        program: Bytes,
+        function: EntryPoint,
+        program_name: String,
+        enable_trace: bool,
+        calldata: Vec<Felt>,
+    },
+    L1Handler(L1Message),
+}
+
+/// [`Transaction`] with a compressed payload, encoded like it.
+#[derive(Serialize)]
+struct CompressedTransaction<'a> {
+    id: &'a str,
+    transaction_hash: &'a str,
+    transaction_type: WireType,
+    signature: &'a Option<TxSignature>,
+}
+
+impl TryFrom<WireType> for TransactionType {
+    type Error = color_eyre::Report;
+
+    fn try_from(wire: WireType) -> Result<Self> {
+        Ok(match wire {
+            WireType::FunctionExecution {
+                program,
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            } => TransactionType::FunctionExecution {
+                program,
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            },
+            WireType::ProofVerification {
+                proof,
+                public_inputs,
+            } => TransactionType::ProofVerification {
+                proof,
+                public_inputs,
+            },
+            WireType::ClassExecution {
+                class_hash,
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            } => TransactionType::ClassExecution {
+                class_hash,
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            },
+            WireType::CompressedFunctionExecution {
+                program,
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            } => TransactionType::FunctionExecution {
+                program: decompress_program(&program)?.into(),
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            },
+            WireType::L1Handler(message) => TransactionType::L1Handler(message),
+        })
+    }
+}
+
+impl From<TransactionType> for WireType {
+    fn from(transaction_type: TransactionType) -> Self {
+        match transaction_type {
+            TransactionType::FunctionExecution {
+                program,
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            } => WireType::FunctionExecution {
+                program,
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            },
+            TransactionType::ProofVerification {
+                proof,
+                public_inputs,
+            } => WireType::ProofVerification {
+                proof,
+                public_inputs,
+            },
+            TransactionType::ClassExecution {
+                class_hash,
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            } => WireType::ClassExecution {
+                class_hash,
+                function,
+                program_name,
+                enable_trace,
+                calldata,
+            },
+            TransactionType::L1Handler(message) => WireType::L1Handler(message),
+        }
+    }
+}
+
+/// Decompresses a zstd-compressed program, failing if it's larger than [`MAX_PROGRAM_SIZE`].
+fn decompress_program(compressed: &[u8]) -> Result<String> {
+    let mut program = Vec::new();
+    zstd::stream::read::Decoder::new(compressed)?
+        .take(MAX_PROGRAM_SIZE + 1)
+        .read_to_end(&mut program)?;
+    ensure!(
+        program.len() as u64 <= MAX_PROGRAM_SIZE,
+        "Compressed program is larger than {MAX_PROGRAM_SIZE} bytes"
+    );
+    Ok(String::from_utf8(program)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: &str = include_str!("../../examples/programs/fibonacci.json");
+
+    fn transaction() -> Transaction {
+        Transaction::with_type(TransactionType::FunctionExecution {
+            program: PROGRAM.into(),
+            function: EntryPoint::Name("main".to_string()),
+            program_name: "fibonacci.json".to_string(),
+            enable_trace: false,
+            calldata: Vec::new(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn compressed_transactions_decode_to_the_same_transaction() {
+        let tx = transaction();
+        let encoded = bincode::serialize(&tx).unwrap();
+        let compressed = tx.encode_compressed().unwrap();
+        assert!(compressed.len() < encoded.len());
+
+        let decoded: Transaction = bincode::deserialize(&compressed).unwrap();
+        assert_eq!(decoded.id, tx.id);
+        assert_eq!(decoded.transaction_hash, tx.transaction_hash);
+        let TransactionType::FunctionExecution { program, .. } = &decoded.transaction_type else {
+            panic!("Compressed function executions decode to function executions");
+        };
+        assert_eq!(&**program, PROGRAM);
+        decoded.verify().unwrap();
+    }
+
+    #[test]
+    fn programs_decompressing_over_the_limit_are_rejected() {
+        let program = vec![b' '; MAX_PROGRAM_SIZE as usize];
+        let compressed = zstd::encode_all(program.as_slice(), 1).unwrap();
+        assert_eq!(
+            decompress_program(&compressed).unwrap().len(),
+            program.len()
+        );
+
+        let program = vec![b' '; MAX_PROGRAM_SIZE as usize + 1];
+        let compressed = zstd::encode_all(program.as_slice(), 1).unwrap();
+        assert!(decompress_program(&compressed).is_err());
+        assert!(decompress_program(b"not zstd").is_err());
     }
 }
@@ -0,0 +1,326 @@
+//! End-to-end tests of signed transactions, run with `cargo test --features testkit`: their
+//! validation in CheckTx and DeliverTx, account contracts, nonces, fees and tips.
+
+mod common;
+
+use cairo_felt::Felt;
+use num_traits::Zero;
+use starkmint::app::{
+    GAS_LIMIT_CODE, INSUFFICIENT_BALANCE_CODE, INVALID_NONCE_CODE, INVALID_TRANSACTION_CODE,
+    UNSIGNED_CODE,
+};
+use starkmint::genesis::{Genesis, GenesisAccount, GenesisParameters};
+use starkmint::signature::{self, TxSignature};
+use starkmint::state;
+use starkmint::testkit::{TestChain, DEFAULT_CHAIN_ID};
+use starkmint::transaction::Transaction;
+
+use common::{call, dev_account, program, signed_transaction, transaction};
+
+/// Genesis funding the dev account 0 with `balance`, tips going to the dev account 1. Gas is
+/// free, so that only tips are charged.
+fn genesis(balance: u64) -> Genesis {
+    let mut genesis = genesis_with_gas_price(balance, 1);
+    genesis.parameters.gas_price = 0;
+    genesis
+}
+
+/// Genesis funding the dev account 0 with `balance`, fees and tips going to the dev account 1.
+fn genesis_with_gas_price(balance: u64, gas_price: u64) -> Genesis {
+    Genesis {
+        accounts: vec![GenesisAccount {
+            address: dev_account(0).1,
+            balance: Felt::new(balance),
+        }],
+        parameters: GenesisParameters {
+            fee_recipient: Some(dev_account(1).1),
+            gas_price,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Genesis of [`genesis`] declaring the example account contract, returned with its address.
+fn account_genesis() -> (Genesis, Felt) {
+    let account = program("account");
+    let address = state::class_hash(&account);
+    let mut genesis = genesis(100);
+    genesis.classes.push(account);
+    (genesis, address)
+}
+
+/// `tx` sent from the example account contract at `account`, whose `__validate__` accepts
+/// signatures whose r is the message hash.
+fn sent_from_account(mut tx: Transaction, account: &Felt, nonce: u64) -> Transaction {
+    let content = tx.signed_content(signature::chain_id_hash(DEFAULT_CHAIN_ID));
+    let message = signature::message_hash(&content, account, nonce, 0, 0, None);
+    tx.signature = Some(TxSignature {
+        sender: account.clone(),
+        nonce,
+        r: message,
+        s: Felt::zero(),
+        tip: 0,
+        max_fee: 0,
+        paymaster: None,
+    });
+    tx
+}
+
+fn assert_calls(chain: &TestChain, account: &Felt, expected: u64) {
+    chain.assert_storage(account, &Felt::new(1), &Felt::new(expected));
+}
+
+fn assert_balance(chain: &TestChain, account: &Felt, expected: u64) {
+    let (address, key) = state::balance_slot(account);
+    chain.assert_storage(&address, &key, &Felt::new(expected));
+}
+
+#[test]
+fn signed_transactions_increment_nonces_and_pay_tips() {
+    let (private_key, sender) = dev_account(0);
+    let recipient = dev_account(1).1;
+    let mut chain = TestChain::new(&genesis(100));
+
+    let first = signed_transaction("fibonacci", &private_key, 0, 10);
+    let second = signed_transaction("factorial", &private_key, 1, 0);
+    for tx in [&first, &second] {
+        let response = chain.check_tx(tx);
+        assert!(response.code.is_ok(), "CheckTx failed: {}", response.log);
+    }
+    chain.execute_block(&[first, second]).assert_delivered();
+
+    chain.assert_nonce(&sender, 2);
+    assert_balance(&chain, &sender, 90);
+    assert_balance(&chain, &recipient, 10);
+}
+
+#[test]
+fn signed_transactions_pay_for_their_gas_up_to_their_max_fee() {
+    let (private_key, sender) = dev_account(0);
+    let recipient = dev_account(1).1;
+    let mut chain = TestChain::new(&genesis_with_gas_price(1_000_000, 2));
+
+    let mut tx = transaction("fibonacci");
+    tx.sign(&private_key, DEFAULT_CHAIN_ID, 0, 10, 1_000_000)
+        .unwrap();
+    let response = chain.check_tx(&tx);
+    assert!(response.code.is_ok(), "CheckTx failed: {}", response.log);
+    let result = chain.execute_block(&[tx]);
+    result.assert_delivered();
+
+    let fee = 2 * result.deliveries[0].gas_used as u64;
+    assert!(fee > 0);
+    assert_balance(&chain, &sender, 1_000_000 - fee - 10);
+    assert_balance(&chain, &recipient, fee + 10);
+
+    // the balance must cover the max fee, and the fee may not exceed it
+    let mut unaffordable = transaction("fibonacci");
+    unaffordable
+        .sign(&private_key, DEFAULT_CHAIN_ID, 1, 0, 1_000_000)
+        .unwrap();
+    assert_eq!(
+        chain.check_tx(&unaffordable).code.value(),
+        INSUFFICIENT_BALANCE_CODE
+    );
+    let mut underpaying = transaction("fibonacci");
+    underpaying
+        .sign(&private_key, DEFAULT_CHAIN_ID, 1, 0, 1)
+        .unwrap();
+    assert_eq!(chain.check_tx(&underpaying).code.value(), GAS_LIMIT_CODE);
+
+    let result = chain.execute_block(&[underpaying]);
+    assert!(result.deliveries[0].code.is_err());
+    chain.assert_nonce(&sender, 1);
+    assert_balance(&chain, &sender, 1_000_000 - fee - 10);
+}
+
+#[test]
+fn transactions_checked_concurrently_are_admitted_in_order() {
+    let (private_key, sender) = dev_account(0);
+    let mut chain = TestChain::new(&genesis(100));
+
+    let txs: Vec<_> = (0..8)
+        .map(|nonce| signed_transaction("fibonacci", &private_key, nonce, 1))
+        .collect();
+    for response in chain.check_txs(&txs) {
+        assert!(response.code.is_ok(), "CheckTx failed: {}", response.log);
+    }
+    chain.execute_block(&txs).assert_delivered();
+    chain.assert_nonce(&sender, 8);
+}
+
+#[test]
+fn transactions_are_prioritized_in_nonce_order() {
+    let (private_key, _) = dev_account(0);
+    let mut chain = TestChain::new(&genesis(100));
+
+    let priorities: Vec<_> = [(0, 5), (1, 10), (2, 1), (3, 3)]
+        .into_iter()
+        .map(|(nonce, tip)| {
+            let response =
+                chain.check_tx(&signed_transaction("fibonacci", &private_key, nonce, tip));
+            assert!(response.code.is_ok(), "CheckTx failed: {}", response.log);
+            response.priority
+        })
+        .collect();
+    assert_eq!(priorities, [5, 5, 1, 1]);
+}
+
+#[test]
+fn transactions_with_invalid_signatures_are_rejected() {
+    let (private_key, sender) = dev_account(0);
+    let mut chain = TestChain::new(&genesis(100));
+
+    // signed by another key on behalf of the sender
+    let mut tx = signed_transaction("fibonacci", &dev_account(2).0, 0, 0);
+    tx.signature.as_mut().unwrap().sender = sender.clone();
+    assert_eq!(chain.check_tx(&tx).code.value(), INVALID_TRANSACTION_CODE);
+
+    // a tampered tip invalidates the signature too
+    let mut tx = signed_transaction("fibonacci", &private_key, 0, 0);
+    tx.signature.as_mut().unwrap().tip = 10;
+    assert_eq!(chain.check_tx(&tx).code.value(), INVALID_TRANSACTION_CODE);
+
+    // as does swapping the program, changing the id, or signing for another chain
+    let mut tampered = signed_transaction("fibonacci", &private_key, 0, 0);
+    tampered.transaction_type = transaction("factorial").transaction_type;
+    assert_eq!(
+        chain.check_tx(&tampered).code.value(),
+        INVALID_TRANSACTION_CODE
+    );
+    let mut renamed = signed_transaction("fibonacci", &private_key, 0, 0);
+    renamed.id = "another-id".to_string();
+    assert_eq!(
+        chain.check_tx(&renamed).code.value(),
+        INVALID_TRANSACTION_CODE
+    );
+    let mut replayed = transaction("fibonacci");
+    replayed
+        .sign(&private_key, "another-chain", 0, 0, 0)
+        .unwrap();
+    assert_eq!(
+        chain.check_tx(&replayed).code.value(),
+        INVALID_TRANSACTION_CODE
+    );
+
+    let result = chain.execute_block(&[tx, replayed]);
+    assert!(result.deliveries[0].code.is_err());
+    assert!(result.deliveries[1].code.is_err());
+    chain.assert_nonce(&sender, 0);
+    assert_balance(&chain, &sender, 100);
+}
+
+#[test]
+fn transactions_with_invalid_nonces_are_rejected() {
+    let (private_key, sender) = dev_account(0);
+    let mut chain = TestChain::new(&genesis(100));
+
+    let skipping = signed_transaction("fibonacci", &private_key, 1, 0);
+    assert_eq!(chain.check_tx(&skipping).code.value(), INVALID_NONCE_CODE);
+
+    let first = signed_transaction("fibonacci", &private_key, 0, 0);
+    let reusing = signed_transaction("factorial", &private_key, 0, 0);
+    assert!(chain.check_tx(&first).code.is_ok());
+    assert_eq!(chain.check_tx(&reusing).code.value(), INVALID_NONCE_CODE);
+
+    // proposers may include them anyway, but only the first one is applied
+    let result = chain.execute_block(&[first, reusing, skipping]);
+    assert!(result.deliveries[0].code.is_ok());
+    assert!(result.deliveries[1].code.is_err());
+    assert!(result.deliveries[2].code.is_ok());
+    chain.assert_nonce(&sender, 2);
+
+    // the nonce was used by the last block
+    let result = chain.execute_block(&[signed_transaction("fibonacci", &private_key, 1, 0)]);
+    assert!(result.deliveries[0].code.is_err());
+    chain.assert_nonce(&sender, 2);
+}
+
+#[test]
+fn tips_over_the_payer_balance_are_rejected() {
+    let (private_key, sender) = dev_account(0);
+    let mut chain = TestChain::new(&genesis(100));
+
+    let tx = signed_transaction("fibonacci", &private_key, 0, 101);
+    assert_eq!(chain.check_tx(&tx).code.value(), INSUFFICIENT_BALANCE_CODE);
+
+    // tips of the pending transactions add up
+    let first = signed_transaction("fibonacci", &private_key, 0, 60);
+    let second = signed_transaction("factorial", &private_key, 1, 60);
+    assert!(chain.check_tx(&first).code.is_ok());
+    assert_eq!(
+        chain.check_tx(&second).code.value(),
+        INSUFFICIENT_BALANCE_CODE
+    );
+
+    let result = chain.execute_block(&[tx, first, second]);
+    assert!(result.deliveries[0].code.is_err());
+    assert!(result.deliveries[1].code.is_ok());
+    assert!(result.deliveries[2].code.is_err());
+    chain.assert_nonce(&sender, 1);
+    assert_balance(&chain, &sender, 40);
+}
+
+#[test]
+fn account_contracts_validate_and_execute_their_transactions() {
+    let (genesis, account) = account_genesis();
+    let mut chain = TestChain::new(&genesis);
+
+    let txs: Vec<_> = (0..2)
+        .map(|nonce| sent_from_account(transaction("fibonacci"), &account, nonce))
+        .collect();
+    for tx in &txs {
+        let response = chain.check_tx(tx);
+        assert!(response.code.is_ok(), "CheckTx failed: {}", response.log);
+    }
+    chain.execute_block(&txs).assert_delivered();
+
+    // `__execute__` counted the calls it executed
+    chain.assert_nonce(&account, 2);
+    assert_calls(&chain, &account, 2);
+}
+
+#[test]
+fn account_contracts_reject_transactions() {
+    let (genesis, account) = account_genesis();
+    let mut chain = TestChain::new(&genesis);
+
+    // `__validate__` rejects the signature
+    let mut forged = sent_from_account(transaction("fibonacci"), &account, 0);
+    forged.signature.as_mut().unwrap().r = Felt::new(1);
+    assert_eq!(
+        chain.check_tx(&forged).code.value(),
+        INVALID_TRANSACTION_CODE
+    );
+
+    // `__execute__` rejects calls to other functions than `main`, once delivered
+    let other_call = sent_from_account(
+        call("factorial", "factorial", vec![Felt::new(5)]),
+        &account,
+        0,
+    );
+    assert!(chain.check_tx(&other_call).code.is_ok());
+
+    let result = chain.execute_block(&[forged, other_call]);
+    assert!(result.deliveries[0].code.is_err());
+    assert!(result.deliveries[1].code.is_err());
+    chain.assert_nonce(&account, 0);
+    assert_calls(&chain, &account, 0);
+}
+
+#[test]
+fn unsigned_executions_are_rejected_once_accounts_are_funded() {
+    let tx = transaction("fibonacci");
+    let mut chain = TestChain::new(&genesis(100));
+    assert_eq!(chain.check_tx(&tx).code.value(), UNSIGNED_CODE);
+    let result = chain.execute_block(&[tx.clone()]);
+    assert!(result.deliveries[0].code.is_err());
+
+    // unless the chain is a devnet accepting them
+    let mut devnet = genesis(100);
+    devnet.parameters.unsigned_executions = true;
+    let mut chain = TestChain::new(&devnet);
+    assert!(chain.check_tx(&tx).code.is_ok());
+    chain.execute_block(&[tx]).assert_delivered();
+}
@@ -0,0 +1,210 @@
+//! End-to-end tests of block execution, run with `cargo test --features testkit`.
+
+mod common;
+
+use cairo_felt::Felt;
+use starkmint::app::{StarknetApp, DUPLICATE_CODE, SENDER_LIMIT_CODE};
+use starkmint::execution::ExecutionConfig;
+use starkmint::genesis::{Genesis, GenesisAccount, GenesisParameters};
+use starkmint::testkit::{BlockResult, TestChain, DEFAULT_CHAIN_ID};
+
+use common::{dev_account, signed_transaction, transaction};
+
+/// Chain initialized with `genesis`, whose app executes blocks on `workers` threads.
+fn parallel_chain(genesis: &Genesis, workers: usize) -> TestChain {
+    let execution = ExecutionConfig {
+        gas_price: genesis.parameters.gas_price,
+        fee_recipient: genesis.parameters.fee_recipient.clone(),
+        max_tx_gas: genesis.parameters.max_tx_gas,
+        ..Default::default()
+    };
+    let app = StarknetApp::new()
+        .with_execution_config(execution)
+        .with_parallel_execution(workers);
+    TestChain::with_app(app, genesis)
+}
+
+/// Panics unless both blocks were committed with the same app hash and their transactions
+/// delivered with the same codes.
+fn assert_same_results(serial: &BlockResult, parallel: &BlockResult) {
+    let codes = |result: &BlockResult| -> Vec<u32> {
+        result
+            .deliveries
+            .iter()
+            .map(|delivery| delivery.code.value())
+            .collect()
+    };
+    assert_eq!(
+        codes(serial),
+        codes(parallel),
+        "at height {}",
+        serial.height
+    );
+    assert_eq!(
+        serial.app_hash, parallel.app_hash,
+        "at height {}",
+        serial.height
+    );
+}
+
+#[test]
+fn same_blocks_give_same_app_hashes() {
+    let blocks = vec![
+        vec![transaction("fibonacci")],
+        vec![transaction("factorial"), transaction("fibonacci")],
+        Vec::new(),
+    ];
+
+    let mut first = TestChain::new(&Genesis::default());
+    let mut second = TestChain::new(&Genesis::default());
+    for txs in &blocks {
+        let result = first.execute_block(txs);
+        result.assert_delivered();
+        assert!(!result.app_hash.is_empty());
+
+        second.execute_block(txs).assert_delivered();
+        second.assert_app_hash(&result.app_hash);
+    }
+    assert_eq!(first.height(), 3);
+}
+
+#[test]
+fn different_blocks_give_different_app_hashes() {
+    let mut first = TestChain::new(&Genesis::default());
+    let mut second = TestChain::new(&Genesis::default());
+
+    let fibonacci = first.execute_block(&[transaction("fibonacci")]);
+    let factorial = second.execute_block(&[transaction("factorial")]);
+    fibonacci.assert_delivered();
+    factorial.assert_delivered();
+    assert_ne!(fibonacci.app_hash, factorial.app_hash);
+}
+
+#[test]
+fn restarted_apps_keep_their_chain_id() {
+    let mut chain = TestChain::new(&Genesis::default());
+    chain
+        .execute_block(&[transaction("fibonacci")])
+        .assert_delivered();
+
+    // the chain ID is read back from the data dir before Tendermint initializes the chain
+    let app = StarknetApp::new().with_data_dir(chain.data_dir());
+    assert_eq!(app.chain_id().as_deref(), Some(DEFAULT_CHAIN_ID));
+
+    // an app started for another chain refuses to initialize this one
+    let other = StarknetApp::new().with_chain_id("starkmint-other".to_string());
+    assert!(chain.restart(other).is_err());
+
+    // one started without a chain ID replays the chain, to the same app hash
+    chain.restart(StarknetApp::new()).unwrap();
+    assert_eq!(chain.app().chain_id().as_deref(), Some(DEFAULT_CHAIN_ID));
+    assert_eq!(chain.height(), 1);
+}
+
+#[test]
+fn duplicate_transactions_are_rejected() {
+    let mut chain = TestChain::new(&Genesis::default());
+    let tx = transaction("fibonacci");
+
+    // only the first copy of a block is applied, so the block hashes like one without the
+    // second
+    let result = chain.execute_block(&[tx.clone(), tx.clone()]);
+    assert!(result.deliveries[0].code.is_ok());
+    assert!(result.deliveries[1].code.is_err());
+    let mut single = TestChain::new(&Genesis::default());
+    single.execute_block(&[tx.clone()]).assert_delivered();
+    single.assert_app_hash(&result.app_hash);
+
+    // and it's rejected once included
+    assert_eq!(chain.check_tx(&tx).code.value(), DUPLICATE_CODE);
+    let result = chain.execute_block(&[tx]);
+    assert!(result.deliveries[0].code.is_err());
+
+    // the same call can still be sent again as a new transaction
+    let tx = transaction("fibonacci");
+    assert!(chain.check_tx(&tx).code.is_ok());
+    chain.execute_block(&[tx]).assert_delivered();
+}
+
+#[test]
+fn unsigned_transactions_share_the_pending_limit() {
+    let app = StarknetApp::new().with_max_pending_per_sender(2);
+    let mut chain = TestChain::with_app(app, &Genesis::default());
+
+    let txs = [
+        transaction("fibonacci"),
+        transaction("factorial"),
+        transaction("fibonacci"),
+    ];
+    assert!(chain.check_tx(&txs[0]).code.is_ok());
+    assert!(chain.check_tx(&txs[1]).code.is_ok());
+    assert_eq!(chain.check_tx(&txs[2]).code.value(), SENDER_LIMIT_CODE);
+
+    // the count starts over once the pending transactions are included
+    chain.execute_block(&txs[..2]).assert_delivered();
+    assert!(chain.check_tx(&txs[2]).code.is_ok());
+}
+
+#[test]
+fn parallel_execution_gives_the_same_results_as_serial() {
+    let (private_key, sender) = dev_account(0);
+    let genesis = Genesis {
+        accounts: vec![GenesisAccount {
+            address: sender.clone(),
+            balance: Felt::new(100),
+        }],
+        parameters: GenesisParameters {
+            gas_price: 0,
+            unsigned_executions: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let duplicate = transaction("factorial");
+    let blocks = vec![
+        vec![
+            transaction("fibonacci"),
+            signed_transaction("factorial", &private_key, 0, 10),
+            duplicate.clone(),
+            signed_transaction("fibonacci", &private_key, 1, 10),
+            duplicate.clone(),
+        ],
+        vec![
+            // invalid nonce, then a tip over the sender's balance
+            signed_transaction("fibonacci", &private_key, 0, 0),
+            signed_transaction("fibonacci", &private_key, 2, 1000),
+            signed_transaction("factorial", &private_key, 2, 80),
+            duplicate,
+        ],
+        Vec::new(),
+    ];
+
+    let mut serial = TestChain::new(&genesis);
+    let mut parallel = parallel_chain(&genesis, 4);
+    for txs in &blocks {
+        assert_same_results(&serial.execute_block(txs), &parallel.execute_block(txs));
+    }
+    serial.assert_nonce(&sender, 3);
+    parallel.assert_nonce(&sender, 3);
+}
+
+#[test]
+fn transactions_over_the_gas_limit_fail_on_every_node() {
+    let genesis = Genesis {
+        parameters: GenesisParameters {
+            max_tx_gas: Some(100),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    // fibonacci loops for thousands of steps, but CheckTx only estimates it from its size
+    let tx = transaction("fibonacci");
+
+    let mut serial = TestChain::new(&genesis);
+    assert!(serial.check_tx(&tx).code.is_ok());
+    let result = serial.execute_block(&[tx.clone()]);
+    assert!(result.deliveries[0].code.is_err());
+
+    let mut parallel = parallel_chain(&genesis, 2);
+    assert_same_results(&result, &parallel.execute_block(&[tx]));
+}
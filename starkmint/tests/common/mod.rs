@@ -0,0 +1,49 @@
+//! Helpers shared by the end-to-end tests.
+
+use std::path::Path;
+
+use cairo_felt::Felt;
+use starkmint::signature;
+use starkmint::testkit::DEFAULT_CHAIN_ID;
+use starkmint::transaction::{Transaction, TransactionType};
+
+/// Compiled example program.
+pub fn program(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../examples/programs")
+        .join(format!("{name}.json"));
+    std::fs::read_to_string(path).expect("example program is readable")
+}
+
+/// Transaction running `main` of an example program.
+pub fn transaction(name: &str) -> Transaction {
+    call(name, "main", Vec::new())
+}
+
+/// Transaction running `function` of an example program.
+pub fn call(name: &str, function: &str, calldata: Vec<Felt>) -> Transaction {
+    Transaction::with_type(TransactionType::FunctionExecution {
+        program: program(name).into(),
+        function: function.parse().unwrap(),
+        program_name: format!("{name}.json"),
+        enable_trace: false,
+        calldata,
+    })
+    .expect("example program executes")
+}
+
+/// Private key and address of the dev account at `index`.
+pub fn dev_account(index: u32) -> (Felt, Felt) {
+    let private_key = signature::dev_private_key(index);
+    let address = signature::public_key(&private_key).expect("dev keys are valid");
+    (private_key, address)
+}
+
+/// Transaction running `main` of an example program, signed by `private_key` without paying for
+/// gas, for chains whose gas price is zero.
+pub fn signed_transaction(name: &str, private_key: &Felt, nonce: u64, tip: u64) -> Transaction {
+    let mut tx = transaction(name);
+    tx.sign(private_key, DEFAULT_CHAIN_ID, nonce, tip, 0)
+        .expect("transaction can be signed");
+    tx
+}